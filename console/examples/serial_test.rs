@@ -101,7 +101,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("ftdi context in created - OK");
 
     if vid != 0 && pid != 0 && interface != ftdi_interface::INTERFACE_ANY {
-        ftdi.ftdi_set_interface(ftdi_interface::INTERFACE_ANY);
+        ftdi.ftdi_set_interface(ftdi_interface::INTERFACE_ANY)?;
 
         info!("start find all usb device(s)...");
         let mut ftdi_list = ftdi_device_list::new(&ftdi)?;
@@ -115,7 +115,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                   manufacturer_description.0, manufacturer_description.1, manufacturer_description.2);
         }
     } else {
-        ftdi.ftdi_set_interface(interface);
+        ftdi.ftdi_set_interface(interface)?;
         // Open device
         ftdi.ftdi_usb_open(vid, pid)?;
     }
@@ -151,7 +151,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 },
             }
         } else {
-            let size_to_read = buffer.len();
+            let size_to_read = buffer.capacity();
             let read_result = ftdi.ftdi_read_data(&mut buffer, size_to_read);
             match read_result {
                 Err(err) => {
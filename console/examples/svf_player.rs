@@ -0,0 +1,89 @@
+use ::ftdi_library::ftdi::ftdi_context::ftdi_context;
+use log::info;
+use log4rs;
+use clap::{value_t, Arg, App};
+use ftdi_library::ftdi::constants::{ftdi_chip_type, ftdi_interface, ftdi_mpsse_mode};
+use ftdi_library::ftdi::jtag::JtagController;
+use ftdi_library::ftdi::svf::SvfPlayer;
+use ftdi_library::ftdi::xsvf::XsvfPlayer;
+
+const PATH_TO_YAML_LOG_CONFIG:&'static str = "log4rs.yaml"; // string path to log config
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // construction for command line parameters
+    let matches = App::new("SVF / XSVF player.")
+        .version("v 0.1")
+        .author("Blandger <blandger@gmail.com>")
+        .about("Play SVF or XSVF file on JTAG chain connected to FTDI MPSSE interface")
+        .arg(Arg::with_name("interface")
+            .short("i")
+            .long("interface")
+            .value_name("INTERFACE")
+            .help("INTERFACE_ANY | A | B | C | D, values: 0 - 4")
+            .default_value("1"))
+        .arg(Arg::with_name("v")
+            .short("v")
+            .long("vendorId")
+            .value_name("Vendor ID")
+            .help("Vendor ID usb value, default is '0403' for FTDI")
+            .default_value("0x0403"))
+        .arg(Arg::with_name("p")
+            .short("p")
+            .long("productId")
+            .value_name("Product ID")
+            .help("Product ID usb value, usual FTDI values are :0x6010, 0x6011, 0x6014")
+            .required(true))
+        .arg(Arg::with_name("f")
+            .short("f")
+            .long("frequency")
+            .value_name("TCK frequency")
+            .help("TCK frequency in Hz, default is '1000000'")
+            .default_value("1000000"))
+        .arg(Arg::with_name("FILE")
+            .help("SVF file, or XSVF file when it has '.xsvf' extension")
+            .required(true))
+        .get_matches();
+    match log4rs::init_file(PATH_TO_YAML_LOG_CONFIG, Default::default()) {
+        Ok(_) => println!("log4rs config file is found - OK"),
+        Err(error) => println!("Log config not found as \'{}\', error: \'{}\'", PATH_TO_YAML_LOG_CONFIG, error),
+    }
+
+    let interface = value_t!(matches.value_of("interface"), ftdi_interface).unwrap_or(ftdi_interface::INTERFACE_A);
+    let vid = ftdi_context::parse_number_str(matches.value_of("v").unwrap()).unwrap_or_default();
+    let pid = ftdi_context::parse_number_str(matches.value_of("p").unwrap()).unwrap_or_default();
+    let frequency = value_t!(matches.value_of("f"), u32).unwrap_or(1_000_000);
+    let file_name = matches.value_of("FILE").unwrap();
+    let data = std::fs::read(file_name)?;
+
+    let mut ftdi = ftdi_context::new_with_log_level(Some(4))?;
+    ftdi.ftdi_set_interface(interface)?;
+    ftdi.ftdi_usb_open(vid, pid)?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_MPSSE)?;
+
+    // 2232H, 4232H and 232H have 60 MHz MPSSE clock
+    let high_speed = matches!(ftdi.r#type,
+        ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H | ftdi_chip_type::TYPE_232H);
+    let mut jtag = JtagController::new(ftdi);
+    let actual = jtag.init(frequency, high_speed)?;
+    info!("TCK = {} Hz", actual);
+    for device in jtag.scan_chain()? {
+        println!("{}", device);
+    }
+
+    let mut last_percent = 0;
+    let mut progress = |done: usize, total: usize| {
+        let percent = done * 100 / total.max(1);
+        if percent != last_percent {
+            last_percent = percent;
+            print!("\r{:3}%", percent);
+        }
+    };
+    if file_name.to_ascii_lowercase().ends_with(".xsvf") {
+        XsvfPlayer::new(&mut jtag).play(&data, &mut progress)?;
+    } else {
+        SvfPlayer::new(&mut jtag).play_str(&String::from_utf8_lossy(&data), &mut progress)?;
+    }
+    println!("\rdone");
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use clap::{value_t, Arg, ArgMatches};
+use log::info;
+use ftdi_library::ftdi::chip::chip_info;
+use ftdi_library::ftdi::constants::{ftdi_interface, ftdi_mpsse_mode};
+use ftdi_library::ftdi::ftdi_context::ftdi_context;
+
+/// Command line arguments selecting FTDI device and interface
+pub fn device_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = device_selection_args(true);
+    args.push(Arg::with_name("f")
+        .short("f")
+        .long("frequency")
+        .value_name("Clock frequency")
+        .help("MPSSE clock frequency in Hz")
+        .default_value("10000000"));
+    args
+}
+
+/// Vendor ID, product ID and interface arguments.
+/// Product ID is optional for commands which work on files without device as well.
+pub fn device_selection_args<'a, 'b>(product_required: bool) -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("v")
+            .short("v")
+            .long("vendorId")
+            .value_name("Vendor ID")
+            .help("Vendor ID usb value, default is '0403' for FTDI")
+            .default_value("0x0403"),
+        Arg::with_name("p")
+            .short("p")
+            .long("productId")
+            .value_name("Product ID")
+            .help("Product ID usb value, usual MPSSE capable FTDI values are: 0x6010, 0x6011, 0x6014")
+            .required(product_required),
+        Arg::with_name("interface")
+            .short("i")
+            .long("interface")
+            .value_name("INTERFACE")
+            .help("INTERFACE_ANY | A | B | C | D, values: 0 - 4")
+            .default_value("1"),
+    ]
+}
+
+/// Parse decimal or '0x' prefixed hex number
+pub fn parse_u32(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if value.starts_with("0x") || value.starts_with("0X") {
+        u32::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Open device selected by command line
+pub fn open_device(matches: &ArgMatches) -> Result<ftdi_context, Box<dyn std::error::Error>> {
+    let vid = ftdi_context::parse_number_str(matches.value_of("v").unwrap()).unwrap_or_default();
+    let pid = match matches.value_of("p") {
+        Some(pid) => ftdi_context::parse_number_str(pid).unwrap_or_default(),
+        None => return Err("product ID (-p) is required to open device".into()),
+    };
+    let interface = value_t!(matches.value_of("interface"), ftdi_interface).unwrap_or(ftdi_interface::INTERFACE_A);
+
+    let mut ftdi = ftdi_context::new_with_log_level(Some(4))?;
+    ftdi.ftdi_set_interface(interface)?;
+    ftdi.ftdi_usb_open(vid, pid)?;
+    info!("device {:04x}:{:04x} opened, {:?}", vid, pid, ftdi.r#type);
+    Ok(ftdi)
+}
+
+/// Open device selected by command line and switch it to MPSSE mode
+/// return context, requested clock frequency and true for chips with 60 MHz MPSSE clock
+pub fn open_mpsse(matches: &ArgMatches) -> Result<(ftdi_context, u32, bool), Box<dyn std::error::Error>> {
+    let frequency = value_t!(matches.value_of("f"), u32).unwrap_or(10_000_000);
+    let mut ftdi = open_device(matches)?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_MPSSE)?;
+    // hi-speed chips have 60 MHz MPSSE clock
+    let high_speed = chip_info(ftdi.r#type).high_speed;
+    info!("MPSSE mode enabled");
+    Ok((ftdi, frequency, high_speed))
+}
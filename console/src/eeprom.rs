@@ -0,0 +1,255 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde::{Deserialize, Serialize};
+use ftdi_library::ftdi::constants::ftdi_chip_type;
+use ftdi_library::ftdi::eeprom::{ftdi_eeprom, FTX_EEPROM_RESERVED_WORDS};
+use ftdi_library::ftdi::eeprom_config::{chip_from_name, chip_name, EepromConfig};
+use ftdi_library::ftdi::ftdi_context::ftdi_context;
+use crate::device::{device_selection_args, open_device};
+
+/// Image bytes per line of backup file
+const IMAGE_LINE_BYTES: usize = 16;
+
+/// 'eeprom' subcommand: backup, restore, compare and show EEPROM content
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("eeprom")
+        .about("Backup, restore, compare and show EEPROM content")
+        .args(&device_selection_args(false))
+        .subcommand(SubCommand::with_name("backup")
+            .about("Save raw EEPROM image with chip type, chip ID and serial into file")
+            .arg(Arg::with_name("FILE").required(true)))
+        .subcommand(SubCommand::with_name("restore")
+            .about("Write image from backup file to EEPROM and verify it")
+            .arg(Arg::with_name("FILE").required(true))
+            .arg(Arg::with_name("yes")
+                .long("yes")
+                .help("Really write EEPROM, only the differences are shown otherwise")))
+        .subcommand(SubCommand::with_name("diff")
+            .about("Show fields which differ between backups, configuration files (.toml, .conf) or device")
+            .arg(Arg::with_name("FIRST").required(true))
+            .arg(Arg::with_name("SECOND").help("Compared with device when missing")))
+        .subcommand(SubCommand::with_name("show")
+            .about("Show decoded EEPROM content of backup file or device")
+            .arg(Arg::with_name("FILE").help("Device EEPROM is read when missing")))
+}
+
+/// Raw EEPROM image with the data identifying where it comes from, it's stored as TOML file
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct EepromBackup {
+    /// Chip name, e.g. "FT232R"
+    pub chip: String,
+    /// FTDIChip-ID of FT232R and FT-X
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_id: Option<String>,
+    pub serial: String,
+    /// Seconds since UNIX epoch
+    pub timestamp: u64,
+    /// EEPROM size in bytes, the image holds as much
+    pub size: i32,
+    /// Image in hex, IMAGE_LINE_BYTES bytes per line
+    pub image: String,
+}
+impl EepromBackup {
+    /// Take image read into 'ftdi.eeprom'
+    pub fn from_device(ftdi: &ftdi_context) -> Result<Self, Box<dyn std::error::Error>> {
+        let size = ftdi.eeprom.size;
+        if size <= 0 {
+            return Err("EEPROM is blank or missing".into());
+        }
+        let image = &ftdi.eeprom.buf[..size as usize];
+        // serial is informative only, image with bad checksum is still backed up
+        let serial = ftdi_eeprom::decode(image, ftdi.r#type).map(|eeprom| eeprom.serial).unwrap_or_default();
+        let chip_id = if ftdi.r#type == ftdi_chip_type::TYPE_R || ftdi.r#type == ftdi_chip_type::TYPE_230X {
+            ftdi.ftdi_read_chipid().ok().map(|chip_id| chip_id.to_string())
+        } else {
+            None
+        };
+        Ok(EepromBackup {
+            chip: chip_name(ftdi.r#type).to_string(),
+            chip_id,
+            serial,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default(),
+            size,
+            image: image.chunks(IMAGE_LINE_BYTES)
+                .map(|line| line.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>().join("\n"),
+        })
+    }
+
+    pub fn chip_type(&self) -> Result<ftdi_chip_type, Box<dyn std::error::Error>> {
+        Ok(chip_from_name(&self.chip)?)
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let bytes = self.image.split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| format!("invalid image byte '{}'", byte)))
+            .collect::<Result<Vec<u8>, String>>()?;
+        if bytes.len() != self.size as usize {
+            return Err(format!("image has {} bytes, size is {}", bytes.len(), self.size).into());
+        }
+        Ok(bytes)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(|error| format!("{}: {}", path, error).into())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Decoded content, checksum is checked
+    pub fn config(&self) -> Result<EepromConfig, Box<dyn std::error::Error>> {
+        let chip = self.chip_type()?;
+        Ok(EepromConfig::from_eeprom(&ftdi_eeprom::decode(&self.bytes()?, chip)?, chip)?)
+    }
+}
+
+fn read_device(matches: &ArgMatches) -> Result<ftdi_context, Box<dyn std::error::Error>> {
+    let mut ftdi = open_device(matches)?;
+    ftdi.ftdi_read_eeprom()?;
+    Ok(ftdi)
+}
+
+fn device_config(ftdi: &ftdi_context) -> Result<EepromConfig, Box<dyn std::error::Error>> {
+    Ok(EepromConfig::from_eeprom(&ftdi_eeprom::decode(&ftdi.eeprom.buf, ftdi.r#type)?, ftdi.r#type)?)
+}
+
+/// Backup (it has image), TOML or INI (.conf, .ini) configuration file, INI defaults depend on chip
+fn load_config(path: &str, chip: Option<ftdi_chip_type>) -> Result<EepromConfig, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    if path.ends_with(".conf") || path.ends_with(".ini") {
+        return Ok(EepromConfig::from_ini(&text, chip)?);
+    }
+    let value: toml::Value = toml::from_str(&text).map_err(|error| format!("{}: {}", path, error))?;
+    if value.get("image").is_some() {
+        EepromBackup::load(path)?.config()
+    } else {
+        Ok(EepromConfig::from_toml(&text)?)
+    }
+}
+
+/// Put values of nested tables under dotted keys
+fn flatten(prefix: &str, value: toml::Value, fields: &mut BTreeMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => for (key, value) in table {
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            flatten(&key, value, fields);
+        },
+        value => {
+            fields.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Field with different values, value missing on one side is shown as '-'
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldDiff {
+    /// Dotted key, e.g. "channel_a.driver"
+    pub key: String,
+    pub left: String,
+    pub right: String,
+}
+
+pub fn diff(left: &EepromConfig, right: &EepromConfig) -> Result<Vec<FieldDiff>, Box<dyn std::error::Error>> {
+    let mut left_fields = BTreeMap::new();
+    flatten("", toml::Value::try_from(left)?, &mut left_fields);
+    let mut right_fields = BTreeMap::new();
+    flatten("", toml::Value::try_from(right)?, &mut right_fields);
+    let keys: BTreeSet<&String> = left_fields.keys().chain(right_fields.keys()).collect();
+    Ok(keys.into_iter()
+        .filter(|key| left_fields.get(*key) != right_fields.get(*key))
+        .map(|key| {
+            let side = |fields: &BTreeMap<String, String>| fields.get(key).cloned().unwrap_or_else(|| "-".to_string());
+            FieldDiff { key: key.clone(), left: side(&left_fields), right: side(&right_fields) }
+        })
+        .collect())
+}
+
+fn print_diff(differences: &[FieldDiff]) {
+    if differences.is_empty() {
+        println!("no differences");
+    }
+    for difference in differences {
+        println!("{}: {} -> {}", difference.key, difference.left, difference.right);
+    }
+}
+
+/// Offsets of image bytes EEPROM doesn't hold after writing, FT-X reserved words aren't written and are skipped
+fn verify(chip: ftdi_chip_type, written: &[u8], read: &[u8]) -> Vec<usize> {
+    let reserved = (FTX_EEPROM_RESERVED_WORDS.start * 2) as usize..(FTX_EEPROM_RESERVED_WORDS.end * 2) as usize;
+    (0..written.len())
+        .filter(|offset| !(chip == ftdi_chip_type::TYPE_230X && reserved.contains(offset)))
+        .filter(|offset| read.get(*offset) != Some(&written[*offset]))
+        .collect()
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    match matches.subcommand() {
+        ("backup", Some(m)) => {
+            let path = m.value_of("FILE").unwrap();
+            let ftdi = read_device(matches)?;
+            let backup = EepromBackup::from_device(&ftdi)?;
+            backup.save(path)?;
+            println!("{} bytes of {} EEPROM saved to {}", backup.size, backup.chip, path);
+        }
+        ("restore", Some(m)) => {
+            let backup = EepromBackup::load(m.value_of("FILE").unwrap())?;
+            let image = backup.bytes()?;
+            let backup_config = backup.config()?;
+            let mut ftdi = read_device(matches)?;
+            if backup.chip_type()? != ftdi.r#type {
+                return Err(format!("backup is made from {}, device is {}", backup.chip, chip_name(ftdi.r#type)).into());
+            }
+            match device_config(&ftdi) {
+                Ok(current) => print_diff(&diff(&current, &backup_config)?),
+                Err(error) => println!("device EEPROM can't be decoded: {}", error),
+            }
+            if !m.is_present("yes") {
+                println!("EEPROM isn't written, add --yes to write it");
+                return Ok(());
+            }
+            ftdi.eeprom.buf = image.clone();
+            ftdi.eeprom.size = backup.size;
+            ftdi.ftdi_write_eeprom(true)?;
+            ftdi.ftdi_read_eeprom()?;
+            let mismatches = verify(ftdi.r#type, &image, &ftdi.eeprom.buf);
+            if !mismatches.is_empty() {
+                let offsets: Vec<String> = mismatches.iter().map(|offset| format!("{:#04x}", offset)).collect();
+                return Err(format!("verify failed at {}", offsets.join(", ")).into());
+            }
+            println!("{} bytes written and verified", image.len());
+        }
+        ("diff", Some(m)) => {
+            let (first, second) = match m.value_of("SECOND") {
+                Some(path) => (load_config(m.value_of("FIRST").unwrap(), None)?, load_config(path, None)?),
+                None => {
+                    let ftdi = read_device(matches)?;
+                    (load_config(m.value_of("FIRST").unwrap(), Some(ftdi.r#type))?, device_config(&ftdi)?)
+                }
+            };
+            print_diff(&diff(&first, &second)?);
+        }
+        ("show", Some(m)) => {
+            let config = match m.value_of("FILE") {
+                Some(path) => {
+                    let backup = EepromBackup::load(path)?;
+                    println!("# chip {}, chip ID {}, serial '{}', saved at {} (UNIX time), {} bytes",
+                             backup.chip, backup.chip_id.as_deref().unwrap_or("-"), backup.serial, backup.timestamp, backup.size);
+                    backup.config()?
+                }
+                None => {
+                    let ftdi = read_device(matches)?;
+                    println!("# chip {}, {} bytes", chip_name(ftdi.r#type), ftdi.eeprom.size);
+                    device_config(&ftdi)?
+                }
+            };
+            print!("{}", config.to_toml()?);
+        }
+        _ => println!("{}", matches.usage()),
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::io::Write;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use ftdi_library::ftdi::spi::{SpiMaster, SpiMode};
+use ftdi_library::ftdi::spi_flash::SpiFlash;
+use crate::device::{device_args, open_mpsse, parse_u32};
+
+/// 'flash' subcommand: SPI NOR flash on ADBUS (SCK 0, MOSI 1, MISO 2, CS 3)
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    let address = Arg::with_name("address")
+        .short("a")
+        .long("address")
+        .value_name("ADDRESS")
+        .help("Flash address, decimal or 0x prefixed hex")
+        .default_value("0");
+    SubCommand::with_name("flash")
+        .about("Read, erase and write SPI NOR flash")
+        .args(&device_args())
+        .subcommand(SubCommand::with_name("id")
+            .about("Show JEDEC ID and geometry"))
+        .subcommand(SubCommand::with_name("dump")
+            .about("Read flash into file")
+            .arg(Arg::with_name("FILE").required(true))
+            .arg(address.clone())
+            .arg(Arg::with_name("length")
+                .short("l")
+                .long("length")
+                .value_name("LENGTH")
+                .help("Bytes to read, default is up to the end of flash")))
+        .subcommand(SubCommand::with_name("write")
+            .about("Erase, program and verify file content")
+            .arg(Arg::with_name("FILE").required(true))
+            .arg(address.clone()))
+        .subcommand(SubCommand::with_name("erase")
+            .about("Erase area or whole chip")
+            .arg(address)
+            .arg(Arg::with_name("length")
+                .short("l")
+                .long("length")
+                .value_name("LENGTH")
+                .help("Bytes to erase, aligned to erase size"))
+            .arg(Arg::with_name("chip")
+                .long("chip")
+                .conflicts_with("length")
+                .help("Erase whole chip, --yes is needed too"))
+            .arg(Arg::with_name("yes")
+                .long("yes")
+                .requires("chip")
+                .help("Really erase whole chip")))
+}
+
+/// Area or whole chip has to be told explicitly, checked before device is opened
+fn check_erase_args(erase: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    if erase.is_present("length") {
+        return Ok(());
+    }
+    if erase.occurrences_of("address") > 0 {
+        return Err("-a ADDRESS needs -l LENGTH".into());
+    }
+    if !erase.is_present("chip") {
+        return Err("give -a ADDRESS -l LENGTH to erase area or --chip --yes to erase whole chip".into());
+    }
+    if !erase.is_present("yes") {
+        return Err("whole chip isn't erased, add --yes to erase it".into());
+    }
+    Ok(())
+}
+
+fn number(matches: &ArgMatches, name: &str) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    match matches.value_of(name) {
+        None => Ok(None),
+        Some(value) => parse_u32(value)
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} value '{}'", name, value).into()),
+    }
+}
+
+fn print_progress(done: usize, total: usize) {
+    print!("\r{:3}%", done * 100 / total.max(1));
+    std::io::stdout().flush().unwrap_or_default();
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    if let ("erase", Some(erase)) = matches.subcommand() {
+        check_erase_args(erase)?;
+    }
+    let (ftdi, frequency, high_speed) = open_mpsse(matches)?;
+    let mut spi = SpiMaster::new(ftdi, SpiMode::Mode0);
+    spi.init(frequency, high_speed)?;
+    let mut flash = SpiFlash::probe(spi)?;
+    println!("JEDEC ID: {}", flash.id());
+    match matches.subcommand() {
+        ("dump", Some(dump)) => {
+            let address = number(dump, "address")?.unwrap_or(0);
+            let size = flash.geometry().size;
+            if address as u64 >= size {
+                return Err(format!("address {:#x} is beyond the end of {} bytes flash", address, size).into());
+            }
+            let length = match number(dump, "length")? {
+                Some(length) => length as usize,
+                None => (size - address as u64) as usize,
+            };
+            let data = flash.read(address, length)?;
+            std::fs::write(dump.value_of("FILE").unwrap(), &data)?;
+            println!("{} bytes read from {:#x}", data.len(), address);
+        }
+        ("write", Some(write)) => {
+            let address = number(write, "address")?.unwrap_or(0);
+            let data = std::fs::read(write.value_of("FILE").unwrap())?;
+            flash.write_image(address, &data, print_progress)?;
+            println!("\r{} bytes written and verified at {:#x}", data.len(), address);
+        }
+        ("erase", Some(erase)) => {
+            // arguments are checked above, no length means --chip --yes
+            match number(erase, "length")? {
+                Some(length) => flash.erase(number(erase, "address")?.unwrap_or(0), length as usize)?,
+                None => flash.erase_chip()?,
+            }
+            println!("erased");
+        }
+        _ => {
+            let geometry = flash.geometry();
+            println!("size: {} bytes, page: {} bytes, geometry from {}", geometry.size, geometry.page_size,
+                     if geometry.from_sfdp { "SFDP" } else { "JEDEC ID" });
+            for erase in &geometry.erase_types {
+                println!("erase {} bytes with {:#04x}", erase.size, erase.opcode);
+            }
+        }
+    }
+    flash.into_inner()?;
+    Ok(())
+}
@@ -1,8 +1,15 @@
 use libusb_sys as ffi;
 use std::str;
 use std::ffi::CStr;
+use clap::{App, SubCommand};
 
-fn main() {
+mod device;
+mod eeprom;
+mod flash;
+
+const PATH_TO_YAML_LOG_CONFIG:&'static str = "log4rs.yaml"; // string path to log config
+
+fn print_libusb_version() {
     let version = unsafe { ffi::libusb_get_version() };
 
     let rc       = str::from_utf8(unsafe { CStr::from_ptr((*version).rc)       }.to_bytes()).unwrap_or("");
@@ -13,3 +20,27 @@ fn main() {
              unsafe {(*version).micro}, unsafe {(*version).nano},
              rc, describe);
 }
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = App::new("FTDI console")
+        .version("v 0.1")
+        .author("Blandger <blandger@gmail.com>")
+        .about("Console tools for FTDI chips")
+        .subcommand(SubCommand::with_name("version")
+            .about("Print libusb version (default)"))
+        .subcommand(eeprom::subcommand())
+        .subcommand(flash::subcommand())
+        .get_matches();
+    if log4rs::init_file(PATH_TO_YAML_LOG_CONFIG, Default::default()).is_err() {
+        println!("Log config not found as \'{}\'", PATH_TO_YAML_LOG_CONFIG);
+    }
+
+    match matches.subcommand() {
+        ("eeprom", Some(eeprom_matches)) => eeprom::run(eeprom_matches),
+        ("flash", Some(flash_matches)) => flash::run(flash_matches),
+        _ => {
+            print_libusb_version();
+            Ok(())
+        }
+    }
+}
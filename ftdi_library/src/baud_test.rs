@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use crate::ftdi::baud::{*};
+    use crate::ftdi::chip::{chip_info, CHIPS};
+    use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface};
+
+    /// Eighths of divisor by encoded fraction, inverse of FRAC_CODE
+    const FRAC_EIGHTHS: [u32; 8] = [0, 4, 2, 1, 3, 5, 6, 7];
+
+    /// Baud rate the chip runs at with value and index of SIO_SET_BAUDRATE_REQUEST
+    fn decode(chip: ftdi_chip_type, value: u16, index: u16) -> f64 {
+        let info = chip_info(chip);
+        let encoded = if info.high_speed || info.interfaces > 1 {
+            value as u32 | ((index as u32 & 0xff00) << 8)
+        } else {
+            value as u32 | ((index as u32) << 16)
+        };
+        let eighths = match encoded & 0x1ffff {
+            0 => 8,
+            1 => 12,
+            divisor => (divisor & 0x3fff) * 8 + FRAC_EIGHTHS[((divisor >> 14) & 7) as usize],
+        };
+        let clock = if encoded & DIVISOR_CLOCK_120MHZ != 0 { 12_000_000.0 } else { 3_000_000.0 };
+        clock * 8.0 / eighths as f64
+    }
+
+    #[test]
+    fn baud_divisor_an232b_05_tables() {
+        // AN232B-05 divisors of BM, R and FT-X chips: baud rate, value, index
+        let rates = [
+            (300, 0x2710, 0), (600, 0x1388, 0), (1200, 0x09c4, 0), (2400, 0x04e2, 0), (4800, 0x0271, 0),
+            (9600, 0x4138, 0), (19200, 0x809c, 0), (38400, 0xc04e, 0), (115200, 0x001a, 0), (230400, 0x000d, 0),
+            (460800, 0x4006, 0), (921600, 0x8003, 0), (1_000_000, 0x0003, 0), (2_000_000, 0x0001, 0), (3_000_000, 0x0000, 0),
+        ];
+        for chip_type in [ftdi_chip_type::TYPE_BM, ftdi_chip_type::TYPE_R, ftdi_chip_type::TYPE_230X].iter() {
+            for (baudrate, value, index) in rates.iter() {
+                let solution = compute_baud_divisor(*chip_type, ftdi_interface::INTERFACE_ANY, *baudrate).unwrap();
+                assert_eq!((solution.value, solution.index), (*value, *index), "{:?} {}", chip_type, baudrate);
+                assert_eq!(solution.clock, BaudClock::Clock3MHz);
+            }
+        }
+        // 2232C takes channel in index
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_2232C, ftdi_interface::INTERFACE_B, 9600).unwrap();
+        assert_eq!((solution.value, solution.index), (0x4138, 0x0002));
+        // AM has only 0, 1/8, 1/4 and 1/2 fractions, 57600 is 52 1/8 as on the others
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_AM, ftdi_interface::INTERFACE_A, 57600).unwrap();
+        assert_eq!((solution.value, solution.index, solution.clock), (0xc034, 0, BaudClock::Am3MHz));
+        // and no divisor 1.5
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_AM, ftdi_interface::INTERFACE_A, 2_000_000).unwrap();
+        assert_eq!((solution.value, solution.achieved), (0x0002, 1_500_000));
+
+        // Hi-speed chips run 12M from H-clock, slow rates from 3 MHz clock
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_2232H, ftdi_interface::INTERFACE_B, 12_000_000).unwrap();
+        assert_eq!((solution.value, solution.index, solution.achieved), (0x0000, 0x0202, 12_000_000));
+        assert_eq!(solution.clock, BaudClock::HClock12MHz);
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_232H, ftdi_interface::INTERFACE_A, 115200).unwrap();
+        assert_eq!((solution.value, solution.index, solution.clock), (0xc068, 0x0201, BaudClock::HClock12MHz));
+        let solution = compute_baud_divisor(ftdi_chip_type::TYPE_4232H, ftdi_interface::INTERFACE_D, 300).unwrap();
+        assert_eq!((solution.value, solution.index, solution.clock), (0x2710, 0x0004, BaudClock::Clock3MHz));
+
+        assert!(compute_baud_divisor(ftdi_chip_type::TYPE_R, ftdi_interface::INTERFACE_A, 0).is_err());
+    }
+
+    #[test]
+    fn baud_divisor_every_chip() {
+        for info in CHIPS.iter() {
+            let max_baudrate = if info.high_speed { 12_000_000 } else { 3_000_000 };
+            let mut previous = 0;
+            let mut baudrate = 200u32;
+            while baudrate <= max_baudrate {
+                let solution = compute_baud_divisor(info.chip_type, ftdi_interface::INTERFACE_A, baudrate).unwrap();
+                let context = format!("{:?} {} {:?}", info.chip_type, baudrate, solution);
+                assert_eq!(solution.requested, baudrate);
+                assert!((decode(info.chip_type, solution.value, solution.index) - solution.achieved as f64).abs() <= 1.0, "{}", context);
+                let error = (solution.achieved as f64 - baudrate as f64) * 100.0 / baudrate as f64;
+                assert!((solution.error - error).abs() < 1e-12, "{}", context);
+                // fractions are fine enough far from the top rate, AM has coarser ones
+                let fine = if info.chip_type == ftdi_chip_type::TYPE_AM { max_baudrate / 30 } else { max_baudrate / 3 };
+                if baudrate <= fine {
+                    assert!(solution.error.abs() < 3.0, "{}", context);
+                }
+                assert!(solution.achieved >= previous, "{}", context);
+                if info.interfaces > 1 || info.high_speed {
+                    assert_eq!(solution.index & 0xff, 1, "{}", context);
+                }
+                previous = solution.achieved;
+                baudrate += baudrate / 50 + 1;
+            }
+        }
+    }
+
+    #[test]
+    fn baud_policy_and_exact_rates() {
+        use ftdi_chip_type::*;
+        let a = ftdi_interface::INTERFACE_A;
+        // DMX, MIDI and 12M on H chips are spot on
+        for (chip_type, baudrate) in [(TYPE_R, 250_000), (TYPE_230X, 31250), (TYPE_AM, 250_000), (TYPE_2232H, 12_000_000), (TYPE_232H, 31250)].iter() {
+            let solution = compute_baud_divisor(*chip_type, a, *baudrate).unwrap();
+            assert!(BaudPolicy::Exact.check(&solution).is_ok(), "{:?} {:?}", chip_type, solution);
+            assert!(exact_baudrates(*chip_type, a, *baudrate, 3).unwrap().contains(baudrate));
+        }
+        // 115200 is 0.16% off on BM, 2.5M is 20% off
+        let solution = compute_baud_divisor(TYPE_BM, a, 115200).unwrap();
+        assert_eq!(solution.achieved, 115385);
+        assert!(BaudPolicy::Exact.check(&solution).is_err());
+        assert!(BaudPolicy::Strict(0.2).check(&solution).is_ok());
+        assert!(BaudPolicy::Strict(0.1).check(&solution).is_err());
+        let solution = compute_baud_divisor(TYPE_BM, a, 2_500_000).unwrap();
+        assert_eq!(solution.achieved, 2_000_000);
+        assert!(BaudPolicy::default().check(&solution).is_err());
+        assert!(BaudPolicy::BestEffort.check(&solution).is_ok());
+        // default is libftdi's window, -1/21 below and 5% above
+        let solution = |requested: u32, achieved: u32| BaudSolution {
+            requested, achieved, error: (achieved as f64 - requested as f64) * 100.0 / requested as f64,
+            ..compute_baud_divisor(TYPE_BM, a, requested).unwrap()
+        };
+        for (requested, achieved, accepted) in [(2000, 2100, true), (2000, 2101, false), (2100, 2000, true), (2101, 2000, false)].iter() {
+            assert_eq!(BaudPolicy::default().check(&solution(*requested, *achieved)).is_ok(), *accepted, "{} {}", requested, achieved);
+        }
+
+        assert_eq!(exact_baudrates(TYPE_R, a, 115200, 4).unwrap(), vec![120_000, 125_000, 100_000, 96_000]);
+        assert_eq!(exact_baudrates(TYPE_R, a, 3_000_000, 3).unwrap(), vec![3_000_000, 2_000_000, 1_500_000]);
+        // AM has no divisor 1.5
+        assert_eq!(exact_baudrates(TYPE_AM, a, 3_000_000, 3).unwrap(), vec![3_000_000, 1_500_000, 1_200_000]);
+        assert_eq!(exact_baudrates(TYPE_4232H, a, 12_000_000, 3).unwrap(), vec![12_000_000, 8_000_000, 6_000_000]);
+        for info in CHIPS.iter() {
+            for rate in exact_baudrates(info.chip_type, a, 9600, 20).unwrap() {
+                let solution = compute_baud_divisor(info.chip_type, a, rate).unwrap();
+                assert!(BaudPolicy::Exact.check(&solution).is_ok(), "{:?} {:?}", info.chip_type, solution);
+            }
+        }
+        assert!(exact_baudrates(TYPE_R, ftdi_interface::INTERFACE_B, 9600, 1).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use crate::ftdi::bitbang::{*};
+    use crate::ftdi::constants::ftdi_mpsse_mode;
+    use crate::ftdi::ftdi_context::Result;
+
+    /// Bitbang chip: output pins follow written bytes, input pins are driven by 'inputs'
+    struct SimulatedBitbang {
+        mode: ftdi_mpsse_mode,
+        direction: u8,
+        /// baud rate the chip was set to, multiplied as ftdi_set_baudrate does
+        baudrate: u32,
+        inputs: u8,
+        output: u8,
+        patterns: Vec<u8>,
+        samples: VecDeque<u8>,
+    }
+    impl SimulatedBitbang {
+        fn new(inputs: u8) -> Self {
+            SimulatedBitbang {
+                mode: ftdi_mpsse_mode::BITMODE_RESET, direction: 0, baudrate: 0, inputs, output: 0,
+                patterns: Vec::new(), samples: VecDeque::new(),
+            }
+        }
+
+        fn pins(&self) -> u8 {
+            (self.output & self.direction) | (self.inputs & !self.direction)
+        }
+    }
+    impl BitbangTransport for SimulatedBitbang {
+        fn set_bitmode(&mut self, direction: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+            self.direction = direction;
+            self.mode = mode;
+            Ok(())
+        }
+
+        fn set_baudrate(&mut self, baudrate: u32) -> Result<()> {
+            let bitbang = self.mode != ftdi_mpsse_mode::BITMODE_RESET;
+            self.baudrate = if bitbang { baudrate * 4 } else { baudrate };
+            Ok(())
+        }
+
+        fn write_data(&mut self, data: &[u8]) -> Result<()> {
+            for pattern in data {
+                if self.mode == ftdi_mpsse_mode::BITMODE_SYNCBB {
+                    assert!(self.samples.len() < BITBANG_SYNC_CHUNK, "sync bitbang receive buffer overflow");
+                    self.samples.push_back(self.pins());
+                    // input pins change while patterns are clocked
+                    self.inputs = self.inputs.wrapping_add(0x10);
+                }
+                self.output = *pattern;
+                self.patterns.push(*pattern);
+            }
+            Ok(())
+        }
+
+        fn read_data(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.samples.len() >= size, "simulated bitbang has {} samples, {} requested", self.samples.len(), size);
+            Ok(self.samples.drain(..size).collect())
+        }
+
+        fn read_pins(&mut self) -> Result<u8> {
+            Ok(self.pins())
+        }
+    }
+
+    #[test]
+    fn async_bitbang_rate_and_patterns() {
+        let mut port = BitbangPort::open(SimulatedBitbang::new(0xa0), BitbangMode::Async, 0x0f, 1_000_000).unwrap();
+        assert_eq!(port.rate(), 1_000_000);
+        let simulator = port.transport();
+        assert_eq!(simulator.mode, ftdi_mpsse_mode::BITMODE_BITBANG);
+        // ×4 multiplier is applied once, by the chip driver
+        assert_eq!(simulator.baudrate, 1_000_000);
+        assert_eq!(port.set_rate(1_000_003).unwrap(), 1_000_000);
+        assert!(port.set_rate(3).is_err());
+        port.write(&[0x01, 0x02, 0x04, 0x08, 0x05]).unwrap();
+        assert_eq!(port.read_pins().unwrap(), 0xa5);
+        assert_eq!(port.transport().patterns, vec![0x01, 0x02, 0x04, 0x08, 0x05]);
+        assert!(port.write_read(&[0x00]).is_err());
+        port.set_direction(0xff).unwrap();
+        assert_eq!(port.read_pins().unwrap(), 0x05);
+        let simulator = port.close().unwrap();
+        assert_eq!(simulator.mode, ftdi_mpsse_mode::BITMODE_RESET);
+    }
+
+    #[test]
+    fn sync_bitbang_samples_every_pattern() {
+        let mut port = BitbangPort::open(SimulatedBitbang::new(0x00), BitbangMode::Sync, 0x0f, 400_000).unwrap();
+        assert_eq!(port.transport().mode, ftdi_mpsse_mode::BITMODE_SYNCBB);
+        let patterns: Vec<u8> = (0..300).map(|index| (index % 16) as u8).collect();
+        let samples = port.write_read(&patterns).unwrap();
+        assert_eq!(samples.len(), 300);
+        // sample is taken before the pattern is applied: previous outputs, inputs counting up
+        assert_eq!(samples[0], 0x00);
+        assert_eq!(samples[1], 0x10);
+        assert_eq!(samples[5], 0x54);
+        assert_eq!(samples[17], 0x10);
+        // samples of plain writes are read and dropped
+        port.write(&patterns).unwrap();
+        assert!(port.transport().samples.is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::ftdi::bitbang::BitbangTransport;
+    use crate::ftdi::cbus::{*};
+    use crate::ftdi::constants::{ftdi_chip_type, ftdi_mpsse_mode};
+    use crate::ftdi::core::{ftdi_cbus_func, ftdi_cbush_func, ftdi_cbusx_func};
+    use crate::ftdi::ftdi_context::Result;
+
+    /// CBUS pins: bitmode argument sets them, external levels drive input pins
+    struct SimulatedCbus {
+        mode: ftdi_mpsse_mode,
+        inputs: u8,
+        /// bitmode arguments in order
+        bitmasks: Vec<u8>,
+    }
+    impl BitbangTransport for SimulatedCbus {
+        fn set_bitmode(&mut self, direction: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+            self.mode = mode;
+            self.bitmasks.push(direction);
+            Ok(())
+        }
+
+        fn set_baudrate(&mut self, _baudrate: u32) -> Result<()> {
+            unreachable!("CBUS port doesn't set baud rate")
+        }
+
+        fn write_data(&mut self, _data: &[u8]) -> Result<()> {
+            unreachable!("CBUS pins aren't set by data")
+        }
+
+        fn read_data(&mut self, _size: usize) -> Result<Vec<u8>> {
+            unreachable!("CBUS pins aren't read by data")
+        }
+
+        fn read_pins(&mut self) -> Result<u8> {
+            let bitmask = self.bitmasks.last().copied().unwrap_or(0);
+            let direction = bitmask >> 4;
+            // upper nibble isn't CBUS, chip returns garbage there
+            Ok(0xa0 | (bitmask & direction) | (self.inputs & !direction & 0x0f))
+        }
+    }
+
+    fn simulator(inputs: u8) -> SimulatedCbus {
+        SimulatedCbus { mode: ftdi_mpsse_mode::BITMODE_RESET, inputs, bitmasks: Vec::new() }
+    }
+
+    #[test]
+    fn cbus_function_typed_per_chip() {
+        assert_eq!(ftdi_cbus_func::CBUS_IOMODE, ftdi_cbus_func::from(0xa as u8));
+        assert_eq!(ftdi_cbush_func::CBUSH_CLK7_5, ftdi_cbush_func::from(12 as u8));
+        assert_eq!(ftdi_cbusx_func::CBUSX_AWAKE, ftdi_cbusx_func::from(21 as u8));
+        assert_eq!(CbusFunction::decode(ftdi_chip_type::TYPE_R, 8), Some(CbusFunction::R(ftdi_cbus_func::CBUS_CLK12)));
+        assert_eq!(CbusFunction::decode(ftdi_chip_type::TYPE_232H, 8), Some(CbusFunction::H(ftdi_cbush_func::CBUSH_IOMODE)));
+        assert_eq!(CbusFunction::decode(ftdi_chip_type::TYPE_233HP, 8), Some(CbusFunction::H(ftdi_cbush_func::CBUSH_IOMODE)));
+        assert_eq!(cbus_bitbang_slots(ftdi_chip_type::TYPE_232HP), cbus_bitbang_slots(ftdi_chip_type::TYPE_232H));
+        assert!(CbusFunction::decode(ftdi_chip_type::TYPE_230X, 8).unwrap().is_iomode());
+        assert!(!CbusFunction::decode(ftdi_chip_type::TYPE_R, 8).unwrap().is_iomode());
+        assert_eq!(CbusFunction::decode(ftdi_chip_type::TYPE_R, 13), None);
+        assert_eq!(CbusFunction::decode(ftdi_chip_type::TYPE_2232H, 8), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ftdi_cbus_func is unknown for value = 13")]
+    fn ftdi_cbus_func_conversion_fail() {
+        ftdi_cbus_func::from(13 as u8);
+    }
+
+    #[test]
+    fn cbus_port_checks_eeprom_iomode() {
+        // FT232R: CBUS0 TXLED, CBUS1 RXLED, CBUS2 and CBUS3 IOMODE
+        let functions = [3, 2, 0xa, 0xa, 1, 0, 0, 0, 0, 0];
+        let mut port = CbusPort::open(simulator(0x0f), ftdi_chip_type::TYPE_R, &functions).unwrap();
+        assert_eq!(port.available(), 0x0c);
+        assert_eq!(port.transport().mode, ftdi_mpsse_mode::BITMODE_CBUS);
+        assert!(port.set_value(0, true).is_err());
+        assert!(port.set_direction(4, true).is_err());
+        assert!(port.write(0x03, 0x00).is_err());
+        assert!(CbusPort::open(simulator(0), ftdi_chip_type::TYPE_R, &[0; 10]).is_err());
+        assert!(CbusPort::open(simulator(0), ftdi_chip_type::TYPE_2232H, &functions).is_err());
+        // FT232H: CBUS pins are ACBUS5, 6, 8 and 9
+        let functions = [0, 0, 0, 0, 0, 8, 1, 0, 8, 8];
+        let port = CbusPort::open(simulator(0), ftdi_chip_type::TYPE_232H, &functions).unwrap();
+        assert_eq!(port.available(), 0x0d);
+    }
+
+    #[test]
+    fn cbus_port_nibble_encoding() {
+        let functions = [8, 8, 8, 8, 0, 0, 0, 0, 0, 0];
+        let mut port = CbusPort::open(simulator(0x06), ftdi_chip_type::TYPE_230X, &functions).unwrap();
+        port.set_direction(0, true).unwrap();
+        port.set_value(0, true).unwrap();
+        port.set_direction(3, true).unwrap();
+        assert_eq!(port.state(), (0x01, 0x09));
+        assert_eq!(port.transport().bitmasks, vec![0x00, 0x10, 0x11, 0x91]);
+        // outputs read their driven level, inputs the external one
+        assert_eq!(port.read().unwrap(), 0x07);
+        assert!(port.read_pin(2).unwrap());
+        assert!(!port.read_pin(3).unwrap());
+        // reset pulse: low, then back high
+        port.pulse(0, false, Duration::from_millis(1)).unwrap();
+        assert_eq!(port.transport().bitmasks[4..], [0x90, 0x91]);
+        port.write(0x09, 0x08).unwrap();
+        assert_eq!(port.transport().bitmasks.last(), Some(&0x98));
+        let simulator = port.close().unwrap();
+        assert_eq!(simulator.mode, ftdi_mpsse_mode::BITMODE_RESET);
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use libusb_sys as ffi;
+    use crate::ftdi::chip::{*};
+    use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface, ftdi_mpsse_mode};
+    use crate::ftdi::eeprom::ftdi_eeprom;
+    use crate::ftdi::eeprom_config::{chip_from_name, chip_name};
+
+    fn descriptor(bcd_device: u16, serial: u8) -> ffi::libusb_device_descriptor {
+        ffi::libusb_device_descriptor {
+            bLength: 18,
+            bDescriptorType: 1,
+            bcdUSB: 0x0200,
+            bDeviceClass: 0,
+            bDeviceSubClass: 0,
+            bDeviceProtocol: 0,
+            bMaxPacketSize0: 64,
+            idVendor: 0x0403,
+            idProduct: 0x6010,
+            bcdDevice: bcd_device,
+            iManufacturer: 1,
+            iProduct: 2,
+            iSerialNumber: serial,
+            bNumConfigurations: 1,
+        }
+    }
+
+    #[test]
+    fn detect_every_chip() {
+        use ftdi_chip_type::*;
+        use EepromKind::*;
+        let a = ftdi_interface::INTERFACE_A;
+        let b = ftdi_interface::INTERFACE_B;
+        // bcdDevice, chip, interfaces, hi-speed, MPSSE channels, EEPROM
+        let chips: [(u16, ftdi_chip_type, u8, bool, &[ftdi_interface], EepromKind); 15] = [
+            (0x0200, TYPE_AM, 1, false, &[], External),
+            (0x0400, TYPE_BM, 1, false, &[], External),
+            (0x0500, TYPE_2232C, 2, false, &[a], External),
+            (0x0600, TYPE_R, 1, false, &[], Internal),
+            (0x0700, TYPE_2232H, 2, true, &[a, b], External),
+            (0x0800, TYPE_4232H, 4, true, &[a, b], External),
+            (0x0900, TYPE_232H, 1, true, &[a], External),
+            (0x1000, TYPE_230X, 1, false, &[], Mtp),
+            (0x2800, TYPE_2233HP, 2, true, &[a, b], External),
+            (0x2900, TYPE_4233HP, 4, true, &[a, b], External),
+            (0x3000, TYPE_2232HP, 2, true, &[a, b], External),
+            (0x3100, TYPE_4232HP, 4, true, &[a, b], External),
+            (0x3200, TYPE_233HP, 1, true, &[a], External),
+            (0x3300, TYPE_232HP, 1, true, &[a], External),
+            (0x3600, TYPE_4232HA, 4, true, &[a, b], External),
+        ];
+        for (bcd_device, chip_type, interfaces, high_speed, mpsse, eeprom) in chips.iter() {
+            let info = detect_chip(&descriptor(*bcd_device, 3), *interfaces).unwrap();
+            assert_eq!((info.chip_type, info.bcd_device, info.interfaces), (*chip_type, *bcd_device, *interfaces));
+            assert_eq!((info.high_speed, info.mpsse_interfaces, info.eeprom), (*high_speed, *mpsse, *eeprom), "{:?}", chip_type);
+            assert_eq!(chip_info(*chip_type), info);
+            // interface count is only checked
+            assert_eq!(detect_chip(&descriptor(*bcd_device, 3), 0).unwrap(), info);
+            assert_eq!(chip_from_name(chip_name(*chip_type)).unwrap(), *chip_type);
+        }
+        assert_eq!(chips.len(), CHIPS.len());
+    }
+
+    #[test]
+    fn detect_chip_quirks_and_unknown() {
+        // BM chips without serial number report bcdDevice of AM
+        assert_eq!(detect_chip(&descriptor(0x0200, 0), 1).unwrap().chip_type, ftdi_chip_type::TYPE_BM);
+        for bcd_device in [0x0000, 0x0300, 0x1700, 0x3400, 0xffff].iter() {
+            assert!(detect_chip(&descriptor(*bcd_device, 3), 1).is_err(), "{:#06x}", bcd_device);
+        }
+        // FT-X chips share bcdDevice, product string tells them apart
+        assert_eq!(detect_ftx_variant("FT231X USB UART"), Some("FT231X"));
+        assert_eq!(detect_ftx_variant("ft234xd basic uart"), Some("FT234XD"));
+        assert_eq!(detect_ftx_variant("FT240X USB FIFO"), Some("FT240X"));
+        assert_eq!(detect_ftx_variant("Board console"), None);
+        // HP series EEPROM layouts aren't known
+        assert!(ftdi_eeprom::decode(&[0u8; 0x100], ftdi_chip_type::TYPE_2232HP).is_err());
+        assert!(ftdi_eeprom::default().build(ftdi_chip_type::TYPE_4232HA).is_err());
+    }
+
+    #[test]
+    fn capabilities_per_chip_and_channel() {
+        use ftdi_chip_type::*;
+        use ftdi_interface::*;
+        use ftdi_mpsse_mode::*;
+        // chip, channel, MPSSE, sync FIFO, CBUS pins, max baud rate
+        let channels = [
+            (TYPE_BM, INTERFACE_ANY, false, false, 0, 3_000_000),
+            (TYPE_2232C, INTERFACE_A, true, false, 0, 3_000_000),
+            (TYPE_2232C, INTERFACE_B, false, false, 0, 3_000_000),
+            (TYPE_R, INTERFACE_A, false, false, 5, 3_000_000),
+            (TYPE_2232H, INTERFACE_A, true, true, 0, 12_000_000),
+            (TYPE_2232H, INTERFACE_B, true, false, 0, 12_000_000),
+            (TYPE_4232H, INTERFACE_C, false, false, 0, 12_000_000),
+            (TYPE_232H, INTERFACE_A, true, true, 10, 12_000_000),
+            (TYPE_230X, INTERFACE_A, false, false, 4, 3_000_000),
+            (TYPE_4232HA, INTERFACE_D, false, false, 0, 12_000_000),
+        ];
+        for (chip_type, interface, mpsse, sync_fifo, cbus_pins, max_baudrate) in channels.iter() {
+            let capabilities = ChipCapabilities::new(*chip_type, *interface).unwrap();
+            assert_eq!(capabilities.check_bitmode(BITMODE_MPSSE).is_ok(), *mpsse, "{:?} {:?}", chip_type, interface);
+            assert_eq!(capabilities.check_bitmode(BITMODE_SYNCFF).is_ok(), *sync_fifo, "{:?} {:?}", chip_type, interface);
+            assert_eq!((capabilities.sync_fifo, capabilities.cbus_pins, capabilities.max_baudrate), (*sync_fifo, *cbus_pins, *max_baudrate));
+            assert!(capabilities.check_bitmode(BITMODE_RESET).is_ok());
+            assert!(capabilities.check_baudrate(*max_baudrate).is_ok());
+            assert!(capabilities.check_baudrate(max_baudrate + 1).is_err());
+        }
+        assert_eq!(ChipCapabilities::new(TYPE_BM, INTERFACE_ANY).unwrap().interface, INTERFACE_A);
+        assert!(ChipCapabilities::new(TYPE_2232H, INTERFACE_C).is_err());
+        assert!(ChipCapabilities::new(TYPE_R, INTERFACE_B).is_err());
+        assert!(ChipCapabilities::new(TYPE_AM, INTERFACE_A).unwrap().check_bitmode(BITMODE_BITBANG).is_err());
+        assert!(ChipCapabilities::new(TYPE_R, INTERFACE_A).unwrap().check_bitmode(BITMODE_CBUS).is_ok());
+        // MCU host bus emulation on channel A, fast serial on channel B
+        for chip_type in [TYPE_2232C, TYPE_2232H, TYPE_2232HP, TYPE_2233HP].iter() {
+            let a = ChipCapabilities::new(*chip_type, INTERFACE_A).unwrap();
+            let b = ChipCapabilities::new(*chip_type, INTERFACE_B).unwrap();
+            assert!(a.check_bitmode(BITMODE_MCU).is_ok() && a.check_bitmode(BITMODE_OPTO).is_err(), "{:?}", chip_type);
+            assert!(b.check_bitmode(BITMODE_OPTO).is_ok() && b.check_bitmode(BITMODE_MCU).is_err(), "{:?}", chip_type);
+        }
+
+        let capabilities = ChipCapabilities::new(TYPE_230X, INTERFACE_A).unwrap();
+        assert_eq!(capabilities.eeprom, EepromKind::Mtp);
+        assert!(capabilities.check_eeprom_size(0x100).is_ok());
+        assert!(capabilities.check_eeprom_size(0x80).is_err());
+        assert!(ChipCapabilities::new(TYPE_2232H, INTERFACE_B).unwrap().check_eeprom_size(0x80).is_ok());
+    }
+}
@@ -5,6 +5,7 @@ mod tests {
         ftdi_chip_type, ftdi_parity_type, ftdi_stopbits_type, ftdi_bits_type,
         ftdi_break_type, ftdi_mpsse_mode, ftdi_interface, ftdi_module_detach_mode
     };
+    use std::convert::TryFrom;
     use crate::ftdi::eeprom::{ftdi_eeprom_value};
 
     #[test]
@@ -17,12 +18,19 @@ mod tests {
         assert_eq!(ftdi_chip_type::TYPE_4232H, ftdi_chip_type::from(5 as u8));
         assert_eq!(ftdi_chip_type::TYPE_232H, ftdi_chip_type::from(6 as u8));
         assert_eq!(ftdi_chip_type::TYPE_230X, ftdi_chip_type::from(7 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_2233HP, ftdi_chip_type::from(8 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_4233HP, ftdi_chip_type::from(9 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_2232HP, ftdi_chip_type::from(10 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_4232HP, ftdi_chip_type::from(11 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_233HP, ftdi_chip_type::from(12 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_232HP, ftdi_chip_type::from(13 as u8));
+        assert_eq!(ftdi_chip_type::TYPE_4232HA, ftdi_chip_type::from(14 as u8));
     }
 
     #[test]
-    #[should_panic(expected = "ftdi_chip_type is unknown for value = 8")]
+    #[should_panic(expected = "ftdi_chip_type is unknown for value = 15")]
     fn ftdi_chip_type_conversion_fail() {
-        ftdi_chip_type::from(8 as u8);
+        ftdi_chip_type::from(15 as u8);
     }
 
     #[test]
@@ -119,15 +127,22 @@ mod tests {
 
     #[test]
     fn fftdi_eeprom_value_conversion() {
-        assert_eq!(ftdi_eeprom_value::VENDOR_ID,  ftdi_eeprom_value::from(0 as u8));
-        assert_eq!(ftdi_eeprom_value::PRODUCT_ID, ftdi_eeprom_value::from(1 as u8));
-        assert_eq!(ftdi_eeprom_value::HIGH_CURRENT_A, ftdi_eeprom_value::from(28 as u8));
-        assert_eq!(ftdi_eeprom_value::USER_DATA_ADDR, ftdi_eeprom_value::from(57 as u8));
+        assert_eq!(ftdi_eeprom_value::VENDOR_ID,  ftdi_eeprom_value::try_from(0 as u8).unwrap());
+        assert_eq!(ftdi_eeprom_value::PRODUCT_ID, ftdi_eeprom_value::try_from(1 as u8).unwrap());
+        assert_eq!(ftdi_eeprom_value::HIGH_CURRENT_A, ftdi_eeprom_value::try_from(28 as u8).unwrap());
+        assert_eq!(ftdi_eeprom_value::USER_DATA_ADDR, ftdi_eeprom_value::try_from(57 as u8).unwrap());
+        for value in ftdi_eeprom_value::all() {
+            assert_eq!(ftdi_eeprom_value::try_from(value as u8).unwrap(), value);
+            assert_eq!(value.name().parse::<ftdi_eeprom_value>().unwrap(), value);
+        }
+        assert_eq!("HIGH_CURRENT_A".parse::<ftdi_eeprom_value>().unwrap(), ftdi_eeprom_value::HIGH_CURRENT_A);
+        assert_eq!(ftdi_eeprom_value::CHANNEL_A_RS485.to_string(), "channel_a_rs485");
     }
     #[test]
-    #[should_panic(expected = "ftdi_eeprom_value is unknown for value = 200")]
     fn ftdi_eeprom_value_conversion_fail() {
-        ftdi_eeprom_value::from(200 as u8);
+        assert!(ftdi_eeprom_value::try_from(200 as u8).is_err());
+        assert!(ftdi_eeprom_value::try_from(58 as u8).is_err());
+        assert!("vendor".parse::<ftdi_eeprom_value>().is_err());
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::eeprom::{ftdi_eeprom, EEPROM_CHIP_93X56};
+    use crate::ftdi::eeprom_config::{*};
+    use crate::ftdi::mcu::Ft1284Config;
+
+    const FT232R_TOML: &str = r#"
+chip = "FT232R"
+vendor_id = 0x0403
+product_id = "0x6001"
+manufacturer = "ACME"
+product = "Programmer"
+serial = "ACME0001"
+use_serial = true
+max_power = 91
+high_current = true
+invert = ["TXD", "rts"]
+cbus = ["TXLED", "RXLED", "IOMODE", "iomode", "SLEEP"]
+
+[channel_a]
+driver = "D2XX"
+"#;
+
+    const FT2232H_INI: &str = r#"
+# libftdi ftdi_eeprom configuration
+filename="eeprom.bin"   # tool option
+vendor_id=0x0403
+product_id=0x6010
+max_power=500
+self_powered=false
+remote_wakeup=true
+manufacturer="ACME"
+product="Dual \"HS\" bridge"
+serial="AB#12"
+use_serial=true
+change_usb_version=false
+usb_version=0x0110
+eeprom_type=0x56
+cha_type=FIFO
+cha_vcp=false
+chb_type=UART
+chb_vcp=true
+group0_drive=16MA
+group0_schmitt=true
+group3_slew=yes
+"#;
+
+    fn round_trip(config: &EepromConfig, chip: ftdi_chip_type) -> EepromConfig {
+        let mut eeprom = ftdi_eeprom::default();
+        config.apply(&mut eeprom, chip).unwrap();
+        let image = eeprom.build(chip).unwrap();
+        EepromConfig::from_eeprom(&ftdi_eeprom::decode(&image, chip).unwrap(), chip).unwrap()
+    }
+
+    #[test]
+    fn eeprom_config_toml() {
+        let config = EepromConfig::from_toml(FT232R_TOML).unwrap();
+        let mut eeprom = ftdi_eeprom::default();
+        config.apply(&mut eeprom, ftdi_chip_type::TYPE_R).unwrap();
+        // power is stored in 2 mA units
+        assert_eq!(eeprom.max_power, 92);
+        assert_eq!(eeprom.invert, (INVERT_TXD | INVERT_RTS) as i32);
+        assert_eq!(eeprom.cbus_function[..5], [3, 2, 0xa, 0xa, 5]);
+        assert_eq!((eeprom.channel_a_driver, eeprom.high_current), (0, HIGH_CURRENT_DRIVE_R as i32));
+
+        let decoded = round_trip(&config, ftdi_chip_type::TYPE_R);
+        assert_eq!(decoded.max_power, 92);
+        assert_eq!(decoded.cbus, vec!["TXLED", "RXLED", "IOMODE", "IOMODE", "SLEEP"]);
+        assert_eq!(decoded.invert, vec!["TXD", "RTS"]);
+        assert_eq!(decoded.channel_a, Some(ChannelConfig { driver: Some("D2XX".to_string()), ..ChannelConfig::default() }));
+        let text = decoded.to_toml().unwrap();
+        assert!(text.contains("vendor_id = '0x0403'"), "{}", text);
+        assert_eq!(EepromConfig::from_toml(&text).unwrap(), decoded);
+    }
+
+    #[test]
+    fn eeprom_config_checks_chip() {
+        let config = EepromConfig::from_toml(FT232R_TOML).unwrap();
+        assert!(config.apply(&mut ftdi_eeprom::default(), ftdi_chip_type::TYPE_232H).is_err());
+        let config = EepromConfig { chip: None, ..config };
+        // FT232H has different CBUS functions and no high current IO
+        assert!(config.apply(&mut ftdi_eeprom::default(), ftdi_chip_type::TYPE_232H).is_err());
+        let config = EepromConfig { cbus: vec!["PWREN".to_string()], ..EepromConfig::default() };
+        assert!(config.apply(&mut ftdi_eeprom::default(), ftdi_chip_type::TYPE_2232H).is_err());
+        let config = EepromConfig { channel_b: Some(ChannelConfig::default()), max_power: 600, ..EepromConfig::default() };
+        assert!(config.apply(&mut ftdi_eeprom::default(), ftdi_chip_type::TYPE_R).is_err());
+        assert!(EepromConfig::from_toml("vendor = 1").is_err());
+        assert!(EepromConfig::from_toml("vendor_id = 0x10000").is_err());
+        assert_eq!(chip_from_name("232h").unwrap(), ftdi_chip_type::TYPE_232H);
+    }
+
+    #[test]
+    fn eeprom_config_ini() {
+        let config = EepromConfig::from_ini(FT2232H_INI, Some(ftdi_chip_type::TYPE_2232H)).unwrap();
+        assert_eq!(config.product, "Dual \"HS\" bridge");
+        assert_eq!(config.serial, "AB#12");
+        assert_eq!(config.usb_version, None);
+        assert_eq!(config.eeprom_chip.as_deref(), Some("93x56"));
+        assert_eq!(config.groups[0], PinGroupConfig { drive: 16, schmitt: true, slow_slew: false });
+        assert_eq!(config.groups[3], PinGroupConfig { drive: 4, schmitt: false, slow_slew: true });
+        let mut eeprom = ftdi_eeprom::default();
+        config.apply(&mut eeprom, ftdi_chip_type::TYPE_2232H).unwrap();
+        assert_eq!((eeprom.channel_a_type, eeprom.channel_a_driver), (CHANNEL_IS_FIFO as i32, 0));
+        assert_eq!((eeprom.channel_b_type, eeprom.channel_b_driver), (CHANNEL_IS_UART as i32, DRIVER_VCP as i32));
+        assert_eq!((eeprom.chip, eeprom.group0_drive, eeprom.group3_slew), (EEPROM_CHIP_93X56, DRIVE_16MA as i32, 1));
+
+        let decoded = round_trip(&config, ftdi_chip_type::TYPE_2232H);
+        let text = decoded.to_ini();
+        assert!(text.contains("group0_drive=16MA") && !text.contains("chip="), "{}", text);
+        assert_eq!(EepromConfig::from_ini(&text, Some(ftdi_chip_type::TYPE_2232H)).unwrap(), decoded);
+        assert!(EepromConfig::from_ini("vendor_id=0x0403\nbogus=1", None).is_err());
+        assert!(EepromConfig::from_ini("change_usb_version=true", None).is_err());
+        assert!(EepromConfig::from_ini("group0_drive=16", None).is_err());
+        assert!(EepromConfig::from_ini("chip=FT2232H", None).is_err());
+
+        // ftdi_eeprom defaults, VCP driver on channels the chip has
+        let config = EepromConfig::from_ini("chb_vcp=false\ngroup1_drive=\"8ma\"", Some(ftdi_chip_type::TYPE_2232H)).unwrap();
+        assert_eq!((config.self_powered, config.remote_wakeup, config.max_power), (true, true, 0));
+        assert_eq!(config.groups[1].drive, 8);
+        assert_eq!(config.channel_a, Some(ChannelConfig { driver: Some("VCP".to_string()), ..ChannelConfig::default() }));
+        assert_eq!(config.channel_b.unwrap().driver.as_deref(), Some("D2XX"));
+        assert_eq!(config.channel_c, None);
+        assert_eq!(EepromConfig::from_ini("", None).unwrap().channel_a, None);
+    }
+
+    #[test]
+    fn eeprom_config_ft232h_ini_keys() {
+        let config = EepromConfig::from_ini("cbush5=IOMODE\ncbush9=iomode\nclock_polarity=HIGH\ndata_order=MSB\npowersave=true", None).unwrap();
+        assert_eq!(config.chip.as_deref(), Some("FT232H"));
+        assert_eq!(config.cbus, vec!["", "", "", "", "", "IOMODE", "", "", "", "iomode"]);
+        assert_eq!(config.ft1284, Some(Ft1284Config { clock_idle_high: true, lsb_first: false, flow_control: false }));
+        let decoded = round_trip(&config, ftdi_chip_type::TYPE_232H);
+        assert_eq!(decoded.cbus[5], "IOMODE");
+        assert_eq!(decoded.channel_a.as_ref().unwrap().channel_type.as_deref(), Some("FT1284"));
+        assert_eq!(decoded.powersave, Some(true));
+        assert!(decoded.to_ini().contains("cbush9=IOMODE"));
+    }
+}
@@ -0,0 +1,305 @@
+#[cfg(test)]
+mod tests {
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::eeprom::{*};
+
+    // No dumps of real devices are at hand, so these images are synthetic: they're written by hand
+    // from the layouts in FTDI application notes (AN_121, AN_201) and libftdi's ftdi_eeprom_build,
+    // with string descriptors at the usual offsets and checksum in the last word. Serial numbers
+    // are placeholders. The images check decode against build and the documented bit positions,
+    // not against what FTDI tools write; replace them with dumps of real devices when there are some.
+    /// FT232R with FTDI default settings
+    const FT232R_IMAGE: [u8; 0x80] = [
+        0x00, 0x40, 0x03, 0x04, 0x01, 0x60, 0x00, 0x06, 0xa0, 0x2d, 0x08, 0x00, 0x00, 0x02, 0x98, 0x0a,
+        0xa2, 0x20, 0xc2, 0x12, 0x23, 0x10, 0x05, 0x00, 0x0a, 0x03, 0x46, 0x00, 0x54, 0x00, 0x44, 0x00,
+        0x49, 0x00, 0x20, 0x03, 0x46, 0x00, 0x54, 0x00, 0x32, 0x00, 0x33, 0x00, 0x32, 0x00, 0x52, 0x00,
+        0x20, 0x00, 0x55, 0x00, 0x53, 0x00, 0x42, 0x00, 0x20, 0x00, 0x55, 0x00, 0x41, 0x00, 0x52, 0x00,
+        0x54, 0x00, 0x12, 0x03, 0x54, 0x00, 0x45, 0x00, 0x53, 0x00, 0x54, 0x00, 0x32, 0x00, 0x33, 0x00,
+        0x32, 0x00, 0x52, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x53, 0x7e,
+    ];
+    /// FT2232H: channel A FIFO with D2XX driver, channel B UART with VCP driver and DBUS7 suspend
+    const FT2232H_IMAGE: [u8; 0x100] = [
+        0x01, 0x88, 0x03, 0x04, 0x10, 0x60, 0x00, 0x07, 0x80, 0x32, 0x08, 0x00, 0x1b, 0x04, 0x9a, 0x0a,
+        0xa4, 0x1c, 0xc0, 0x12, 0x00, 0x00, 0x00, 0x00, 0x56, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x03, 0x46, 0x00, 0x54, 0x00,
+        0x44, 0x00, 0x49, 0x00, 0x1c, 0x03, 0x44, 0x00, 0x75, 0x00, 0x61, 0x00, 0x6c, 0x00, 0x20, 0x00,
+        0x52, 0x00, 0x53, 0x00, 0x32, 0x00, 0x33, 0x00, 0x32, 0x00, 0x2d, 0x00, 0x48, 0x00, 0x53, 0x00,
+        0x12, 0x03, 0x54, 0x00, 0x45, 0x00, 0x53, 0x00, 0x54, 0x00, 0x32, 0x00, 0x32, 0x00, 0x33, 0x00,
+        0x32, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x6a, 0xe7,
+    ];
+    /// FT232H: self powered FT1284 with VCP driver, SCLK idles high, flow control, 93x66 EEPROM
+    const FT232H_IMAGE: [u8; 0x100] = [
+        0x18, 0x05, 0x03, 0x04, 0x14, 0x60, 0x00, 0x09, 0xc0, 0x32, 0x08, 0x00, 0x09, 0x00, 0xa0, 0x0a,
+        0xaa, 0x20, 0xca, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x80, 0x04, 0x88, 0x00, 0x66, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0a, 0x03, 0x46, 0x00, 0x54, 0x00, 0x44, 0x00, 0x49, 0x00, 0x20, 0x03, 0x53, 0x00, 0x69, 0x00,
+        0x6e, 0x00, 0x67, 0x00, 0x6c, 0x00, 0x65, 0x00, 0x20, 0x00, 0x52, 0x00, 0x53, 0x00, 0x32, 0x00,
+        0x33, 0x00, 0x32, 0x00, 0x2d, 0x00, 0x48, 0x00, 0x53, 0x00, 0x12, 0x03, 0x54, 0x00, 0x45, 0x00,
+        0x53, 0x00, 0x54, 0x00, 0x32, 0x00, 0x33, 0x00, 0x32, 0x00, 0x48, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x37, 0xaa,
+    ];
+    /// FT230X, its user area holds data which isn't covered by checksum
+    const FT230X_IMAGE: [u8; 0x100] = [
+        0x00, 0x00, 0x03, 0x04, 0x15, 0x60, 0x00, 0x10, 0x80, 0x2d, 0x08, 0x00, 0x00, 0x00, 0xa0, 0x0a,
+        0xaa, 0x24, 0xce, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x08, 0x11, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0a, 0x03, 0x46, 0x00, 0x54, 0x00, 0x44, 0x00, 0x49, 0x00, 0x24, 0x03, 0x46, 0x00, 0x54, 0x00,
+        0x32, 0x00, 0x33, 0x00, 0x30, 0x00, 0x58, 0x00, 0x20, 0x00, 0x42, 0x00, 0x61, 0x00, 0x73, 0x00,
+        0x69, 0x00, 0x63, 0x00, 0x20, 0x00, 0x55, 0x00, 0x41, 0x00, 0x52, 0x00, 0x54, 0x00, 0x12, 0x03,
+        0x54, 0x00, 0x45, 0x00, 0x53, 0x00, 0x54, 0x00, 0x32, 0x00, 0x33, 0x00, 0x30, 0x00, 0x58, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x89, 0x5a,
+    ];
+
+    fn image(size: usize) -> Vec<u8> {
+        // smaller chips ignore upper address bits, their content repeats
+        (0..FTDI_MAX_EEPROM_SIZE).map(|index| ((index % size) * 5 + 1) as u8).collect()
+    }
+
+    #[test]
+    fn eeprom_size_detection() {
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_2232C, &image(0x80)), (0x80, EEPROM_CHIP_93X46));
+        let mut buf = image(0x100);
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_2232H, &buf), (0x100, EEPROM_CHIP_93X56));
+        buf[0x18] = 0x66;
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_2232H, &buf), (0x100, EEPROM_CHIP_93X66));
+        // chip type is elsewhere on FT232H
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_232H, &buf), (0x100, EEPROM_CHIP_93X56));
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_232H, &[0xff; FTDI_MAX_EEPROM_SIZE]), (-1, -1));
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_232H, &buf[..0x80]), (-1, -1));
+        // internal EEPROMs
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_R, &image(0x80)), (FT232R_EEPROM_SIZE, 0));
+        assert_eq!(ftdi_eeprom::detect_size(ftdi_chip_type::TYPE_230X, &image(0x80)), (FTX_EEPROM_SIZE, 0));
+        assert_eq!(ftdi_eeprom::chip_type_location(ftdi_chip_type::TYPE_BM), Some(0x14));
+        assert_eq!(ftdi_eeprom::chip_type_location(ftdi_chip_type::TYPE_R), None);
+    }
+
+    #[test]
+    fn eeprom_decode_ft232r() {
+        let eeprom = ftdi_eeprom::decode(&FT232R_IMAGE, ftdi_chip_type::TYPE_R).unwrap();
+        assert_eq!((eeprom.size, eeprom.chip), (0x80, 0));
+        assert_eq!((eeprom.vendor_id, eeprom.product_id, eeprom.release_number), (0x0403, 0x6001, 0x0600));
+        assert_eq!((eeprom.self_powered, eeprom.remote_wakeup, eeprom.max_power), (0, 1, 90));
+        assert!(eeprom.use_serial);
+        assert_eq!(eeprom.usb_version, 0x0200);
+        assert_eq!(eeprom.manufacturer, "FTDI");
+        assert_eq!(eeprom.product, "FT232R USB UART");
+        assert_eq!(eeprom.serial, "TEST232R");
+        assert_eq!(eeprom.channel_a_driver, DRIVER_VCP as i32);
+        assert_eq!(eeprom.high_current, 0);
+        assert_eq!(eeprom.cbus_function[..5], [3, 2, 0, 1, 5]);
+        assert_eq!(eeprom.buf, FT232R_IMAGE.to_vec());
+        // image read over USB is twice as long, upper half repeats
+        let mut read = FT232R_IMAGE.to_vec();
+        read.extend_from_slice(&FT232R_IMAGE);
+        assert_eq!(ftdi_eeprom::decode(&read, ftdi_chip_type::TYPE_R).unwrap(), eeprom);
+    }
+
+    #[test]
+    fn eeprom_decode_ft2232h() {
+        let eeprom = ftdi_eeprom::decode(&FT2232H_IMAGE, ftdi_chip_type::TYPE_2232H).unwrap();
+        assert_eq!((eeprom.size, eeprom.chip), (0x100, EEPROM_CHIP_93X56));
+        assert_eq!(eeprom.product_id, 0x6010);
+        assert_eq!(eeprom.product, "Dual RS232-HS");
+        assert_eq!(eeprom.serial, "TEST2232");
+        assert_eq!((eeprom.channel_a_type, eeprom.channel_a_driver), (CHANNEL_IS_FIFO as i32, 0));
+        assert_eq!((eeprom.channel_b_type, eeprom.channel_b_driver), (CHANNEL_IS_UART as i32, DRIVER_VCP as i32));
+        assert_eq!(eeprom.suspend_dbus7, 1);
+        assert_eq!((eeprom.group0_drive, eeprom.group0_schmitt, eeprom.group0_slew), (DRIVE_16MA as i32, 1, 0));
+        assert_eq!((eeprom.group1_drive, eeprom.group1_schmitt, eeprom.group1_slew), (DRIVE_8MA as i32, 0, 0));
+        assert_eq!((eeprom.group2_drive, eeprom.group2_schmitt, eeprom.group2_slew), (DRIVE_4MA as i32, 0, 1));
+    }
+
+    #[test]
+    fn eeprom_decode_ft232h() {
+        let eeprom = ftdi_eeprom::decode(&FT232H_IMAGE, ftdi_chip_type::TYPE_232H).unwrap();
+        assert_eq!((eeprom.size, eeprom.chip), (0x100, EEPROM_CHIP_93X66));
+        assert_eq!((eeprom.self_powered, eeprom.max_power), (1, 100));
+        assert_eq!(eeprom.product, "Single RS232-HS");
+        assert_eq!((eeprom.channel_a_type, eeprom.channel_a_driver), (CHANNEL_IS_FT1284 as i32, DRIVER_VCP as i32));
+        assert_eq!((eeprom.clock_polarity, eeprom.data_order, eeprom.flow_control, eeprom.powersave), (1, 0, 1, 0));
+        assert_eq!((eeprom.group0_drive, eeprom.group0_schmitt), (DRIVE_8MA as i32, 1));
+        assert_eq!(*eeprom.cbus_function, [0, 0, 2, 1, 0, 8, 4, 0, 8, 8]);
+    }
+
+    #[test]
+    fn eeprom_decode_ft230x() {
+        let eeprom = ftdi_eeprom::decode(&FT230X_IMAGE, ftdi_chip_type::TYPE_230X).unwrap();
+        assert_eq!((eeprom.size, eeprom.chip), (0x100, 0));
+        assert_eq!(eeprom.product, "FT230X Basic UART");
+        assert_eq!(eeprom.serial, "TEST230X");
+        assert_eq!(eeprom.cbus_function[..4], [1, 2, 8, 0x11]);
+        // user area may change without checksum update
+        let mut image = FT230X_IMAGE;
+        image[0x44] = 0xff;
+        assert!(ftdi_eeprom::decode(&image, ftdi_chip_type::TYPE_230X).is_ok());
+    }
+
+    #[test]
+    fn eeprom_decode_rejects_corrupted_image() {
+        let mut image = FT2232H_IMAGE;
+        image[0x04] = 0x11;
+        assert!(ftdi_eeprom::decode(&image, ftdi_chip_type::TYPE_2232H).is_err());
+        assert!(ftdi_eeprom::decode(&FT2232H_IMAGE[..0x40], ftdi_chip_type::TYPE_2232H).is_err());
+        assert!(ftdi_eeprom::decode(&[0xff; FTDI_MAX_EEPROM_SIZE], ftdi_chip_type::TYPE_2232H).is_err());
+        // serial string runs past the end of EEPROM
+        let mut image = FT232R_IMAGE;
+        image[0x13] = 0x40;
+        let checksum = ftdi_eeprom::checksum(ftdi_chip_type::TYPE_R, &image, 0x80);
+        image[0x7e..].copy_from_slice(&checksum.to_le_bytes());
+        assert!(ftdi_eeprom::decode(&image, ftdi_chip_type::TYPE_R).is_err());
+    }
+
+    #[test]
+    fn eeprom_build_round_trip() {
+        for (image, chip_type) in [(&FT232R_IMAGE[..], ftdi_chip_type::TYPE_R), (&FT2232H_IMAGE[..], ftdi_chip_type::TYPE_2232H),
+                (&FT232H_IMAGE[..], ftdi_chip_type::TYPE_232H), (&FT230X_IMAGE[..], ftdi_chip_type::TYPE_230X)].iter() {
+            let eeprom = ftdi_eeprom::decode(image, *chip_type).unwrap();
+            assert_eq!(eeprom.build(*chip_type).unwrap(), image.to_vec(), "{:?}", chip_type);
+        }
+    }
+
+    #[test]
+    fn eeprom_build_from_fields() {
+        let mut eeprom = ftdi_eeprom {
+            vendor_id: 0x0403, product_id: 0x6001, release_number: 0x0600, remote_wakeup: 1, max_power: 90,
+            use_serial: true, usb_version: 0x0200, channel_a_driver: DRIVER_VCP as i32,
+            manufacturer: "FTDI".to_string(), product: "FT232R USB UART".to_string(), serial: "TEST232R".to_string(),
+            ..ftdi_eeprom::default()
+        };
+        eeprom.cbus_function[..5].copy_from_slice(&[3, 2, 0, 1, 5]);
+        assert_eq!(eeprom.build(ftdi_chip_type::TYPE_R).unwrap(), FT232R_IMAGE.to_vec());
+
+        // longer serial moves nothing else, shorter one leaves no trace of the old one
+        eeprom.buf = FT232R_IMAGE.to_vec();
+        eeprom.serial = "X".to_string();
+        let image = eeprom.build(ftdi_chip_type::TYPE_R).unwrap();
+        assert_eq!(image[0x13], 4);
+        assert!(image[0x46..0x7e].iter().all(|byte| *byte == 0));
+        eeprom.serial = "ABCDEFGHIJKLMNOP".to_string();
+        let decoded = ftdi_eeprom::decode(&eeprom.build(ftdi_chip_type::TYPE_R).unwrap(), ftdi_chip_type::TYPE_R).unwrap();
+        assert_eq!(decoded.serial, "ABCDEFGHIJKLMNOP");
+        assert_eq!(decoded.product, "FT232R USB UART");
+    }
+
+    #[test]
+    fn eeprom_build_checks_layout() {
+        let mut eeprom = ftdi_eeprom::decode(&FT232R_IMAGE, ftdi_chip_type::TYPE_R).unwrap();
+        // 0x66 bytes are left for strings in 128 byte EEPROM
+        eeprom.product = "x".repeat(40);
+        assert!(eeprom.build(ftdi_chip_type::TYPE_R).is_err());
+        eeprom.product = "x".repeat(30);
+        assert!(eeprom.build(ftdi_chip_type::TYPE_R).is_ok());
+        eeprom.max_power = 600;
+        assert!(eeprom.build(ftdi_chip_type::TYPE_R).is_err());
+
+        let mut eeprom = ftdi_eeprom::decode(&FT2232H_IMAGE, ftdi_chip_type::TYPE_2232H).unwrap();
+        eeprom.user_data[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        eeprom.user_data_size = 4;
+        // right after strings
+        let image = eeprom.build(ftdi_chip_type::TYPE_2232H).unwrap();
+        assert_eq!(image[0xd2..0xd6], [0xde, 0xad, 0xbe, 0xef]);
+        assert!(ftdi_eeprom::decode(&image, ftdi_chip_type::TYPE_2232H).is_ok());
+        eeprom.user_data_addr = 0x40;
+        assert_eq!(eeprom.build(ftdi_chip_type::TYPE_2232H).unwrap()[0x40..0x44], [0xde, 0xad, 0xbe, 0xef]);
+        for address in [0x10, 0xa0, 0xfc].iter() {
+            eeprom.user_data_addr = *address;
+            assert!(eeprom.build(ftdi_chip_type::TYPE_2232H).is_err(), "user area at {:#x}", address);
+        }
+        let mut eeprom = ftdi_eeprom::decode(&FT230X_IMAGE, ftdi_chip_type::TYPE_230X).unwrap();
+        eeprom.user_data_size = 4;
+        eeprom.user_data_addr = 0x7e;
+        assert!(eeprom.build(ftdi_chip_type::TYPE_230X).is_err());
+    }
+
+    #[test]
+    fn eeprom_user_area() {
+        let mut eeprom = ftdi_eeprom::decode(&FT2232H_IMAGE, ftdi_chip_type::TYPE_2232H).unwrap();
+        // from the end of strings to the checksum
+        assert_eq!(eeprom.user_area(ftdi_chip_type::TYPE_2232H).unwrap(), 0xd2..0xfe);
+        assert!(eeprom.user_area_data(ftdi_chip_type::TYPE_2232H).unwrap().iter().all(|byte| *byte == 0));
+        let changed = eeprom.write_user_area(ftdi_chip_type::TYPE_2232H, 3, b"rev B").unwrap();
+        assert_eq!(changed, vec![0x6a, 0x6b, 0x6c, 0x7f]);
+        assert_eq!(eeprom.user_area_data(ftdi_chip_type::TYPE_2232H).unwrap()[..8], *b"\0\0\0rev B");
+        assert_eq!(eeprom.buf[..0xd2], FT2232H_IMAGE[..0xd2]);
+        let decoded = ftdi_eeprom::decode(&eeprom.buf, ftdi_chip_type::TYPE_2232H).unwrap();
+        assert_eq!((decoded.manufacturer.as_str(), decoded.serial.as_str()), (eeprom.manufacturer.as_str(), eeprom.serial.as_str()));
+        assert!(eeprom.write_user_area(ftdi_chip_type::TYPE_2232H, 0x28, &[0; 5]).is_err());
+
+        // at user_data_addr up to the strings, as build() puts it
+        eeprom.user_data_addr = 0x40;
+        assert_eq!(eeprom.user_area(ftdi_chip_type::TYPE_2232H).unwrap(), 0x40..0x9a);
+        eeprom.user_data_addr = 0xb0;
+        assert!(eeprom.user_area(ftdi_chip_type::TYPE_2232H).is_err());
+
+        // FT-X words the checksum doesn't cover
+        let mut eeprom = ftdi_eeprom::decode(&FT230X_IMAGE, ftdi_chip_type::TYPE_230X).unwrap();
+        assert_eq!(eeprom.user_area(ftdi_chip_type::TYPE_230X).unwrap(), 0x24..0x80);
+        let changed = eeprom.write_user_area(ftdi_chip_type::TYPE_230X, 0, b"rev C").unwrap();
+        assert_eq!(changed, vec![0x12, 0x13, 0x14]);
+        eeprom.user_data[..2].copy_from_slice(b"id");
+        eeprom.user_data_size = 2;
+        assert_eq!(eeprom.build(ftdi_chip_type::TYPE_230X).unwrap()[0x24..0x26], *b"id");
+        eeprom.buf[0x02] ^= 0x01;
+        assert!(eeprom.write_user_area(ftdi_chip_type::TYPE_230X, 0, &[1]).is_err());
+    }
+
+    #[test]
+    fn eeprom_values_by_name() {
+        let mut eeprom = ftdi_eeprom::decode(&FT232R_IMAGE, ftdi_chip_type::TYPE_R).unwrap();
+        assert_eq!(eeprom.get_value(ftdi_eeprom_value::PRODUCT_ID).unwrap(), 0x6001);
+        assert_eq!(eeprom.get_value(ftdi_eeprom_value::CBUS_FUNCTION_4).unwrap(), 5);
+        assert_eq!(eeprom.get_value(ftdi_eeprom_value::USE_SERIAL).unwrap(), 1);
+        eeprom.set_value("cbus_function_2".parse().unwrap(), 0x0a).unwrap();
+        eeprom.set_value(ftdi_eeprom_value::HIGH_CURRENT, 1).unwrap();
+        let decoded = ftdi_eeprom::decode(&eeprom.build(ftdi_chip_type::TYPE_R).unwrap(), ftdi_chip_type::TYPE_R).unwrap();
+        assert_eq!(decoded.get_value(ftdi_eeprom_value::CBUS_FUNCTION_2).unwrap(), 0x0a);
+        assert_eq!(decoded.get_value(ftdi_eeprom_value::HIGH_CURRENT).unwrap(), HIGH_CURRENT_DRIVE_R as i32);
+        // FT232R has neither these values nor 16 CBUS functions
+        assert!(eeprom.get_value(ftdi_eeprom_value::GROUP0_DRIVE).is_err());
+        assert!(eeprom.set_value(ftdi_eeprom_value::CBUS_FUNCTION_5, 1).is_err());
+        assert!(eeprom.set_value(ftdi_eeprom_value::CBUS_FUNCTION_0, 0x10).is_err());
+        assert!(eeprom.set_value(ftdi_eeprom_value::CHIP_TYPE, EEPROM_CHIP_93X46).is_err());
+        assert!(eeprom.set_value(ftdi_eeprom_value::CHIP_SIZE, 0x100).is_err());
+        assert!(eeprom.set_value(ftdi_eeprom_value::MAX_POWER, 510).is_err());
+
+        let mut eeprom = ftdi_eeprom::decode(&FT2232H_IMAGE, ftdi_chip_type::TYPE_2232H).unwrap();
+        assert!(eeprom.set_value(ftdi_eeprom_value::CHANNEL_C_RS485, 1).is_err());
+        eeprom.set_value(ftdi_eeprom_value::GROUP3_DRIVE, DRIVE_12MA as i32).unwrap();
+        eeprom.set_value(ftdi_eeprom_value::CHIP_TYPE, EEPROM_CHIP_93X66).unwrap();
+        let image = eeprom.build(ftdi_chip_type::TYPE_2232H).unwrap();
+        assert_eq!((image[0x0d], image[0x18]), (0x24, 0x66));
+        // structure not bound to chip accepts everything
+        let mut eeprom = ftdi_eeprom::default();
+        for value in ftdi_eeprom_value::all().filter(|value| *value != ftdi_eeprom_value::CHIP_SIZE) {
+            assert!(eeprom.get_value(value).is_ok());
+            assert!(eeprom.set_value(value, EEPROM_CHIP_93X56).is_err() || eeprom.get_value(value).unwrap() != 0, "{}", value);
+        }
+    }
+}
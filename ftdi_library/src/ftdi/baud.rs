@@ -0,0 +1,281 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use log::debug;
+use crate::ftdi::chip::{chip_info, ChipCapabilities, BAUD_CLOCK_120MHZ, BAUD_CLOCK_48MHZ};
+use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface};
+use crate::ftdi::ftdi_context::{Result, common_error};
+
+/// Encoded sub-integer divisor bits by eighths of the fractional part, see AN232B-05
+pub const FRAC_CODE: [u32; 8] = [0, 3, 2, 4, 1, 5, 6, 7];
+/// Encoded divisor bit switching hi-speed chips to 120 MHz / 10 clock
+pub const DIVISOR_CLOCK_120MHZ: u32 = 0x20000;
+/// Largest divisor in eighths, 14 bit integer part and 3 bit fraction
+pub const MAX_DIVISOR: u32 = 0x1ffff;
+
+/// Baud rate generator clock divided down to the rate of divisor 1
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BaudClock {
+    /// 3 MHz of AM chips, they have only 0, 1/8, 1/4 and 1/2 fractions
+    Am3MHz,
+    /// 48 MHz / 16
+    Clock3MHz,
+    /// 120 MHz / 10 of hi-speed chips (H-clock)
+    HClock12MHz,
+}
+impl BaudClock {
+    /// Rate of divisor 1, Hz
+    pub fn frequency(self) -> u32 {
+        match self {
+            BaudClock::Am3MHz | BaudClock::Clock3MHz => BAUD_CLOCK_48MHZ / 16,
+            BaudClock::HClock12MHz => BAUD_CLOCK_120MHZ / 10,
+        }
+    }
+}
+
+/// Divisor closest to requested baud rate and what it gives
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BaudSolution {
+    pub requested: u32,
+    /// Rate the chip runs at, rounded to integer
+    pub achieved: u32,
+    /// (achieved - requested) / requested, percent
+    pub error: f64,
+    /// wValue of SIO_SET_BAUDRATE_REQUEST
+    pub value: u16,
+    /// wIndex of SIO_SET_BAUDRATE_REQUEST, it carries the channel on multi channel chips
+    pub index: u16,
+    pub clock: BaudClock,
+    /// Divisor of clock in eighths
+    pub divisor: u32,
+}
+
+/// Find divisor of chip channel closest to requested baud rate
+pub fn compute_baud_divisor(chip: ftdi_chip_type, interface: ftdi_interface, requested: u32) -> Result<BaudSolution> {
+    debug!("start 'compute_baud_divisor' {:?} {:?} requested = {} ...", chip, interface, requested);
+    if requested == 0 {
+        return Err(common_error(-2, "Incorrect baudrate".to_string()));
+    }
+    let info = chip_info(chip);
+    let (achieved, encoded_divisor, clock) = if info.high_speed {
+        if requested as u64 * 10 > (BAUD_CLOCK_120MHZ / 0x3fff) as u64 {
+            /* On H Devices, use 12 000 000 Baudrate when possible
+               We have a 14 bit divisor, a 1 bit divisor switch (10 or 16)
+               three fractional bits and a 120 MHz clock
+               Assume AN_120 "Sub-integer divisors between 0 and 2 are not allowed" holds for
+               DIV/10 CLK too, so /1, /1.5 and /2 can be handled the same */
+            let (achieved, encoded_divisor) = to_clkbits(requested, BAUD_CLOCK_120MHZ, 10);
+            (achieved, encoded_divisor | DIVISOR_CLOCK_120MHZ, BaudClock::HClock12MHz)
+        } else {
+            let (achieved, encoded_divisor) = to_clkbits(requested, BAUD_CLOCK_48MHZ, 16);
+            (achieved, encoded_divisor, BaudClock::Clock3MHz)
+        }
+    } else if chip == ftdi_chip_type::TYPE_AM {
+        let (achieved, encoded_divisor) = to_clkbits_am(requested);
+        (achieved, encoded_divisor, BaudClock::Am3MHz)
+    } else {
+        let (achieved, encoded_divisor) = to_clkbits(requested, BAUD_CLOCK_48MHZ, 16);
+        (achieved, encoded_divisor, BaudClock::Clock3MHz)
+    };
+    // Split into "value" and "index" values, multi channel chips take the channel in the low byte of index
+    let port: u8 = if interface == ftdi_interface::INTERFACE_ANY { ftdi_interface::INTERFACE_A.into() } else { interface.into() };
+    let value = (encoded_divisor & 0xffff) as u16;
+    let index = if info.high_speed || info.interfaces > 1 {
+        ((encoded_divisor >> 8) & 0xff00) as u16 | port as u16
+    } else {
+        (encoded_divisor >> 16) as u16
+    };
+    let divisor = match encoded_divisor & 0x1ffff {
+        0 => 8,
+        1 => 12,
+        encoded => (encoded & 0x3fff) * 8
+            + FRAC_CODE.iter().position(|code| *code == (encoded >> 14) & 7).unwrap_or_default() as u32,
+    };
+    let solution = BaudSolution {
+        requested, achieved, error: (achieved as f64 - requested as f64) * 100.0 / requested as f64, value, index, clock, divisor,
+    };
+    debug!("'compute_baud_divisor' - OK, {:?}", solution);
+    Ok(solution)
+}
+
+/// Divisor of AM chips, it's in 1/8 of 3 MHz clock steps with only 0, 1/8, 1/4 and 1/2 fractions
+/// return achieved baud rate and encoded divisor
+fn to_clkbits_am(baudrate: u32) -> (u32, u32) {
+    const AM_ADJUST_UP: [u32; 8] = [0, 0, 0, 1, 0, 3, 2, 1];
+    const AM_ADJUST_DN: [u32; 8] = [0, 0, 0, 1, 0, 1, 2, 3];
+    let mut divisor = 24_000_000 / baudrate;
+    // Round down to supported fraction (AM only)
+    divisor -= AM_ADJUST_DN[(divisor & 7) as usize];
+    let mut best_divisor = 0;
+    let mut best_baud = 0;
+    let mut best_baud_diff = 0;
+    // Try this divisor and the one above it (because division rounds down)
+    for i in 0..2 {
+        let mut try_divisor = divisor + i;
+        if try_divisor <= 8 {
+            // Round up to minimum supported divisor
+            try_divisor = 8;
+        } else if divisor < 16 {
+            // AM doesn't support divisors 9 through 15 inclusive
+            try_divisor = 16;
+        } else {
+            // Round up to supported fraction (AM only)
+            try_divisor += AM_ADJUST_UP[(try_divisor & 7) as usize];
+            if try_divisor > 0x1fff8 {
+                // Round down to maximum supported divisor value (for AM)
+                try_divisor = 0x1fff8;
+            }
+        }
+        // Get estimated baud rate (to nearest integer)
+        let baud_estimate = (24_000_000 + try_divisor / 2) / try_divisor;
+        let baud_diff = baudrate.abs_diff(baud_estimate);
+        if i == 0 || baud_diff < best_baud_diff {
+            // Closest to requested baud rate so far
+            best_divisor = try_divisor;
+            best_baud = baud_estimate;
+            best_baud_diff = baud_diff;
+            if baud_diff == 0 {
+                // Spot on! No point trying
+                break;
+            }
+        }
+    }
+    // Encode the best divisor value
+    let mut encoded_divisor = (best_divisor >> 3) | (FRAC_CODE[(best_divisor & 7) as usize] << 14);
+    // Deal with special cases for encoded value
+    if encoded_divisor == 1 {
+        encoded_divisor = 0; // 3000000 baud
+    } else if encoded_divisor == 0x4001 {
+        encoded_divisor = 1; // 2000000 baud (BM only)
+    }
+    (best_baud, encoded_divisor)
+}
+
+/// Divisor for a given system clock and predivisor, see AN120
+///    clk/1   -> 0
+///    clk/1.5 -> 1
+///    clk/2   -> 2
+///    From /2, 0.125 steps may be taken.
+///    The fractional part has frac_code encoding
+///
+///    value[13:0] of value is the divisor
+///    index[9] mean 12 MHz Base(120 MHz/10) rate versus 3 MHz (48 MHz/16) else
+///
+///    H Type have all features above with
+///    {index[8],value[15:14]} is the encoded subdivisor
+///
+///    FT232R, FT2232 and FT232BM have no option for 12 MHz and with
+///    {index[0],value[15:14]} is the encoded subdivisor
+/// return achieved baud rate and encoded divisor
+fn to_clkbits(baudrate: u32, clk: u32, clk_div: u32) -> (u32, u32) {
+    if baudrate >= clk / clk_div {
+        (clk / clk_div, 0)
+    } else if baudrate >= clk / (clk_div + clk_div / 2) {
+        (clk / (clk_div + clk_div / 2), 1)
+    } else if baudrate >= clk / (2 * clk_div) {
+        (clk / (2 * clk_div), 2)
+    } else {
+        // We divide by 16 to have 3 fractional bits and one bit for rounding
+        let divisor = clk as u64 * 16 / clk_div as u64 / baudrate as u64;
+        // Decide if to round up or down
+        let mut best_divisor = if divisor & 1 != 0 { divisor / 2 + 1 } else { divisor / 2 };
+        if best_divisor > 0x20000 {
+            best_divisor = 0x1ffff;
+        }
+        let best_baud = clk as u64 * 16 / clk_div as u64 / best_divisor;
+        let best_baud = if best_baud & 1 != 0 { best_baud / 2 + 1 } else { best_baud / 2 };
+        let encoded_divisor = (best_divisor >> 3) as u32 | (FRAC_CODE[(best_divisor & 7) as usize] << 14);
+        (best_baud as u32, encoded_divisor)
+    }
+}
+
+/// How far achieved baud rate may be from requested one for ftdi_set_baudrate
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BaudPolicy {
+    /// The higher of achieved and requested rates is at most the percentage above the lower one.
+    /// Default 5% is libftdi's window: BaudSolution::error from -4.76% (-1/21) to +5%.
+    Strict(f64),
+    /// Closest rate whatever it is
+    BestEffort,
+    /// Only rates the chip generates exactly, 250000 (DMX) and 31250 (MIDI) among them
+    Exact,
+}
+impl Default for BaudPolicy {
+    fn default() -> Self {
+        BaudPolicy::Strict(5.0)
+    }
+}
+/// "5%", "best_effort" or "exact"
+impl Display for BaudPolicy {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BaudPolicy::Strict(percent) => write!(f, "{}%", percent),
+            BaudPolicy::BestEffort => write!(f, "best_effort"),
+            BaudPolicy::Exact => write!(f, "exact"),
+        }
+    }
+}
+impl FromStr for BaudPolicy {
+    type Err = String;
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "best_effort" => Ok(BaudPolicy::BestEffort),
+            "exact" => Ok(BaudPolicy::Exact),
+            _ => Some(value).filter(|value| value.ends_with('%'))
+                .and_then(|value| value[..value.len() - 1].trim().parse::<f64>().ok())
+                .filter(|percent| *percent >= 0.0)
+                .map(BaudPolicy::Strict)
+                .ok_or_else(|| format!("baud policy '{}' is unknown, it's percentage, 'best_effort' or 'exact'", value)),
+        }
+    }
+}
+impl BaudPolicy {
+    pub fn check(&self, solution: &BaudSolution) -> Result<()> {
+        let accepted = match *self {
+            BaudPolicy::Strict(percent) => {
+                let (low, high) = if solution.achieved < solution.requested {
+                    (solution.achieved, solution.requested)
+                } else {
+                    (solution.requested, solution.achieved)
+                };
+                high as f64 * 100.0 <= low as f64 * (100.0 + percent)
+            }
+            BaudPolicy::BestEffort => true,
+            BaudPolicy::Exact => solution.achieved == solution.requested
+                && (solution.clock.frequency() as u64 * 8).is_multiple_of(solution.divisor as u64),
+        };
+        if !accepted {
+            return Err(common_error(-1, format!("Unsupported baudrate {}, closest is {} ({:+.2}%) with {:?} policy. \
+                Note: bitbang baudrates are automatically multiplied by 4",
+                solution.requested, solution.achieved, solution.error, self)));
+        }
+        Ok(())
+    }
+}
+
+
+/// Baud rates chip channel generates exactly, 'count' of them closest to 'target' first
+pub fn exact_baudrates(chip: ftdi_chip_type, interface: ftdi_interface, target: u32, count: usize) -> Result<Vec<u32>> {
+    debug!("start 'exact_baudrates' {:?} {:?} target = {} ...", chip, interface, target);
+    let capabilities = ChipCapabilities::new(chip, interface)?;
+    let mut rates = BTreeSet::new();
+    for clk in capabilities.baud_clocks {
+        let clock = if *clk == BAUD_CLOCK_120MHZ { clk / 10 } else { clk / 16 };
+        // divisor in eighths divides clock without remainder
+        for divisor in (8..=MAX_DIVISOR).filter(|divisor| clock * 8 % divisor == 0) {
+            let rate = clock * 8 / divisor;
+            // the rate may be reached with other clock or not at all (AM fractions)
+            let solution = compute_baud_divisor(chip, interface, rate)?;
+            if rate <= capabilities.max_baudrate && solution.clock.frequency() == clock && solution.divisor == divisor {
+                rates.insert(rate);
+            }
+        }
+    }
+    let mut rates: Vec<u32> = rates.into_iter().collect();
+    rates.sort_by_key(|rate| (*rate as i64 - target as i64).abs());
+    rates.truncate(count);
+    debug!("'exact_baudrates' - OK, {:?}", rates);
+    Ok(rates)
+}
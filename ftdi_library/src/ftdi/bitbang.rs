@@ -0,0 +1,156 @@
+#![allow(dead_code)]
+
+use log::{debug, info};
+use crate::ftdi::constants::ftdi_mpsse_mode;
+use crate::ftdi::ftdi_context::{ftdi_context, Result, common_error};
+use crate::ftdi::mpsse::{read_exact, write_all};
+
+/// ftdi_set_baudrate multiplies the baud rate by this value when bitbang is enabled,
+/// pins are updated at the multiplied rate
+pub const BITBANG_BAUDRATE_MULTIPLIER: u32 = 4;
+/// Bytes written before their samples are read back in synchronous mode, the chip stops
+/// clocking out data when its receive buffer is full
+pub const BITBANG_SYNC_CHUNK: usize = 128;
+
+/// Chip functions used by bitbang port
+pub trait BitbangTransport {
+    /// Enable bitbang mode, 'direction' bit 1 makes the pin an output
+    fn set_bitmode(&mut self, direction: u8, mode: ftdi_mpsse_mode) -> Result<()>;
+    /// Set baud rate, it's multiplied by BITBANG_BAUDRATE_MULTIPLIER while bitbang is enabled
+    fn set_baudrate(&mut self, baudrate: u32) -> Result<()>;
+    /// Write all bytes
+    fn write_data(&mut self, data: &[u8]) -> Result<()>;
+    /// Read exactly 'size' bytes
+    fn read_data(&mut self, size: usize) -> Result<Vec<u8>>;
+    /// Read pins right now, bypassing data buffers
+    fn read_pins(&mut self) -> Result<u8>;
+}
+
+impl BitbangTransport for ftdi_context {
+    fn set_bitmode(&mut self, direction: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+        self.ftdi_set_bitmode(direction, mode)
+    }
+
+    fn set_baudrate(&mut self, baudrate: u32) -> Result<()> {
+        self.ftdi_set_baudrate(baudrate as i32)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        write_all(self, data, "bitbang")
+    }
+
+    fn read_data(&mut self, size: usize) -> Result<Vec<u8>> {
+        read_exact(self, size, "bitbang")
+    }
+
+    fn read_pins(&mut self) -> Result<u8> {
+        self.ftdi_read_pins()
+    }
+}
+
+/// Bitbang flavours
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BitbangMode {
+    /// Written bytes are put on pins at the configured rate, nothing is read back
+    Async,
+    /// Pins are sampled for every written byte, samples are read back
+    Sync,
+}
+impl BitbangMode {
+    fn bitmode(self) -> ftdi_mpsse_mode {
+        match self {
+            BitbangMode::Async => ftdi_mpsse_mode::BITMODE_BITBANG,
+            BitbangMode::Sync => ftdi_mpsse_mode::BITMODE_SYNCBB,
+        }
+    }
+}
+
+/// Eight pins driven by byte patterns in asynchronous or synchronous bitbang mode
+pub struct BitbangPort<T: BitbangTransport> {
+    transport: T,
+    mode: BitbangMode,
+    direction: u8,
+    rate: u32,
+}
+impl<T: BitbangTransport> BitbangPort<T> {
+    /// Enable bitbang mode and set rate of pin updates (bytes per second)
+    pub fn open(transport: T, mode: BitbangMode, direction: u8, rate: u32) -> Result<Self> {
+        debug!("start 'bitbang open' {:?}, direction = {:#04x} ...", mode, direction);
+        let mut port = BitbangPort { transport, mode, direction, rate: 0 };
+        // bitbang must be enabled before the rate is set, otherwise the multiplier isn't applied
+        port.transport.set_bitmode(direction, mode.bitmode())?;
+        port.set_rate(rate)?;
+        info!("bitbang port opened, {:?}, direction = {:#04x}, rate = {} bytes/s", mode, direction, port.rate);
+        Ok(port)
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Leave bitbang mode and return the transport
+    pub fn close(mut self) -> Result<T> {
+        self.transport.set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+        Ok(self.transport)
+    }
+
+    pub fn mode(&self) -> BitbangMode {
+        self.mode
+    }
+
+    pub fn direction(&self) -> u8 {
+        self.direction
+    }
+
+    /// Actual rate of pin updates, bytes per second
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Change pin directions, 1 makes the pin an output
+    pub fn set_direction(&mut self, direction: u8) -> Result<()> {
+        self.transport.set_bitmode(direction, self.mode.bitmode())?;
+        self.direction = direction;
+        Ok(())
+    }
+
+    /// Set rate of pin updates, bytes per second
+    /// return actual rate
+    pub fn set_rate(&mut self, rate: u32) -> Result<u32> {
+        let baudrate = rate / BITBANG_BAUDRATE_MULTIPLIER;
+        if baudrate == 0 {
+            return Err(common_error(-1, format!("bitbang rate {} is too low", rate)));
+        }
+        self.transport.set_baudrate(baudrate)?;
+        self.rate = baudrate * BITBANG_BAUDRATE_MULTIPLIER;
+        debug!("bitbang rate = {} bytes/s", self.rate);
+        Ok(self.rate)
+    }
+
+    /// Put byte patterns on pins one after another, samples of sync mode are dropped
+    pub fn write(&mut self, patterns: &[u8]) -> Result<()> {
+        match self.mode {
+            BitbangMode::Async => self.transport.write_data(patterns),
+            BitbangMode::Sync => self.write_read(patterns).map(|_| ()),
+        }
+    }
+
+    /// Put byte patterns on pins in sync mode
+    /// return pins sampled for every pattern, just before it was put on pins
+    pub fn write_read(&mut self, patterns: &[u8]) -> Result<Vec<u8>> {
+        if self.mode != BitbangMode::Sync {
+            return Err(common_error(-1, "pins are sampled for written bytes in sync bitbang mode only".to_string()));
+        }
+        let mut samples = Vec::with_capacity(patterns.len());
+        for chunk in patterns.chunks(BITBANG_SYNC_CHUNK) {
+            self.transport.write_data(chunk)?;
+            samples.extend(self.transport.read_data(chunk.len())?);
+        }
+        Ok(samples)
+    }
+
+    /// Read pins right now
+    pub fn read_pins(&mut self) -> Result<u8> {
+        self.transport.read_pins()
+    }
+}
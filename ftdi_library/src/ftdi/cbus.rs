@@ -0,0 +1,208 @@
+#![allow(dead_code)]
+
+use std::thread;
+use std::time::Duration;
+use log::{debug, info};
+use crate::ftdi::bitbang::BitbangTransport;
+use crate::ftdi::constants::{ftdi_chip_type, ftdi_mpsse_mode};
+use crate::ftdi::core::{ftdi_cbus_func, ftdi_cbush_func, ftdi_cbusx_func};
+use crate::ftdi::ftdi_context::{ftdi_context, Result, common_error};
+
+/// Number of pins switched by CBUS bitbang mode
+pub const CBUS_PIN_COUNT: u8 = 4;
+
+/// CBUS pin function stored in EEPROM, value meaning depends on chip type
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CbusFunction {
+    R(ftdi_cbus_func),
+    H(ftdi_cbush_func),
+    X(ftdi_cbusx_func),
+}
+impl CbusFunction {
+    /// Decode EEPROM cbus_function value for chip
+    /// return None for chips without CBUS functions and for values out of range
+    pub fn decode(chip: ftdi_chip_type, value: i32) -> Option<CbusFunction> {
+        match chip {
+            ftdi_chip_type::TYPE_R if (0..=0xc).contains(&value) =>
+                Some(CbusFunction::R(ftdi_cbus_func::from(value as u8))),
+            ftdi_chip_type::TYPE_232H | ftdi_chip_type::TYPE_232HP | ftdi_chip_type::TYPE_233HP
+                if (0..=12).contains(&value) =>
+                Some(CbusFunction::H(ftdi_cbush_func::from(value as u8))),
+            ftdi_chip_type::TYPE_230X if (0..=21).contains(&value) =>
+                Some(CbusFunction::X(ftdi_cbusx_func::from(value as u8))),
+            _ => None,
+        }
+    }
+
+    /// Function name without CBUS prefix as FTDI tools show it, e.g. "TXLED"
+    pub fn name(self) -> String {
+        let name = match self {
+            CbusFunction::R(function) => format!("{:?}", function),
+            CbusFunction::H(function) => format!("{:?}", function),
+            CbusFunction::X(function) => format!("{:?}", function),
+        };
+        name.split_once('_').map(|(_, name)| name).unwrap_or_default().to_string()
+    }
+
+    /// Find function of chip by name, case insensitive
+    pub fn from_name(chip: ftdi_chip_type, name: &str) -> Option<CbusFunction> {
+        (0..=0xff).filter_map(|value| CbusFunction::decode(chip, value))
+            .find(|function| function.name().eq_ignore_ascii_case(name))
+    }
+
+    /// EEPROM cbus_function value
+    pub fn value(self) -> i32 {
+        match self {
+            CbusFunction::R(function) => function as i32,
+            CbusFunction::H(function) => function as i32,
+            CbusFunction::X(function) => function as i32,
+        }
+    }
+
+    /// Pin is usable as GPIO in CBUS bitbang mode
+    pub fn is_iomode(self) -> bool {
+        matches!(self,
+            CbusFunction::R(ftdi_cbus_func::CBUS_IOMODE)
+            | CbusFunction::H(ftdi_cbush_func::CBUSH_IOMODE)
+            | CbusFunction::X(ftdi_cbusx_func::CBUSX_IOMODE))
+    }
+}
+
+/// EEPROM cbus_function slots of the pins switched by CBUS bitbang mode, CBUS0 first.
+/// FT232H/HP has them on ACBUS5, ACBUS6, ACBUS8 and ACBUS9.
+pub fn cbus_bitbang_slots(chip: ftdi_chip_type) -> Option<[usize; 4]> {
+    match chip {
+        ftdi_chip_type::TYPE_R | ftdi_chip_type::TYPE_230X => Some([0, 1, 2, 3]),
+        ftdi_chip_type::TYPE_232H | ftdi_chip_type::TYPE_232HP | ftdi_chip_type::TYPE_233HP => Some([5, 6, 8, 9]),
+        _ => None,
+    }
+}
+
+/// Four CBUS pins driven in CBUS bitbang mode. Only pins configured as IOMODE in EEPROM
+/// are accepted, the others keep their EEPROM function.
+/// Bitmode argument carries the pins: high nibble is direction (1 - output), low nibble is value.
+pub struct CbusPort<T: BitbangTransport> {
+    transport: T,
+    /// pins configured as IOMODE
+    available: u8,
+    value: u8,
+    direction: u8,
+}
+impl<T: BitbangTransport> CbusPort<T> {
+    /// Check EEPROM cbus_function slots and enable CBUS bitbang with all pins as inputs
+    pub fn open(transport: T, chip: ftdi_chip_type, cbus_function: &[i32]) -> Result<Self> {
+        debug!("start 'cbus open' for {:?} ...", chip);
+        let slots = cbus_bitbang_slots(chip)
+            .ok_or_else(|| common_error(-1, format!("{:?} has no CBUS bitbang mode", chip)))?;
+        let mut available = 0;
+        for (pin, slot) in slots.iter().enumerate() {
+            let value = cbus_function.get(*slot).copied().unwrap_or(-1);
+            match CbusFunction::decode(chip, value) {
+                Some(function) if function.is_iomode() => available |= 1 << pin,
+                function => debug!("CBUS{} (EEPROM slot {}) is {:?}, not IOMODE", pin, slot, function),
+            }
+        }
+        if available == 0 {
+            return Err(common_error(-1, format!(
+                "no CBUS pin of {:?} is configured as IOMODE in EEPROM, was EEPROM read and decoded?", chip)));
+        }
+        let mut port = CbusPort { transport, available, value: 0, direction: 0 };
+        port.update(0, 0)?;
+        info!("CBUS port opened, IOMODE pins = {:#03x}", available);
+        Ok(port)
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Leave CBUS bitbang mode and return the transport
+    pub fn close(mut self) -> Result<T> {
+        self.transport.set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+        Ok(self.transport)
+    }
+
+    /// Mask of pins configured as IOMODE
+    pub fn available(&self) -> u8 {
+        self.available
+    }
+
+    /// Cached value and direction of pins
+    pub fn state(&self) -> (u8, u8) {
+        (self.value, self.direction)
+    }
+
+    pub fn set_direction(&mut self, pin: u8, output: bool) -> Result<()> {
+        let mask = self.pin_mask(pin)?;
+        let direction = if output { self.direction | mask } else { self.direction & !mask };
+        self.update(self.value, direction)
+    }
+
+    pub fn set_value(&mut self, pin: u8, high: bool) -> Result<()> {
+        let mask = self.pin_mask(pin)?;
+        self.write(mask, if high { mask } else { 0 })
+    }
+
+    /// Set values of pins in mask at once
+    pub fn write(&mut self, mask: u8, value: u8) -> Result<()> {
+        self.check_available(mask)?;
+        self.update((self.value & !mask) | (value & mask), self.direction)
+    }
+
+    /// Drive pin to 'level' for 'duration' then back, e.g. to pulse board reset.
+    /// Pin is made an output.
+    pub fn pulse(&mut self, pin: u8, level: bool, duration: Duration) -> Result<()> {
+        let mask = self.pin_mask(pin)?;
+        let idle = if level { self.value & !mask } else { self.value | mask };
+        let active = if level { self.value | mask } else { self.value & !mask };
+        let direction = self.direction | mask;
+        self.update(active, direction)?;
+        thread::sleep(duration);
+        self.update(idle, direction)
+    }
+
+    /// Read levels of the four pins right now
+    pub fn read(&mut self) -> Result<u8> {
+        Ok(self.transport.read_pins()? & 0x0f)
+    }
+
+    pub fn read_pin(&mut self, pin: u8) -> Result<bool> {
+        let mask = self.pin_mask(pin)?;
+        Ok(self.read()? & mask != 0)
+    }
+
+    fn pin_mask(&self, pin: u8) -> Result<u8> {
+        if pin >= CBUS_PIN_COUNT {
+            return Err(common_error(-1, format!("there is no CBUS{} in CBUS bitbang mode", pin)));
+        }
+        self.check_available(1 << pin)?;
+        Ok(1 << pin)
+    }
+
+    fn check_available(&self, mask: u8) -> Result<()> {
+        let foreign = mask & !self.available;
+        if foreign != 0 {
+            return Err(common_error(-1, format!("CBUS pins {:#04x} are not configured as IOMODE in EEPROM", foreign)));
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, value: u8, direction: u8) -> Result<()> {
+        let bitmask = ((direction & 0x0f) << 4) | (value & 0x0f);
+        self.transport.set_bitmode(bitmask, ftdi_mpsse_mode::BITMODE_CBUS)?;
+        self.value = value;
+        self.direction = direction;
+        debug!("CBUS value = {:#03x}, direction = {:#03x}", value, direction);
+        Ok(())
+    }
+}
+impl CbusPort<ftdi_context> {
+    /// Open CBUS port using chip type and CBUS functions read from EEPROM
+    pub fn from_context(mut ftdi: ftdi_context) -> Result<Self> {
+        ftdi.ftdi_read_eeprom()?;
+        ftdi.ftdi_eeprom_decode()?;
+        let chip = ftdi.r#type;
+        let cbus_function = *ftdi.eeprom.cbus_function;
+        CbusPort::open(ftdi, chip, &cbus_function)
+    }
+}
@@ -0,0 +1,209 @@
+#![allow(dead_code)]
+
+use libusb_sys as ffi;
+use log::{debug, error, warn};
+use snafu::GenerateBacktrace;
+use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface, ftdi_mpsse_mode};
+use crate::ftdi::ftdi_context::{FtdiContextError, Result, common_error};
+
+fn unsupported_error(chip_type: ftdi_chip_type, message: String) -> FtdiContextError {
+    let error = FtdiContextError::UnsupportedChip {
+        chip_type, message, backtrace: GenerateBacktrace::generate()
+    };
+    error!("{}", error);
+    error
+}
+
+/// Where chip keeps its configuration
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EepromKind {
+    /// External 93x46, 93x56 or 93x66 Microwire EEPROM, it may be missing
+    External,
+    /// 128 bytes of internal EEPROM, FT232R
+    Internal,
+    /// 2 kbit of internal multi-time programmable memory, FT-X series
+    Mtp,
+}
+
+/// What a chip family is, as told by bcdDevice of its device descriptor
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ChipInfo {
+    pub chip_type: ftdi_chip_type,
+    /// bcdDevice the chip reports with factory settings
+    pub bcd_device: u16,
+    /// Number of USB interfaces (channels)
+    pub interfaces: u8,
+    /// USB 2.0 hi-speed device with 512 byte packets and 120 MHz baud rate clock
+    pub high_speed: bool,
+    /// Channels with MPSSE engine, empty when chip has none
+    pub mpsse_interfaces: &'static [ftdi_interface],
+    pub eeprom: EepromKind,
+}
+
+const MPSSE_NONE: &[ftdi_interface] = &[];
+const MPSSE_A: &[ftdi_interface] = &[ftdi_interface::INTERFACE_A];
+const MPSSE_A_B: &[ftdi_interface] = &[ftdi_interface::INTERFACE_A, ftdi_interface::INTERFACE_B];
+
+const fn chip(chip_type: ftdi_chip_type, bcd_device: u16, interfaces: u8, high_speed: bool,
+              mpsse_interfaces: &'static [ftdi_interface], eeprom: EepromKind) -> ChipInfo {
+    ChipInfo { chip_type, bcd_device, interfaces, high_speed, mpsse_interfaces, eeprom }
+}
+
+/// Every known chip family, FT-X series chips (FT230X, FT231X, FT234XD, FT240X...) share one bcdDevice
+pub const CHIPS: [ChipInfo; 15] = [
+    chip(ftdi_chip_type::TYPE_AM, 0x0200, 1, false, MPSSE_NONE, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_BM, 0x0400, 1, false, MPSSE_NONE, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_2232C, 0x0500, 2, false, MPSSE_A, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_R, 0x0600, 1, false, MPSSE_NONE, EepromKind::Internal),
+    chip(ftdi_chip_type::TYPE_2232H, 0x0700, 2, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_4232H, 0x0800, 4, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_232H, 0x0900, 1, true, MPSSE_A, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_230X, 0x1000, 1, false, MPSSE_NONE, EepromKind::Mtp),
+    chip(ftdi_chip_type::TYPE_2233HP, 0x2800, 2, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_4233HP, 0x2900, 4, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_2232HP, 0x3000, 2, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_4232HP, 0x3100, 4, true, MPSSE_A_B, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_233HP, 0x3200, 1, true, MPSSE_A, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_232HP, 0x3300, 1, true, MPSSE_A, EepromKind::External),
+    chip(ftdi_chip_type::TYPE_4232HA, 0x3600, 4, true, MPSSE_A_B, EepromKind::External),
+];
+
+/// Description of chip type
+pub fn chip_info(chip_type: ftdi_chip_type) -> ChipInfo {
+    *CHIPS.iter().find(|info| info.chip_type == chip_type).expect("every chip type is in CHIPS")
+}
+
+/// Tell chip from its device descriptor and number of interfaces in its configuration descriptor.
+/// Interface count 0 means it's unknown, the chip is detected by bcdDevice only then.
+pub fn detect_chip(descriptor: &ffi::libusb_device_descriptor, interfaces: u8) -> Result<ChipInfo> {
+    debug!("start 'detect_chip' bcdDevice = {:#06x}, interfaces = {} ...", descriptor.bcdDevice, interfaces);
+    // Bug in the BM type chips: bcdDevice is 0x200 for serial == 0
+    let bcd_device = if descriptor.bcdDevice == 0x0200 && descriptor.iSerialNumber == 0 { 0x0400 } else { descriptor.bcdDevice };
+    let info = *CHIPS.iter().find(|info| info.bcd_device == bcd_device)
+        .ok_or_else(|| common_error(-8, format!("bcdDevice {:#06x} isn't a known FTDI chip", descriptor.bcdDevice)))?;
+    if interfaces != 0 && interfaces != info.interfaces {
+        warn!("{:?} has {} interfaces, {} are expected", info.chip_type, interfaces, info.interfaces);
+    }
+    debug!("'detect_chip' - OK, {:?}", info);
+    Ok(info)
+}
+
+/// FT-X series chips, they share bcdDevice and EEPROM layout of FT230X
+pub const FTX_VARIANTS: [&str; 8] = ["FT200XD", "FT201X", "FT220X", "FT221X", "FT230X", "FT231X", "FT234XD", "FT240X"];
+
+/// Tell FT-X series chip from its product string, e.g. "FT231X USB UART". Factory strings name the chip,
+/// None when a reprogrammed string doesn't.
+pub fn detect_ftx_variant(product: &str) -> Option<&'static str> {
+    let product = product.to_ascii_uppercase();
+    FTX_VARIANTS.iter().copied().find(|variant| product.contains(variant))
+}
+
+/// Baud rate generator clock of all chips, 3 MHz after the fixed divide by 16
+pub const BAUD_CLOCK_48MHZ: u32 = 48_000_000;
+/// Additional baud rate generator clock of hi-speed chips, 12 MHz after divide by 10
+pub const BAUD_CLOCK_120MHZ: u32 = 120_000_000;
+
+/// What one channel of a chip can do. HP series chips are described as their H counterparts.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChipCapabilities {
+    pub chip_type: ftdi_chip_type,
+    /// Channel, INTERFACE_A..INTERFACE_D
+    pub interface: ftdi_interface,
+    /// Modes ftdi_set_bitmode accepts on the channel, BITMODE_RESET among them
+    pub bitmodes: Vec<ftdi_mpsse_mode>,
+    /// Highest UART baud rate
+    pub max_baudrate: u32,
+    /// Baud rate generator clocks, Hz
+    pub baud_clocks: &'static [u32],
+    /// CBUS pins configurable in EEPROM
+    pub cbus_pins: u8,
+    pub eeprom: EepromKind,
+    /// EEPROM sizes in bytes the chip works with
+    pub eeprom_sizes: &'static [usize],
+    /// Synchronous 245 FIFO mode
+    pub sync_fifo: bool,
+}
+impl ChipCapabilities {
+    /// Capabilities of channel, error if the chip has no such channel
+    pub fn new(chip_type: ftdi_chip_type, interface: ftdi_interface) -> Result<Self> {
+        use ftdi_chip_type::*;
+        use ftdi_mpsse_mode::*;
+        let info = chip_info(chip_type);
+        let interface = if interface == ftdi_interface::INTERFACE_ANY { ftdi_interface::INTERFACE_A } else { interface };
+        if interface as u8 > info.interfaces {
+            return Err(unsupported_error(chip_type, format!("chip has {} interface(s), {:?} isn't one of them", info.interfaces, interface)));
+        }
+        let first = interface == ftdi_interface::INTERFACE_A;
+        let mut bitmodes = vec![BITMODE_RESET];
+        let (cbus_pins, sync_fifo) = match chip_type {
+            TYPE_AM => (0, false),
+            TYPE_BM => {
+                bitmodes.push(BITMODE_BITBANG);
+                (0, false)
+            }
+            TYPE_2232C => {
+                // MCU host bus emulation and fast serial use pins of both channels, they're set on one
+                bitmodes.extend_from_slice(&[BITMODE_BITBANG, BITMODE_SYNCBB, if first { BITMODE_MCU } else { BITMODE_OPTO }]);
+                (0, false)
+            }
+            TYPE_R | TYPE_230X => {
+                bitmodes.extend_from_slice(&[BITMODE_BITBANG, BITMODE_SYNCBB, BITMODE_CBUS]);
+                (if chip_type == TYPE_R { 5 } else { 4 }, false)
+            }
+            TYPE_2232H | TYPE_2232HP | TYPE_2233HP => {
+                bitmodes.extend_from_slice(&[BITMODE_BITBANG, BITMODE_SYNCBB, if first { BITMODE_MCU } else { BITMODE_OPTO }]);
+                if first {
+                    bitmodes.push(BITMODE_SYNCFF);
+                }
+                (0, first)
+            }
+            TYPE_4232H | TYPE_4232HP | TYPE_4233HP | TYPE_4232HA => {
+                bitmodes.extend_from_slice(&[BITMODE_BITBANG, BITMODE_SYNCBB]);
+                (0, false)
+            }
+            TYPE_232H | TYPE_232HP | TYPE_233HP => {
+                bitmodes.extend_from_slice(&[BITMODE_BITBANG, BITMODE_SYNCBB, BITMODE_CBUS, BITMODE_SYNCFF, BITMODE_FT1284]);
+                (10, true)
+            }
+        };
+        if info.mpsse_interfaces.contains(&interface) {
+            bitmodes.push(BITMODE_MPSSE);
+        }
+        let (eeprom_sizes, max_baudrate, baud_clocks): (&'static [usize], u32, &'static [u32]) = match info.eeprom {
+            _ if info.high_speed => (&[0x80, 0x100], 12_000_000, &[BAUD_CLOCK_48MHZ, BAUD_CLOCK_120MHZ]),
+            // AM works with 93x46 only
+            EepromKind::External if chip_type == TYPE_AM => (&[0x80], 3_000_000, &[BAUD_CLOCK_48MHZ]),
+            EepromKind::External => (&[0x80, 0x100], 3_000_000, &[BAUD_CLOCK_48MHZ]),
+            EepromKind::Internal => (&[0x80], 3_000_000, &[BAUD_CLOCK_48MHZ]),
+            EepromKind::Mtp => (&[0x100], 3_000_000, &[BAUD_CLOCK_48MHZ]),
+        };
+        Ok(ChipCapabilities {
+            chip_type, interface, bitmodes, max_baudrate, baud_clocks, cbus_pins,
+            eeprom: info.eeprom, eeprom_sizes, sync_fifo,
+        })
+    }
+
+    pub fn check_bitmode(&self, mode: ftdi_mpsse_mode) -> Result<()> {
+        if !self.bitmodes.contains(&mode) {
+            return Err(unsupported_error(self.chip_type, format!("{:?} isn't available on {:?}, modes are {:?}",
+                                                                  mode, self.interface, self.bitmodes)));
+        }
+        Ok(())
+    }
+
+    pub fn check_baudrate(&self, baudrate: u32) -> Result<()> {
+        if baudrate > self.max_baudrate {
+            return Err(unsupported_error(self.chip_type, format!("baud rate {} is above {} maximum", baudrate, self.max_baudrate)));
+        }
+        Ok(())
+    }
+
+    /// Check image of 'size' bytes fits EEPROM of chip
+    pub fn check_eeprom_size(&self, size: usize) -> Result<()> {
+        if !self.eeprom_sizes.contains(&size) {
+            return Err(unsupported_error(self.chip_type, format!("{:?} EEPROM of {} bytes isn't used by chip, sizes are {:?}",
+                                                                  self.eeprom, size, self.eeprom_sizes)));
+        }
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 
 use libusb_sys as ffi;
 use std::str::FromStr;
+use serde::{Deserialize, Serialize};
 
 pub const FTDI_MAJOR_VERSION: u8 = 1;
 pub const FTDI_MINOR_VERSION: u8 = 5;
@@ -13,7 +14,7 @@ pub const FTDI_SNAPSHOT_VERSION: &str = "v1.5rc1";
 
 /// FTDI chip type
 #[non_exhaustive]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum ftdi_chip_type {
     TYPE_AM = 0,
@@ -24,6 +25,13 @@ pub enum ftdi_chip_type {
     TYPE_4232H = 5,
     TYPE_232H = 6,
     TYPE_230X = 7,
+    TYPE_2233HP = 8,
+    TYPE_4233HP = 9,
+    TYPE_2232HP = 10,
+    TYPE_4232HP = 11,
+    TYPE_233HP = 12,
+    TYPE_232HP = 13,
+    TYPE_4232HA = 14,
 }
 impl From<u8> for ftdi_chip_type {
     // #[inline]
@@ -38,13 +46,20 @@ impl From<u8> for ftdi_chip_type {
             5 => ftdi_chip_type::TYPE_4232H,
             6 => ftdi_chip_type::TYPE_232H,
             7 => ftdi_chip_type::TYPE_230X,
+            8 => ftdi_chip_type::TYPE_2233HP,
+            9 => ftdi_chip_type::TYPE_4233HP,
+            10 => ftdi_chip_type::TYPE_2232HP,
+            11 => ftdi_chip_type::TYPE_4232HP,
+            12 => ftdi_chip_type::TYPE_233HP,
+            13 => ftdi_chip_type::TYPE_232HP,
+            14 => ftdi_chip_type::TYPE_4232HA,
             _ => panic!("ftdi_chip_type is unknown for value = {}", value),
         }
     }
 }
 
 /// Parity mode for ftdi_set_line_property()
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum ftdi_parity_type {
     NONE = 0,
@@ -67,7 +82,7 @@ impl From<u8> for ftdi_parity_type {
 }
 
 /// Number of stop bits for ftdi_set_line_property()
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum ftdi_stopbits_type {
     STOP_BIT_1 = 0,
@@ -281,6 +296,9 @@ pub const DRIVE_OPEN_COLLECTOR: u8 = 0x9e;
 /// Value HIGH */ /*rate is 12000000/((1+value)*2)
 //pub static DIV_VALUE(rate) = (rate > 6000000)?0:((6000000/rate -1) > 0xffff)? 0xffff: (6000000/rate -1);
 
+/// Reply of MPSSE engine to an invalid command, followed by the offending command byte
+pub const MPSSE_BAD_COMMAND: u8 = 0xfa;
+
 /// Commands in MPSSE and Host Emulation Mode
 pub const SEND_IMMEDIATE: u8 = 0x87;
 pub const WAIT_ON_HIGH: u8 = 0x88;
@@ -339,19 +357,19 @@ pub const SIO_RESET_PURGE_TX: u8 = 2;
 pub const SIO_TCIFLUSH: u8 = 2;
 pub const SIO_TCOFLUSH: u8 = 1;
 
-pub const SIO_DISABLE_FLOW_CTRL: u8 = 0x0;
-pub const SIO_RTS_CTS_HS: u8 = (0x1 << 8) as u8;
-pub const SIO_DTR_DSR_HS: u8 = (0x2 << 8) as u8;
-pub const SIO_XON_XOFF_HS: u8 = (0x4 << 8) as u8;
-
-pub const SIO_SET_DTR_MASK: u8 = 0x1;
-pub const SIO_SET_DTR_HIGH: u8 = (1 | ((SIO_SET_DTR_MASK << 8) as u8) as u8) as u8;
-// pub const SIO_SET_DTR_LOW: u8 = (0 | ((SIO_SET_DTR_MASK << 8) as u8) as u8) as u8;
-pub const SIO_SET_RTS_MASK: u8 = 0x2;
-pub const SIO_SET_DTR_LOW: u8 = ((SIO_SET_RTS_MASK << 8) as u8) as u8;
-pub const SIO_SET_RTS_HIGH: u8 = (2 | ((SIO_SET_RTS_MASK << 8) as u8) as u8) as u8;
-// pub const SIO_SET_RTS_LOW: u8 = (0 | ((SIO_SET_RTS_MASK << 8) as u8) as u8) as u8;
-pub const SIO_SET_RTS_LOW: u8 = ((SIO_SET_RTS_MASK << 8) as u8) as u8;
+/// Flow control handshake, high byte of SIO_SET_FLOW_CTRL_REQUEST index
+pub const SIO_DISABLE_FLOW_CTRL: u16 = 0x0;
+pub const SIO_RTS_CTS_HS: u16 = 0x1 << 8;
+pub const SIO_DTR_DSR_HS: u16 = 0x2 << 8;
+pub const SIO_XON_XOFF_HS: u16 = 0x4 << 8;
+
+/// SIO_SET_MODEM_CTRL_REQUEST values, the mask in high byte tells which line is set
+pub const SIO_SET_DTR_MASK: u16 = 0x1;
+pub const SIO_SET_DTR_HIGH: u16 = 1 | (SIO_SET_DTR_MASK << 8);
+pub const SIO_SET_DTR_LOW: u16 = SIO_SET_DTR_MASK << 8;
+pub const SIO_SET_RTS_MASK: u16 = 0x2;
+pub const SIO_SET_RTS_HIGH: u16 = 2 | (SIO_SET_RTS_MASK << 8);
+pub const SIO_SET_RTS_LOW: u16 = SIO_SET_RTS_MASK << 8;
 
 pub const FT1284_CLK_IDLE_STATE: u8 = 0x01;
 /// DS_FT232H 1.3 amd ftd2xx.h 1.0.4 disagree here
@@ -84,25 +84,103 @@ impl ftdi_transfer_control {
 }
 
 
-enum ftdi_cbus_func {
+/// CBUS pin functions of FT232R (EEPROM cbus_function values)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ftdi_cbus_func {
     CBUS_TXDEN = 0, CBUS_PWREN = 1, CBUS_RXLED = 2, CBUS_TXLED = 3, CBUS_TXRXLED = 4,
     CBUS_SLEEP = 5, CBUS_CLK48 = 6, CBUS_CLK24 = 7, CBUS_CLK12 = 8, CBUS_CLK6 =  9,
     CBUS_IOMODE = 0xa, CBUS_BB_WR = 0xb, CBUS_BB_RD = 0xc
 }
+impl From<u8> for ftdi_cbus_func {
+    fn from(value: u8) -> ftdi_cbus_func {
+        match value {
+            0 => ftdi_cbus_func::CBUS_TXDEN,
+            1 => ftdi_cbus_func::CBUS_PWREN,
+            2 => ftdi_cbus_func::CBUS_RXLED,
+            3 => ftdi_cbus_func::CBUS_TXLED,
+            4 => ftdi_cbus_func::CBUS_TXRXLED,
+            5 => ftdi_cbus_func::CBUS_SLEEP,
+            6 => ftdi_cbus_func::CBUS_CLK48,
+            7 => ftdi_cbus_func::CBUS_CLK24,
+            8 => ftdi_cbus_func::CBUS_CLK12,
+            9 => ftdi_cbus_func::CBUS_CLK6,
+            0xa => ftdi_cbus_func::CBUS_IOMODE,
+            0xb => ftdi_cbus_func::CBUS_BB_WR,
+            0xc => ftdi_cbus_func::CBUS_BB_RD,
+            _ => panic!("ftdi_cbus_func is unknown for value = {}", value),
+        }
+    }
+}
 
-enum ftdi_cbush_func {
+/// CBUS pin functions of FT232H (EEPROM cbus_function values)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ftdi_cbush_func {
     CBUSH_TRISTATE = 0, CBUSH_TXLED = 1, CBUSH_RXLED = 2, CBUSH_TXRXLED = 3, CBUSH_PWREN = 4,
     CBUSH_SLEEP = 5, CBUSH_DRIVE_0 = 6, CBUSH_DRIVE1 = 7, CBUSH_IOMODE = 8, CBUSH_TXDEN =  9,
     CBUSH_CLK30 = 10, CBUSH_CLK15 = 11, CBUSH_CLK7_5 = 12
 }
+impl From<u8> for ftdi_cbush_func {
+    fn from(value: u8) -> ftdi_cbush_func {
+        match value {
+            0 => ftdi_cbush_func::CBUSH_TRISTATE,
+            1 => ftdi_cbush_func::CBUSH_TXLED,
+            2 => ftdi_cbush_func::CBUSH_RXLED,
+            3 => ftdi_cbush_func::CBUSH_TXRXLED,
+            4 => ftdi_cbush_func::CBUSH_PWREN,
+            5 => ftdi_cbush_func::CBUSH_SLEEP,
+            6 => ftdi_cbush_func::CBUSH_DRIVE_0,
+            7 => ftdi_cbush_func::CBUSH_DRIVE1,
+            8 => ftdi_cbush_func::CBUSH_IOMODE,
+            9 => ftdi_cbush_func::CBUSH_TXDEN,
+            10 => ftdi_cbush_func::CBUSH_CLK30,
+            11 => ftdi_cbush_func::CBUSH_CLK15,
+            12 => ftdi_cbush_func::CBUSH_CLK7_5,
+            _ => panic!("ftdi_cbush_func is unknown for value = {}", value),
+        }
+    }
+}
 
-enum ftdi_cbusx_func {
+/// CBUS pin functions of FT230X/FT231X/FT234X (EEPROM cbus_function values)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ftdi_cbusx_func {
     CBUSX_TRISTATE = 0, CBUSX_TXLED = 1, CBUSX_RXLED = 2, CBUSX_TXRXLED = 3, CBUSX_PWREN = 4,
     CBUSX_SLEEP = 5, CBUSX_DRIVE_0 = 6, CBUSX_DRIVE1 = 7, CBUSX_IOMODE = 8, CBUSX_TXDEN =  9,
     CBUSX_CLK24 = 10, CBUSX_CLK12 = 11, CBUSX_CLK6 = 12, CBUSX_BAT_DETECT = 13,
     CBUSX_BAT_DETECT_NEG = 14, CBUSX_I2C_TXE = 15, CBUSX_I2C_RXF = 16, CBUSX_VBUS_SENSE = 17,
     CBUSX_BB_WR = 18, CBUSX_BB_RD = 19, CBUSX_TIME_STAMP = 20, CBUSX_AWAKE = 21
 }
+impl From<u8> for ftdi_cbusx_func {
+    fn from(value: u8) -> ftdi_cbusx_func {
+        match value {
+            0 => ftdi_cbusx_func::CBUSX_TRISTATE,
+            1 => ftdi_cbusx_func::CBUSX_TXLED,
+            2 => ftdi_cbusx_func::CBUSX_RXLED,
+            3 => ftdi_cbusx_func::CBUSX_TXRXLED,
+            4 => ftdi_cbusx_func::CBUSX_PWREN,
+            5 => ftdi_cbusx_func::CBUSX_SLEEP,
+            6 => ftdi_cbusx_func::CBUSX_DRIVE_0,
+            7 => ftdi_cbusx_func::CBUSX_DRIVE1,
+            8 => ftdi_cbusx_func::CBUSX_IOMODE,
+            9 => ftdi_cbusx_func::CBUSX_TXDEN,
+            10 => ftdi_cbusx_func::CBUSX_CLK24,
+            11 => ftdi_cbusx_func::CBUSX_CLK12,
+            12 => ftdi_cbusx_func::CBUSX_CLK6,
+            13 => ftdi_cbusx_func::CBUSX_BAT_DETECT,
+            14 => ftdi_cbusx_func::CBUSX_BAT_DETECT_NEG,
+            15 => ftdi_cbusx_func::CBUSX_I2C_TXE,
+            16 => ftdi_cbusx_func::CBUSX_I2C_RXF,
+            17 => ftdi_cbusx_func::CBUSX_VBUS_SENSE,
+            18 => ftdi_cbusx_func::CBUSX_BB_WR,
+            19 => ftdi_cbusx_func::CBUSX_BB_RD,
+            20 => ftdi_cbusx_func::CBUSX_TIME_STAMP,
+            21 => ftdi_cbusx_func::CBUSX_AWAKE,
+            _ => panic!("ftdi_cbusx_func is unknown for value = {}", value),
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
@@ -1,13 +1,41 @@
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
 
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{FtdiContextError, Result, common_error};
+
 pub const FTDI_MAX_EEPROM_SIZE: usize = 256;
 
 pub const MAX_POWER_MILLIAMP_PER_UNIT: u8 = 2;
 
-// #[derive(Copy, Clone, Debug)]
-// #[derive(Debug)]
+/// External EEPROM chips, value of 'chip'
+pub const EEPROM_CHIP_93X46: i32 = 0x46;
+pub const EEPROM_CHIP_93X56: i32 = 0x56;
+pub const EEPROM_CHIP_93X66: i32 = 0x66;
+/// Word written to EEPROM when its size is probed after erase
+pub const EEPROM_SIZE_PROBE_MAGIC: u16 = 0x55aa;
+/// Internal EEPROM of FT232R, bytes
+pub const FT232R_EEPROM_SIZE: i32 = 0x80;
+/// Internal MTP memory of FT-X chips, bytes
+pub const FTX_EEPROM_SIZE: i32 = 0x100;
+/// FT-X words in this range are reserved and never written
+pub const FTX_EEPROM_RESERVED_WORDS: std::ops::Range<u16> = 0x40..0x50;
+/// FT232R and FT-X words the FTDIChip-ID is stored in, high word first
+pub const CHIPID_EEPROM_WORDS: std::ops::Range<u16> = 0x43..0x45;
+/// FT-X words in this range are user area, they aren't covered by the checksum
+pub const FTX_EEPROM_UNCHECKED_WORDS: std::ops::Range<usize> = 0x12..0x40;
+/// USB string descriptor type
+const STRING_DESCRIPTOR_TYPE: u8 = 0x03;
+
 /// brief FTDI eeprom structure
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ftdi_eeprom {
     /// vendor id
     pub vendor_id: i32,
@@ -44,13 +72,11 @@ pub struct ftdi_eeprom {
     pub max_power: i32,
 
     /// manufacturer name
-    pub manufacturer: Box<[u8; FTDI_MAX_EEPROM_SIZE]>,
-    // pub manufacturer: String,
+    pub manufacturer: String,
     /// product name
-    // pub product: String,
-    pub product: Box<[u8; FTDI_MAX_EEPROM_SIZE]>,
+    pub product: String,
     /// serial number
-    pub serial: Box<[u8; FTDI_MAX_EEPROM_SIZE]>,
+    pub serial: String,
 
     /// 2232D/H specific
     /// Hardware type, 0 = RS232 Uart, 1 = 245 FIFO, 2 = CPU FIFO, 4 = OPTO Isolate
@@ -108,6 +134,7 @@ pub struct ftdi_eeprom {
     /// user data
     pub user_data_addr: i32,
     pub user_data_size: i32,
+    #[serde(with = "user_data_serde")]
     pub user_data: Box<[u8; FTDI_MAX_EEPROM_SIZE]>,
 
     /// eeprom size in bytes. This doesn't get stored in the eeprom but is the only way to pass it to ftdi_eeprom_build.
@@ -119,7 +146,31 @@ pub struct ftdi_eeprom {
 
     /// device release number
     pub release_number: i32,
+
+    /// Chip the values are for, it's set by decode. get_value and set_value reject
+    /// values the chip doesn't have, any value is accepted when it's None.
+    pub chip_type: Option<ftdi_chip_type>,
+}
+/// serde has no arrays that long, user data goes as byte sequence
+mod user_data_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use super::FTDI_MAX_EEPROM_SIZE;
+
+    pub fn serialize<S: Serializer>(data: &[u8; FTDI_MAX_EEPROM_SIZE], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(data)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<[u8; FTDI_MAX_EEPROM_SIZE]>, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        if bytes.len() > FTDI_MAX_EEPROM_SIZE {
+            return Err(D::Error::custom(format!("user data is {} bytes, EEPROM has {}", bytes.len(), FTDI_MAX_EEPROM_SIZE)));
+        }
+        let mut data = Box::new([0u8; FTDI_MAX_EEPROM_SIZE]);
+        data[..bytes.len()].copy_from_slice(&bytes);
+        Ok(data)
+    }
 }
+
 impl Default for ftdi_eeprom {
     fn default() -> Self {
         ftdi_eeprom {
@@ -137,9 +188,9 @@ impl Default for ftdi_eeprom {
             usb_version: 0,
             use_usb_version: 0,
             max_power: 0,
-            manufacturer: Box::new([0u8; FTDI_MAX_EEPROM_SIZE]),
-            product: Box::new([0u8; FTDI_MAX_EEPROM_SIZE]),
-            serial: Box::new([0u8; FTDI_MAX_EEPROM_SIZE]),
+            manufacturer: String::new(),
+            product: String::new(),
+            serial: String::new(),
             channel_a_type: 0,
             channel_b_type: 0,
             channel_a_driver: 0,
@@ -179,8 +230,682 @@ impl Default for ftdi_eeprom {
             chip: 0,
             buf: Vec::new(),
             release_number: 0,
+            chip_type: None,
+        }
+    }
+}
+
+impl ftdi_eeprom {
+    /// Byte offset of the 93xx chip type in the EEPROM image, None for chips with internal EEPROM
+    pub fn chip_type_location(chip_type: ftdi_chip_type) -> Option<usize> {
+        match chip_type {
+            ftdi_chip_type::TYPE_BM | ftdi_chip_type::TYPE_2232C => Some(0x14),
+            ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H => Some(0x18),
+            ftdi_chip_type::TYPE_232H => Some(0x1e),
+            _ => None,
+        }
+    }
+
+    /// Checksum of the first 'size' bytes of EEPROM image, it's stored in the last word
+    pub fn checksum(chip_type: ftdi_chip_type, buf: &[u8], size: usize) -> u16 {
+        let mut checksum: u16 = 0xaaaa;
+        for word in 0..size / 2 - 1 {
+            if chip_type == ftdi_chip_type::TYPE_230X && FTX_EEPROM_UNCHECKED_WORDS.contains(&word) {
+                continue;
+            }
+            checksum ^= u16::from_le_bytes([buf[word * 2], buf[word * 2 + 1]]);
+            checksum = checksum.rotate_left(1);
+        }
+        checksum
+    }
+
+    /// Decode EEPROM image of chip. Image may be longer than EEPROM (read over USB),
+    /// its size is detected then. Raw image is kept in 'buf'.
+    pub fn decode(image: &[u8], chip_type: ftdi_chip_type) -> Result<ftdi_eeprom> {
+        debug!("start 'ftdi_eeprom decode' for {:?}, image = {} bytes ...", chip_type, image.len());
+        ftdi_eeprom::check_layout(chip_type)?;
+        let (size, chip) = if image.len() >= FTDI_MAX_EEPROM_SIZE {
+            ftdi_eeprom::detect_size(chip_type, image)
+        } else if image.len() == 0x80 && chip_type != ftdi_chip_type::TYPE_230X {
+            let chip = if chip_type == ftdi_chip_type::TYPE_R { 0 } else { EEPROM_CHIP_93X46 };
+            (0x80, chip)
+        } else {
+            (-1, -1)
+        };
+        if size <= 0 || image.len() < size as usize {
+            return Err(common_error(-1, format!("EEPROM image of {} bytes is blank or too short for {:?}", image.len(), chip_type)));
+        }
+        let size = size as usize;
+        let buf = &image[..size];
+        let expected = ftdi_eeprom::checksum(chip_type, buf, size);
+        let stored = u16::from_le_bytes([buf[size - 2], buf[size - 1]]);
+        if stored != expected {
+            return Err(common_error(-1, format!("EEPROM checksum error, stored = {:#06x}, calculated = {:#06x}", stored, expected)));
+        }
+
+        let mut eeprom = ftdi_eeprom {
+            vendor_id: u16::from_le_bytes([buf[0x02], buf[0x03]]) as i32,
+            product_id: u16::from_le_bytes([buf[0x04], buf[0x05]]) as i32,
+            release_number: u16::from_le_bytes([buf[0x06], buf[0x07]]) as i32,
+            // config descriptor attributes: bit 6 self powered, bit 5 remote wakeup
+            self_powered: (buf[0x08] & 0x40 != 0) as i32,
+            remote_wakeup: (buf[0x08] & 0x20 != 0) as i32,
+            max_power: MAX_POWER_MILLIAMP_PER_UNIT as i32 * buf[0x09] as i32,
+            in_is_isochronous: buf[0x0a] & 0x01 != 0,
+            out_is_isochronous: buf[0x0a] & 0x02 != 0,
+            suspend_pull_downs: (buf[0x0a] & 0x04 != 0) as i32,
+            use_serial: buf[0x0a] & USE_SERIAL_NUM != 0,
+            use_usb_version: (buf[0x0a] & USE_USB_VERSION_BIT != 0) as i32,
+            usb_version: u16::from_le_bytes([buf[0x0c], buf[0x0d]]) as i32,
+            manufacturer: decode_string(buf, 0x0e)?,
+            product: decode_string(buf, 0x10)?,
+            serial: decode_string(buf, 0x12)?,
+            size: size as i32,
+            chip,
+            buf: buf.to_vec(),
+            chip_type: Some(chip_type),
+            ..ftdi_eeprom::default()
+        };
+
+        match chip_type {
+            ftdi_chip_type::TYPE_AM | ftdi_chip_type::TYPE_BM => eeprom.chip = -1,
+            ftdi_chip_type::TYPE_2232C => {
+                eeprom.channel_a_type = (buf[0x00] & 0x07) as i32;
+                eeprom.channel_a_driver = (buf[0x00] & DRIVER_VCP) as i32;
+                eeprom.high_current_a = (buf[0x00] & HIGH_CURRENT_DRIVE) as i32;
+                eeprom.channel_b_type = (buf[0x01] & 0x07) as i32;
+                eeprom.channel_b_driver = (buf[0x01] & DRIVER_VCP) as i32;
+                eeprom.high_current_b = (buf[0x01] & HIGH_CURRENT_DRIVE) as i32;
+                eeprom.chip = buf[0x14] as i32;
+            }
+            ftdi_chip_type::TYPE_R => {
+                // FT232R flags D2XX driver, not VCP as all others
+                eeprom.channel_a_driver = (!buf[0x00] & DRIVER_VCP) as i32;
+                eeprom.high_current = (buf[0x00] & HIGH_CURRENT_DRIVE_R) as i32;
+                eeprom.external_oscillator = (buf[0x00] & 0x02) as i32;
+                if buf[0x01] & 0x40 != 0x40 {
+                    warn!("FT232R EEPROM byte 0x01 bit 6 is clear, unexpected endpoint size");
+                }
+                // works on FT232R only, not FT245R, but they can't be told apart
+                eeprom.invert = buf[0x0b] as i32;
+                eeprom.cbus_function[0] = (buf[0x14] & 0x0f) as i32;
+                eeprom.cbus_function[1] = (buf[0x14] >> 4) as i32;
+                eeprom.cbus_function[2] = (buf[0x15] & 0x0f) as i32;
+                eeprom.cbus_function[3] = (buf[0x15] >> 4) as i32;
+                eeprom.cbus_function[4] = (buf[0x16] & 0x0f) as i32;
+            }
+            ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H => {
+                eeprom.channel_a_driver = (buf[0x00] & DRIVER_VCP) as i32;
+                eeprom.channel_b_driver = (buf[0x01] & DRIVER_VCP) as i32;
+                if chip_type == ftdi_chip_type::TYPE_2232H {
+                    eeprom.channel_a_type = (buf[0x00] & 0x07) as i32;
+                    eeprom.channel_b_type = (buf[0x01] & 0x07) as i32;
+                    eeprom.suspend_dbus7 = (buf[0x01] & SUSPEND_DBUS7_BIT != 0) as i32;
+                } else {
+                    eeprom.channel_c_driver = ((buf[0x00] >> 4) & DRIVER_VCP) as i32;
+                    eeprom.channel_d_driver = ((buf[0x01] >> 4) & DRIVER_VCP) as i32;
+                    eeprom.channel_a_rs485enable = buf[0x0b] & CHANNEL_IS_RS485 != 0;
+                    eeprom.channel_b_rs485enable = buf[0x0b] & (CHANNEL_IS_RS485 << 1) != 0;
+                    eeprom.channel_c_rs485enable = buf[0x0b] & (CHANNEL_IS_RS485 << 2) != 0;
+                    eeprom.channel_d_rs485enable = buf[0x0b] & (CHANNEL_IS_RS485 << 3) != 0;
+                }
+                eeprom.chip = buf[0x18] as i32;
+                decode_group(&mut eeprom.group0_drive, &mut eeprom.group0_schmitt, &mut eeprom.group0_slew, buf[0x0c], DRIVE_16MA);
+                decode_group(&mut eeprom.group1_drive, &mut eeprom.group1_schmitt, &mut eeprom.group1_slew, buf[0x0c] >> 4, DRIVE_16MA);
+                decode_group(&mut eeprom.group2_drive, &mut eeprom.group2_schmitt, &mut eeprom.group2_slew, buf[0x0d], DRIVE_16MA);
+                decode_group(&mut eeprom.group3_drive, &mut eeprom.group3_schmitt, &mut eeprom.group3_slew, buf[0x0d] >> 4, DRIVE_16MA);
+            }
+            ftdi_chip_type::TYPE_232H => {
+                eeprom.channel_a_type = (buf[0x00] & 0x0f) as i32;
+                eeprom.channel_a_driver = if buf[0x00] & DRIVER_VCPH != 0 { DRIVER_VCP as i32 } else { 0 };
+                eeprom.clock_polarity = (buf[0x01] & FT1284_CLK_IDLE_STATE != 0) as i32;
+                eeprom.data_order = (buf[0x01] & FT1284_DATA_LSB != 0) as i32;
+                eeprom.flow_control = (buf[0x01] & FT1284_FLOW_CONTROL != 0) as i32;
+                eeprom.powersave = (buf[0x01] & POWER_SAVE_DISABLE_H != 0) as i32;
+                decode_group(&mut eeprom.group0_drive, &mut eeprom.group0_schmitt, &mut eeprom.group0_slew, buf[0x0c], DRIVE_16MA);
+                decode_group(&mut eeprom.group1_drive, &mut eeprom.group1_schmitt, &mut eeprom.group1_slew, buf[0x0d], DRIVE_16MA);
+                // ACBUS0..ACBUS9, two per byte
+                for index in 0..5 {
+                    eeprom.cbus_function[2 * index] = (buf[0x18 + index] & 0x0f) as i32;
+                    eeprom.cbus_function[2 * index + 1] = (buf[0x18 + index] >> 4) as i32;
+                }
+                eeprom.chip = buf[0x1e] as i32;
+            }
+            ftdi_chip_type::TYPE_230X => {
+                for index in 0..4 {
+                    eeprom.cbus_function[index] = buf[0x1a + index] as i32;
+                }
+                // FT-X has no 16 mA drive
+                decode_group(&mut eeprom.group0_drive, &mut eeprom.group0_schmitt, &mut eeprom.group0_slew, buf[0x0c], DRIVE_12MA | DRIVE_8MA);
+                decode_group(&mut eeprom.group1_drive, &mut eeprom.group1_schmitt, &mut eeprom.group1_slew, buf[0x0c] >> 4, DRIVE_12MA | DRIVE_8MA);
+                eeprom.invert = buf[0x0b] as i32;
+            }
+            _ => unreachable!("{:?} has no EEPROM layout", chip_type),
+        }
+        debug!("'ftdi_eeprom decode' - OK, size = {}, '{}' '{}' '{}'", size, eeprom.manufacturer, eeprom.product, eeprom.serial);
+        Ok(eeprom)
+    }
+
+    /// Serialize EEPROM structure into image of chip. Fields are put over 'buf' when it holds
+    /// an image of the same size, so bytes the structure doesn't describe are kept.
+    /// Strings are placed where FTDI tools put them, user data goes to 'user_data_addr'
+    /// or right after strings when it's 0.
+    pub fn build(&self, chip_type: ftdi_chip_type) -> Result<Vec<u8>> {
+        debug!("start 'ftdi_eeprom build' for {:?} ...", chip_type);
+        ftdi_eeprom::check_layout(chip_type)?;
+        let size = match chip_type {
+            ftdi_chip_type::TYPE_R => FT232R_EEPROM_SIZE as usize,
+            ftdi_chip_type::TYPE_230X => FTX_EEPROM_SIZE as usize,
+            _ if self.size > 0 => self.size as usize,
+            _ if self.chip == EEPROM_CHIP_93X56 || self.chip == EEPROM_CHIP_93X66 => FTDI_MAX_EEPROM_SIZE,
+            _ => 0x80,
+        };
+        if size != 0x80 && size != FTDI_MAX_EEPROM_SIZE {
+            return Err(common_error(-1, format!("EEPROM size {} isn't supported", size)));
+        }
+        let mask = size - 1;
+        let mut buf = if self.buf.len() == size {
+            self.buf.clone()
+        } else {
+            let mut buf = vec![0u8; size];
+            if chip_type == ftdi_chip_type::TYPE_R {
+                // 64 byte endpoints
+                buf[0x01] = 0x40;
+            }
+            buf
+        };
+
+        if self.max_power < 0 || self.max_power > 500 {
+            return Err(common_error(-1, format!("max power {} mA is out of USB limit 500 mA", self.max_power)));
+        }
+        buf[0x02..0x04].copy_from_slice(&(self.vendor_id as u16).to_le_bytes());
+        buf[0x04..0x06].copy_from_slice(&(self.product_id as u16).to_le_bytes());
+        buf[0x06..0x08].copy_from_slice(&(self.release_number as u16).to_le_bytes());
+        // bit 7 is reserved and always set
+        buf[0x08] = (buf[0x08] & !0x60) | 0x80 | flag(self.self_powered != 0, 0x40) | flag(self.remote_wakeup != 0, 0x20);
+        buf[0x09] = ((self.max_power + MAX_POWER_MILLIAMP_PER_UNIT as i32 - 1) / MAX_POWER_MILLIAMP_PER_UNIT as i32) as u8;
+        buf[0x0a] = (buf[0x0a] & !(0x07 | USE_SERIAL_NUM | USE_USB_VERSION_BIT))
+            | flag(self.in_is_isochronous, 0x01)
+            | flag(self.out_is_isochronous, 0x02)
+            | flag(self.suspend_pull_downs != 0, 0x04)
+            | flag(self.use_serial, USE_SERIAL_NUM)
+            | flag(self.use_usb_version != 0, USE_USB_VERSION_BIT);
+
+        // old descriptors may be elsewhere or longer
+        for pointer in [0x0e, 0x10, 0x12].iter() {
+            let offset = buf[*pointer] as usize & mask;
+            let length = buf[*pointer + 1] as usize;
+            if offset >= ftdi_eeprom::config_size(chip_type) && offset + length <= size - 2 {
+                buf[offset..offset + length].iter_mut().for_each(|byte| *byte = 0);
+            }
+        }
+        let strings_start = ftdi_eeprom::strings_location(chip_type);
+        let mut free = strings_start & mask;
+        for (pointer, string) in [(0x0e, &self.manufacturer), (0x10, &self.product), (0x12, &self.serial)].iter() {
+            let descriptor = encode_string(string);
+            if free + descriptor.len() > size - 2 {
+                return Err(common_error(-3, format!(
+                    "strings '{}' '{}' '{}' don't fit {} bytes of {:?} EEPROM, {} bytes are available for them",
+                    self.manufacturer, self.product, self.serial, size, chip_type, size - 2 - (strings_start & mask))));
+            }
+            buf[*pointer] = (free | (strings_start & !mask)) as u8;
+            buf[*pointer + 1] = descriptor.len() as u8;
+            buf[free..free + descriptor.len()].copy_from_slice(&descriptor);
+            free += descriptor.len();
+        }
+
+        match chip_type {
+            ftdi_chip_type::TYPE_AM | ftdi_chip_type::TYPE_BM => {
+                buf[0x0c..0x0e].copy_from_slice(&(self.usb_version as u16).to_le_bytes());
+            }
+            ftdi_chip_type::TYPE_2232C => {
+                buf[0x00] = encode_channel(buf[0x00], self.channel_a_type, self.channel_a_driver, self.high_current_a);
+                buf[0x01] = encode_channel(buf[0x01], self.channel_b_type, self.channel_b_driver, self.high_current_b);
+                buf[0x0c..0x0e].copy_from_slice(&(self.usb_version as u16).to_le_bytes());
+            }
+            ftdi_chip_type::TYPE_R => {
+                buf[0x00] = (buf[0x00] & !(DRIVER_VCP | HIGH_CURRENT_DRIVE_R | 0x02))
+                    | flag(self.channel_a_driver == 0, DRIVER_VCP)
+                    | flag(self.high_current != 0, HIGH_CURRENT_DRIVE_R)
+                    | flag(self.external_oscillator != 0, 0x02);
+                buf[0x0b] = self.invert as u8;
+                buf[0x0c..0x0e].copy_from_slice(&(self.usb_version as u16).to_le_bytes());
+                let cbus = ftdi_eeprom::cbus_nibbles(&self.cbus_function[..5])?;
+                buf[0x14] = cbus[0] | (cbus[1] << 4);
+                buf[0x15] = cbus[2] | (cbus[3] << 4);
+                buf[0x16] = (buf[0x16] & 0xf0) | cbus[4];
+            }
+            ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H => {
+                if chip_type == ftdi_chip_type::TYPE_2232H {
+                    buf[0x00] = (buf[0x00] & !(0x07 | DRIVER_VCP)) | (self.channel_a_type as u8 & 0x07)
+                        | flag(self.channel_a_driver != 0, DRIVER_VCP);
+                    buf[0x01] = (buf[0x01] & !(0x07 | DRIVER_VCP | SUSPEND_DBUS7_BIT)) | (self.channel_b_type as u8 & 0x07)
+                        | flag(self.channel_b_driver != 0, DRIVER_VCP)
+                        | flag(self.suspend_dbus7 != 0, SUSPEND_DBUS7_BIT);
+                } else {
+                    buf[0x00] = (buf[0x00] & !(DRIVER_VCP | DRIVER_VCP << 4))
+                        | flag(self.channel_a_driver != 0, DRIVER_VCP) | flag(self.channel_c_driver != 0, DRIVER_VCP << 4);
+                    buf[0x01] = (buf[0x01] & !(DRIVER_VCP | DRIVER_VCP << 4))
+                        | flag(self.channel_b_driver != 0, DRIVER_VCP) | flag(self.channel_d_driver != 0, DRIVER_VCP << 4);
+                    buf[0x0b] = (buf[0x0b] & 0x0f)
+                        | flag(self.channel_a_rs485enable, CHANNEL_IS_RS485)
+                        | flag(self.channel_b_rs485enable, CHANNEL_IS_RS485 << 1)
+                        | flag(self.channel_c_rs485enable, CHANNEL_IS_RS485 << 2)
+                        | flag(self.channel_d_rs485enable, CHANNEL_IS_RS485 << 3);
+                }
+                buf[0x0c] = encode_group(self.group0_drive, self.group0_schmitt, self.group0_slew)
+                    | (encode_group(self.group1_drive, self.group1_schmitt, self.group1_slew) << 4);
+                buf[0x0d] = encode_group(self.group2_drive, self.group2_schmitt, self.group2_slew)
+                    | (encode_group(self.group3_drive, self.group3_schmitt, self.group3_slew) << 4);
+            }
+            ftdi_chip_type::TYPE_232H => {
+                buf[0x00] = (buf[0x00] & !(0x0f | DRIVER_VCPH)) | (self.channel_a_type as u8 & 0x0f)
+                    | flag(self.channel_a_driver != 0, DRIVER_VCPH);
+                buf[0x01] = (buf[0x01] & !(FT1284_CLK_IDLE_STATE | FT1284_DATA_LSB | FT1284_FLOW_CONTROL | POWER_SAVE_DISABLE_H))
+                    | flag(self.clock_polarity != 0, FT1284_CLK_IDLE_STATE)
+                    | flag(self.data_order != 0, FT1284_DATA_LSB)
+                    | flag(self.flow_control != 0, FT1284_FLOW_CONTROL)
+                    | flag(self.powersave != 0, POWER_SAVE_DISABLE_H);
+                buf[0x0c] = (buf[0x0c] & 0xf0) | encode_group(self.group0_drive, self.group0_schmitt, self.group0_slew);
+                buf[0x0d] = (buf[0x0d] & 0xf0) | encode_group(self.group1_drive, self.group1_schmitt, self.group1_slew);
+                let cbus = ftdi_eeprom::cbus_nibbles(&self.cbus_function[..])?;
+                for index in 0..5 {
+                    buf[0x18 + index] = cbus[2 * index] | (cbus[2 * index + 1] << 4);
+                }
+            }
+            ftdi_chip_type::TYPE_230X => {
+                for index in 0..4 {
+                    if !(0..=0xff).contains(&self.cbus_function[index]) {
+                        return Err(common_error(-1, format!("CBUS{} function {} is out of range", index, self.cbus_function[index])));
+                    }
+                    buf[0x1a + index] = self.cbus_function[index] as u8;
+                }
+                buf[0x0b] = self.invert as u8;
+                buf[0x0c] = encode_group(self.group0_drive, self.group0_schmitt, self.group0_slew)
+                    | (encode_group(self.group1_drive, self.group1_schmitt, self.group1_slew) << 4);
+            }
+            _ => unreachable!("{:?} has no EEPROM layout", chip_type),
+        }
+        match (chip_type, ftdi_eeprom::chip_type_location(chip_type)) {
+            (ftdi_chip_type::TYPE_BM, _) => {}
+            (_, Some(location)) if self.chip > 0 => buf[location] = self.chip as u8,
+            _ => {}
+        }
+
+        if self.user_data_size > 0 {
+            let area = self.user_area_in(chip_type, size, free)?;
+            let end = area.start + self.user_data_size as usize;
+            if self.user_data_size as usize > FTDI_MAX_EEPROM_SIZE || end > area.end {
+                return Err(common_error(-3, format!("user data {:#04x}..{:#04x} doesn't fit free space {:#04x}..{:#04x} of {:?} EEPROM",
+                                                    area.start, end, area.start, area.end, chip_type)));
+            }
+            buf[area.start..end].copy_from_slice(&self.user_data[..self.user_data_size as usize]);
+        }
+
+        let checksum = ftdi_eeprom::checksum(chip_type, &buf, size);
+        buf[size - 2..].copy_from_slice(&checksum.to_le_bytes());
+        debug!("'ftdi_eeprom build' - OK, size = {}, strings end at {:#04x}", size, free);
+        Ok(buf)
+    }
+
+    /// Free bytes of image in 'buf' where build() puts user data: at 'user_data_addr', or right after
+    /// string descriptors when it's 0, up to the strings, the checksum or FT-X reserved words.
+    /// FT-X keeps it in words 0x12..0x40 the checksum doesn't cover when 'user_data_addr' is 0.
+    /// Board data can be kept there, the chip doesn't use it.
+    pub fn user_area(&self, chip_type: ftdi_chip_type) -> Result<Range<usize>> {
+        ftdi_eeprom::check_layout(chip_type)?;
+        let size = self.image_size()?;
+        let mask = size - 1;
+        let mut strings_end = ftdi_eeprom::strings_location(chip_type) & mask;
+        for pointer in [0x0e, 0x10, 0x12].iter() {
+            let offset = self.buf[*pointer] as usize & mask;
+            let length = self.buf[*pointer + 1] as usize;
+            if offset >= ftdi_eeprom::config_size(chip_type) && offset + length <= size - 2 {
+                strings_end = strings_end.max(offset + length);
+            }
+        }
+        let area = self.user_area_in(chip_type, size, strings_end)?;
+        debug!("'ftdi_eeprom user_area' for {:?} is {:#04x}..{:#04x}", chip_type, area.start, area.end);
+        Ok(area)
+    }
+
+    /// User area of image of 'size' bytes with string descriptors ending at 'strings_end'
+    fn user_area_in(&self, chip_type: ftdi_chip_type, size: usize, strings_end: usize) -> Result<Range<usize>> {
+        let strings_start = ftdi_eeprom::strings_location(chip_type) & (size - 1);
+        let reserved = (FTX_EEPROM_RESERVED_WORDS.start * 2) as usize..(FTX_EEPROM_RESERVED_WORDS.end * 2) as usize;
+        let is_ftx = chip_type == ftdi_chip_type::TYPE_230X;
+        let start = match self.user_data_addr as usize {
+            0 if is_ftx => FTX_EEPROM_UNCHECKED_WORDS.start * 2,
+            0 => strings_end,
+            address => address,
+        };
+        let mut end = size - 2;
+        if start < strings_start {
+            end = strings_start;
+        }
+        if is_ftx && start < reserved.start {
+            end = end.min(reserved.start);
+        }
+        if start < ftdi_eeprom::config_size(chip_type) || start > size - 2 || (strings_start <= start && start < strings_end)
+            || (is_ftx && reserved.contains(&start)) {
+            return Err(common_error(-3, format!("user area at {:#04x} isn't in free space of {:?} EEPROM", start, chip_type)));
+        }
+        Ok(start..end)
+    }
+
+    /// Content of user area of image in 'buf'
+    pub fn user_area_data(&self, chip_type: ftdi_chip_type) -> Result<&[u8]> {
+        let area = self.user_area(chip_type)?;
+        Ok(&self.buf[area])
+    }
+
+    /// Put data into user area of image in 'buf' at 'offset' from the area start and update the checksum.
+    /// Configuration words and strings are left as they are, image checksum must be valid.
+    /// return addresses of changed words, the checksum word among them
+    pub fn write_user_area(&mut self, chip_type: ftdi_chip_type, offset: usize, data: &[u8]) -> Result<Vec<u16>> {
+        debug!("start 'ftdi_eeprom write_user_area' offset = {}, data = {} bytes ...", offset, data.len());
+        let size = self.image_size()?;
+        let stored = u16::from_le_bytes([self.buf[size - 2], self.buf[size - 1]]);
+        let expected = ftdi_eeprom::checksum(chip_type, &self.buf, size);
+        if stored != expected {
+            return Err(common_error(-1, format!(
+                "EEPROM checksum error, stored = {:#06x}, calculated = {:#06x}, user area isn't written", stored, expected)));
+        }
+        let area = self.user_area(chip_type)?;
+        if offset + data.len() > area.len() {
+            return Err(common_error(-3, format!("{} bytes at offset {} don't fit user area {:#04x}..{:#04x} of {:?} EEPROM",
+                                                data.len(), offset, area.start, area.end, chip_type)));
+        }
+        let start = area.start + offset;
+        let old = self.buf[..size].to_vec();
+        self.buf[start..start + data.len()].copy_from_slice(data);
+        let checksum = ftdi_eeprom::checksum(chip_type, &self.buf, size);
+        self.buf[size - 2..size].copy_from_slice(&checksum.to_le_bytes());
+        let changed: Vec<u16> = (0..size / 2)
+            .filter(|word| old[word * 2..word * 2 + 2] != self.buf[word * 2..word * 2 + 2])
+            .map(|word| word as u16)
+            .collect();
+        debug!("'ftdi_eeprom write_user_area' - OK, {} words changed", changed.len());
+        Ok(changed)
+    }
+
+    /// Size of image in 'buf', it's set by reading or decoding EEPROM
+    fn image_size(&self) -> Result<usize> {
+        let size = self.size;
+        if size <= 0 || self.buf.len() < size as usize || (size != 0x80 && size as usize != FTDI_MAX_EEPROM_SIZE) {
+            return Err(common_error(-2, format!("no EEPROM image, size = {}, image = {} bytes", size, self.buf.len())));
         }
+        Ok(size as usize)
     }
+
+    /// EEPROM layouts of HP series chips and FT4232HA aren't known
+    fn check_layout(chip_type: ftdi_chip_type) -> Result<()> {
+        match chip_type {
+            ftdi_chip_type::TYPE_AM | ftdi_chip_type::TYPE_BM | ftdi_chip_type::TYPE_2232C | ftdi_chip_type::TYPE_R
+            | ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H | ftdi_chip_type::TYPE_232H
+            | ftdi_chip_type::TYPE_230X => Ok(()),
+            _ => Err(common_error(-1, format!("EEPROM layout of {:?} isn't supported", chip_type))),
+        }
+    }
+
+    /// Bytes at the start of image taken by configuration, strings and user area go after them
+    fn config_size(chip_type: ftdi_chip_type) -> usize {
+        match chip_type {
+            ftdi_chip_type::TYPE_AM | ftdi_chip_type::TYPE_BM => 0x14,
+            ftdi_chip_type::TYPE_2232C => 0x16,
+            ftdi_chip_type::TYPE_R => 0x18,
+            ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H => 0x1a,
+            ftdi_chip_type::TYPE_232H => 0x20,
+            ftdi_chip_type::TYPE_230X => 0x24,
+            _ => unreachable!("{:?} has no EEPROM layout", chip_type),
+        }
+    }
+
+    /// Offset of the first string descriptor as it's stored in image, it has bit 7 set
+    /// so it wraps into the lower half of 128 byte EEPROMs
+    fn strings_location(chip_type: ftdi_chip_type) -> usize {
+        match chip_type {
+            ftdi_chip_type::TYPE_AM | ftdi_chip_type::TYPE_BM => 0x94,
+            ftdi_chip_type::TYPE_2232C => 0x96,
+            ftdi_chip_type::TYPE_R => 0x98,
+            ftdi_chip_type::TYPE_2232H | ftdi_chip_type::TYPE_4232H => 0x9a,
+            ftdi_chip_type::TYPE_232H | ftdi_chip_type::TYPE_230X => 0xa0,
+            _ => unreachable!("{:?} has no EEPROM layout", chip_type),
+        }
+    }
+
+    fn cbus_nibbles(functions: &[i32]) -> Result<Vec<u8>> {
+        functions.iter().enumerate().map(|(index, function)| {
+            if (0..=0x0f).contains(function) {
+                Ok(*function as u8)
+            } else {
+                Err(common_error(-1, format!("CBUS{} function {} is out of range", index, function)))
+            }
+        }).collect()
+    }
+
+    /// Get value, flags are returned as 0 or 1
+    pub fn get_value(&self, value: ftdi_eeprom_value) -> Result<i32> {
+        self.check_applies(value)?;
+        use ftdi_eeprom_value::*;
+        let result = match value {
+            VENDOR_ID => self.vendor_id,
+            PRODUCT_ID => self.product_id,
+            SELF_POWERED => self.self_powered,
+            REMOTE_WAKEUP => self.remote_wakeup,
+            IS_NOT_PNP => self.is_not_pnp as i32,
+            SUSPEND_DBUS7 => self.suspend_dbus7,
+            IN_IS_ISOCHRONOUS => self.in_is_isochronous as i32,
+            OUT_IS_ISOCHRONOUS => self.out_is_isochronous as i32,
+            SUSPEND_PULL_DOWNS => self.suspend_pull_downs,
+            USE_SERIAL => self.use_serial as i32,
+            USB_VERSION => self.usb_version,
+            USE_USB_VERSION => self.use_usb_version,
+            MAX_POWER => self.max_power,
+            CHANNEL_A_TYPE => self.channel_a_type,
+            CHANNEL_B_TYPE => self.channel_b_type,
+            CHANNEL_A_DRIVER => self.channel_a_driver,
+            CHANNEL_B_DRIVER => self.channel_b_driver,
+            CHANNEL_C_DRIVER => self.channel_c_driver,
+            CHANNEL_D_DRIVER => self.channel_d_driver,
+            CBUS_FUNCTION_0 | CBUS_FUNCTION_1 | CBUS_FUNCTION_2 | CBUS_FUNCTION_3 | CBUS_FUNCTION_4
+            | CBUS_FUNCTION_5 | CBUS_FUNCTION_6 | CBUS_FUNCTION_7 | CBUS_FUNCTION_8 | CBUS_FUNCTION_9 =>
+                self.cbus_function[value as usize - CBUS_FUNCTION_0 as usize],
+            HIGH_CURRENT => self.high_current,
+            HIGH_CURRENT_A => self.high_current_a,
+            HIGH_CURRENT_B => self.high_current_b,
+            INVERT => self.invert,
+            GROUP0_DRIVE => self.group0_drive,
+            GROUP0_SCHMITT => self.group0_schmitt,
+            GROUP0_SLEW => self.group0_slew,
+            GROUP1_DRIVE => self.group1_drive,
+            GROUP1_SCHMITT => self.group1_schmitt,
+            GROUP1_SLEW => self.group1_slew,
+            GROUP2_DRIVE => self.group2_drive,
+            GROUP2_SCHMITT => self.group2_schmitt,
+            GROUP2_SLEW => self.group2_slew,
+            GROUP3_DRIVE => self.group3_drive,
+            GROUP3_SCHMITT => self.group3_schmitt,
+            GROUP3_SLEW => self.group3_slew,
+            CHIP_SIZE => self.size,
+            CHIP_TYPE => self.chip,
+            POWER_SAVE => self.powersave,
+            CLOCK_POLARITY => self.clock_polarity,
+            DATA_ORDER => self.data_order,
+            FLOW_CONTROL => self.flow_control,
+            CHANNEL_A_RS485 => self.channel_a_rs485enable as i32,
+            CHANNEL_B_RS485 => self.channel_b_rs485enable as i32,
+            CHANNEL_C_RS485 => self.channel_c_rs485enable as i32,
+            CHANNEL_D_RS485 => self.channel_d_rs485enable as i32,
+            RELEASE_NUMBER => self.release_number,
+            EXTERNAL_OSCILLATOR => self.external_oscillator,
+            USER_DATA_ADDR => self.user_data_addr,
+        };
+        Ok(result)
+    }
+
+    /// Set value, it's put into image by build. Flags take any non zero value as set.
+    /// EEPROM size is detected, not set.
+    pub fn set_value(&mut self, value: ftdi_eeprom_value, new_value: i32) -> Result<()> {
+        self.check_applies(value)?;
+        use ftdi_eeprom_value::*;
+        let check_range = |range: std::ops::RangeInclusive<i32>| {
+            if range.contains(&new_value) {
+                Ok(new_value)
+            } else {
+                Err(common_error(-1, format!("{} = {} is out of range {:?}", value, new_value, range)))
+            }
+        };
+        match value {
+            VENDOR_ID => self.vendor_id = check_range(0..=0xffff)?,
+            PRODUCT_ID => self.product_id = check_range(0..=0xffff)?,
+            SELF_POWERED => self.self_powered = (new_value != 0) as i32,
+            REMOTE_WAKEUP => self.remote_wakeup = (new_value != 0) as i32,
+            IS_NOT_PNP => self.is_not_pnp = new_value != 0,
+            SUSPEND_DBUS7 => self.suspend_dbus7 = (new_value != 0) as i32,
+            IN_IS_ISOCHRONOUS => self.in_is_isochronous = new_value != 0,
+            OUT_IS_ISOCHRONOUS => self.out_is_isochronous = new_value != 0,
+            SUSPEND_PULL_DOWNS => self.suspend_pull_downs = (new_value != 0) as i32,
+            USE_SERIAL => self.use_serial = new_value != 0,
+            USB_VERSION => self.usb_version = check_range(0..=0xffff)?,
+            USE_USB_VERSION => self.use_usb_version = (new_value != 0) as i32,
+            MAX_POWER => self.max_power = check_range(0..=500)?,
+            CHANNEL_A_TYPE => self.channel_a_type = check_range(0..=0x0f)?,
+            CHANNEL_B_TYPE => self.channel_b_type = check_range(0..=0x07)?,
+            CHANNEL_A_DRIVER => self.channel_a_driver = if new_value != 0 { DRIVER_VCP as i32 } else { 0 },
+            CHANNEL_B_DRIVER => self.channel_b_driver = if new_value != 0 { DRIVER_VCP as i32 } else { 0 },
+            CHANNEL_C_DRIVER => self.channel_c_driver = if new_value != 0 { DRIVER_VCP as i32 } else { 0 },
+            CHANNEL_D_DRIVER => self.channel_d_driver = if new_value != 0 { DRIVER_VCP as i32 } else { 0 },
+            CBUS_FUNCTION_0 | CBUS_FUNCTION_1 | CBUS_FUNCTION_2 | CBUS_FUNCTION_3 | CBUS_FUNCTION_4
+            | CBUS_FUNCTION_5 | CBUS_FUNCTION_6 | CBUS_FUNCTION_7 | CBUS_FUNCTION_8 | CBUS_FUNCTION_9 => {
+                let limit = if self.chip_type == Some(ftdi_chip_type::TYPE_230X) { 0xff } else { 0x0f };
+                self.cbus_function[value as usize - CBUS_FUNCTION_0 as usize] = check_range(0..=limit)?;
+            }
+            HIGH_CURRENT => self.high_current = if new_value != 0 { HIGH_CURRENT_DRIVE_R as i32 } else { 0 },
+            HIGH_CURRENT_A => self.high_current_a = if new_value != 0 { HIGH_CURRENT_DRIVE as i32 } else { 0 },
+            HIGH_CURRENT_B => self.high_current_b = if new_value != 0 { HIGH_CURRENT_DRIVE as i32 } else { 0 },
+            INVERT => self.invert = check_range(0..=0xff)?,
+            GROUP0_DRIVE => self.group0_drive = check_range(0..=DRIVE_16MA as i32)?,
+            GROUP0_SCHMITT => self.group0_schmitt = (new_value != 0) as i32,
+            GROUP0_SLEW => self.group0_slew = (new_value != 0) as i32,
+            GROUP1_DRIVE => self.group1_drive = check_range(0..=DRIVE_16MA as i32)?,
+            GROUP1_SCHMITT => self.group1_schmitt = (new_value != 0) as i32,
+            GROUP1_SLEW => self.group1_slew = (new_value != 0) as i32,
+            GROUP2_DRIVE => self.group2_drive = check_range(0..=DRIVE_16MA as i32)?,
+            GROUP2_SCHMITT => self.group2_schmitt = (new_value != 0) as i32,
+            GROUP2_SLEW => self.group2_slew = (new_value != 0) as i32,
+            GROUP3_DRIVE => self.group3_drive = check_range(0..=DRIVE_16MA as i32)?,
+            GROUP3_SCHMITT => self.group3_schmitt = (new_value != 0) as i32,
+            GROUP3_SLEW => self.group3_slew = (new_value != 0) as i32,
+            CHIP_SIZE => return Err(common_error(-1, "EEPROM size is detected, it can't be set".to_string())),
+            CHIP_TYPE => {
+                if matches!(self.chip_type, Some(ftdi_chip_type::TYPE_R) | Some(ftdi_chip_type::TYPE_230X)) {
+                    return Err(common_error(-1, "internal EEPROM has no 93xx chip type".to_string()));
+                }
+                if ![EEPROM_CHIP_93X46, EEPROM_CHIP_93X56, EEPROM_CHIP_93X66].contains(&new_value) {
+                    return Err(common_error(-1, format!("{:#x} isn't 93xx EEPROM chip type", new_value)));
+                }
+                self.chip = new_value;
+            }
+            POWER_SAVE => self.powersave = (new_value != 0) as i32,
+            CLOCK_POLARITY => self.clock_polarity = (new_value != 0) as i32,
+            DATA_ORDER => self.data_order = (new_value != 0) as i32,
+            FLOW_CONTROL => self.flow_control = (new_value != 0) as i32,
+            CHANNEL_A_RS485 => self.channel_a_rs485enable = new_value != 0,
+            CHANNEL_B_RS485 => self.channel_b_rs485enable = new_value != 0,
+            CHANNEL_C_RS485 => self.channel_c_rs485enable = new_value != 0,
+            CHANNEL_D_RS485 => self.channel_d_rs485enable = new_value != 0,
+            RELEASE_NUMBER => self.release_number = check_range(0..=0xffff)?,
+            EXTERNAL_OSCILLATOR => self.external_oscillator = if new_value != 0 { 0x02 } else { 0 },
+            USER_DATA_ADDR => self.user_data_addr = check_range(0..=FTDI_MAX_EEPROM_SIZE as i32 - 2)?,
+        }
+        debug!("EEPROM value {} = {}", value, new_value);
+        Ok(())
+    }
+
+    fn check_applies(&self, value: ftdi_eeprom_value) -> Result<()> {
+        match self.chip_type {
+            Some(chip_type) if !value.applies_to(chip_type) =>
+                Err(common_error(-1, format!("{:?} EEPROM has no value {}", chip_type, value))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Guess EEPROM size (bytes) and 93xx chip from the image read over USB.
+    /// Small chips show up mirrored, blank EEPROM gives size -1. Internal EEPROMs have fixed size and chip 0.
+    /// return tuple of size and chip
+    pub fn detect_size(chip_type: ftdi_chip_type, buf: &[u8]) -> (i32, i32) {
+        match chip_type {
+            ftdi_chip_type::TYPE_R => return (FT232R_EEPROM_SIZE, 0),
+            ftdi_chip_type::TYPE_230X => return (FTX_EEPROM_SIZE, 0),
+            _ => {}
+        }
+        if buf.len() < FTDI_MAX_EEPROM_SIZE || buf.iter().all(|byte| *byte == 0xff) {
+            return (-1, -1);
+        }
+        if buf[..0x80] == buf[0x80..FTDI_MAX_EEPROM_SIZE] {
+            return (0x80, EEPROM_CHIP_93X46);
+        }
+        // 93x56 and 93x66 differ only in the part beyond FTDI_MAX_EEPROM_SIZE, trust the chip type stored in image
+        let stored = ftdi_eeprom::chip_type_location(chip_type).map(|location| buf[location] as i32);
+        match stored {
+            Some(EEPROM_CHIP_93X66) => (FTDI_MAX_EEPROM_SIZE as i32, EEPROM_CHIP_93X66),
+            _ => (FTDI_MAX_EEPROM_SIZE as i32, EEPROM_CHIP_93X56),
+        }
+    }
+}
+
+/// Drive strength, schmitt trigger and slow slew flags of a pin group from the low nibble of 'bits'
+fn decode_group(drive: &mut i32, schmitt: &mut i32, slew: &mut i32, bits: u8, drive_mask: u8) {
+    *drive = (bits & drive_mask) as i32;
+    *schmitt = (bits & IS_SCHMITT != 0) as i32;
+    *slew = (bits & SLOW_SLEW != 0) as i32;
+}
+
+fn flag(set: bool, bits: u8) -> u8 {
+    if set { bits } else { 0 }
+}
+
+/// Pin group nibble: drive strength, schmitt trigger and slow slew flags
+fn encode_group(drive: i32, schmitt: i32, slew: i32) -> u8 {
+    (drive as u8 & DRIVE_16MA) | flag(schmitt != 0, IS_SCHMITT) | flag(slew != 0, SLOW_SLEW)
+}
+
+/// FT2232C channel byte: hardware type, VCP driver, high current drive
+fn encode_channel(old: u8, channel_type: i32, driver: i32, high_current: i32) -> u8 {
+    (old & !(0x07 | DRIVER_VCP | HIGH_CURRENT_DRIVE)) | (channel_type as u8 & 0x07)
+        | flag(driver != 0, DRIVER_VCP) | flag(high_current != 0, HIGH_CURRENT_DRIVE)
+}
+
+/// USB string descriptor: length, type, UTF-16LE characters. Empty string takes no space.
+fn encode_string(string: &str) -> Vec<u8> {
+    if string.is_empty() {
+        return Vec::new();
+    }
+    let mut descriptor = vec![0, STRING_DESCRIPTOR_TYPE];
+    string.encode_utf16().for_each(|unit| descriptor.extend_from_slice(&unit.to_le_bytes()));
+    descriptor[0] = descriptor.len() as u8;
+    descriptor
+}
+
+/// Decode USB string descriptor referenced by offset and length bytes at 'pointer'.
+/// Offset has bit 7 set in images of 128 byte EEPROMs, it's masked by EEPROM size.
+fn decode_string(buf: &[u8], pointer: usize) -> Result<String> {
+    let length = buf[pointer + 1] as usize;
+    if length == 0 {
+        return Ok(String::new());
+    }
+    let offset = buf[pointer] as usize & (buf.len() - 1);
+    if length < 2 || !length.is_multiple_of(2) || offset + length > buf.len() {
+        return Err(common_error(-2, format!("string descriptor at {:#04x}, {} bytes, doesn't fit EEPROM", offset, length)));
+    }
+    if buf[offset] as usize != length || buf[offset + 1] != STRING_DESCRIPTOR_TYPE {
+        warn!("string descriptor at {:#04x} has header {:02x?}", offset, &buf[offset..offset + 2]);
+    }
+    let units: Vec<u16> = buf[offset + 2..offset + length].chunks(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+    String::from_utf16(&units)
+        .map_err(|_| common_error(-2, format!("string descriptor at {:#04x} isn't valid UTF-16", offset)))
 }
 
 /// List all handled EEPROM values.
@@ -247,68 +972,129 @@ pub enum ftdi_eeprom_value {
     EXTERNAL_OSCILLATOR= 56,
     USER_DATA_ADDR     = 57,
 }
-impl From<u8> for ftdi_eeprom_value {
-    fn from(value: u8) -> ftdi_eeprom_value {
-        match value {
-            0 => ftdi_eeprom_value::VENDOR_ID,
-            1 => ftdi_eeprom_value::PRODUCT_ID,
-            2 => ftdi_eeprom_value::SELF_POWERED,
-            3 => ftdi_eeprom_value::REMOTE_WAKEUP,
-            4 => ftdi_eeprom_value::IS_NOT_PNP,
-            5 => ftdi_eeprom_value::SUSPEND_DBUS7,
-            6 => ftdi_eeprom_value::IN_IS_ISOCHRONOUS,
-            7 => ftdi_eeprom_value::OUT_IS_ISOCHRONOUS,
-            8 => ftdi_eeprom_value::SUSPEND_PULL_DOWNS,
-            9 => ftdi_eeprom_value::USE_SERIAL,
-            10 => ftdi_eeprom_value::USB_VERSION,
-            11 => ftdi_eeprom_value::USE_USB_VERSION,
-            12 => ftdi_eeprom_value::MAX_POWER,
-            13 => ftdi_eeprom_value::CHANNEL_A_TYPE,
-            14 => ftdi_eeprom_value::CHANNEL_B_TYPE,
-            15 => ftdi_eeprom_value::CHANNEL_A_DRIVER,
-            16 => ftdi_eeprom_value::CHANNEL_B_DRIVER,
-            17 => ftdi_eeprom_value::CBUS_FUNCTION_0,
-            18 => ftdi_eeprom_value::CBUS_FUNCTION_1,
-            19 => ftdi_eeprom_value::CBUS_FUNCTION_2,
-            20 => ftdi_eeprom_value::CBUS_FUNCTION_3,
-            21 => ftdi_eeprom_value::CBUS_FUNCTION_4,
-            22 => ftdi_eeprom_value::CBUS_FUNCTION_5,
-            23 => ftdi_eeprom_value::CBUS_FUNCTION_6,
-            24 => ftdi_eeprom_value::CBUS_FUNCTION_7,
-            25 => ftdi_eeprom_value::CBUS_FUNCTION_8,
-            26 => ftdi_eeprom_value::CBUS_FUNCTION_9,
-            27 => ftdi_eeprom_value::HIGH_CURRENT,
-            28 => ftdi_eeprom_value::HIGH_CURRENT_A,
-            29 => ftdi_eeprom_value::HIGH_CURRENT_B,
-            30 => ftdi_eeprom_value::INVERT,
-            31 => ftdi_eeprom_value::GROUP0_DRIVE,
-            32 => ftdi_eeprom_value::GROUP0_SCHMITT,
-            33 => ftdi_eeprom_value::GROUP0_SLEW,
-            34 => ftdi_eeprom_value::GROUP1_DRIVE,
-            35 => ftdi_eeprom_value::GROUP1_SCHMITT,
-            36 => ftdi_eeprom_value::GROUP1_SLEW,
-            37 => ftdi_eeprom_value::GROUP2_DRIVE,
-            38 => ftdi_eeprom_value::GROUP2_SCHMITT,
-            39 => ftdi_eeprom_value::GROUP2_SLEW,
-            40 => ftdi_eeprom_value::GROUP3_DRIVE,
-            41 => ftdi_eeprom_value::GROUP3_SCHMITT,
-            42 => ftdi_eeprom_value::GROUP3_SLEW,
-            43 => ftdi_eeprom_value::CHIP_SIZE,
-            44 => ftdi_eeprom_value::CHIP_TYPE,
-            45 => ftdi_eeprom_value::POWER_SAVE,
-            46 => ftdi_eeprom_value::CLOCK_POLARITY,
-            47 => ftdi_eeprom_value::DATA_ORDER,
-            48 => ftdi_eeprom_value::FLOW_CONTROL,
-            49 => ftdi_eeprom_value::CHANNEL_C_DRIVER,
-            50 => ftdi_eeprom_value::CHANNEL_D_DRIVER,
-            51 => ftdi_eeprom_value::CHANNEL_A_RS485,
-            52 => ftdi_eeprom_value::CHANNEL_B_RS485,
-            53 => ftdi_eeprom_value::CHANNEL_C_RS485,
-            54 => ftdi_eeprom_value::CHANNEL_D_RS485,
-            55 => ftdi_eeprom_value::RELEASE_NUMBER,
-            56 => ftdi_eeprom_value::EXTERNAL_OSCILLATOR,
-            57 => ftdi_eeprom_value::USER_DATA_ADDR,
-            _ => panic!("ftdi_eeprom_value is unknown for value = {}", value),
-        }
+/// Values in order of their numbers with names used by scripts and configuration files
+const EEPROM_VALUES: [(ftdi_eeprom_value, &str); 58] = [
+    (ftdi_eeprom_value::VENDOR_ID, "vendor_id"),
+    (ftdi_eeprom_value::PRODUCT_ID, "product_id"),
+    (ftdi_eeprom_value::SELF_POWERED, "self_powered"),
+    (ftdi_eeprom_value::REMOTE_WAKEUP, "remote_wakeup"),
+    (ftdi_eeprom_value::IS_NOT_PNP, "is_not_pnp"),
+    (ftdi_eeprom_value::SUSPEND_DBUS7, "suspend_dbus7"),
+    (ftdi_eeprom_value::IN_IS_ISOCHRONOUS, "in_is_isochronous"),
+    (ftdi_eeprom_value::OUT_IS_ISOCHRONOUS, "out_is_isochronous"),
+    (ftdi_eeprom_value::SUSPEND_PULL_DOWNS, "suspend_pull_downs"),
+    (ftdi_eeprom_value::USE_SERIAL, "use_serial"),
+    (ftdi_eeprom_value::USB_VERSION, "usb_version"),
+    (ftdi_eeprom_value::USE_USB_VERSION, "use_usb_version"),
+    (ftdi_eeprom_value::MAX_POWER, "max_power"),
+    (ftdi_eeprom_value::CHANNEL_A_TYPE, "channel_a_type"),
+    (ftdi_eeprom_value::CHANNEL_B_TYPE, "channel_b_type"),
+    (ftdi_eeprom_value::CHANNEL_A_DRIVER, "channel_a_driver"),
+    (ftdi_eeprom_value::CHANNEL_B_DRIVER, "channel_b_driver"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_0, "cbus_function_0"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_1, "cbus_function_1"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_2, "cbus_function_2"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_3, "cbus_function_3"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_4, "cbus_function_4"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_5, "cbus_function_5"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_6, "cbus_function_6"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_7, "cbus_function_7"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_8, "cbus_function_8"),
+    (ftdi_eeprom_value::CBUS_FUNCTION_9, "cbus_function_9"),
+    (ftdi_eeprom_value::HIGH_CURRENT, "high_current"),
+    (ftdi_eeprom_value::HIGH_CURRENT_A, "high_current_a"),
+    (ftdi_eeprom_value::HIGH_CURRENT_B, "high_current_b"),
+    (ftdi_eeprom_value::INVERT, "invert"),
+    (ftdi_eeprom_value::GROUP0_DRIVE, "group0_drive"),
+    (ftdi_eeprom_value::GROUP0_SCHMITT, "group0_schmitt"),
+    (ftdi_eeprom_value::GROUP0_SLEW, "group0_slew"),
+    (ftdi_eeprom_value::GROUP1_DRIVE, "group1_drive"),
+    (ftdi_eeprom_value::GROUP1_SCHMITT, "group1_schmitt"),
+    (ftdi_eeprom_value::GROUP1_SLEW, "group1_slew"),
+    (ftdi_eeprom_value::GROUP2_DRIVE, "group2_drive"),
+    (ftdi_eeprom_value::GROUP2_SCHMITT, "group2_schmitt"),
+    (ftdi_eeprom_value::GROUP2_SLEW, "group2_slew"),
+    (ftdi_eeprom_value::GROUP3_DRIVE, "group3_drive"),
+    (ftdi_eeprom_value::GROUP3_SCHMITT, "group3_schmitt"),
+    (ftdi_eeprom_value::GROUP3_SLEW, "group3_slew"),
+    (ftdi_eeprom_value::CHIP_SIZE, "chip_size"),
+    (ftdi_eeprom_value::CHIP_TYPE, "chip_type"),
+    (ftdi_eeprom_value::POWER_SAVE, "power_save"),
+    (ftdi_eeprom_value::CLOCK_POLARITY, "clock_polarity"),
+    (ftdi_eeprom_value::DATA_ORDER, "data_order"),
+    (ftdi_eeprom_value::FLOW_CONTROL, "flow_control"),
+    (ftdi_eeprom_value::CHANNEL_C_DRIVER, "channel_c_driver"),
+    (ftdi_eeprom_value::CHANNEL_D_DRIVER, "channel_d_driver"),
+    (ftdi_eeprom_value::CHANNEL_A_RS485, "channel_a_rs485"),
+    (ftdi_eeprom_value::CHANNEL_B_RS485, "channel_b_rs485"),
+    (ftdi_eeprom_value::CHANNEL_C_RS485, "channel_c_rs485"),
+    (ftdi_eeprom_value::CHANNEL_D_RS485, "channel_d_rs485"),
+    (ftdi_eeprom_value::RELEASE_NUMBER, "release_number"),
+    (ftdi_eeprom_value::EXTERNAL_OSCILLATOR, "external_oscillator"),
+    (ftdi_eeprom_value::USER_DATA_ADDR, "user_data_addr"),
+];
+impl TryFrom<u8> for ftdi_eeprom_value {
+    type Error = FtdiContextError;
+    fn try_from(value: u8) -> Result<ftdi_eeprom_value> {
+        EEPROM_VALUES.get(value as usize).map(|(eeprom_value, _)| *eeprom_value)
+            .ok_or_else(|| common_error(-1, format!("EEPROM value {} is unknown", value)))
+    }
+}
+impl FromStr for ftdi_eeprom_value {
+    type Err = FtdiContextError;
+    /// Name is case insensitive, "vendor_id" and "VENDOR_ID" are the same
+    fn from_str(name: &str) -> Result<ftdi_eeprom_value> {
+        EEPROM_VALUES.iter().find(|(_, value_name)| value_name.eq_ignore_ascii_case(name))
+            .map(|(eeprom_value, _)| *eeprom_value)
+            .ok_or_else(|| common_error(-1, format!("EEPROM value '{}' is unknown", name)))
     }
-}
\ No newline at end of file
+}
+impl Display for ftdi_eeprom_value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl ftdi_eeprom_value {
+    /// Lower case name, it's the name of ftdi_eeprom field for most values
+    pub fn name(self) -> &'static str {
+        EEPROM_VALUES[self as usize].1
+    }
+
+    /// All values in order of their numbers
+    pub fn all() -> impl Iterator<Item = ftdi_eeprom_value> {
+        EEPROM_VALUES.iter().map(|(eeprom_value, _)| *eeprom_value)
+    }
+
+    /// Value is stored in EEPROM of chip
+    pub fn applies_to(self, chip_type: ftdi_chip_type) -> bool {
+        use ftdi_chip_type::*;
+        use ftdi_eeprom_value::*;
+        let chips: &[ftdi_chip_type] = match self {
+            VENDOR_ID | PRODUCT_ID | RELEASE_NUMBER | SELF_POWERED | REMOTE_WAKEUP | IN_IS_ISOCHRONOUS
+            | OUT_IS_ISOCHRONOUS | SUSPEND_PULL_DOWNS | USE_SERIAL | USE_USB_VERSION | MAX_POWER
+            | CHIP_SIZE | CHIP_TYPE | USER_DATA_ADDR => return true,
+            // no layout keeps it
+            IS_NOT_PNP => &[],
+            USB_VERSION => &[TYPE_AM, TYPE_BM, TYPE_2232C, TYPE_R],
+            SUSPEND_DBUS7 => &[TYPE_2232H],
+            CHANNEL_A_TYPE => &[TYPE_2232C, TYPE_2232H, TYPE_232H],
+            CHANNEL_B_TYPE => &[TYPE_2232C, TYPE_2232H],
+            CHANNEL_A_DRIVER => &[TYPE_2232C, TYPE_R, TYPE_2232H, TYPE_4232H, TYPE_232H],
+            CHANNEL_B_DRIVER => &[TYPE_2232C, TYPE_2232H, TYPE_4232H],
+            CHANNEL_C_DRIVER | CHANNEL_D_DRIVER | CHANNEL_A_RS485 | CHANNEL_B_RS485 | CHANNEL_C_RS485
+            | CHANNEL_D_RS485 => &[TYPE_4232H],
+            CBUS_FUNCTION_0 | CBUS_FUNCTION_1 | CBUS_FUNCTION_2 | CBUS_FUNCTION_3 => &[TYPE_R, TYPE_232H, TYPE_230X],
+            CBUS_FUNCTION_4 => &[TYPE_R, TYPE_232H],
+            CBUS_FUNCTION_5 | CBUS_FUNCTION_6 | CBUS_FUNCTION_7 | CBUS_FUNCTION_8 | CBUS_FUNCTION_9 => &[TYPE_232H],
+            HIGH_CURRENT | EXTERNAL_OSCILLATOR => &[TYPE_R],
+            HIGH_CURRENT_A | HIGH_CURRENT_B => &[TYPE_2232C],
+            INVERT => &[TYPE_R, TYPE_230X],
+            GROUP0_DRIVE | GROUP0_SCHMITT | GROUP0_SLEW | GROUP1_DRIVE | GROUP1_SCHMITT | GROUP1_SLEW =>
+                &[TYPE_2232H, TYPE_4232H, TYPE_232H, TYPE_230X],
+            GROUP2_DRIVE | GROUP2_SCHMITT | GROUP2_SLEW | GROUP3_DRIVE | GROUP3_SCHMITT | GROUP3_SLEW =>
+                &[TYPE_2232H, TYPE_4232H],
+            POWER_SAVE | CLOCK_POLARITY | DATA_ORDER | FLOW_CONTROL => &[TYPE_232H],
+        };
+        chips.contains(&chip_type)
+    }
+}
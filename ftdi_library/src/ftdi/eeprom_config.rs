@@ -0,0 +1,792 @@
+#![allow(dead_code)]
+
+use std::fmt::Write;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::ftdi::cbus::CbusFunction;
+use crate::ftdi::constants::{*};
+use crate::ftdi::eeprom::{ftdi_eeprom, ftdi_eeprom_value, EEPROM_CHIP_93X46, EEPROM_CHIP_93X56, EEPROM_CHIP_93X66,
+                          FTDI_MAX_EEPROM_SIZE, MAX_POWER_MILLIAMP_PER_UNIT};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mcu::Ft1284Config;
+
+/// Chip names used in configuration files
+const CHIP_NAMES: [(ftdi_chip_type, &str); 15] = [
+    (ftdi_chip_type::TYPE_AM, "FT8U232AM"),
+    (ftdi_chip_type::TYPE_BM, "FT232BM"),
+    (ftdi_chip_type::TYPE_2232C, "FT2232C"),
+    (ftdi_chip_type::TYPE_R, "FT232R"),
+    (ftdi_chip_type::TYPE_2232H, "FT2232H"),
+    (ftdi_chip_type::TYPE_4232H, "FT4232H"),
+    (ftdi_chip_type::TYPE_232H, "FT232H"),
+    (ftdi_chip_type::TYPE_230X, "FT230X"),
+    (ftdi_chip_type::TYPE_2233HP, "FT2233HP"),
+    (ftdi_chip_type::TYPE_4233HP, "FT4233HP"),
+    (ftdi_chip_type::TYPE_2232HP, "FT2232HP"),
+    (ftdi_chip_type::TYPE_4232HP, "FT4232HP"),
+    (ftdi_chip_type::TYPE_233HP, "FT233HP"),
+    (ftdi_chip_type::TYPE_232HP, "FT232HP"),
+    (ftdi_chip_type::TYPE_4232HA, "FT4232HA"),
+];
+const CHANNEL_TYPES: [(u8, &str); 5] = [
+    (CHANNEL_IS_UART, "UART"),
+    (CHANNEL_IS_FIFO, "FIFO"),
+    (CHANNEL_IS_OPTO, "OPTO"),
+    (CHANNEL_IS_CPU, "CPU"),
+    (CHANNEL_IS_FT1284, "FT1284"),
+];
+const INVERTED_SIGNALS: [(u8, &str); 8] = [
+    (INVERT_TXD, "TXD"),
+    (INVERT_RXD, "RXD"),
+    (INVERT_RTS, "RTS"),
+    (INVERT_CTS, "CTS"),
+    (INVERT_DTR, "DTR"),
+    (INVERT_DSR, "DSR"),
+    (INVERT_DCD, "DCD"),
+    (INVERT_RI, "RI"),
+];
+const EEPROM_CHIPS: [(i32, &str); 3] = [
+    (EEPROM_CHIP_93X46, "93x46"),
+    (EEPROM_CHIP_93X56, "93x56"),
+    (EEPROM_CHIP_93X66, "93x66"),
+];
+/// Drive strength of pin group, mA, index is DRIVE_xxx value
+const GROUP_DRIVES: [u8; 4] = [4, 8, 12, 16];
+/// Options of libftdi ftdi_eeprom tool which don't describe EEPROM content
+const INI_TOOL_KEYS: [&str; 4] = ["filename", "flash_raw", "default_pid", "user_data_file"];
+
+/// Name of chip in configuration files, e.g. "FT232R"
+pub fn chip_name(chip: ftdi_chip_type) -> &'static str {
+    CHIP_NAMES.iter().find(|(chip_type, _)| *chip_type == chip).map(|(_, name)| *name).unwrap_or("unknown")
+}
+
+/// Chip by its name in configuration files, case insensitive, "FT" prefix is optional
+pub fn chip_from_name(name: &str) -> Result<ftdi_chip_type> {
+    CHIP_NAMES.iter()
+        .find(|(_, chip_name)| chip_name.eq_ignore_ascii_case(name) || chip_name[2..].eq_ignore_ascii_case(name))
+        .map(|(chip_type, _)| *chip_type)
+        .ok_or_else(|| common_error(-1, format!("chip '{}' is unknown", name)))
+}
+
+/// Look name up in table, case insensitive
+fn lookup<T: Copy>(table: &[(T, &str)], name: &str, what: &str) -> Result<T> {
+    table.iter().find(|(_, table_name)| table_name.eq_ignore_ascii_case(name)).map(|(value, _)| *value)
+        .ok_or_else(|| common_error(-1, format!("{} '{}' is unknown, expected one of {:?}",
+                                                what, name, table.iter().map(|(_, name)| *name).collect::<Vec<_>>())))
+}
+
+/// Ids and versions are written as "0x0403", numbers are accepted as well
+mod hex {
+    use std::fmt;
+    use serde::{de::{Error, Visitor}, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#06x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        deserializer.deserialize_any(HexVisitor)
+    }
+
+    struct HexVisitor;
+    impl<'de> Visitor<'de> for HexVisitor {
+        type Value = u16;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("16 bit number or string like \"0x0403\"")
+        }
+
+        fn visit_i64<E: Error>(self, value: i64) -> Result<u16, E> {
+            if !(0..=0xffff).contains(&value) {
+                return Err(E::custom(format!("{} doesn't fit 16 bits", value)));
+            }
+            Ok(value as u16)
+        }
+
+        fn visit_u64<E: Error>(self, value: u64) -> Result<u16, E> {
+            self.visit_i64(value.min(i64::MAX as u64) as i64)
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<u16, E> {
+            super::parse_number(value).map_err(E::custom).and_then(|value| self.visit_i64(value))
+        }
+    }
+
+    pub mod option {
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(value: &Option<u16>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Decimal or 0x prefixed hexadecimal number
+fn parse_number(value: &str) -> std::result::Result<i64, String> {
+    let value = value.trim();
+    let parsed = if value.starts_with("0x") || value.starts_with("0X") {
+        i64::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse::<i64>()
+    };
+    parsed.map_err(|_| format!("'{}' isn't a number", value))
+}
+
+/// Interface settings, a value the chip doesn't have on this channel must be left out
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelConfig {
+    /// "UART", "FIFO", "OPTO", "CPU" or "FT1284"
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub channel_type: Option<String>,
+    /// "VCP" or "D2XX"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rs485: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_current: Option<bool>,
+}
+impl ChannelConfig {
+    fn is_empty(&self) -> bool {
+        *self == ChannelConfig::default()
+    }
+}
+
+/// Pin group drive settings of H and X chips
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PinGroupConfig {
+    /// Drive strength, 4, 8, 12 or 16 mA
+    pub drive: u8,
+    pub schmitt: bool,
+    pub slow_slew: bool,
+}
+impl Default for PinGroupConfig {
+    fn default() -> Self {
+        PinGroupConfig { drive: 4, schmitt: false, slow_slew: false }
+    }
+}
+
+/// EEPROM content in the form people write it: names instead of numbers, power in mA.
+/// It's kept in TOML files, libftdi ftdi_eeprom INI files are read and written as well.
+/// Optional values left out keep what EEPROM structure has when the configuration is applied.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EepromConfig {
+    /// Chip the configuration is written for, e.g. "FT232R". It's checked when applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip: Option<String>,
+    #[serde(with = "hex")]
+    pub vendor_id: u16,
+    #[serde(with = "hex")]
+    pub product_id: u16,
+    #[serde(with = "hex")]
+    pub release_number: u16,
+    pub manufacturer: String,
+    pub product: String,
+    pub serial: String,
+    pub use_serial: bool,
+    pub self_powered: bool,
+    pub remote_wakeup: bool,
+    /// Current drawn from USB bus, mA, it's stored in units of MAX_POWER_MILLIAMP_PER_UNIT mA
+    pub max_power: u16,
+    pub in_is_isochronous: bool,
+    pub out_is_isochronous: bool,
+    pub suspend_pull_downs: bool,
+    /// USB version reported instead of chip default (BM, 2232C and R)
+    #[serde(with = "hex::option", skip_serializing_if = "Option::is_none")]
+    pub usb_version: Option<u16>,
+    /// External EEPROM chip, "93x46", "93x56" or "93x66"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eeprom_chip: Option<String>,
+    /// FT232R high current IO
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_current: Option<bool>,
+    /// FT232R external oscillator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_oscillator: Option<bool>,
+    /// FT2232H suspend on DBUS7 low
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspend_dbus7: Option<bool>,
+    /// FT232H power save disable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub powersave: Option<bool>,
+    /// Inverted UART signals, e.g. ["TXD", "RXD"] (R and X chips)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub invert: Vec<String>,
+    /// CBUS pin functions by name, CBUS0 (ACBUS0 on FT232H) first, empty name keeps pin as it is
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cbus: Vec<String>,
+    #[serde(with = "hex::option", skip_serializing_if = "Option::is_none")]
+    pub user_data_addr: Option<u16>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub user_data: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_a: Option<ChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_b: Option<ChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_c: Option<ChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_d: Option<ChannelConfig>,
+    /// FT232H FT1284 options, channel A is configured as FT1284 when they're present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft1284: Option<Ft1284Config>,
+    /// Pin groups, group 0 first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<PinGroupConfig>,
+}
+impl Default for EepromConfig {
+    fn default() -> Self {
+        EepromConfig {
+            chip: None,
+            vendor_id: 0x0403,
+            product_id: 0x6001,
+            release_number: 0,
+            manufacturer: String::new(),
+            product: String::new(),
+            serial: String::new(),
+            use_serial: false,
+            self_powered: false,
+            remote_wakeup: false,
+            max_power: 100,
+            in_is_isochronous: false,
+            out_is_isochronous: false,
+            suspend_pull_downs: false,
+            usb_version: None,
+            eeprom_chip: None,
+            high_current: None,
+            external_oscillator: None,
+            suspend_dbus7: None,
+            powersave: None,
+            invert: Vec::new(),
+            cbus: Vec::new(),
+            user_data_addr: None,
+            user_data: Vec::new(),
+            channel_a: None,
+            channel_b: None,
+            channel_c: None,
+            channel_d: None,
+            ft1284: None,
+            groups: Vec::new(),
+        }
+    }
+}
+
+/// EEPROM values of one channel: type, driver, RS485, high current
+struct ChannelValues {
+    name: char,
+    channel_type: Option<ftdi_eeprom_value>,
+    driver: ftdi_eeprom_value,
+    rs485: ftdi_eeprom_value,
+    high_current: Option<ftdi_eeprom_value>,
+}
+const CHANNEL_VALUES: [ChannelValues; 4] = [
+    ChannelValues { name: 'A', channel_type: Some(ftdi_eeprom_value::CHANNEL_A_TYPE), driver: ftdi_eeprom_value::CHANNEL_A_DRIVER,
+        rs485: ftdi_eeprom_value::CHANNEL_A_RS485, high_current: Some(ftdi_eeprom_value::HIGH_CURRENT_A) },
+    ChannelValues { name: 'B', channel_type: Some(ftdi_eeprom_value::CHANNEL_B_TYPE), driver: ftdi_eeprom_value::CHANNEL_B_DRIVER,
+        rs485: ftdi_eeprom_value::CHANNEL_B_RS485, high_current: Some(ftdi_eeprom_value::HIGH_CURRENT_B) },
+    ChannelValues { name: 'C', channel_type: None, driver: ftdi_eeprom_value::CHANNEL_C_DRIVER,
+        rs485: ftdi_eeprom_value::CHANNEL_C_RS485, high_current: None },
+    ChannelValues { name: 'D', channel_type: None, driver: ftdi_eeprom_value::CHANNEL_D_DRIVER,
+        rs485: ftdi_eeprom_value::CHANNEL_D_RS485, high_current: None },
+];
+const CBUS_VALUES: [ftdi_eeprom_value; 10] = [
+    ftdi_eeprom_value::CBUS_FUNCTION_0, ftdi_eeprom_value::CBUS_FUNCTION_1, ftdi_eeprom_value::CBUS_FUNCTION_2,
+    ftdi_eeprom_value::CBUS_FUNCTION_3, ftdi_eeprom_value::CBUS_FUNCTION_4, ftdi_eeprom_value::CBUS_FUNCTION_5,
+    ftdi_eeprom_value::CBUS_FUNCTION_6, ftdi_eeprom_value::CBUS_FUNCTION_7, ftdi_eeprom_value::CBUS_FUNCTION_8,
+    ftdi_eeprom_value::CBUS_FUNCTION_9,
+];
+/// Drive, schmitt and slew values of pin groups
+const GROUP_VALUES: [[ftdi_eeprom_value; 3]; 4] = [
+    [ftdi_eeprom_value::GROUP0_DRIVE, ftdi_eeprom_value::GROUP0_SCHMITT, ftdi_eeprom_value::GROUP0_SLEW],
+    [ftdi_eeprom_value::GROUP1_DRIVE, ftdi_eeprom_value::GROUP1_SCHMITT, ftdi_eeprom_value::GROUP1_SLEW],
+    [ftdi_eeprom_value::GROUP2_DRIVE, ftdi_eeprom_value::GROUP2_SCHMITT, ftdi_eeprom_value::GROUP2_SLEW],
+    [ftdi_eeprom_value::GROUP3_DRIVE, ftdi_eeprom_value::GROUP3_SCHMITT, ftdi_eeprom_value::GROUP3_SLEW],
+];
+
+impl EepromConfig {
+    /// Describe decoded EEPROM of chip, only values the chip has are filled
+    pub fn from_eeprom(eeprom: &ftdi_eeprom, chip: ftdi_chip_type) -> Result<Self> {
+        let applies = |value: ftdi_eeprom_value| value.applies_to(chip);
+        let flag = |value: ftdi_eeprom_value| -> Result<Option<bool>> {
+            Ok(if applies(value) { Some(eeprom.get_value(value)? != 0) } else { None })
+        };
+        let mut config = EepromConfig {
+            chip: Some(chip_name(chip).to_string()),
+            vendor_id: eeprom.vendor_id as u16,
+            product_id: eeprom.product_id as u16,
+            release_number: eeprom.release_number as u16,
+            manufacturer: eeprom.manufacturer.clone(),
+            product: eeprom.product.clone(),
+            serial: eeprom.serial.clone(),
+            use_serial: eeprom.use_serial,
+            self_powered: eeprom.self_powered != 0,
+            remote_wakeup: eeprom.remote_wakeup != 0,
+            max_power: eeprom.max_power as u16,
+            in_is_isochronous: eeprom.in_is_isochronous,
+            out_is_isochronous: eeprom.out_is_isochronous,
+            suspend_pull_downs: eeprom.suspend_pull_downs != 0,
+            high_current: flag(ftdi_eeprom_value::HIGH_CURRENT)?,
+            external_oscillator: flag(ftdi_eeprom_value::EXTERNAL_OSCILLATOR)?,
+            suspend_dbus7: flag(ftdi_eeprom_value::SUSPEND_DBUS7)?,
+            powersave: flag(ftdi_eeprom_value::POWER_SAVE)?,
+            ..EepromConfig::default()
+        };
+        if eeprom.use_usb_version != 0 && applies(ftdi_eeprom_value::USB_VERSION) {
+            config.usb_version = Some(eeprom.usb_version as u16);
+        }
+        config.eeprom_chip = EEPROM_CHIPS.iter().find(|(eeprom_chip, _)| *eeprom_chip == eeprom.chip)
+            .map(|(_, name)| name.to_string());
+        if applies(ftdi_eeprom_value::INVERT) {
+            config.invert = INVERTED_SIGNALS.iter().filter(|(bit, _)| eeprom.invert & *bit as i32 != 0)
+                .map(|(_, name)| name.to_string()).collect();
+        }
+        for (pin, function) in eeprom.cbus_function.iter().enumerate() {
+            if !applies(CBUS_VALUES[pin]) {
+                break;
+            }
+            let function = CbusFunction::decode(chip, *function)
+                .ok_or_else(|| common_error(-1, format!("CBUS{} function {} is unknown for {}", pin, function, chip_name(chip))))?;
+            config.cbus.push(function.name());
+        }
+        for (channel, values) in CHANNEL_VALUES.iter().enumerate() {
+            let mut channel_config = ChannelConfig::default();
+            if let Some(value) = values.channel_type.filter(|value| applies(*value)) {
+                let channel_type = eeprom.get_value(value)? as u8;
+                channel_config.channel_type = Some(CHANNEL_TYPES.iter().find(|(known, _)| *known == channel_type)
+                    .map(|(_, name)| name.to_string()).unwrap_or_else(|| format!("{:#x}", channel_type)));
+            }
+            if applies(values.driver) {
+                let vcp = eeprom.get_value(values.driver)? != 0;
+                channel_config.driver = Some(if vcp { "VCP" } else { "D2XX" }.to_string());
+            }
+            channel_config.rs485 = flag(values.rs485)?;
+            if let Some(value) = values.high_current {
+                channel_config.high_current = flag(value)?;
+            }
+            if !channel_config.is_empty() {
+                *config.channel_mut(channel) = Some(channel_config);
+            }
+        }
+        // FT1284 options are kept in EEPROM for any channel type, they matter in FT1284 mode only
+        config.ft1284 = Ft1284Config::from_eeprom(eeprom).filter(|_| chip == ftdi_chip_type::TYPE_232H);
+        for values in GROUP_VALUES.iter().filter(|values| applies(values[0])) {
+            config.groups.push(PinGroupConfig {
+                drive: GROUP_DRIVES[(eeprom.get_value(values[0])? & 0x03) as usize],
+                schmitt: eeprom.get_value(values[1])? != 0,
+                slow_slew: eeprom.get_value(values[2])? != 0,
+            });
+        }
+        Ok(config)
+    }
+
+    /// Put configuration into EEPROM structure of chip, it has to be built and written to take effect.
+    /// Values the chip doesn't have are errors.
+    pub fn apply(&self, eeprom: &mut ftdi_eeprom, chip: ftdi_chip_type) -> Result<()> {
+        debug!("start 'eeprom config apply' for {:?} ...", chip);
+        if let Some(name) = &self.chip {
+            let config_chip = chip_from_name(name)?;
+            if config_chip != chip {
+                return Err(common_error(-1, format!("configuration is for {}, chip is {}", name, chip_name(chip))));
+            }
+        }
+        eeprom.chip_type = Some(chip);
+        eeprom.set_value(ftdi_eeprom_value::VENDOR_ID, self.vendor_id as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::PRODUCT_ID, self.product_id as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::RELEASE_NUMBER, self.release_number as i32)?;
+        eeprom.manufacturer = self.manufacturer.clone();
+        eeprom.product = self.product.clone();
+        eeprom.serial = self.serial.clone();
+        eeprom.set_value(ftdi_eeprom_value::USE_SERIAL, self.use_serial as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::SELF_POWERED, self.self_powered as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::REMOTE_WAKEUP, self.remote_wakeup as i32)?;
+        let unit = MAX_POWER_MILLIAMP_PER_UNIT as u16;
+        let max_power = self.max_power.div_ceil(unit) * unit;
+        if max_power != self.max_power {
+            warn!("max power {} mA is rounded up to {} mA", self.max_power, max_power);
+        }
+        eeprom.set_value(ftdi_eeprom_value::MAX_POWER, max_power as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::IN_IS_ISOCHRONOUS, self.in_is_isochronous as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::OUT_IS_ISOCHRONOUS, self.out_is_isochronous as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::SUSPEND_PULL_DOWNS, self.suspend_pull_downs as i32)?;
+        eeprom.set_value(ftdi_eeprom_value::USE_USB_VERSION, self.usb_version.is_some() as i32)?;
+        if let Some(usb_version) = self.usb_version {
+            eeprom.set_value(ftdi_eeprom_value::USB_VERSION, usb_version as i32)?;
+        }
+        if let Some(name) = &self.eeprom_chip {
+            eeprom.set_value(ftdi_eeprom_value::CHIP_TYPE, lookup(&EEPROM_CHIPS, name, "EEPROM chip")?)?;
+        }
+        let flags = [
+            (self.high_current, ftdi_eeprom_value::HIGH_CURRENT),
+            (self.external_oscillator, ftdi_eeprom_value::EXTERNAL_OSCILLATOR),
+            (self.suspend_dbus7, ftdi_eeprom_value::SUSPEND_DBUS7),
+            (self.powersave, ftdi_eeprom_value::POWER_SAVE),
+        ];
+        for (flag, value) in flags.iter() {
+            if let Some(flag) = flag {
+                eeprom.set_value(*value, *flag as i32)?;
+            }
+        }
+        if !self.invert.is_empty() {
+            let mut invert = 0;
+            for name in &self.invert {
+                invert |= lookup(&INVERTED_SIGNALS, name, "signal")?;
+            }
+            eeprom.set_value(ftdi_eeprom_value::INVERT, invert as i32)?;
+        }
+        if self.cbus.len() > CBUS_VALUES.len() {
+            return Err(common_error(-1, format!("{} CBUS functions are given, chips have up to {} CBUS pins",
+                                                self.cbus.len(), CBUS_VALUES.len())));
+        }
+        for (pin, name) in self.cbus.iter().enumerate().filter(|(_, name)| !name.is_empty()) {
+            let function = CbusFunction::from_name(chip, name)
+                .ok_or_else(|| common_error(-1, format!("CBUS{} function '{}' is unknown for {}", pin, name, chip_name(chip))))?;
+            eeprom.set_value(CBUS_VALUES[pin], function.value())?;
+        }
+        for (channel, values) in CHANNEL_VALUES.iter().enumerate() {
+            let channel_config = match self.channel(channel) {
+                Some(channel_config) => channel_config,
+                None => continue,
+            };
+            if let Some(name) = &channel_config.channel_type {
+                let value = values.channel_type
+                    .ok_or_else(|| common_error(-1, format!("channel {} type can't be set", values.name)))?;
+                eeprom.set_value(value, lookup(&CHANNEL_TYPES, name, "channel type")? as i32)?;
+            }
+            if let Some(driver) = &channel_config.driver {
+                eeprom.set_value(values.driver, lookup(&[(1, "VCP"), (0, "D2XX")], driver, "driver")?)?;
+            }
+            if let Some(rs485) = channel_config.rs485 {
+                eeprom.set_value(values.rs485, rs485 as i32)?;
+            }
+            if let Some(high_current) = channel_config.high_current {
+                let value = values.high_current
+                    .ok_or_else(|| common_error(-1, format!("channel {} has no high current drive", values.name)))?;
+                eeprom.set_value(value, high_current as i32)?;
+            }
+        }
+        if let Some(ft1284) = &self.ft1284 {
+            if chip != ftdi_chip_type::TYPE_232H {
+                return Err(common_error(-1, format!("{} has no FT1284 mode", chip_name(chip))));
+            }
+            ft1284.apply(eeprom);
+        }
+        if self.groups.len() > GROUP_VALUES.len() {
+            return Err(common_error(-1, format!("{} pin groups are given, chips have up to {}", self.groups.len(), GROUP_VALUES.len())));
+        }
+        for (group, values) in self.groups.iter().zip(GROUP_VALUES.iter()) {
+            let drive = GROUP_DRIVES.iter().position(|drive| *drive == group.drive)
+                .ok_or_else(|| common_error(-1, format!("drive {} mA isn't one of {:?} mA", group.drive, GROUP_DRIVES)))?;
+            eeprom.set_value(values[0], drive as i32)?;
+            eeprom.set_value(values[1], group.schmitt as i32)?;
+            eeprom.set_value(values[2], group.slow_slew as i32)?;
+        }
+        if let Some(address) = self.user_data_addr {
+            eeprom.set_value(ftdi_eeprom_value::USER_DATA_ADDR, address as i32)?;
+        }
+        if self.user_data.len() > FTDI_MAX_EEPROM_SIZE {
+            return Err(common_error(-1, format!("user data is {} bytes, EEPROM has {}", self.user_data.len(), FTDI_MAX_EEPROM_SIZE)));
+        }
+        eeprom.user_data_size = self.user_data.len() as i32;
+        eeprom.user_data[..self.user_data.len()].copy_from_slice(&self.user_data);
+        debug!("'eeprom config apply' - OK");
+        Ok(())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|error| common_error(-1, format!("EEPROM configuration: {}", error)))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|error| common_error(-1, format!("EEPROM configuration: {}", error)))
+    }
+
+    /// Read configuration file of libftdi ftdi_eeprom tool. Tool options (output file name etc.) are skipped.
+    /// Values the file leaves out take ftdi_eeprom defaults: self powered, remote wakeup, 0 mA and VCP driver
+    /// on channels of the chip. FT232H and FT-X CBUS keys tell the chip when it isn't given, channel drivers
+    /// of unknown chip are left as they are.
+    pub fn from_ini(text: &str, chip: Option<ftdi_chip_type>) -> Result<Self> {
+        let mut config = EepromConfig {
+            chip: chip.map(|chip| chip_name(chip).to_string()),
+            self_powered: true,
+            remote_wakeup: true,
+            max_power: 0,
+            ..EepromConfig::default()
+        };
+        // usb_version is used only when change_usb_version is set, keys come in any order
+        let mut change_usb_version = false;
+        for (number, line) in text.lines().enumerate() {
+            let line = strip_ini_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(position) => (line[..position].trim().to_ascii_lowercase(), line[position + 1..].trim()),
+                None => return Err(common_error(-1, format!("line {}: '{}' isn't key=value", number + 1, line))),
+            };
+            let result = if key == "change_usb_version" {
+                parse_ini_bool(value).map(|change| change_usb_version = change)
+            } else {
+                config.set_ini_value(&key, value)
+            };
+            result.map_err(|error| common_error(-1, format!("line {}: {}", number + 1, error)))?;
+        }
+        if !change_usb_version {
+            config.usb_version = None;
+        } else if config.usb_version.is_none() {
+            return Err(common_error(-1, "change_usb_version is set, usb_version is missing".to_string()));
+        }
+        if let Some(chip) = config.chip.as_deref().map(chip_from_name).transpose()? {
+            for (channel, values) in CHANNEL_VALUES.iter().enumerate() {
+                if values.driver.applies_to(chip) {
+                    let channel_config = config.channel_mut(channel).get_or_insert_with(ChannelConfig::default);
+                    channel_config.driver.get_or_insert_with(|| "VCP".to_string());
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Write configuration in libftdi ftdi_eeprom tool format, only keys the tool knows are written (no chip)
+    pub fn to_ini(&self) -> String {
+        let mut ini = String::new();
+        let _ = writeln!(ini, "# ftdi_eeprom configuration");
+        let _ = writeln!(ini, "vendor_id={:#06x}", self.vendor_id);
+        let _ = writeln!(ini, "product_id={:#06x}", self.product_id);
+        let _ = writeln!(ini, "release_number={:#06x}", self.release_number);
+        let _ = writeln!(ini, "manufacturer=\"{}\"", escape_ini(&self.manufacturer));
+        let _ = writeln!(ini, "product=\"{}\"", escape_ini(&self.product));
+        let _ = writeln!(ini, "serial=\"{}\"", escape_ini(&self.serial));
+        let _ = writeln!(ini, "use_serial={}", self.use_serial);
+        let _ = writeln!(ini, "self_powered={}", self.self_powered);
+        let _ = writeln!(ini, "remote_wakeup={}", self.remote_wakeup);
+        let _ = writeln!(ini, "max_power={}", self.max_power);
+        let _ = writeln!(ini, "in_is_isochronous={}", self.in_is_isochronous);
+        let _ = writeln!(ini, "out_is_isochronous={}", self.out_is_isochronous);
+        let _ = writeln!(ini, "suspend_pull_downs={}", self.suspend_pull_downs);
+        let _ = writeln!(ini, "change_usb_version={}", self.usb_version.is_some());
+        if let Some(usb_version) = self.usb_version {
+            let _ = writeln!(ini, "usb_version={:#06x}", usb_version);
+        }
+        if let Some(name) = &self.eeprom_chip {
+            let _ = writeln!(ini, "eeprom_type={}", lookup(&EEPROM_CHIPS, name, "EEPROM chip")
+                .map(|chip| format!("{:#04x}", chip)).unwrap_or_else(|_| name.clone()));
+        }
+        let flags = [("high_current", self.high_current), ("external_oscillator", self.external_oscillator),
+            ("suspend_dbus7", self.suspend_dbus7), ("powersave", self.powersave)];
+        for (key, flag) in flags.iter() {
+            if let Some(flag) = flag {
+                let _ = writeln!(ini, "{}={}", key, flag);
+            }
+        }
+        if !self.invert.is_empty() {
+            for (_, signal) in INVERTED_SIGNALS.iter() {
+                let inverted = self.invert.iter().any(|name| name.eq_ignore_ascii_case(signal));
+                let _ = writeln!(ini, "invert_{}={}", signal.to_ascii_lowercase(), inverted);
+            }
+        }
+        let cbus_key = match self.chip.as_deref().map(chip_from_name) {
+            Some(Ok(ftdi_chip_type::TYPE_232H)) => "cbush",
+            Some(Ok(ftdi_chip_type::TYPE_230X)) => "cbusx",
+            _ => "cbus",
+        };
+        for (pin, name) in self.cbus.iter().enumerate().filter(|(_, name)| !name.is_empty()) {
+            let _ = writeln!(ini, "{}{}={}", cbus_key, pin, name);
+        }
+        for (channel, prefix) in ["cha", "chb", "chc", "chd"].iter().enumerate() {
+            if let Some(channel_config) = self.channel(channel) {
+                if let Some(channel_type) = &channel_config.channel_type {
+                    let _ = writeln!(ini, "{}_type={}", prefix, channel_type);
+                }
+                if let Some(driver) = &channel_config.driver {
+                    let _ = writeln!(ini, "{}_vcp={}", prefix, driver.eq_ignore_ascii_case("VCP"));
+                }
+                if let Some(rs485) = channel_config.rs485 {
+                    let _ = writeln!(ini, "{}_rs485={}", prefix, rs485);
+                }
+                if let Some(high_current) = channel_config.high_current {
+                    let _ = writeln!(ini, "{}_high_current={}", prefix, high_current);
+                }
+            }
+        }
+        if let Some(ft1284) = &self.ft1284 {
+            let _ = writeln!(ini, "clock_polarity={}", if ft1284.clock_idle_high { "HIGH" } else { "LOW" });
+            let _ = writeln!(ini, "data_order={}", if ft1284.lsb_first { "LSB" } else { "MSB" });
+            let _ = writeln!(ini, "flow_control={}", ft1284.flow_control);
+        }
+        for (index, group) in self.groups.iter().enumerate() {
+            let _ = writeln!(ini, "group{}_drive={}MA", index, group.drive);
+            let _ = writeln!(ini, "group{}_schmitt={}", index, group.schmitt);
+            let _ = writeln!(ini, "group{}_slew={}", index, group.slow_slew);
+        }
+        if let Some(address) = self.user_data_addr {
+            let _ = writeln!(ini, "user_data_addr={:#04x}", address);
+        }
+        ini
+    }
+
+    fn channel(&self, channel: usize) -> Option<&ChannelConfig> {
+        [&self.channel_a, &self.channel_b, &self.channel_c, &self.channel_d][channel].as_ref()
+    }
+
+    fn channel_mut(&mut self, channel: usize) -> &mut Option<ChannelConfig> {
+        match channel {
+            0 => &mut self.channel_a,
+            1 => &mut self.channel_b,
+            2 => &mut self.channel_c,
+            _ => &mut self.channel_d,
+        }
+    }
+
+    fn set_ini_value(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        let number = |value: &str| parse_number(value);
+        let hex = |value: &str| number(value).and_then(|number| {
+            if (0..=0xffff).contains(&number) { Ok(number as u16) } else { Err(format!("{} doesn't fit 16 bits", number)) }
+        });
+        match key {
+            "vendor_id" => self.vendor_id = hex(value)?,
+            "product_id" => self.product_id = hex(value)?,
+            "release_number" => self.release_number = hex(value)?,
+            "manufacturer" => self.manufacturer = parse_ini_string(value),
+            "product" => self.product = parse_ini_string(value),
+            "serial" => self.serial = parse_ini_string(value),
+            "use_serial" => self.use_serial = parse_ini_bool(value)?,
+            "self_powered" => self.self_powered = parse_ini_bool(value)?,
+            "remote_wakeup" => self.remote_wakeup = parse_ini_bool(value)?,
+            "max_power" => self.max_power = hex(value)?,
+            "in_is_isochronous" => self.in_is_isochronous = parse_ini_bool(value)?,
+            "out_is_isochronous" => self.out_is_isochronous = parse_ini_bool(value)?,
+            "suspend_pull_downs" => self.suspend_pull_downs = parse_ini_bool(value)?,
+            "usb_version" => self.usb_version = Some(hex(value)?),
+            "eeprom_type" => {
+                let chip = number(value)? as i32;
+                let name = EEPROM_CHIPS.iter().find(|(eeprom_chip, _)| *eeprom_chip == chip)
+                    .ok_or_else(|| format!("EEPROM type {:#x} is unknown", chip))?.1;
+                self.eeprom_chip = Some(name.to_string());
+            }
+            "high_current" => self.high_current = Some(parse_ini_bool(value)?),
+            "external_oscillator" => self.external_oscillator = Some(parse_ini_bool(value)?),
+            "suspend_dbus7" => self.suspend_dbus7 = Some(parse_ini_bool(value)?),
+            "powersave" => self.powersave = Some(parse_ini_bool(value)?),
+            "clock_polarity" => self.ft1284.get_or_insert_with(Ft1284Config::default).clock_idle_high =
+                parse_ini_choice(value, "HIGH", "LOW")?,
+            "data_order" => self.ft1284.get_or_insert_with(Ft1284Config::default).lsb_first =
+                parse_ini_choice(value, "LSB", "MSB")?,
+            "flow_control" => self.ft1284.get_or_insert_with(Ft1284Config::default).flow_control = parse_ini_bool(value)?,
+            "user_data_addr" => self.user_data_addr = Some(hex(value)?),
+            _ if INI_TOOL_KEYS.contains(&key) => warn!("ftdi_eeprom tool option '{}' is skipped", key),
+            _ if key.starts_with("invert_") => {
+                let signal = key["invert_".len()..].to_ascii_uppercase();
+                lookup(&INVERTED_SIGNALS, &signal, "signal").map_err(|error| error.to_string())?;
+                self.invert.retain(|name| !name.eq_ignore_ascii_case(&signal));
+                if parse_ini_bool(value)? {
+                    self.invert.push(signal);
+                }
+            }
+            _ if key.starts_with("cbus") => {
+                let (chip, pin) = if let Some(pin) = key.strip_prefix("cbush") {
+                    (Some("FT232H"), pin)
+                } else if let Some(pin) = key.strip_prefix("cbusx") {
+                    (Some("FT230X"), pin)
+                } else {
+                    (None, &key["cbus".len()..])
+                };
+                let pin: usize = pin.parse().map_err(|_| format!("'{}' is unknown", key))?;
+                if pin >= 10 {
+                    return Err(format!("CBUS pin {} is out of range", pin));
+                }
+                if let (Some(chip), None) = (chip, &self.chip) {
+                    self.chip = Some(chip.to_string());
+                }
+                if self.cbus.len() <= pin {
+                    self.cbus.resize(pin + 1, String::new());
+                }
+                self.cbus[pin] = value.to_string();
+            }
+            _ if key.len() > 4 && ["cha_", "chb_", "chc_", "chd_"].contains(&&key[..4]) => {
+                let channel = (key.as_bytes()[2] - b'a') as usize;
+                let channel_config = self.channel_mut(channel).get_or_insert_with(ChannelConfig::default);
+                match &key[4..] {
+                    "type" => channel_config.channel_type = Some(value.to_string()),
+                    "vcp" => channel_config.driver = Some(if parse_ini_bool(value)? { "VCP" } else { "D2XX" }.to_string()),
+                    "rs485" => channel_config.rs485 = Some(parse_ini_bool(value)?),
+                    "high_current" => channel_config.high_current = Some(parse_ini_bool(value)?),
+                    _ => return Err(format!("'{}' is unknown", key)),
+                }
+            }
+            _ if key.starts_with("group") && key.len() > 7 && &key[6..7] == "_" => {
+                let group = key[5..6].parse::<usize>().ok().filter(|group| *group < GROUP_VALUES.len())
+                    .ok_or_else(|| format!("'{}' is unknown", key))?;
+                if self.groups.len() <= group {
+                    self.groups.resize(group + 1, PinGroupConfig::default());
+                }
+                match &key[7..] {
+                    "drive" => {
+                        let value = parse_ini_string(value);
+                        self.groups[group].drive = GROUP_DRIVES.iter().copied()
+                            .find(|drive| value.eq_ignore_ascii_case(&format!("{}MA", drive)))
+                            .ok_or_else(|| format!("drive '{}' isn't one of 4MA, 8MA, 12MA, 16MA", value))?;
+                    }
+                    "schmitt" => self.groups[group].schmitt = parse_ini_bool(value)?,
+                    "slew" => self.groups[group].slow_slew = parse_ini_bool(value)?,
+                    _ => return Err(format!("'{}' is unknown", key)),
+                }
+            }
+            _ => return Err(format!("'{}' is unknown", key)),
+        }
+        Ok(())
+    }
+}
+
+/// Comment starts with '#' outside of quoted string
+fn strip_ini_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (position, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..position],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_ini_string(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_ini(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn parse_ini_bool(value: &str) -> std::result::Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("'{}' isn't true or false", value)),
+    }
+}
+
+fn parse_ini_choice(value: &str, set: &str, clear: &str) -> std::result::Result<bool, String> {
+    if value.eq_ignore_ascii_case(set) {
+        Ok(true)
+    } else if value.eq_ignore_ascii_case(clear) {
+        Ok(false)
+    } else {
+        parse_ini_bool(value).map_err(|_| format!("'{}' isn't {} or {}", value, set, clear))
+    }
+}
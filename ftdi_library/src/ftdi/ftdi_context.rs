@@ -7,6 +7,7 @@ use std::{
     any::Any,
     convert::TryFrom,
     fmt::{Debug, Display, Formatter},
+    thread,
     time::Duration,
     io,
     mem::{MaybeUninit, transmute},
@@ -26,9 +27,12 @@ use snafu::{Backtrace, ensure, ErrorCompat, ResultExt, Snafu, GenerateBacktrace}
 use crate::ftdi::core::FtdiError;
 
 use crate::ftdi::{
+    baud::{compute_baud_divisor, exact_baudrates, BaudPolicy},
+    chip::{chip_info, detect_chip, detect_ftx_variant, ChipCapabilities, EepromKind},
     constants::{*},
     core::{ftdi_transfer_control},
-    eeprom::{ftdi_eeprom, FTDI_MAX_EEPROM_SIZE},
+    eeprom::{ftdi_eeprom, FTDI_MAX_EEPROM_SIZE, EEPROM_CHIP_93X46, EEPROM_CHIP_93X56, EEPROM_CHIP_93X66,
+             EEPROM_SIZE_PROBE_MAGIC, FTX_EEPROM_RESERVED_WORDS, CHIPID_EEPROM_WORDS},
     ftdi_device_list::{ftdi_device_list, print_debug_device_descriptor}
 };
 use crate::scanf;
@@ -60,6 +64,13 @@ pub enum FtdiContextError {
         code: i32,
         message: String,
         backtrace: Backtrace,
+    },
+    /// Operation isn't available on the opened chip
+    #[snafu(display("UNSUPPORTED CHIP: {:?}, message: \'{}\'\n{}", chip_type, message, backtrace))]
+    UnsupportedChip {
+        chip_type: ftdi_chip_type,
+        message: String,
+        backtrace: Backtrace,
     }
 }
 impl From<crate::ftdi::core::FtdiError> for FtdiContextError {
@@ -91,13 +102,119 @@ impl PartialEq for FtdiContextError {
             (FtdiContextError::UsbCommonError {code, message, backtrace: _trace },
                 FtdiContextError::UsbCommonError {code: code2, message: message2, backtrace: _trace2})
                     => code == code2 && message.eq(&message2.as_str()),
+            (FtdiContextError::UnsupportedChip {chip_type, message, backtrace: _trace },
+                FtdiContextError::UnsupportedChip {chip_type: chip_type2, message: message2, backtrace: _trace2})
+                    => chip_type == chip_type2 && message.eq(&message2.as_str()),
             _ => false
         }
     }
 }
 
+/// Lets stream types (sync FIFO, serial) implement io::Read/io::Write
+impl From<FtdiContextError> for io::Error {
+    fn from(error: FtdiContextError) -> Self {
+        io::Error::new(io::ErrorKind::Other, error)
+    }
+}
+
 pub type Result<T, E = FtdiContextError> = std::result::Result<T, E>;
 
+/// Builds and logs a UsbCommonError, used by the protocol and EEPROM modules
+pub(crate) fn common_error(code: i32, message: String) -> FtdiContextError {
+    let error = FtdiContextError::UsbCommonError {
+        code, message, backtrace: GenerateBacktrace::generate()
+    };
+    error!("{}", error);
+    error
+}
+
+/// FTDIChip-ID, unique 32-bit number programmed into FT232R and FT-X chips at the factory
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChipId(pub u32);
+impl ChipId {
+    /// Descramble EEPROM words 0x43 and 0x44 the ID is stored in
+    pub fn from_words(high: u16, low: u16) -> Self {
+        // words come byte swapped
+        let value = (high.swap_bytes() as u32) << 16 | low.swap_bytes() as u32;
+        let value = value.to_le_bytes().iter().enumerate()
+            .fold(0u32, |id, (index, byte)| id | ChipId::shift(*byte) << (index * 8));
+        ChipId(value ^ 0xa5f0f7d1)
+    }
+
+    /// Bit shuffle applied to every byte of the stored ID
+    pub fn shift(value: u8) -> u32 {
+        let value = value as u32;
+        ((value & 1) << 1) |
+            ((value & 2) << 5) |
+            ((value & 4) >> 2) |
+            ((value & 8) << 4) |
+            ((value & 16) >> 1) |
+            ((value & 32) >> 1) |
+            ((value & 64) >> 4) |
+            ((value & 128) >> 2)
+    }
+}
+impl Display for ChipId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+impl std::fmt::LowerHex for ChipId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+impl From<ChipId> for u32 {
+    fn from(chip_id: ChipId) -> Self {
+        chip_id.0
+    }
+}
+
+/// RS232 line characteristics last set by ftdi_set_line_property2(), chip starts with 8N1
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ftdi_line_property {
+    pub bits: ftdi_bits_type,
+    pub stopbits: ftdi_stopbits_type,
+    pub parity: ftdi_parity_type,
+    pub break_type: ftdi_break_type,
+}
+impl Default for ftdi_line_property {
+    fn default() -> Self {
+        ftdi_line_property {
+            bits: ftdi_bits_type::BITS_8,
+            stopbits: ftdi_stopbits_type::STOP_BIT_1,
+            parity: ftdi_parity_type::NONE,
+            break_type: ftdi_break_type::BREAK_OFF,
+        }
+    }
+}
+impl ftdi_line_property {
+    /// wValue of SIO_SET_DATA_REQUEST
+    pub fn value(&self) -> u16 {
+        let mut value: u16 = self.bits as u16;
+
+        match self.parity {
+            ftdi_parity_type::NONE => value |= 0x00 << 8,
+            ftdi_parity_type::ODD => value |= 0x01 << 8,
+            ftdi_parity_type::EVEN => value |= 0x02 << 8,
+            ftdi_parity_type::MARK => value |= 0x03 << 8,
+            ftdi_parity_type::SPACE => value |= 0x04 << 8,
+        }
+
+        match self.stopbits {
+            ftdi_stopbits_type::STOP_BIT_1 => value |= 0x00 << 11,
+            ftdi_stopbits_type::STOP_BIT_15 => value |= 0x01 << 11,
+            ftdi_stopbits_type::STOP_BIT_2 => value |= 0x02 << 11,
+        }
+
+        match self.break_type {
+            ftdi_break_type::BREAK_OFF => value |= 0x00 << 14,
+            ftdi_break_type::BREAK_ON => value |= 0x01 << 14,
+        }
+        value
+    }
+}
+
 
 /// brief Main context structure for all libftdi functions.
 /// Do not access directly if possible.
@@ -118,12 +235,18 @@ pub struct ftdi_context {
     /// FTDI specific
     /// FTDI chip type
     pub r#type: ftdi_chip_type,
+    /// FT-X series chip told by product string, see detect_ftx_variant
+    pub ftx_variant: Option<&'static str>,
     /// baudrate
     pub baudrate: i32,
+    /// How close ftdi_set_baudrate has to come to requested baudrate
+    pub baud_policy: BaudPolicy,
+    /// Line characteristics, break toggling keeps them
+    pub line_property: ftdi_line_property,
     /// bitbang mode state
     pub bitbang_enabled: bool /*libc::c_char*/,
-    /// pointer to read buffer for ftdi_read_data
-    pub readbuffer: Box<[u8; FTDI_MAX_EEPROM_SIZE]>,
+    /// read buffer for ftdi_read_data, sized to 'readbuffer_chunksize'
+    pub readbuffer: Vec<u8>,
     /// read buffer offset
     pub readbuffer_offset: u32,
     /// number of remaining data in internal read buffer
@@ -179,9 +302,12 @@ impl Default for ftdi_context {
             usb_read_timeout: 5000,
             usb_write_timeout: 5000,
             r#type: ftdi_chip_type::TYPE_BM,
+            ftx_variant: Option::None,
             baudrate: -1,
+            baud_policy: BaudPolicy::default(),
+            line_property: ftdi_line_property::default(),
             bitbang_enabled: false,
-            readbuffer: Box::new([0u8; FTDI_MAX_EEPROM_SIZE]),
+            readbuffer: Vec::new(),
             readbuffer_offset: 0,
             readbuffer_remaining: 0,
             readbuffer_chunksize: 0,
@@ -200,11 +326,6 @@ impl Default for ftdi_context {
 }
 
 impl ftdi_context {
-    // several internal constants
-    const FRAC_CODE: [u16; 8] = [0, 3, 2, 4, 1, 5, 6, 7]; // static const char
-    const H_CLK: i32 = 120000000;
-    const C_CLK: i32 =  48000000;
-
     /// Helper function to convert USB system error code into FtdiContextError enum
     pub fn get_usb_sys_native_error(err: c_int) -> FtdiContextError {
         match err {
@@ -335,9 +456,12 @@ impl ftdi_context {
                 usb_read_timeout: 5000,
                 usb_write_timeout: 5000,
                 r#type: ftdi_chip_type::TYPE_BM,
+                ftx_variant: Option::None,
                 baudrate: -1,
+                baud_policy: BaudPolicy::default(),
+                line_property: ftdi_line_property::default(),
                 bitbang_enabled: false,
-                readbuffer: Box::new([0u8; FTDI_MAX_EEPROM_SIZE]),
+                readbuffer: vec![0u8; calculated_max_chunk_size as usize],
                 readbuffer_offset: 0,
                 readbuffer_remaining: 0,
                 readbuffer_chunksize: calculated_max_chunk_size as i32,
@@ -355,8 +479,16 @@ impl ftdi_context {
         )
     }
 
-    pub fn ftdi_set_interface(&mut self, interface_type: ftdi_interface) {
+    pub fn ftdi_set_interface(&mut self, interface_type: ftdi_interface) -> Result<()> {
         debug!("set interface type \'{:?}\' to ftdi context", interface_type);
+        if self.usb_dev.is_some() {
+            let error = FtdiContextError::UsbCommonError {
+                code: -3, message: format!("interface can't be changed to {:?} on already open device", interface_type),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
         match interface_type {
             ftdi_interface::INTERFACE_ANY | ftdi_interface::INTERFACE_A => {
                 self.interface = 0;
@@ -384,6 +516,12 @@ impl ftdi_context {
             }
         }
         self.bitbang_mode = 1; /* when bitbang is enabled this holds the number of the mode  */
+        Ok(())
+    }
+
+    /// What the opened channel of the chip can do
+    pub fn capabilities(&self) -> Result<ChipCapabilities> {
+        ChipCapabilities::new(self.r#type, ftdi_interface::from(self.interface + 1))
     }
 
     /// We can't set read_buffer_chunksize larger than MAX_BULK_BUFFER_LENGTH,
@@ -575,6 +713,7 @@ impl ftdi_context {
         };
         let configuration: *const ffi::libusb_config_descriptor = unsafe { configuration_uninit.assume_init() };
         let cfg0: c_int = unsafe { (*configuration).bConfigurationValue as c_int};
+        let interfaces = unsafe { (*configuration).bNumInterfaces };
         unsafe { ffi::libusb_free_config_descriptor(configuration) };
 
         let mut detach_errno = 0;
@@ -683,30 +822,29 @@ impl ftdi_context {
         }
 
         // Try to guess chip type
-        // Bug in the BM type chips: bcdDevice is 0x200 for serial == 0
-        if descriptor.bcdDevice == 0x400 || (descriptor.bcdDevice == 0x200 && descriptor.iSerialNumber == 0) {
-            self.r#type = ftdi_chip_type::TYPE_BM;
-        } else if descriptor.bcdDevice == 0x200 {
-            self.r#type = ftdi_chip_type::TYPE_AM;
-        } else if descriptor.bcdDevice == 0x500 {
-            self.r#type = ftdi_chip_type::TYPE_2232C;
-        } else if descriptor.bcdDevice == 0x600 {
-            self.r#type = ftdi_chip_type::TYPE_R;
-        } else if descriptor.bcdDevice == 0x700 {
-            self.r#type = ftdi_chip_type::TYPE_2232H;
-        } else if descriptor.bcdDevice == 0x800 {
-            self.r#type = ftdi_chip_type::TYPE_4232H;
-        } else if descriptor.bcdDevice == 0x900 {
-            self.r#type = ftdi_chip_type::TYPE_232H;
-        } else if descriptor.bcdDevice == 0x1000 {
-            self.r#type = ftdi_chip_type::TYPE_230X;
-        } else {
-            let error = FtdiContextError::UsbInit {
-                code: -8, message: "Is it new 'ftdi_chip_type' ?? or type is not guessed".to_string(),
-                backtrace: GenerateBacktrace::generate(),
-                source: Box::new(FtdiContextError::None)
-            };
-            error!("{}", error);
+        match detect_chip(&descriptor, interfaces) {
+            Ok(info) => {
+                self.r#type = info.chip_type;
+                self.ftx_variant = Option::None;
+                if info.chip_type == ftdi_chip_type::TYPE_230X {
+                    let product = super::ftdi_device_list::get_string_descriptor(self.usb_dev.unwrap(), descriptor.iProduct);
+                    self.ftx_variant = product.as_deref().and_then(detect_ftx_variant);
+                    debug!("FT-X chip is {:?}", self.ftx_variant);
+                }
+            }
+            Err(error) => {
+                let error = FtdiContextError::UsbInit {
+                    code: -8, message: "Is it new 'ftdi_chip_type' ?? or type is not guessed".to_string(),
+                    backtrace: GenerateBacktrace::generate(),
+                    source: Box::new(error)
+                };
+                error!("{}", error);
+                return Err(error);
+            }
+        }
+        // chip has to have the interface set before opening
+        if let Err(error) = self.capabilities() {
+            self.ftdi_usb_close_internal();
             return Err(error);
         }
         // Determine maximum packet size
@@ -1114,189 +1252,15 @@ impl ftdi_context {
         Ok(())
     }
 
-    /// ftdi_to_clkbits_AM For the AM device, convert a requested baudrate
-    ///                     to encoded divisor and the achievable baudrate
-    ///  Function is only used internally
-    ///
-    ///     See AN120
-    ///    clk/1   -> 0
-    ///    clk/1.5 -> 1
-    ///    clk/2   -> 2
-    ///    From /2, 0.125/ 0.25 and 0.5 steps may be taken
-    ///    The fractional part has frac_code encoding
-    fn ftdi_to_clkbits_am(&mut self, baudrate: i32, encoded_divisor: &mut u32) -> i32 {
-        debug!("start \'ftdi_to_clkbits_am\' ...");
-        let am_adjust_up: [u16; 8] = [0, 0, 0, 1, 0, 3, 2, 1];
-        let am_adjust_dn: [u16; 8] = [0, 0, 0, 1, 0, 1, 2, 3];
-        let mut divisor = 24000000 / baudrate;
-        let mut best_divisor = 0;
-        let mut best_baud = 0;
-        let mut best_baud_diff = 0;
-        let mut i = 0;
-        // divisor = 24000000 / baudrate;
-        // Round down to supported fraction (AM only)
-        divisor -= am_adjust_dn[ (divisor & 7) as usize] as i32;
-
-        // Try this divisor and the one above it (because division rounds down)
-        // for (i = 0; i < 2; i++) {
-        while i < 2 {
-            let mut try_divisor: i32 = divisor + i;
-            let mut baud_estimate = 0;
-            let mut baud_diff = 0;
-
-            // Round up to supported divisor value
-            if try_divisor <= 8 {
-                // Round up to minimum supported divisor
-                try_divisor = 8;
-            } else if divisor < 16 {
-                // AM doesn't support divisors 9 through 15 inclusive
-                try_divisor = 16;
-            } else {
-                // Round up to supported fraction (AM only)
-                try_divisor += am_adjust_up[ (try_divisor & 7) as usize] as i32;
-                if try_divisor > 0x1FFF8 {
-                    // Round down to maximum supported divisor value (for AM)
-                    try_divisor = 0x1FFF8;
-                }
-            }
-            // Get estimated baud rate (to nearest integer)
-            baud_estimate = (24000000 + (try_divisor / 2)) / try_divisor;
-            // Get absolute difference from requested baud rate
-            if baud_estimate < baudrate {
-                baud_diff = baudrate - baud_estimate;
-            } else {
-                baud_diff = baud_estimate - baudrate;
-            }
-            if i == 0 || baud_diff < best_baud_diff {
-                // Closest to requested baud rate so far
-                best_divisor = try_divisor;
-                best_baud = baud_estimate;
-                best_baud_diff = baud_diff;
-                if baud_diff == 0 {
-                    // Spot on! No point trying
-                    break;
-                }
-            }
-            i += 1;
-        }
-
-        // Encode the best divisor value
-        *encoded_divisor = ((best_divisor >> 3) | (ftdi_context::FRAC_CODE[ (best_divisor & 7) as usize ] << 14) as i32) as u32;
-        // Deal with special cases for encoded value
-        if *encoded_divisor == 1 {
-            *encoded_divisor = 0;    // 3000000 baud
-        } else if *encoded_divisor == 0x4001 {
-            *encoded_divisor = 1;    // 2000000 baud (BM only)
-        }
-        debug!("\'ftdi_usb_open_bus_addr\' best_baud = {}", best_baud);
-        return best_baud;
-    }
-
-    /// ftdi_to_clkbits Convert a requested baudrate for a given system clock  and predivisor
-    /// to encoded divisor and the achievable baudrate
-    /// Function is only used internally
-    ///
-    ///  See AN120
-    ///    clk/1   -> 0
-    ///    clk/1.5 -> 1
-    ///    clk/2   -> 2
-    ///    From /2, 0.125 steps may be taken.
-    ///    The fractional part has frac_code encoding
-    ///
-    ///    value[13:0] of value is the divisor
-    ///    index[9] mean 12 MHz Base(120 MHz/10) rate versus 3 MHz (48 MHz/16) else
-    ///
-    ///    H Type have all features above with
-    ///    {index[8],value[15:14]} is the encoded subdivisor
-    ///
-    ///    FT232R, FT2232 and FT232BM have no option for 12 MHz and with
-    ///    {index[0],value[15:14]} is the encoded subdivisor
-    ///
-    ///    AM Type chips have only four fractional subdivisors at value[15:14]
-    ///    for subdivisors 0, 0.5, 0.25, 0.125
-    fn ftdi_to_clkbits(&mut self, baudrate: i32, clk: i32, clk_div: i32, encoded_divisor: &mut u32) -> i32 {
-        debug!("start \'ftdi_to_clkbits\' ...");
-        let mut best_baud = 0;
-        let mut divisor = 0;
-        let mut best_divisor = 0;
-        if baudrate >= clk/clk_div {
-            *encoded_divisor = 0;
-            best_baud = clk/clk_div;
-        } else if baudrate >= clk/(clk_div + clk_div/2) {
-            *encoded_divisor = 1;
-            best_baud = clk/(clk_div + clk_div/2);
-        } else if baudrate >= clk/(2*clk_div) {
-            *encoded_divisor = 2;
-            best_baud = clk/(2*clk_div);
-        } else {
-            /* We divide by 16 to have 3 fractional bits and one bit for rounding */
-            divisor = clk*16/clk_div/baudrate;
-            if (divisor & 1) != 0 {
-                /* Decide if to round up or down*/
-                best_divisor = divisor / 2 + 1;
-            } else {
-                best_divisor = divisor / 2;
-            }
-            if best_divisor > 0x20000 {
-                best_divisor = 0x1ffff;
-            }
-            best_baud = clk*16/clk_div/best_divisor;
-            if (best_baud & 1) != 0 {
-                /* Decide if to round up or down*/
-                best_baud = best_baud / 2 + 1;
-            } else {
-                best_baud = best_baud / 2;
-            }
-            *encoded_divisor = ((best_divisor >> 3) | (ftdi_context::FRAC_CODE[ (best_divisor & 0x7) as usize] << 14) as i32) as u32;
-        }
-        debug!("\'ftdi_to_clkbits_am\' OK: best_baud = {}", best_baud);
-        return best_baud;
+    /// Sets how close ftdi_set_baudrate has to come to requested baudrate, 5% by default
+    pub fn ftdi_set_baud_policy(&mut self, policy: BaudPolicy) {
+        debug!("set baud policy {:?}", policy);
+        self.baud_policy = policy;
     }
 
-    /// ftdi_convert_baudrate returns nearest supported baud rate to that requested.
-    ///  Function is only used internally
-    fn ftdi_convert_baudrate(&mut self, baudrate: i32, value: &mut u16, index: &mut u16) -> i32 {
-        debug!("start \'ftdi_convert_baudrate\' ...");
-        let mut best_baud = -1;
-        let mut encoded_divisor: u32 = 0;
-        if baudrate <= 0 {
-            let error = FtdiContextError::UsbCommonError {code: -2, message: "Incorrect baudrate".to_string(),
-                backtrace: GenerateBacktrace::generate()
-            };
-            warn!("{}", error);
-            return -1;
-        }
-        if (self.r#type == ftdi_chip_type::TYPE_2232H)
-            || (self.r#type == ftdi_chip_type::TYPE_4232H)
-            || (self.r#type == ftdi_chip_type::TYPE_232H) {
-            if (baudrate * 10) > (ftdi_context::H_CLK / 0x3fff) {
-                /* On H Devices, use 12 000 000 Baudrate when possible
-               We have a 14 bit divisor, a 1 bit divisor switch (10 or 16)
-               three fractional bits and a 120 MHz clock
-               Assume AN_120 "Sub-integer divisors between 0 and 2 are not allowed" holds for
-               DIV/10 CLK too, so /1, /1.5 and /2 can be handled the same */
-                best_baud = self.ftdi_to_clkbits(baudrate, ftdi_context::H_CLK, 10, &mut encoded_divisor);
-                encoded_divisor |= 0x20000; /* switch on CLK/10*/
-            } else {
-                best_baud = self.ftdi_to_clkbits(baudrate, ftdi_context::C_CLK, 16, &mut encoded_divisor);
-            }
-        } else {
-            best_baud = self.ftdi_to_clkbits_am(baudrate, &mut encoded_divisor);
-        }
-        // Split into "value" and "index" values
-        *value = (encoded_divisor & 0xFFFF) as u16;
-        if self.r#type == ftdi_chip_type::TYPE_2232H
-            || self.r#type == ftdi_chip_type::TYPE_4232H
-            || self.r#type == ftdi_chip_type::TYPE_232H {
-            *index = (encoded_divisor >> 8) as u16;
-            *index &= 0xFF00;
-            *index |= self.index as u16;
-        } else {
-            *index = (encoded_divisor >> 16) as u16;
-        }
-        // Return the nearest baud rate
-        debug!("\'ftdi_convert_baudrate\' - OK: best_baud = {}", best_baud);
-        return best_baud;
+    /// Baud rates the chip channel generates exactly, 'count' of them closest to 'target' first
+    pub fn ftdi_exact_baudrates(&self, target: u32, count: usize) -> Result<Vec<u32>> {
+        exact_baudrates(self.r#type, ftdi_interface::from(self.interface + 1), target, count)
     }
 
     /// Sets the chip baud rate
@@ -1307,32 +1271,19 @@ impl ftdi_context {
         self.check_usb_device()?;
         if self.bitbang_enabled {
             baudrate = baudrate * 4;
+        } else if baudrate > 0 {
+            self.capabilities()?.check_baudrate(baudrate as u32)?;
         }
-        let mut value: u16 = 0;
-        let mut index: u16 = 0;
-        let actual_baudrate: i32 = self.ftdi_convert_baudrate(baudrate, &mut value, &mut index);
-        if actual_baudrate <= 0 {
+        if baudrate <= 0 {
             let error = FtdiContextError::UsbCommonError {code: -1, message: "Silly baudrate <= 0.".to_string(),
                 backtrace: GenerateBacktrace::generate()
             };
             error!("{}", error);
             return Err(error);
         }
-        // Check within tolerance (about 5%)
-        let compute_result = if actual_baudrate < baudrate {
-            actual_baudrate * 21 < baudrate * 20
-        } else {
-            baudrate * 21i32 < actual_baudrate * 20
-        };
-        if (actual_baudrate * 2 < baudrate /* Catch overflows */ )
-            || compute_result {
-            let error = FtdiContextError::UsbCommonError {code: -1, message: "Unsupported baudrate. \
-                Note: bitbang baudrates are automatically multiplied by 4".to_string(),
-                backtrace: GenerateBacktrace::generate()
-            };
-            error!("{}", error);
-            return Err(error);
-        }
+        let solution = compute_baud_divisor(self.r#type, ftdi_interface::from(self.interface + 1), baudrate as u32)?;
+        self.baud_policy.check(&solution)?;
+        let (value, index) = (solution.value, solution.index);
         let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
         let control_transfer_result = unsafe {
             ffi::libusb_control_transfer(self.usb_dev.unwrap(),
@@ -1380,33 +1331,14 @@ impl ftdi_context {
                                    break_type: ftdi_break_type ) -> Result<()> {
         debug!("start \'ftdi_set_line_property2\' ...");
         self.check_usb_device()?;
-        let mut value: u16 = bits as u16;
-
-        match parity {
-            ftdi_parity_type::NONE => value |= 0x00 << 8,
-            ftdi_parity_type::ODD => value |= 0x01 << 8,
-            ftdi_parity_type::EVEN => value |= 0x02 << 8,
-            ftdi_parity_type::MARK => value |= 0x03 << 8,
-            ftdi_parity_type::SPACE => value |= 0x04 << 8,
-        }
-
-        match sbit {
-            ftdi_stopbits_type::STOP_BIT_1 => value |= 0x00 << 11,
-            ftdi_stopbits_type::STOP_BIT_15 => value |= 0x01 << 11,
-            ftdi_stopbits_type::STOP_BIT_2 => value |= 0x02 << 11,
-        }
-
-        match break_type {
-            ftdi_break_type::BREAK_OFF => value |= 0x00 << 14,
-            ftdi_break_type::BREAK_ON => value |= 0x01 << 14,
-        }
+        let line_property = ftdi_line_property { bits, stopbits: sbit, parity, break_type };
 
         let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
         let control_transfer_result = unsafe {
             ffi::libusb_control_transfer(self.usb_dev.unwrap(),
                                          FTDI_DEVICE_OUT_REQTYPE,
-                                         SIO_SET_BAUDRATE_REQUEST,
-                                         value,
+                                         SIO_SET_DATA_REQUEST,
+                                         line_property.value(),
                                          self.index as u16, null_data_ptr,
                                          0,
                                          self.usb_write_timeout as c_uint)};
@@ -1419,10 +1351,26 @@ impl ftdi_context {
             error!("{}", error);
             return Err(error);
         }
+        self.line_property = line_property;
         debug!("\'ftdi_set_line_property2\' = OK");
         Ok(())
     }
 
+    /// Turn break on or off keeping the other line characteristics
+    pub fn ftdi_set_break(&mut self, break_type: ftdi_break_type) -> Result<()> {
+        let line_property = self.line_property;
+        self.ftdi_set_line_property2(line_property.bits, line_property.stopbits, line_property.parity, break_type)
+    }
+
+    /// Hold TX low for 'duration', e.g. LIN and DMX512 frame start or bootloader entry.
+    /// Timing is as good as sleep of the host.
+    pub fn send_break(&mut self, duration: Duration) -> Result<()> {
+        debug!("start \'send_break\' for {:?} ...", duration);
+        self.ftdi_set_break(ftdi_break_type::BREAK_ON)?;
+        thread::sleep(duration);
+        self.ftdi_set_break(ftdi_break_type::BREAK_OFF)
+    }
+
     /// Writes data in chunks (see ftdi_write_data_set_chunksize()) to the chip
     /// buf Vector is buffer with the data and size
     pub fn ftdi_write_data(&self, buffer: &mut Vec<u8>, size_to_write: u32) -> Result<usize> {
@@ -1787,13 +1735,20 @@ impl ftdi_context {
         unimplemented!()
     }
 
+    /// Reads data in chunks (see ftdi_read_data_set_chunksize()) from the chip.
+    /// Every USB packet starts with two modem status bytes, they are stripped here.
+    /// Data left over from a previous bulk transfer is returned first.
+    ///
+    /// buffer is filled from the start and truncated to the number of bytes read
+    /// size_to_read is the maximum number of bytes to read
+    /// return number of bytes read, 0 means there was no data available
     pub fn ftdi_read_data(&mut self, buffer: &mut Vec<u8>, size_to_read: usize) -> Result<usize> {
         debug!("start 'ftdi_read_data' ...");
         self.check_usb_device()?;
 
         // Packet size sanity check (avoid division by zero)
-        let packet_size = self.max_packet_size;
-        if packet_size == 0 {
+        let packet_size = self.max_packet_size as usize;
+        if packet_size <= 2 {
             let error = FtdiContextError::UsbCommonError {
                 code: -1, message: "max_packet_size is bogus (zero)".to_string(),
                 backtrace: GenerateBacktrace::generate()
@@ -1801,54 +1756,34 @@ impl ftdi_context {
             error!("{}", error);
             return Err(error);
         }
-        let size = buffer.capacity();
-        if size <= 0 {
-            warn!("Data buffer is empty, nothing write to usb [{}]", size);
+        buffer.clear();
+        if size_to_read == 0 {
+            warn!("Nothing to read from usb, requested size = [{}]", size_to_read);
             return Ok(0);
         }
-
-        // everything we want is still in the readbuffer?
-        if size <= self.readbuffer_remaining as usize {
-            // memcpy (buf, self.readbuffer+self.readbuffer_offset, size_to_read);
-            unsafe {
-                copy::<u8>(self.readbuffer[..self.readbuffer_offset as usize].as_ptr(),
-                           buffer.as_mut_ptr(),
-                           size);
-            }
-            // Fix offsets
-            self.readbuffer_remaining -= size as u32;
-            self.readbuffer_offset += size as u32;
-            /* printf("Returning bytes from buffer: %d - remaining: %d\n", size, ftdi->readbuffer_remaining); */
-            return Ok(size_to_read);
+        if self.readbuffer.len() < self.readbuffer_chunksize as usize {
+            self.readbuffer.resize(self.readbuffer_chunksize as usize, 0);
         }
 
-        let mut offset: usize = 0;
-        // something still in the readbuffer, but not enough to satisfy 'size'?
+        // something still in the readbuffer?
         if self.readbuffer_remaining != 0 {
-            // memcpy (buf, ftdi->readbuffer+ftdi->readbuffer_offset, ftdi->readbuffer_remaining);
-            unsafe {
-                copy::<u8>(self.readbuffer[..self.readbuffer_offset as usize].as_ptr(),
-                           buffer.as_mut_ptr(),
-                           self.readbuffer_remaining as usize);
+            let start = self.readbuffer_offset as usize;
+            let part_size = std::cmp::min(self.readbuffer_remaining as usize, size_to_read);
+            buffer.extend_from_slice(&self.readbuffer[start..start + part_size]);
+            // Fix offsets
+            self.readbuffer_remaining -= part_size as u32;
+            self.readbuffer_offset += part_size as u32;
+            if buffer.len() == size_to_read {
+                debug!("'ftdi_read_data' - OK, returned from readbuffer = {}", buffer.len());
+                return Ok(buffer.len());
             }
-            // Fix offset
-            offset += self.readbuffer_remaining as usize;
         }
 
-        let mut actual_length: c_int = 1 as c_int;
-
-        while offset < size && actual_length > 0 {
+        while buffer.len() < size_to_read {
             self.readbuffer_remaining = 0;
             self.readbuffer_offset = 0;
-            /* returns how much received */
-            // ret = libusb_bulk_transfer(ftdi->usb_dev,
-            //                           ftdi->out_ep,
-            //                           ftdi->readbuffer,
-            //                           ftdi->readbuffer_chunksize,
-            //                           &actual_length,
-            //                           ftdi->usb_read_timeout);
             let mut transferred_uninit = MaybeUninit::<c_int>::zeroed();
-            let buf_data_ptr = self.readbuffer[0..].as_ptr() as *mut c_uchar;
+            let buf_data_ptr = self.readbuffer.as_mut_ptr() as *mut c_uchar;
 
             let transfer_result = unsafe {
                 ffi::libusb_bulk_transfer(self.usb_dev.unwrap(),
@@ -1864,78 +1799,36 @@ impl ftdi_context {
                     backtrace: GenerateBacktrace::generate(),
                     source: Box::new(ftdi_context::get_usb_sys_native_error(transfer_result))
                 };
-                error!("actual_read_data_length = [{:?}], {}", 0, error);
+                error!("actual_read_data_length = [{:?}], {}", buffer.len(), error);
                 return Err(error);
             }
-            actual_length = unsafe { transferred_uninit.assume_init() };
-            if actual_length > 2 {
-                // skip FTDI status bytes.
-                // Maybe stored in the future to enable modem use
-                let num_of_chunks = actual_length / packet_size;
-                let chunk_remains = actual_length % packet_size;
-                println!("actual_length = {}, num_of_chunks = {}, chunk_remains = {}, readbuffer_offset = {}",
-                         actual_length, num_of_chunks, chunk_remains, self.readbuffer_offset);
-
-                self.readbuffer_offset += 2;
-                actual_length -= 2;
-
-                if actual_length > packet_size - 2 {
-                    let i = 1;
-                    while num_of_chunks > 0 {
-                        // let source = &self.readbuffer[..(self.readbuffer_offset + (packet_size * i) as u32) as usize];
-                        // let dest= &mut self.readbuffer[0..(self.readbuffer_offset + ((packet_size - 2) * i) as u32) as usize];
-                        // ptr::copy(source.as_ptr(), dest.as_mut_ptr(), (packet_size - 2) as usize);
-                        /*                    for (i = 1; i < num_of_chunks; i++) {
-                        memmove(ftdi -> readbuffer + ftdi -> readbuffer_offset + (packet_size - 2) * i,
-                        ftdi -> readbuffer + ftdi -> readbuffer_offset + packet_size * i,
-                        packet_size - 2);
-                    }*/
-                    }
-                    if chunk_remains > 2 {
-                        // memmove (ftdi->readbuffer+ftdi->readbuffer_offset+(packet_size - 2)*i,
-                        //     ftdi->readbuffer+ftdi->readbuffer_offset+packet_size*i,
-                        //     chunk_remains-2);
-                        actual_length -= 2 * num_of_chunks;
-                    } else {
-                        actual_length -= 2 * (num_of_chunks - 1) + chunk_remains;
-                    }
-                }
-            } else if actual_length <= 2 {
-                // no more data to read?
-                return Ok( offset );
+            let actual_length = unsafe { transferred_uninit.assume_init() } as usize;
+            if actual_length <= 2 {
+                // no more data to read
+                break;
             }
-            if actual_length > 0 {
-                // data still fits in buf?
-                if offset + (actual_length as usize) <= size {
-                    // memcpy (buf+offset, ftdi->readbuffer+ftdi->readbuffer_offset, actual_length);
-                    //printf("buf[0] = %X, buf[1] = %X\n", buf[0], buf[1]);
-                    offset += actual_length as usize;
-
-                    /* Did we read exactly the right amount of bytes? */
-                    if offset == size {
-                        //printf("read_data exact rem %d offset %d\n",
-                        //ftdi->readbuffer_remaining, offset);
-                        return Ok(offset);
-                    }
-                } else {
-                    // only copy part of the data or size <= readbuffer_chunksize
-                    let part_size = size - offset;
-                    // memcpy (buf+offset, ftdi->readbuffer+ftdi->readbuffer_offset, part_size);
-
-                    self.readbuffer_offset += part_size as u32;
-                    self.readbuffer_remaining = (actual_length - part_size as i32) as u32;
-                    offset += part_size;
-
-                    /* printf("Returning part: %d - size: %d - offset: %d - actual_length: %d - remaining: %d\n",
-                    part_size, size, offset, actual_length, ftdi->readbuffer_remaining); */
-
-                    return Ok(offset);
+            // skip FTDI status bytes at the start of every packet, squeezing payload to the buffer start
+            let mut payload_length = 0;
+            let mut packet_start = 0;
+            while packet_start < actual_length {
+                let packet_end = std::cmp::min(packet_start + packet_size, actual_length);
+                if packet_end > packet_start + 2 {
+                    self.readbuffer.copy_within(packet_start + 2..packet_end, payload_length);
+                    payload_length += packet_end - packet_start - 2;
                 }
+                packet_start += packet_size;
+            }
+            // data still fits in buf?
+            let part_size = std::cmp::min(payload_length, size_to_read - buffer.len());
+            buffer.extend_from_slice(&self.readbuffer[..part_size]);
+            if part_size < payload_length {
+                // keep the rest for the next call
+                self.readbuffer_offset = part_size as u32;
+                self.readbuffer_remaining = (payload_length - part_size) as u32;
             }
-            // should be never reached
-            return Ok(0);
         }
-        Ok(0)
+        debug!("'ftdi_read_data' - OK, read = {}", buffer.len());
+        Ok(buffer.len())
     }
 
     pub fn ftdi_read_data_set_chunksize(self, chunksize: i32) {
@@ -1946,12 +1839,213 @@ impl ftdi_context {
         unimplemented!()
     }
 
-    pub fn ftdi_set_bitmode(self/*, unsigned char bitmask, unsigned char mode*/) -> Result<usize> {
-        unimplemented!()
+    /// Enable/disable bitbang modes.
+    ///
+    /// param bitmask Bitmask to configure lines. HIGH/ON value configures a line as output.
+    /// param mode Bitbang mode: use the values defined in ftdi_mpsse_mode
+    pub fn ftdi_set_bitmode(&mut self, bitmask: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+        debug!("start \'ftdi_set_bitmode\' mask = {:#04x}, mode = {:?} ...", bitmask, mode);
+        self.check_usb_device()?;
+        self.capabilities()?.check_bitmode(mode)?;
+        let usb_val: u16 = ((mode as u16) << 8) | bitmask as u16;
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_SET_BITMODE_REQUEST,
+                                         usb_val,
+                                         self.index as u16, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: "unable to configure bitbang mode. Perhaps not a BM/2232C type chip?".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        self.bitbang_mode = mode as u8;
+        self.bitbang_enabled = mode != ftdi_mpsse_mode::BITMODE_RESET;
+        debug!("\'ftdi_set_bitmode\' - OK");
+        Ok(())
     }
 
+    /// Disable bitbang mode, switch back to regular serial/FIFO mode.
     pub fn ftdi_disable_bitbang(&mut self) -> Result<()> {
-        unimplemented!()
+        debug!("start \'ftdi_disable_bitbang\' ...");
+        self.check_usb_device()?;
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_SET_BITMODE_REQUEST,
+                                         0,
+                                         self.index as u16, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: "unable to leave bitbang mode. Perhaps not a BM type chip?".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        self.bitbang_enabled = false;
+        debug!("\'ftdi_disable_bitbang\' - OK");
+        Ok(())
+    }
+
+    /// Directly read pin state, circumventing the read buffer. Useful for bitbang mode.
+    ///
+    /// return pins state
+    pub fn ftdi_read_pins(&self) -> Result<u8> {
+        debug!("start \'ftdi_read_pins\' ...");
+        self.check_usb_device()?;
+        let mut pins: c_uchar = 0;
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_IN_REQTYPE,
+                                         SIO_READ_PINS_REQUEST,
+                                         0,
+                                         self.index as u16, &mut pins,
+                                         1,
+                                         self.usb_read_timeout as c_uint)};
+        if control_transfer_result != 1 {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: "read pins failed".to_string(),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        debug!("\'ftdi_read_pins\' - OK, pins = {:#04x}", pins);
+        Ok(pins)
+    }
+
+    /// Set latency timer, the chip sends a short packet when it's elapsed and there is data to send.
+    ///
+    /// param latency Value in milliseconds, 1..255
+    pub fn ftdi_set_latency_timer(&mut self, latency: u8) -> Result<()> {
+        debug!("start \'ftdi_set_latency_timer\' latency = {} ...", latency);
+        self.check_usb_device()?;
+        if latency < 1 {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: "latency out of range. Only valid for 1-255".to_string(),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_SET_LATENCY_TIMER_REQUEST,
+                                         latency as u16,
+                                         self.index as u16, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -2, message: "unable to set latency timer".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        debug!("\'ftdi_set_latency_timer\' - OK");
+        Ok(())
+    }
+
+    /// Get latency timer
+    ///
+    /// return latency in milliseconds
+    pub fn ftdi_get_latency_timer(&self) -> Result<u8> {
+        debug!("start \'ftdi_get_latency_timer\' ...");
+        self.check_usb_device()?;
+        let mut latency: c_uchar = 0;
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_IN_REQTYPE,
+                                         SIO_GET_LATENCY_TIMER_REQUEST,
+                                         0,
+                                         self.index as u16, &mut latency,
+                                         1,
+                                         self.usb_read_timeout as c_uint)};
+        if control_transfer_result != 1 {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: "reading latency timer failed".to_string(),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        debug!("\'ftdi_get_latency_timer\' - OK, latency = {}", latency);
+        Ok(latency)
+    }
+
+    /// Set flow control.
+    ///
+    /// param flowctrl Flow control handshake, high byte of the request index:
+    /// 0 - disabled, 0x0100 - RTS/CTS, 0x0200 - DTR/DSR, 0x0400 - XON/XOFF
+    pub fn ftdi_setflowctrl(&mut self, flowctrl: u16) -> Result<()> {
+        debug!("start \'ftdi_setflowctrl\' flowctrl = {:#06x} ...", flowctrl);
+        self.check_usb_device()?;
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_SET_FLOW_CTRL_REQUEST,
+                                         0,
+                                         flowctrl | self.index as u16, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: "set flow control failed".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        debug!("\'ftdi_setflowctrl\' - OK");
+        Ok(())
+    }
+
+    /// Set the special event character, the chip sends data at once when it's received.
+    ///
+    /// param eventch Event character
+    /// param enable false to disable the event character
+    pub fn ftdi_set_event_char(&mut self, eventch: u8, enable: bool) -> Result<()> {
+        debug!("start \'ftdi_set_event_char\' eventch = {:#04x}, enable = {} ...", eventch, enable);
+        self.check_usb_device()?;
+        let value = eventch as u16 | if enable { 1 << 8 } else { 0 };
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_SET_EVENT_CHAR_REQUEST,
+                                         value,
+                                         self.index as u16, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: "setting event character failed".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        debug!("\'ftdi_set_event_char\' - OK");
+        Ok(())
     }
 
     /// Parse vendor/product string supplied in specific format
@@ -2082,71 +2176,286 @@ impl ftdi_context {
         }
     }
 
-    /// ftdi_read_chipid_shift does the bitshift operation needed for the FTDIChip-ID
-    /// It is used internally only
-    fn ftdi_read_chipid_shift(value: u32) -> u32 {
-        ((value & 1) << 1) |
-            ((value & 2) << 5) |
-            ((value & 4) >> 2) |
-            ((value & 8) << 4) |
-            ((value & 16) >> 1) |
-            ((value & 32) >> 1) |
-            ((value & 64) >> 4) |
-            ((value & 128) >> 2)
-    }
-
-    /// Read the FTDIChip-ID from R-type devices
+    /// Read the FTDIChip-ID of FT232R and FT-X chips
     /// ftdi_context should be initialized previously
     /// return FTDIChip-ID value
-    pub fn ftdi_read_chipid(&self) -> Result<u16> {
+    pub fn ftdi_read_chipid(&self) -> Result<ChipId> {
         debug!("start \'ftdi_read_chipid\' ...");
         self.check_usb_device()?;
-        let mut a: c_uchar = 0 as c_uchar;
-        let mut b: c_uchar = 0 as c_uchar;
-        let control_transfer_result_1 = unsafe {
-            ffi::libusb_control_transfer(
-                self.usb_dev.unwrap(),
-                FTDI_DEVICE_IN_REQTYPE,
-                SIO_READ_EEPROM_REQUEST,
-                0, 0x43, &mut a, 2,
-                self.usb_read_timeout as c_uint)
-        };
-        debug!("control_transfer_result_1 = {}", control_transfer_result_1);
-        if control_transfer_result_1 == 2 {
-            a = ((((a as u16) << 8) as u16) | ((a as u16) >> 8) as u16) as u8;
-            let control_transfer_result_2 = unsafe {
-                ffi::libusb_control_transfer(
-                    self.usb_dev.unwrap(),
-                    FTDI_DEVICE_IN_REQTYPE,
-                    SIO_READ_EEPROM_REQUEST,
-                    0, 0x44,&mut b, 2,
-                    self.usb_read_timeout as c_uint)
+        if self.r#type != ftdi_chip_type::TYPE_R && self.r#type != ftdi_chip_type::TYPE_230X {
+            let error = FtdiContextError::UnsupportedChip {
+                chip_type: self.r#type, message: "chip has no FTDIChip-ID".to_string(),
+                backtrace: GenerateBacktrace::generate()
             };
-            debug!("control_transfer_result_2 = {}", control_transfer_result_2);
-            if control_transfer_result_2 == 2 {
-                // b = b << 8 | b >> 8; // old C code
-                b = u16::from(u16::from(b) << 8 | u16::from(b) >> 8) as u8;
-                // a = (a << 16) | (b & 0xFFFF); // old C code
-                a = ((u32::from(a) << 16) | (u32::from(b) & 0xFFFF)) as u8;
-                a = (ftdi_context::ftdi_read_chipid_shift(a as u32)
-                    | ftdi_context::ftdi_read_chipid_shift((u32::from(a) >> 8) as u32) << 8
-                    | ftdi_context::ftdi_read_chipid_shift((u32::from(a) >> 16) as u32) << 16
-                    | ftdi_context::ftdi_read_chipid_shift((u32::from(a) >> 24) as u32) << 24) as u8;
-                let chipid: u32 = ((a as u32) ^ (0xa5f0f7d1 as u32)) as u32;
-                info!("Read ChipId = {}", chipid);
-                return Ok(chipid as u16);
-            } else {
-                debug!("control_transfer_result_2 returned result = {}", control_transfer_result_2);
+            error!("{}", error);
+            return Err(error);
+        }
+        let high = self.ftdi_read_eeprom_location(CHIPID_EEPROM_WORDS.start);
+        let low = self.ftdi_read_eeprom_location(CHIPID_EEPROM_WORDS.start + 1);
+        match (high, low) {
+            (Ok(high), Ok(low)) => {
+                let chip_id = ChipId::from_words(high, low);
+                info!("Read ChipId = {}", chip_id);
+                Ok(chip_id)
+            }
+            (high, low) => {
+                debug!("reading ChipId words returned {:?}, {:?}", high.err(), low.err());
+                let error = FtdiContextError::UsbCommandError {
+                    code: -1, message: "read of FTDIChip-ID failed".to_string(),
+                    backtrace: GenerateBacktrace::generate(),
+                    source: Box::new(FtdiContextError::None)
+                };
+                error!("{}", error);
+                Err(error)
             }
-        } else {
-            debug!("control_transfer_result_1 returned result = {}", control_transfer_result_1);
         }
-        let error = FtdiContextError::UsbCommandError {
-            code: -1, message: "read of FTDIChip-ID failed".to_string(),
-            backtrace: GenerateBacktrace::generate(),
-            source: Box::new(FtdiContextError::None)
+    }
+
+    /// Read one EEPROM word
+    ///
+    /// param address Word address
+    pub fn ftdi_read_eeprom_location(&self, address: u16) -> Result<u16> {
+        self.check_usb_device()?;
+        let mut word = [0u8; 2];
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_IN_REQTYPE,
+                                         SIO_READ_EEPROM_REQUEST,
+                                         0,
+                                         address, word.as_mut_ptr(),
+                                         2,
+                                         self.usb_read_timeout as c_uint)};
+        if control_transfer_result != 2 {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: format!("reading EEPROM word {:#04x} failed", address),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        Ok(u16::from_le_bytes(word))
+    }
+
+    /// Write one EEPROM word. It isn't covered by the checksum,
+    /// so writing words used by the chip makes EEPROM invalid until it's rebuilt.
+    ///
+    /// param address Word address
+    /// param confirm Must be true, EEPROM isn't touched otherwise
+    pub fn ftdi_write_eeprom_location(&mut self, address: u16, value: u16, confirm: bool) -> Result<()> {
+        debug!("start \'ftdi_write_eeprom_location\' address = {:#04x}, value = {:#06x} ...", address, value);
+        self.check_usb_device()?;
+        self.check_eeprom_write_confirmed(confirm)?;
+        // size is known after ftdi_read_eeprom()
+        let words = if self.eeprom.size > 0 { self.eeprom.size as usize / 2 } else { FTDI_MAX_EEPROM_SIZE / 2 };
+        if address as usize >= words {
+            let error = FtdiContextError::UsbCommonError {
+                code: -2, message: format!("EEPROM word {:#x} is out of range, EEPROM has {:#x} words", address, words),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        if self.r#type == ftdi_chip_type::TYPE_230X && FTX_EEPROM_RESERVED_WORDS.contains(&address) {
+            let error = FtdiContextError::UsbCommonError {
+                code: -3, message: format!("EEPROM word {:#x} is reserved on FT-X", address),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        if self.r#type == ftdi_chip_type::TYPE_R && CHIPID_EEPROM_WORDS.contains(&address) {
+            let error = FtdiContextError::UsbCommonError {
+                code: -3, message: format!("EEPROM word {:#x} holds FTDIChip-ID", address),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        self.ftdi_write_eeprom_word(address, value)?;
+        debug!("\'ftdi_write_eeprom_location\' - OK");
+        Ok(())
+    }
+
+    /// Read the whole EEPROM to 'eeprom.buf', detect its size and 93xx chip type
+    pub fn ftdi_read_eeprom(&mut self) -> Result<()> {
+        debug!("start \'ftdi_read_eeprom\' ...");
+        self.check_usb_device()?;
+        let mut buf = Vec::with_capacity(FTDI_MAX_EEPROM_SIZE);
+        for address in 0..(FTDI_MAX_EEPROM_SIZE / 2) as u16 {
+            buf.extend_from_slice(&self.ftdi_read_eeprom_location(address)?.to_le_bytes());
+        }
+        let (size, chip) = ftdi_eeprom::detect_size(self.r#type, &buf);
+        if size < 0 {
+            warn!("EEPROM is blank or missing");
+        }
+        self.eeprom.buf = buf;
+        self.eeprom.size = size;
+        self.eeprom.chip = chip;
+        debug!("\'ftdi_read_eeprom\' - OK, size = {}, chip = {:#x}", size, chip);
+        Ok(())
+    }
+
+    /// Decode 'eeprom.buf' read by ftdi_read_eeprom() into 'eeprom' fields.
+    /// Channel types and CBUS functions used by FIFO, fast serial and CBUS modes come from here.
+    pub fn ftdi_eeprom_decode(&mut self) -> Result<()> {
+        debug!("start \'ftdi_eeprom_decode\' ...");
+        self.eeprom = ftdi_eeprom::decode(&self.eeprom.buf, self.r#type)?;
+        debug!("\'ftdi_eeprom_decode\' - OK");
+        Ok(())
+    }
+
+    /// Write 'eeprom.buf' to EEPROM, 'eeprom.size' bytes of it.
+    /// Reserved FT-X words are skipped.
+    ///
+    /// param confirm Must be true, EEPROM isn't touched otherwise
+    pub fn ftdi_write_eeprom(&mut self, confirm: bool) -> Result<()> {
+        debug!("start \'ftdi_write_eeprom\' ...");
+        self.check_usb_device()?;
+        self.check_eeprom_write_confirmed(confirm)?;
+        let size = self.eeprom.size;
+        if size <= 0 || size as usize > FTDI_MAX_EEPROM_SIZE || self.eeprom.buf.len() < size as usize {
+            let error = FtdiContextError::UsbCommonError {
+                code: -2, message: format!("no EEPROM image to write, size = {}, image = {} bytes", size, self.eeprom.buf.len()),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        self.prepare_eeprom_write(size as usize)?;
+        for address in 0..(size / 2) as u16 {
+            if self.r#type == ftdi_chip_type::TYPE_230X && FTX_EEPROM_RESERVED_WORDS.contains(&address) {
+                continue;
+            }
+            let index = address as usize * 2;
+            let value = u16::from_le_bytes([self.eeprom.buf[index], self.eeprom.buf[index + 1]]);
+            self.ftdi_write_eeprom_word(address, value)?;
+        }
+        debug!("\'ftdi_write_eeprom\' - OK");
+        Ok(())
+    }
+
+    /// Check EEPROM size against the chip and unlock EEPROM writes, the sequence MProg does before writing
+    fn prepare_eeprom_write(&mut self, size: usize) -> Result<()> {
+        self.capabilities()?.check_eeprom_size(size)?;
+        self.ftdi_usb_reset()?;
+        self.ftdi_set_latency_timer(0x77)?;
+        Ok(())
+    }
+
+    /// Read EEPROM and return its user area, see ftdi_eeprom::user_area
+    pub fn ftdi_read_user_area(&mut self) -> Result<Vec<u8>> {
+        debug!("start \'ftdi_read_user_area\' ...");
+        self.ftdi_read_eeprom()?;
+        let data = self.eeprom.user_area_data(self.r#type)?.to_vec();
+        debug!("\'ftdi_read_user_area\' - OK, {} bytes", data.len());
+        Ok(data)
+    }
+
+    /// Write data into EEPROM user area at 'offset' from its start. Only changed words
+    /// and the checksum are written, configuration words and strings aren't touched.
+    ///
+    /// param confirm Must be true, EEPROM isn't touched otherwise
+    pub fn ftdi_write_user_area(&mut self, offset: usize, data: &[u8], confirm: bool) -> Result<()> {
+        debug!("start \'ftdi_write_user_area\' offset = {}, data = {} bytes ...", offset, data.len());
+        self.check_usb_device()?;
+        self.check_eeprom_write_confirmed(confirm)?;
+        self.ftdi_read_eeprom()?;
+        let chip_type = self.r#type;
+        let changed = self.eeprom.write_user_area(chip_type, offset, data)?;
+        self.prepare_eeprom_write(self.eeprom.size as usize)?;
+        for address in changed.iter() {
+            let index = *address as usize * 2;
+            let value = u16::from_le_bytes([self.eeprom.buf[index], self.eeprom.buf[index + 1]]);
+            self.ftdi_write_eeprom_word(*address, value)?;
+        }
+        debug!("\'ftdi_write_user_area\' - OK, {} words written", changed.len());
+        Ok(())
+    }
+
+    /// Erase external EEPROM and detect its chip type by writing a probe word, chip type is stored in 'eeprom.chip'.
+    /// Internal EEPROMs of FT232R and FT-X can't be erased, 'eeprom.chip' is set to 0 for them.
+    ///
+    /// param confirm Must be true, EEPROM isn't touched otherwise
+    pub fn ftdi_erase_eeprom(&mut self, confirm: bool) -> Result<()> {
+        debug!("start \'ftdi_erase_eeprom\' ...");
+        self.check_usb_device()?;
+        self.check_eeprom_write_confirmed(confirm)?;
+        if self.capabilities()?.eeprom != EepromKind::External {
+            warn!("{:?} has internal EEPROM, it isn't erased", self.r#type);
+            self.eeprom.chip = 0;
+            return Ok(());
+        }
+        self.ftdi_erase_eeprom_request()?;
+        // probe word lands on word 0xc0 of 93x66 only, smaller chips ignore upper address bits
+        self.ftdi_write_eeprom_word(0xc0, EEPROM_SIZE_PROBE_MAGIC)?;
+        self.eeprom.chip = if self.ftdi_read_eeprom_location(0x00)? == EEPROM_SIZE_PROBE_MAGIC {
+            EEPROM_CHIP_93X46
+        } else if self.ftdi_read_eeprom_location(0x40)? == EEPROM_SIZE_PROBE_MAGIC {
+            EEPROM_CHIP_93X56
+        } else if self.ftdi_read_eeprom_location(0xc0)? == EEPROM_SIZE_PROBE_MAGIC {
+            EEPROM_CHIP_93X66
+        } else {
+            -1
         };
-        Err(error)
+        self.ftdi_erase_eeprom_request()?;
+        debug!("\'ftdi_erase_eeprom\' - OK, chip = {:#x}", self.eeprom.chip);
+        Ok(())
+    }
+
+    fn check_eeprom_write_confirmed(&self, confirm: bool) -> Result<()> {
+        if !confirm {
+            let error = FtdiContextError::UsbCommonError {
+                code: -10, message: "EEPROM change isn't confirmed".to_string(),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn ftdi_write_eeprom_word(&self, address: u16, value: u16) -> Result<()> {
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_WRITE_EEPROM_REQUEST,
+                                         value,
+                                         address, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: format!("unable to write EEPROM word {:#04x}", address),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn ftdi_erase_eeprom_request(&self) -> Result<()> {
+        let null_data_ptr: *mut c_uchar = ptr::null_mut::<c_uchar>();
+        let control_transfer_result = unsafe {
+            ffi::libusb_control_transfer(self.usb_dev.unwrap(),
+                                         FTDI_DEVICE_OUT_REQTYPE,
+                                         SIO_ERASE_EEPROM_REQUEST,
+                                         0,
+                                         0, null_data_ptr,
+                                         0,
+                                         self.usb_write_timeout as c_uint)};
+        if control_transfer_result < 0 {
+            let error = FtdiContextError::UsbCommandError {
+                code: -1, message: "unable to erase EEPROM".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(control_transfer_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        Ok(())
     }
 
     /// Internal function to determine the maximum packet size.
@@ -2161,8 +2470,7 @@ impl ftdi_context {
         // Determine maximum packet size. Init with default value.
         // New hi-speed devices from FTDI use a packet size of 512 bytes
         // but could be connected to a normal speed USB hub -> 64 bytes packet size.
-        if self.r#type == ftdi_chip_type::TYPE_2232H || self.r#type  == ftdi_chip_type::TYPE_4232H
-            || self.r#type == ftdi_chip_type::TYPE_232H {
+        if chip_info(self.r#type).high_speed {
             packet_size = 512;
         } else {
             packet_size = 64;
@@ -0,0 +1,237 @@
+#![allow(dead_code)]
+
+use log::debug;
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_command_length};
+
+/// Owner of pins driven through GpioController pin functions
+pub const GPIO_OWNER: &str = "gpio";
+
+/// Eight pins set by one MPSSE command. Second channel of dual/quad chips
+/// has its own BDBUS/BCBUS ports reached through a context opened on that interface.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GpioPort {
+    /// ADBUS (BDBUS), shared with protocol engine pins
+    Low,
+    /// ACBUS (BCBUS)
+    High,
+}
+impl GpioPort {
+    fn index(self) -> usize {
+        match self {
+            GpioPort::Low => 0,
+            GpioPort::High => 1,
+        }
+    }
+
+    fn set_command(self) -> u8 {
+        match self {
+            GpioPort::Low => SET_BITS_LOW,
+            GpioPort::High => SET_BITS_HIGH,
+        }
+    }
+
+    fn get_command(self) -> u8 {
+        match self {
+            GpioPort::Low => GET_BITS_LOW,
+            GpioPort::High => GET_BITS_HIGH,
+        }
+    }
+}
+
+/// Which user (GPIO or protocol engine name like "spi") owns every pin
+#[derive(Clone, Default, Debug)]
+pub struct PinRegistry {
+    owners: [[Option<&'static str>; 8]; 2],
+}
+impl PinRegistry {
+    pub fn new() -> Self {
+        PinRegistry::default()
+    }
+
+    /// Assign pins in mask to owner, fails if some of them already belongs to another owner
+    pub fn claim(&mut self, port: GpioPort, mask: u8, owner: &'static str) -> Result<()> {
+        let owners = &mut self.owners[port.index()];
+        if let Some((pin, other)) = (0..8).filter(|pin| mask & (1 << pin) != 0)
+            .filter_map(|pin| owners[pin].filter(|other| *other != owner).map(|other| (pin, other)))
+            .next() {
+            return Err(common_error(-1, format!("{:?} pin {} is owned by '{}', can't be given to '{}'", port, pin, other, owner)));
+        }
+        for (pin, slot) in owners.iter_mut().enumerate() {
+            if mask & (1 << pin) != 0 {
+                *slot = Some(owner);
+            }
+        }
+        debug!("{:?} pins {:#04x} claimed by '{}'", port, mask, owner);
+        Ok(())
+    }
+
+    /// Free all pins of owner
+    pub fn release(&mut self, owner: &'static str) {
+        for slot in self.owners.iter_mut().flat_map(|owners| owners.iter_mut()) {
+            if *slot == Some(owner) {
+                *slot = None;
+            }
+        }
+    }
+
+    pub fn owner(&self, port: GpioPort, pin: u8) -> Option<&'static str> {
+        self.owners[port.index()].get(pin as usize).copied().flatten()
+    }
+
+    /// Mask of pins belonging to owner
+    pub fn pins(&self, port: GpioPort, owner: &'static str) -> u8 {
+        self.mask(port, |other| other == owner)
+    }
+
+    /// Mask of pins belonging to protocol engines, i.e. to anybody but GPIO_OWNER
+    pub fn engine_pins(&self, port: GpioPort) -> u8 {
+        self.mask(port, |owner| owner != GPIO_OWNER)
+    }
+
+    fn mask<F: Fn(&'static str) -> bool>(&self, port: GpioPort, filter: F) -> u8 {
+        self.owners[port.index()].iter().enumerate()
+            .filter(|(_, owner)| owner.is_some_and(&filter))
+            .fold(0, |mask, (pin, _)| mask | (1 << pin))
+    }
+}
+
+/// GPIO over MPSSE low and high byte pins.
+/// It's a transport itself: protocol engines (SPI, I2C...) put on top of it change only pins claimed
+/// by them, the other pins keep the state set by GPIO functions, and vice versa.
+pub struct GpioController<T: MpsseTransport> {
+    transport: T,
+    registry: PinRegistry,
+    /// last written value and direction (1 - output) of every port
+    value: [u8; 2],
+    direction: [u8; 2],
+}
+impl<T: MpsseTransport> GpioController<T> {
+    /// All pins are supposed to be inputs, as after MPSSE mode is enabled
+    pub fn new(transport: T) -> Self {
+        GpioController { transport, registry: PinRegistry::new(), value: [0; 2], direction: [0; 2] }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub fn registry(&self) -> &PinRegistry {
+        &self.registry
+    }
+
+    /// Assign pins to owner, GPIO_OWNER for pins used by GPIO functions or engine name
+    pub fn claim(&mut self, port: GpioPort, mask: u8, owner: &'static str) -> Result<()> {
+        self.registry.claim(port, mask, owner)
+    }
+
+    pub fn release(&mut self, owner: &'static str) {
+        self.registry.release(owner)
+    }
+
+    /// Cached value and direction of port pins
+    pub fn state(&self, port: GpioPort) -> (u8, u8) {
+        (self.value[port.index()], self.direction[port.index()])
+    }
+
+    /// Cached value written to pin
+    pub fn value(&self, port: GpioPort, pin: u8) -> bool {
+        self.value[port.index()] & (1 << pin) != 0
+    }
+
+    pub fn is_output(&self, port: GpioPort, pin: u8) -> bool {
+        self.direction[port.index()] & (1 << pin) != 0
+    }
+
+    pub fn set_direction(&mut self, port: GpioPort, pin: u8, output: bool) -> Result<()> {
+        let mask = self.pin_mask(port, pin)?;
+        let index = port.index();
+        let direction = if output { self.direction[index] | mask } else { self.direction[index] & !mask };
+        self.update(port, self.value[index], direction)
+    }
+
+    pub fn set_value(&mut self, port: GpioPort, pin: u8, high: bool) -> Result<()> {
+        let mask = self.pin_mask(port, pin)?;
+        self.write(port, mask, if high { mask } else { 0 })
+    }
+
+    /// Set values of pins in mask at once, all of them must be claimed by GPIO_OWNER
+    pub fn write(&mut self, port: GpioPort, mask: u8, value: u8) -> Result<()> {
+        self.check_owned(port, mask)?;
+        let index = port.index();
+        self.update(port, (self.value[index] & !mask) | (value & mask), self.direction[index])
+    }
+
+    /// Read levels of all port pins (outputs included) right now
+    pub fn read(&mut self, port: GpioPort) -> Result<u8> {
+        self.transport.mpsse_write(&[port.get_command(), SEND_IMMEDIATE])?;
+        Ok(self.transport.mpsse_read(1)?[0])
+    }
+
+    pub fn read_pin(&mut self, port: GpioPort, pin: u8) -> Result<bool> {
+        let mask = self.pin_mask(port, pin)?;
+        Ok(self.read(port)? & mask != 0)
+    }
+
+    fn pin_mask(&self, port: GpioPort, pin: u8) -> Result<u8> {
+        if pin > 7 {
+            return Err(common_error(-1, format!("{:?} port has no pin {}", port, pin)));
+        }
+        self.check_owned(port, 1 << pin)?;
+        Ok(1 << pin)
+    }
+
+    fn check_owned(&self, port: GpioPort, mask: u8) -> Result<()> {
+        let foreign = mask & !self.registry.pins(port, GPIO_OWNER);
+        if foreign != 0 {
+            return Err(common_error(-1, format!("{:?} pins {:#04x} are not claimed for GPIO", port, foreign)));
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, port: GpioPort, value: u8, direction: u8) -> Result<()> {
+        self.transport.mpsse_write(&[port.set_command(), value, direction])?;
+        self.value[port.index()] = value;
+        self.direction[port.index()] = direction;
+        Ok(())
+    }
+
+    /// Merge pin setup coming from protocol engine with the state of the other pins
+    fn merge(&mut self, port: GpioPort, value: u8, direction: u8) -> (u8, u8) {
+        let index = port.index();
+        let engine = self.registry.engine_pins(port);
+        self.value[index] = (value & engine) | (self.value[index] & !engine);
+        self.direction[index] = (direction & engine) | (self.direction[index] & !engine);
+        (self.value[index], self.direction[index])
+    }
+}
+impl<T: MpsseTransport> MpsseTransport for GpioController<T> {
+    fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+        let mut merged = Vec::with_capacity(commands.len());
+        let mut index = 0;
+        while index < commands.len() {
+            let length = mpsse_command_length(&commands[index..])
+                .ok_or_else(|| common_error(-1, format!("truncated MPSSE command {:#04x}", commands[index])))?;
+            let command = &commands[index..index + length];
+            match command[0] {
+                SET_BITS_LOW | SET_BITS_HIGH => {
+                    let port = if command[0] == SET_BITS_LOW { GpioPort::Low } else { GpioPort::High };
+                    let (value, direction) = self.merge(port, command[1], command[2]);
+                    merged.extend_from_slice(&[command[0], value, direction]);
+                }
+                _ => merged.extend_from_slice(command),
+            }
+            index += length;
+        }
+        self.transport.mpsse_write(&merged)
+    }
+
+    fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+        self.transport.mpsse_read(size)
+    }
+}
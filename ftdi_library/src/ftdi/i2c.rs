@@ -0,0 +1,178 @@
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+use log::{debug, info};
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_clock_commands};
+
+/// ADBUS pins used by I2C: SCL (0), SDA out (1) and SDA in (2), pins 1 and 2 are tied together on the board
+pub const I2C_PIN_SCL: u8 = 0x01;
+pub const I2C_PIN_SDA_OUT: u8 = 0x02;
+pub const I2C_PIN_SDA_IN: u8 = 0x04;
+
+/// How many times pin setup is repeated to keep start/stop setup and hold times at any SCL frequency
+pub const I2C_PIN_REPEAT: usize = 4;
+/// Biggest transfer (in bytes) queued into one USB write
+pub const I2C_MAX_CHUNK: usize = 256;
+
+/// I2C master on MPSSE. SDA is open drain emulated by switching pin direction:
+/// logical one releases the line (pull up resistor is required), zero drives it low.
+pub struct I2cMaster<T: MpsseTransport> {
+    transport: T,
+}
+impl<T: MpsseTransport> I2cMaster<T> {
+    pub fn new(transport: T) -> Self {
+        I2cMaster { transport }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Setup SCL frequency and release the bus. High speed chips use three phase clocking
+    /// (data valid on both SCL edges), FT2232C/D has no such command.
+    /// MPSSE mode must be already enabled on the chip (ftdi_set_bitmode with BITMODE_MPSSE).
+    /// return actual SCL frequency
+    pub fn init(&mut self, frequency: u32, high_speed: bool) -> Result<u32> {
+        debug!("start 'i2c init' ...");
+        let (mut commands, actual) = if high_speed {
+            // three phase clocking makes a bit 3/2 of the normal clock period long
+            let (mut commands, actual) = mpsse_clock_commands(frequency.saturating_mul(3) / 2, true);
+            commands.push(EN_3_PHASE);
+            (commands, actual * 2 / 3)
+        } else {
+            mpsse_clock_commands(frequency, false)
+        };
+        commands.push(LOOPBACK_END);
+        commands.extend_from_slice(&Self::pins(true, true));
+        self.transport.mpsse_write(&commands)?;
+        info!("I2C initialized, SCL = {} Hz", actual);
+        Ok(actual)
+    }
+
+    /// Check a device acknowledges its 7 bit address (write direction)
+    pub fn probe(&mut self, address: u8) -> Result<bool> {
+        let mut commands = Vec::with_capacity(64);
+        Self::append_start(&mut commands);
+        Self::append_write_byte(&mut commands, address << 1);
+        Self::append_stop(&mut commands);
+        commands.push(SEND_IMMEDIATE);
+        self.transport.mpsse_write(&commands)?;
+        Ok(self.transport.mpsse_read(1)?[0] & 0x01 == 0)
+    }
+
+    /// Repeat 'probe' until the device answers, e.g. after EEPROM write cycle
+    /// return number of probes not acknowledged
+    pub fn wait_ack(&mut self, address: u8, timeout: Duration) -> Result<u32> {
+        let start = Instant::now();
+        let mut polls = 0;
+        while !self.probe(address)? {
+            polls += 1;
+            if start.elapsed() > timeout {
+                return Err(common_error(-4, format!("I2C device {:#04x} doesn't answer after {:?}", address, timeout)));
+            }
+        }
+        Ok(polls)
+    }
+
+    /// Write bytes to device in one transfer
+    pub fn write(&mut self, address: u8, data: &[u8]) -> Result<()> {
+        self.write_read(address, data, 0).map(|_| ())
+    }
+
+    /// Read bytes from device in one transfer
+    pub fn read(&mut self, address: u8, size: usize) -> Result<Vec<u8>> {
+        self.write_read(address, &[], size)
+    }
+
+    /// Write 'write' bytes, then repeated start and read 'read_size' bytes.
+    /// Write phase is skipped when 'write' is empty, read phase when 'read_size' is zero.
+    pub fn write_read(&mut self, address: u8, write: &[u8], read_size: usize) -> Result<Vec<u8>> {
+        if write.is_empty() && read_size == 0 {
+            return Err(common_error(-1, "I2C transfer without data".to_string()));
+        }
+        let mut commands = Vec::with_capacity(write.len() * 12 + read_size * 10 + 64);
+        // address and data bytes written, their acknowledge bits come first in reply
+        let mut acks = 0;
+        if !write.is_empty() {
+            Self::append_start(&mut commands);
+            Self::append_write_byte(&mut commands, address << 1);
+            for byte in write {
+                Self::append_write_byte(&mut commands, *byte);
+            }
+            acks += 1 + write.len();
+        }
+        if read_size > 0 {
+            Self::append_start(&mut commands);
+            Self::append_write_byte(&mut commands, (address << 1) | 0x01);
+            acks += 1;
+            for index in 0..read_size {
+                Self::append_read_byte(&mut commands, index + 1 < read_size);
+            }
+        }
+        Self::append_stop(&mut commands);
+        commands.push(SEND_IMMEDIATE);
+        self.transport.mpsse_write(&commands)?;
+        let reply = self.transport.mpsse_read(acks + read_size)?;
+        let (acks, data) = reply.split_at(acks);
+        if let Some(index) = acks.iter().position(|ack| ack & 0x01 != 0) {
+            let what = if index == 0 || index == write.len() + 1 {
+                "address".to_string()
+            } else {
+                format!("byte {}", index - 1)
+            };
+            return Err(common_error(-7, format!("I2C device {:#04x} doesn't acknowledge {}", address, what)));
+        }
+        Ok(data.to_vec())
+    }
+
+    /// SDA high releases the line
+    fn pins(scl: bool, sda: bool) -> [u8; 3] {
+        let value = if scl { I2C_PIN_SCL } else { 0 };
+        let direction = if sda { I2C_PIN_SCL } else { I2C_PIN_SCL | I2C_PIN_SDA_OUT };
+        [SET_BITS_LOW, value, direction]
+    }
+
+    fn append_pins(commands: &mut Vec<u8>, scl: bool, sda: bool) {
+        for _ in 0..I2C_PIN_REPEAT {
+            commands.extend_from_slice(&Self::pins(scl, sda));
+        }
+    }
+
+    /// (Repeated) start: SDA falls while SCL is high
+    fn append_start(commands: &mut Vec<u8>) {
+        Self::append_pins(commands, false, true);
+        Self::append_pins(commands, true, true);
+        Self::append_pins(commands, true, false);
+        Self::append_pins(commands, false, false);
+    }
+
+    /// Stop: SDA rises while SCL is high, bus is left released
+    fn append_stop(commands: &mut Vec<u8>) {
+        Self::append_pins(commands, false, false);
+        Self::append_pins(commands, true, false);
+        Self::append_pins(commands, true, true);
+    }
+
+    /// Clock byte out with SDA driven and clock in acknowledge bit with SDA released,
+    /// acknowledge is returned in bit 0 of a result byte
+    fn append_write_byte(commands: &mut Vec<u8>, byte: u8) {
+        commands.extend_from_slice(&Self::pins(false, false));
+        commands.extend_from_slice(&[MPSSE_DO_WRITE | MPSSE_WRITE_NEG | MPSSE_BITMODE, 7, byte]);
+        commands.extend_from_slice(&Self::pins(false, true));
+        commands.extend_from_slice(&[MPSSE_DO_READ | MPSSE_BITMODE, 0]);
+    }
+
+    /// Clock byte in with SDA released and answer with acknowledge (more bytes wanted) or not
+    fn append_read_byte(commands: &mut Vec<u8>, ack: bool) {
+        commands.extend_from_slice(&Self::pins(false, true));
+        commands.extend_from_slice(&[MPSSE_DO_READ | MPSSE_BITMODE, 7]);
+        commands.extend_from_slice(&Self::pins(false, !ack));
+        commands.extend_from_slice(&[MPSSE_DO_WRITE | MPSSE_WRITE_NEG | MPSSE_BITMODE, 0, if ack { 0x00 } else { 0xff }]);
+    }
+}
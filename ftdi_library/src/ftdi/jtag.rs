@@ -0,0 +1,465 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use log::{debug, error, info};
+use snafu::GenerateBacktrace;
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{FtdiContextError, Result};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_clock_commands};
+
+/// Maximum number of devices looked for by scan chain detection
+pub const JTAG_MAX_DEVICES: usize = 32;
+/// Bits used to flush instruction registers when measuring the IR chain length
+pub const JTAG_MAX_IR_CHAIN_LENGTH: usize = 256;
+
+/// ADBUS pins used by JTAG: TCK (0), TDI (1), TDO (2), TMS (3)
+pub const JTAG_PIN_TCK: u8 = 0x01;
+pub const JTAG_PIN_TDI: u8 = 0x02;
+pub const JTAG_PIN_TDO: u8 = 0x04;
+pub const JTAG_PIN_TMS: u8 = 0x08;
+
+/// Clock data bytes out on -ve edge, LSB first, no read
+const JTAG_WRITE_BYTES: u8 = MPSSE_DO_WRITE | MPSSE_LSB | MPSSE_WRITE_NEG;
+/// Clock data bits out on -ve edge, LSB first, no read
+const JTAG_WRITE_BITS: u8 = MPSSE_DO_WRITE | MPSSE_LSB | MPSSE_BITMODE | MPSSE_WRITE_NEG;
+/// Clock data bytes out on -ve edge and in on +ve edge, LSB first
+const JTAG_RW_BYTES: u8 = MPSSE_DO_WRITE | MPSSE_DO_READ | MPSSE_LSB | MPSSE_WRITE_NEG;
+/// Clock data bits out on -ve edge and in on +ve edge, LSB first
+const JTAG_RW_BITS: u8 = MPSSE_DO_WRITE | MPSSE_DO_READ | MPSSE_LSB | MPSSE_BITMODE | MPSSE_WRITE_NEG;
+/// Clock TMS bits out on -ve edge, LSB first, no read
+const JTAG_TMS_BITS: u8 = MPSSE_WRITE_TMS | MPSSE_LSB | MPSSE_BITMODE | MPSSE_WRITE_NEG;
+/// Clock TMS bits out on -ve edge and read TDO on +ve edge, LSB first
+const JTAG_TMS_RW_BITS: u8 = MPSSE_WRITE_TMS | MPSSE_DO_READ | MPSSE_LSB | MPSSE_BITMODE | MPSSE_WRITE_NEG;
+/// One MPSSE data command can clock up to 65536 bytes
+const MPSSE_MAX_BYTES_PER_COMMAND: usize = 0x10000;
+/// One MPSSE TMS command can clock up to 7 bits
+const MPSSE_MAX_TMS_BITS: usize = 7;
+
+/// IEEE 1149.1 TAP controller states
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TapState {
+    TestLogicReset,
+    RunTestIdle,
+    SelectDrScan,
+    CaptureDr,
+    ShiftDr,
+    Exit1Dr,
+    PauseDr,
+    Exit2Dr,
+    UpdateDr,
+    SelectIrScan,
+    CaptureIr,
+    ShiftIr,
+    Exit1Ir,
+    PauseIr,
+    Exit2Ir,
+    UpdateIr,
+}
+impl TapState {
+    pub const ALL: [TapState; 16] = [
+        TapState::TestLogicReset, TapState::RunTestIdle,
+        TapState::SelectDrScan, TapState::CaptureDr, TapState::ShiftDr, TapState::Exit1Dr,
+        TapState::PauseDr, TapState::Exit2Dr, TapState::UpdateDr,
+        TapState::SelectIrScan, TapState::CaptureIr, TapState::ShiftIr, TapState::Exit1Ir,
+        TapState::PauseIr, TapState::Exit2Ir, TapState::UpdateIr,
+    ];
+
+    /// State the TAP moves to on the TCK rising edge with given TMS value
+    pub fn next(self, tms: bool) -> TapState {
+        match (self, tms) {
+            (TapState::TestLogicReset, false) => TapState::RunTestIdle,
+            (TapState::TestLogicReset, true) => TapState::TestLogicReset,
+            (TapState::RunTestIdle, false) => TapState::RunTestIdle,
+            (TapState::RunTestIdle, true) => TapState::SelectDrScan,
+            (TapState::SelectDrScan, false) => TapState::CaptureDr,
+            (TapState::SelectDrScan, true) => TapState::SelectIrScan,
+            (TapState::CaptureDr, false) => TapState::ShiftDr,
+            (TapState::CaptureDr, true) => TapState::Exit1Dr,
+            (TapState::ShiftDr, false) => TapState::ShiftDr,
+            (TapState::ShiftDr, true) => TapState::Exit1Dr,
+            (TapState::Exit1Dr, false) => TapState::PauseDr,
+            (TapState::Exit1Dr, true) => TapState::UpdateDr,
+            (TapState::PauseDr, false) => TapState::PauseDr,
+            (TapState::PauseDr, true) => TapState::Exit2Dr,
+            (TapState::Exit2Dr, false) => TapState::ShiftDr,
+            (TapState::Exit2Dr, true) => TapState::UpdateDr,
+            (TapState::UpdateDr, false) => TapState::RunTestIdle,
+            (TapState::UpdateDr, true) => TapState::SelectDrScan,
+            (TapState::SelectIrScan, false) => TapState::CaptureIr,
+            (TapState::SelectIrScan, true) => TapState::TestLogicReset,
+            (TapState::CaptureIr, false) => TapState::ShiftIr,
+            (TapState::CaptureIr, true) => TapState::Exit1Ir,
+            (TapState::ShiftIr, false) => TapState::ShiftIr,
+            (TapState::ShiftIr, true) => TapState::Exit1Ir,
+            (TapState::Exit1Ir, false) => TapState::PauseIr,
+            (TapState::Exit1Ir, true) => TapState::UpdateIr,
+            (TapState::PauseIr, false) => TapState::PauseIr,
+            (TapState::PauseIr, true) => TapState::Exit2Ir,
+            (TapState::Exit2Ir, false) => TapState::ShiftIr,
+            (TapState::Exit2Ir, true) => TapState::UpdateIr,
+            (TapState::UpdateIr, false) => TapState::RunTestIdle,
+            (TapState::UpdateIr, true) => TapState::SelectDrScan,
+        }
+    }
+
+    /// Stable states are the ones TAP can stay in while TMS is held
+    pub fn is_stable(self) -> bool {
+        matches!(self, TapState::TestLogicReset | TapState::RunTestIdle
+            | TapState::ShiftDr | TapState::PauseDr
+            | TapState::ShiftIr | TapState::PauseIr)
+    }
+
+    /// Shortest TMS sequence leading from this state to target one.
+    /// return tuple of TMS bits (LSB is clocked first) and number of bits
+    pub fn path_to(self, target: TapState) -> (u8, usize) {
+        if self == target {
+            return (0, 0);
+        }
+        // breadth first search over 16 states, the longest path takes 8 steps
+        let mut visited = vec![self];
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back((self, 0u8, 0usize));
+        while let Some((state, bits, length)) = queue.pop_front() {
+            for &tms in &[false, true] {
+                let next = state.next(tms);
+                let next_bits = bits | ((tms as u8) << length);
+                if next == target {
+                    return (next_bits, length + 1);
+                }
+                if !visited.contains(&next) {
+                    visited.push(next);
+                    queue.push_back((next, next_bits, length + 1));
+                }
+            }
+        }
+        unreachable!("TAP state graph is strongly connected")
+    }
+}
+
+/// Known JEP106 manufacturers, as (bank number, id without parity bit, name)
+const JEP106_MANUFACTURERS: [(u8, u8, &str); 14] = [
+    (0, 0x01, "AMD"),
+    (0, 0x09, "Intel"),
+    (0, 0x0e, "Freescale (Motorola)"),
+    (0, 0x15, "NXP (Philips)"),
+    (0, 0x17, "Texas Instruments"),
+    (0, 0x1f, "Atmel"),
+    (0, 0x20, "STMicroelectronics"),
+    (0, 0x21, "Lattice Semiconductor"),
+    (0, 0x29, "Microchip Technology"),
+    (0, 0x34, "Cypress Semiconductor"),
+    (0, 0x41, "Infineon"),
+    (0, 0x49, "Xilinx"),
+    (0, 0x6e, "Altera"),
+    (4, 0x3b, "ARM Ltd"),
+];
+
+/// Manufacturer name of JEP106 code, bank is the number of continuation codes
+pub fn jep106_manufacturer(bank: u8, id: u8) -> Option<&'static str> {
+    JEP106_MANUFACTURERS.iter()
+        .find(|(known_bank, known_id, _)| *known_bank == bank && *known_id == id)
+        .map(|(_, _, name)| *name)
+}
+
+/// 32 bit JTAG IDCODE: version[31:28], part number[27:12], manufacturer[11:1], always one[0]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IdCode(pub u32);
+impl IdCode {
+    pub fn version(&self) -> u8 {
+        (self.0 >> 28) as u8
+    }
+    pub fn part_number(&self) -> u16 {
+        ((self.0 >> 12) & 0xffff) as u16
+    }
+    /// JEP106 bank (number of continuation codes)
+    pub fn manufacturer_bank(&self) -> u8 {
+        ((self.0 >> 8) & 0x0f) as u8
+    }
+    /// JEP106 identity code without parity bit
+    pub fn manufacturer_id(&self) -> u8 {
+        ((self.0 >> 1) & 0x7f) as u8
+    }
+    pub fn manufacturer(&self) -> Option<&'static str> {
+        jep106_manufacturer(self.manufacturer_bank(), self.manufacturer_id())
+    }
+}
+impl Display for IdCode {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:#010x} (manufacturer: {}, part: {:#06x}, version: {})",
+               self.0, self.manufacturer().unwrap_or("unknown"), self.part_number(), self.version())
+    }
+}
+
+/// Device found on scan chain, position 0 is the device nearest to TDO
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct JtagDevice {
+    pub position: usize,
+    /// None for devices without IDCODE register (BYPASS is selected after reset)
+    pub idcode: Option<IdCode>,
+}
+impl Display for JtagDevice {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.idcode {
+            Some(idcode) => write!(f, "TAP #{}: {}", self.position, idcode),
+            None => write!(f, "TAP #{}: no IDCODE (bypass)", self.position),
+        }
+    }
+}
+
+/// Get bit from LSB first bit vector
+pub(crate) fn get_bit(data: &[u8], index: usize) -> bool {
+    data[index / 8] & (1 << (index % 8)) != 0
+}
+
+/// Set bit in LSB first bit vector
+pub(crate) fn set_bit(data: &mut [u8], index: usize, value: bool) {
+    if value {
+        data[index / 8] |= 1 << (index % 8);
+    } else {
+        data[index / 8] &= !(1 << (index % 8));
+    }
+}
+
+/// JTAG master driving TAP controllers of scan chain through MPSSE
+pub struct JtagController<T: MpsseTransport> {
+    transport: T,
+    state: TapState,
+    /// set by init(), used by later clock changes
+    high_speed: bool,
+}
+impl<T: MpsseTransport> JtagController<T> {
+    /// Create controller, TAP state is unknown until reset() is called
+    pub fn new(transport: T) -> Self {
+        JtagController { transport, state: TapState::TestLogicReset, high_speed: false }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Current TAP state as tracked by controller
+    pub fn state(&self) -> TapState {
+        self.state
+    }
+
+    /// Setup MPSSE pins and TCK frequency and reset TAP.
+    /// MPSSE mode must be already enabled on the chip (ftdi_set_bitmode with BITMODE_MPSSE).
+    /// param high_speed should be true for H type chips
+    /// return actual TCK frequency
+    pub fn init(&mut self, frequency: u32, high_speed: bool) -> Result<u32> {
+        debug!("start 'jtag init' ...");
+        self.high_speed = high_speed;
+        let (mut commands, actual) = mpsse_clock_commands(frequency, high_speed);
+        commands.push(LOOPBACK_END);
+        // TMS high, TCK/TDI low; TCK, TDI, TMS are outputs
+        commands.extend_from_slice(&[SET_BITS_LOW, JTAG_PIN_TMS, JTAG_PIN_TCK | JTAG_PIN_TDI | JTAG_PIN_TMS]);
+        self.transport.mpsse_write(&commands)?;
+        self.reset()?;
+        info!("JTAG initialized, TCK = {} Hz", actual);
+        Ok(actual)
+    }
+
+    /// Change TCK frequency, chip type is the one given to init()
+    /// return actual TCK frequency
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<u32> {
+        let (commands, actual) = mpsse_clock_commands(frequency, self.high_speed);
+        self.transport.mpsse_write(&commands)?;
+        debug!("TCK = {} Hz", actual);
+        Ok(actual)
+    }
+
+    /// Move TAP to Test-Logic-Reset by five TMS high clocks, whatever state it was in
+    pub fn reset(&mut self) -> Result<()> {
+        let mut commands = Vec::with_capacity(3);
+        append_tms(&mut commands, 0x1f, 5, false);
+        self.transport.mpsse_write(&commands)?;
+        self.state = TapState::TestLogicReset;
+        Ok(())
+    }
+
+    /// Move TAP along the shortest path to target state
+    pub fn goto_state(&mut self, target: TapState) -> Result<()> {
+        let mut commands = Vec::new();
+        self.append_goto(&mut commands, target);
+        if !commands.is_empty() {
+            self.transport.mpsse_write(&commands)?;
+        }
+        Ok(())
+    }
+
+    /// Go to Run-Test/Idle and stay there for the number of TCK cycles
+    pub fn run_test(&mut self, cycles: u32) -> Result<()> {
+        self.wait_in_state(TapState::RunTestIdle, cycles)
+    }
+
+    /// Go to stable 'state' and stay there for the number of TCK cycles
+    pub fn wait_in_state(&mut self, state: TapState, cycles: u32) -> Result<()> {
+        if !state.is_stable() {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: format!("TAP can't wait in unstable state {:?}", state),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        let mut commands = Vec::new();
+        self.append_goto(&mut commands, state);
+        // TMS high keeps Test-Logic-Reset, TMS low keeps all the other stable states
+        let hold = if state == TapState::TestLogicReset { 0x7f } else { 0 };
+        let mut remaining = cycles as usize;
+        while remaining > 0 {
+            let count = std::cmp::min(remaining, MPSSE_MAX_TMS_BITS);
+            append_tms(&mut commands, hold, count, false);
+            remaining -= count;
+        }
+        if !commands.is_empty() {
+            self.transport.mpsse_write(&commands)?;
+        }
+        Ok(())
+    }
+
+    /// Shift 'bits' bits of 'tdi' (LSB first) through instruction register and return captured TDO bits.
+    /// TAP ends in 'end_state'.
+    pub fn shift_ir(&mut self, tdi: &[u8], bits: usize, end_state: TapState) -> Result<Vec<u8>> {
+        self.shift(TapState::ShiftIr, tdi, bits, end_state)
+    }
+
+    /// Shift 'bits' bits of 'tdi' (LSB first) through data register and return captured TDO bits.
+    /// TAP ends in 'end_state'.
+    pub fn shift_dr(&mut self, tdi: &[u8], bits: usize, end_state: TapState) -> Result<Vec<u8>> {
+        self.shift(TapState::ShiftDr, tdi, bits, end_state)
+    }
+
+    /// Detect devices on scan chain reading IDCODE registers selected by Test-Logic-Reset.
+    /// Devices without IDCODE have one bit BYPASS register selected that captures zero.
+    pub fn scan_chain(&mut self) -> Result<Vec<JtagDevice>> {
+        debug!("start 'scan_chain' ...");
+        self.reset()?;
+        // fill with ones, chain end is detected when the ones come back as 0xffffffff 'IDCODE'
+        let total_bits = (JTAG_MAX_DEVICES + 1) * 32;
+        let tdi = vec![0xffu8; total_bits / 8];
+        let tdo = self.shift_dr(&tdi, total_bits, TapState::RunTestIdle)?;
+        let mut devices = Vec::new();
+        let mut position = 0;
+        while position + 32 <= total_bits {
+            if !get_bit(&tdo, position) {
+                devices.push(JtagDevice { position: devices.len(), idcode: None });
+                position += 1;
+                continue;
+            }
+            let mut idcode = 0u32;
+            for bit in 0..32 {
+                if get_bit(&tdo, position + bit) {
+                    idcode |= 1 << bit;
+                }
+            }
+            if idcode == 0xffff_ffff {
+                debug!("'scan_chain' - OK, found {} device(s)", devices.len());
+                for device in &devices {
+                    info!("{}", device);
+                }
+                return Ok(devices);
+            }
+            devices.push(JtagDevice { position: devices.len(), idcode: Some(IdCode(idcode)) });
+            position += 32;
+        }
+        let error = FtdiContextError::UsbCommonError {
+            code: -2, message: format!("JTAG scan chain end not found, more than {} devices or TDO stuck", JTAG_MAX_DEVICES),
+            backtrace: GenerateBacktrace::generate()
+        };
+        error!("{}", error);
+        Err(error)
+    }
+
+    /// Total length of all instruction registers on the chain.
+    /// Flushes ones through IR, so every device ends up with BYPASS instruction.
+    pub fn ir_chain_length(&mut self) -> Result<usize> {
+        let total_bits = JTAG_MAX_IR_CHAIN_LENGTH * 2;
+        let mut tdi = vec![0xffu8; total_bits / 8];
+        // zeros fill the chain first, ones come out after exactly 'chain length' zeros
+        for byte in tdi.iter_mut().take(JTAG_MAX_IR_CHAIN_LENGTH / 8) {
+            *byte = 0;
+        }
+        let tdo = self.shift_ir(&tdi, total_bits, TapState::RunTestIdle)?;
+        for length in 0..JTAG_MAX_IR_CHAIN_LENGTH {
+            if get_bit(&tdo, JTAG_MAX_IR_CHAIN_LENGTH + length) {
+                debug!("IR chain length = {}", length);
+                return Ok(length);
+            }
+        }
+        let error = FtdiContextError::UsbCommonError {
+            code: -3, message: format!("JTAG IR chain is longer than {} bits or TDO stuck", JTAG_MAX_IR_CHAIN_LENGTH),
+            backtrace: GenerateBacktrace::generate()
+        };
+        error!("{}", error);
+        Err(error)
+    }
+
+    fn append_goto(&mut self, commands: &mut Vec<u8>, target: TapState) {
+        let (path, length) = self.state.path_to(target);
+        append_tms(commands, path, length, false);
+        self.state = target;
+    }
+
+    fn shift(&mut self, shift_state: TapState, tdi: &[u8], bits: usize, end_state: TapState) -> Result<Vec<u8>> {
+        if bits == 0 || tdi.len() * 8 < bits {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: format!("incorrect JTAG shift length {} for {} data bytes", bits, tdi.len()),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        let mut commands = Vec::with_capacity(bits / 8 + 16);
+        self.append_goto(&mut commands, shift_state);
+
+        // all bits but the last are shifted in Shift-xR, the last one together with TMS high
+        let body_bits = bits - 1;
+        let full_bytes = body_bits / 8;
+        let rest_bits = body_bits % 8;
+        let mut offset = 0;
+        while offset < full_bytes {
+            let count = std::cmp::min(full_bytes - offset, MPSSE_MAX_BYTES_PER_COMMAND);
+            commands.push(JTAG_RW_BYTES);
+            commands.push(((count - 1) & 0xff) as u8);
+            commands.push(((count - 1) >> 8) as u8);
+            commands.extend_from_slice(&tdi[offset..offset + count]);
+            offset += count;
+        }
+        if rest_bits > 0 {
+            commands.extend_from_slice(&[JTAG_RW_BITS, (rest_bits - 1) as u8, tdi[full_bytes]]);
+        }
+        let last_tdi = get_bit(tdi, bits - 1);
+        commands.extend_from_slice(&[JTAG_TMS_RW_BITS, 0, ((last_tdi as u8) << 7) | 0x01]);
+        self.state = shift_state.next(true);
+        self.append_goto(&mut commands, end_state);
+        commands.push(SEND_IMMEDIATE);
+        self.transport.mpsse_write(&commands)?;
+
+        let reply_size = full_bytes + (rest_bits > 0) as usize + 1;
+        let reply = self.transport.mpsse_read(reply_size)?;
+        let mut tdo = vec![0u8; bits.div_ceil(8)];
+        tdo[..full_bytes].copy_from_slice(&reply[..full_bytes]);
+        if rest_bits > 0 {
+            // bits are shifted in from MSB side
+            tdo[full_bytes] = reply[full_bytes] >> (8 - rest_bits);
+        }
+        set_bit(&mut tdo, bits - 1, reply[reply_size - 1] & 0x80 != 0);
+        Ok(tdo)
+    }
+}
+
+/// Append TMS commands clocking 'length' bits of 'tms' (LSB first), TDI is held at 'tdi' value
+fn append_tms(commands: &mut Vec<u8>, tms: u8, length: usize, tdi: bool) {
+    let mut done = 0;
+    while done < length {
+        let count = std::cmp::min(length - done, MPSSE_MAX_TMS_BITS);
+        let bits = (tms >> done) & ((1u8 << count) - 1);
+        commands.extend_from_slice(&[JTAG_TMS_BITS, (count - 1) as u8, ((tdi as u8) << 7) | bits]);
+        done += count;
+    }
+}
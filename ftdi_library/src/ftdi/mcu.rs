@@ -0,0 +1,214 @@
+#![allow(dead_code)]
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use crate::ftdi::constants::{*};
+use crate::ftdi::eeprom::ftdi_eeprom;
+use crate::ftdi::ftdi_context::{ftdi_context, Result, common_error};
+use crate::ftdi::mpsse::MpsseTransport;
+
+/// Bus accesses put into one USB write, their read replies fit chip receive buffer
+pub const MCU_BATCH_ACCESSES: usize = 256;
+
+/// Enable MCU host bus emulation mode, available on channel A of FT2232C/D and FT2232H/HP
+pub fn mcu_mode_enable(ftdi: &mut ftdi_context) -> Result<()> {
+    ftdi.capabilities()?.check_bitmode(ftdi_mpsse_mode::BITMODE_MCU)?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_MCU)
+}
+
+/// One cycle on 8051-style host bus. Short accesses drive 8-bit address on AD bus only,
+/// extended ones put the high address byte on A15:8 as well.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum McuAccess {
+    Read8(u8),
+    Read16(u16),
+    /// address, data
+    Write8(u8, u8),
+    Write16(u16, u8),
+}
+impl McuAccess {
+    fn is_read(self) -> bool {
+        matches!(self, McuAccess::Read8(_) | McuAccess::Read16(_))
+    }
+
+    fn command(self, commands: &mut Vec<u8>) {
+        match self {
+            McuAccess::Read8(address) => commands.extend_from_slice(&[READ_SHORT, address]),
+            McuAccess::Read16(address) => commands.extend_from_slice(&[READ_EXTENDED, (address >> 8) as u8, address as u8]),
+            McuAccess::Write8(address, data) => commands.extend_from_slice(&[WRITE_SHORT, address, data]),
+            McuAccess::Write16(address, data) =>
+                commands.extend_from_slice(&[WRITE_EXTENDED, (address >> 8) as u8, address as u8, data]),
+        }
+    }
+}
+
+/// MCU host bus master. Chip must be in MCU mode (see mcu_mode_enable).
+pub struct McuBus<T: MpsseTransport> {
+    transport: T,
+}
+impl<T: MpsseTransport> McuBus<T> {
+    pub fn new(transport: T) -> Self {
+        McuBus { transport }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub fn read8(&mut self, address: u8) -> Result<u8> {
+        Ok(self.execute(&[McuAccess::Read8(address)])?[0])
+    }
+
+    pub fn read16(&mut self, address: u16) -> Result<u8> {
+        Ok(self.execute(&[McuAccess::Read16(address)])?[0])
+    }
+
+    pub fn write8(&mut self, address: u8, data: u8) -> Result<()> {
+        self.execute(&[McuAccess::Write8(address, data)]).map(|_| ())
+    }
+
+    pub fn write16(&mut self, address: u16, data: u8) -> Result<()> {
+        self.execute(&[McuAccess::Write16(address, data)]).map(|_| ())
+    }
+
+    /// Read 'size' consecutive registers starting at 8-bit address
+    pub fn read_block8(&mut self, start: u8, size: usize) -> Result<Vec<u8>> {
+        Self::check_range(start as usize, size, 0x100)?;
+        let accesses: Vec<McuAccess> = (0..size).map(|offset| McuAccess::Read8(start + offset as u8)).collect();
+        self.execute(&accesses)
+    }
+
+    /// Read 'size' consecutive registers starting at 16-bit address
+    pub fn read_block16(&mut self, start: u16, size: usize) -> Result<Vec<u8>> {
+        Self::check_range(start as usize, size, 0x10000)?;
+        let accesses: Vec<McuAccess> = (0..size).map(|offset| McuAccess::Read16(start + offset as u16)).collect();
+        self.execute(&accesses)
+    }
+
+    /// Write consecutive registers starting at 8-bit address
+    pub fn write_block8(&mut self, start: u8, data: &[u8]) -> Result<()> {
+        Self::check_range(start as usize, data.len(), 0x100)?;
+        let accesses: Vec<McuAccess> = data.iter().enumerate()
+            .map(|(offset, byte)| McuAccess::Write8(start + offset as u8, *byte)).collect();
+        self.execute(&accesses).map(|_| ())
+    }
+
+    /// Write consecutive registers starting at 16-bit address
+    pub fn write_block16(&mut self, start: u16, data: &[u8]) -> Result<()> {
+        Self::check_range(start as usize, data.len(), 0x10000)?;
+        let accesses: Vec<McuAccess> = data.iter().enumerate()
+            .map(|(offset, byte)| McuAccess::Write16(start + offset as u16, *byte)).collect();
+        self.execute(&accesses).map(|_| ())
+    }
+
+    /// Run bus cycles in order, up to MCU_BATCH_ACCESSES of them per USB transfer
+    /// return data of read cycles in order
+    pub fn execute(&mut self, accesses: &[McuAccess]) -> Result<Vec<u8>> {
+        debug!("start 'mcu execute' accesses = {} ...", accesses.len());
+        let mut replies = Vec::new();
+        for batch in accesses.chunks(MCU_BATCH_ACCESSES) {
+            let mut commands = Vec::with_capacity(batch.len() * 4 + 1);
+            let mut reads = 0;
+            for access in batch {
+                access.command(&mut commands);
+                if access.is_read() {
+                    reads += 1;
+                }
+            }
+            if reads > 0 {
+                commands.push(SEND_IMMEDIATE);
+            }
+            self.transport.mpsse_write(&commands)?;
+            if reads > 0 {
+                replies.extend(self.transport.mpsse_read(reads)?);
+            }
+        }
+        debug!("'mcu execute' - OK, read = {}", replies.len());
+        Ok(replies)
+    }
+
+    /// Stop executing commands until IO1 pin is high or low
+    pub fn wait_io(&mut self, high: bool) -> Result<()> {
+        self.transport.mpsse_write(&[if high { WAIT_ON_HIGH } else { WAIT_ON_LOW }])
+    }
+
+    fn check_range(start: usize, size: usize, limit: usize) -> Result<()> {
+        if start + size > limit {
+            return Err(common_error(-1, format!("registers {:#x}..{:#x} are out of address space", start, start + size)));
+        }
+        Ok(())
+    }
+}
+
+/// FT1284 interface options of FT232H, they're stored in EEPROM
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ft1284Config {
+    /// SCLK idles high
+    pub clock_idle_high: bool,
+    /// Data is shifted LSB first
+    pub lsb_first: bool,
+    /// Flow control on MIOSIO0
+    pub flow_control: bool,
+}
+impl Ft1284Config {
+    /// EEPROM option byte, FT1284_xxx bits
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.clock_idle_high {
+            flags |= FT1284_CLK_IDLE_STATE;
+        }
+        if self.lsb_first {
+            flags |= FT1284_DATA_LSB;
+        }
+        if self.flow_control {
+            flags |= FT1284_FLOW_CONTROL;
+        }
+        flags
+    }
+
+    pub fn from_flags(flags: u8) -> Self {
+        Ft1284Config {
+            clock_idle_high: flags & FT1284_CLK_IDLE_STATE != 0,
+            lsb_first: flags & FT1284_DATA_LSB != 0,
+            flow_control: flags & FT1284_FLOW_CONTROL != 0,
+        }
+    }
+
+    /// Options from decoded EEPROM, None if channel isn't configured as FT1284
+    pub fn from_eeprom(eeprom: &ftdi_eeprom) -> Option<Self> {
+        if eeprom.channel_a_type != CHANNEL_IS_FT1284 as i32 {
+            return None;
+        }
+        Some(Ft1284Config {
+            clock_idle_high: eeprom.clock_polarity != 0,
+            lsb_first: eeprom.data_order != 0,
+            flow_control: eeprom.flow_control != 0,
+        })
+    }
+
+    /// Configure channel as FT1284 in EEPROM structure, it has to be built and written to take effect
+    pub fn apply(&self, eeprom: &mut ftdi_eeprom) {
+        eeprom.channel_a_type = CHANNEL_IS_FT1284 as i32;
+        eeprom.clock_polarity = self.clock_idle_high as i32;
+        eeprom.data_order = self.lsb_first as i32;
+        eeprom.flow_control = self.flow_control as i32;
+    }
+}
+
+/// Enable FT1284 mode of FT232H configured as FT1284 in EEPROM
+/// return options stored in EEPROM
+pub fn ft1284_mode_enable(ftdi: &mut ftdi_context) -> Result<Ft1284Config> {
+    ftdi.capabilities()?.check_bitmode(ftdi_mpsse_mode::BITMODE_FT1284)?;
+    ftdi.ftdi_read_eeprom()?;
+    ftdi.ftdi_eeprom_decode()?;
+    let config = Ft1284Config::from_eeprom(&ftdi.eeprom).ok_or_else(|| common_error(-1, format!(
+        "channel is configured as {:#x} in EEPROM, FT1284 ({:#x}) is required", ftdi.eeprom.channel_a_type, CHANNEL_IS_FT1284)))?;
+    ftdi.ftdi_set_bitmode(0, ftdi_mpsse_mode::BITMODE_FT1284)?;
+    info!("FT1284 mode enabled, {:?}", config);
+    Ok(config)
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+use log::{debug, info};
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_clock_commands};
+
+/// ADBUS pins used by Microwire: SK (0), DI of the device (1), DO of the device (2), CS (3, active high)
+pub const MICROWIRE_PIN_SK: u8 = 0x01;
+pub const MICROWIRE_PIN_DI: u8 = 0x02;
+pub const MICROWIRE_PIN_DO: u8 = 0x04;
+pub const MICROWIRE_PIN_CS: u8 = 0x08;
+
+/// Longest command (start bit, opcode, address and data) shifted out at once
+pub const MICROWIRE_MAX_COMMAND_BITS: u8 = 32;
+
+/// Microwire master on MPSSE. Device latches DI on rising SK edge and changes DO after it,
+/// so DO is sampled on the falling edge.
+pub struct MicrowireMaster<T: MpsseTransport> {
+    transport: T,
+}
+impl<T: MpsseTransport> MicrowireMaster<T> {
+    pub fn new(transport: T) -> Self {
+        MicrowireMaster { transport }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Setup MPSSE pins and SK frequency, chip select is left inactive.
+    /// MPSSE mode must be already enabled on the chip (ftdi_set_bitmode with BITMODE_MPSSE).
+    /// return actual SK frequency
+    pub fn init(&mut self, frequency: u32, high_speed: bool) -> Result<u32> {
+        debug!("start 'microwire init' ...");
+        let (mut commands, actual) = mpsse_clock_commands(frequency, high_speed);
+        commands.push(LOOPBACK_END);
+        commands.extend_from_slice(&Self::pins(false));
+        self.transport.mpsse_write(&commands)?;
+        info!("Microwire initialized, SK = {} Hz", actual);
+        Ok(actual)
+    }
+
+    /// Shift out 'bits' lowest bits of 'command' (MSB first) in one chip select cycle
+    pub fn write(&mut self, command: u32, bits: u8) -> Result<()> {
+        self.transaction(command, bits, 0).map(|_| ())
+    }
+
+    /// Shift out 'bits' lowest bits of 'command', then clock in 'read_size' bytes in one chip select cycle
+    pub fn transaction(&mut self, command: u32, bits: u8, read_size: usize) -> Result<Vec<u8>> {
+        if bits == 0 || bits > MICROWIRE_MAX_COMMAND_BITS {
+            return Err(common_error(-1, format!("Microwire command of {} bits", bits)));
+        }
+        let mut commands = Vec::with_capacity(32);
+        commands.extend_from_slice(&Self::pins(true));
+        // shift out head of the command not filling a whole byte first, then byte by byte
+        let mut remaining = bits;
+        while remaining > 0 {
+            let count = (remaining - 1) % 8 + 1;
+            remaining -= count;
+            let chunk = (command >> remaining) as u8 & (0xff >> (8 - count));
+            commands.extend_from_slice(&[MPSSE_DO_WRITE | MPSSE_WRITE_NEG | MPSSE_BITMODE, count - 1, chunk << (8 - count)]);
+        }
+        let mut left = read_size;
+        while left > 0 {
+            let count = std::cmp::min(left, 0x10000);
+            commands.extend_from_slice(&[MPSSE_DO_READ | MPSSE_READ_NEG, ((count - 1) & 0xff) as u8, ((count - 1) >> 8) as u8]);
+            left -= count;
+        }
+        commands.extend_from_slice(&Self::pins(false));
+        if read_size > 0 {
+            commands.push(SEND_IMMEDIATE);
+        }
+        self.transport.mpsse_write(&commands)?;
+        if read_size == 0 {
+            return Ok(Vec::new());
+        }
+        self.transport.mpsse_read(read_size)
+    }
+
+    /// Select the device and poll DO until it goes high (programming finished)
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let mut commands = Self::pins(true).to_vec();
+        commands.extend_from_slice(&[GET_BITS_LOW, SEND_IMMEDIATE]);
+        let result = loop {
+            self.transport.mpsse_write(&commands)?;
+            if self.transport.mpsse_read(1)?[0] & MICROWIRE_PIN_DO != 0 {
+                break Ok(());
+            }
+            if start.elapsed() > timeout {
+                break Err(common_error(-4, format!("Microwire device is still busy after {:?}", timeout)));
+            }
+        };
+        self.transport.mpsse_write(&Self::pins(false))?;
+        result
+    }
+
+    /// Low byte pins setup with chip select active or not, SK idles low
+    fn pins(selected: bool) -> [u8; 3] {
+        let value = if selected { MICROWIRE_PIN_CS } else { 0 };
+        [SET_BITS_LOW, value, MICROWIRE_PIN_SK | MICROWIRE_PIN_DI | MICROWIRE_PIN_CS]
+    }
+}
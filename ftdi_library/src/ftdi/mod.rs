@@ -1,8 +1,26 @@
-
+pub mod baud;
+pub mod bitbang;
+pub mod cbus;
+pub mod chip;
 pub mod constants;
 pub mod core;
 pub mod eeprom;
+pub mod eeprom_config;
 pub mod ftdi_context;
 pub mod ftdi_device_list;
 pub mod ftdi_version_info;
-
+pub mod gpio;
+pub mod i2c;
+pub mod jtag;
+pub mod mcu;
+pub mod microwire;
+pub mod mpsse;
+pub mod opto;
+pub mod serial;
+pub mod serial_eeprom;
+pub mod spi;
+pub mod spi_flash;
+pub mod svf;
+pub mod swd;
+pub mod sync_fifo;
+pub mod xsvf;
@@ -0,0 +1,146 @@
+#![allow(dead_code)]
+
+use log::{debug, error};
+use snafu::GenerateBacktrace;
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{ftdi_context, FtdiContextError, Result};
+
+/// How many empty reads in a row are accepted before MPSSE read is treated as timed out
+pub const MPSSE_READ_RETRIES: u32 = 16;
+/// Base clock of the MPSSE engine on H type chips with divide by 5 disabled
+pub const MPSSE_H_CLOCK: u32 = 60_000_000;
+/// Base clock of the MPSSE engine on FT2232C/D and H type chips with divide by 5 enabled
+pub const MPSSE_C_CLOCK: u32 = 12_000_000;
+
+/// Byte channel used by protocol engines (JTAG, SWD, SPI...) to talk to the MPSSE.
+/// It's implemented by ftdi_context and can be implemented by a simulator in tests.
+pub trait MpsseTransport {
+    /// Send raw MPSSE commands to the chip.
+    fn mpsse_write(&mut self, commands: &[u8]) -> Result<()>;
+    /// Read exactly 'size' bytes of MPSSE command results.
+    fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>>;
+}
+
+impl MpsseTransport for ftdi_context {
+    fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+        write_all(self, commands, "MPSSE")
+    }
+
+    fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+        read_exact(self, size, "MPSSE")
+    }
+}
+
+/// Write the whole buffer, 'mode' names chip mode in error messages
+pub(crate) fn write_all(ftdi: &ftdi_context, data: &[u8], mode: &str) -> Result<()> {
+    let mut buffer = data.to_vec();
+    let written = ftdi.ftdi_write_data(&mut buffer, data.len() as u32)?;
+    if written < data.len() {
+        let error = FtdiContextError::UsbCommonError {
+            code: -1, message: format!("{} short write, {} of {} bytes", mode, written, data.len()),
+            backtrace: GenerateBacktrace::generate()
+        };
+        error!("{}", error);
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Read exactly 'size' bytes, 'mode' names chip mode in error messages
+pub(crate) fn read_exact(ftdi: &mut ftdi_context, size: usize, mode: &str) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(size);
+    let mut chunk = Vec::with_capacity(size);
+    let mut empty_reads = 0;
+    while result.len() < size {
+        let read = ftdi.ftdi_read_data(&mut chunk, size - result.len())?;
+        if read == 0 {
+            empty_reads += 1;
+            if empty_reads > MPSSE_READ_RETRIES {
+                let error = FtdiContextError::UsbCommonError {
+                    code: -2, message: format!("{} read timeout, {} of {} bytes", mode, result.len(), size),
+                    backtrace: GenerateBacktrace::generate()
+                };
+                error!("{}", error);
+                return Err(error);
+            }
+            continue;
+        }
+        empty_reads = 0;
+        result.extend_from_slice(&chunk);
+    }
+    Ok(result)
+}
+
+/// Calculate TCK/SK divisor for requested frequency.
+/// H type chips run MPSSE from 60 MHz (divide by 5 disabled), the others from 12 MHz.
+/// return tuple of divisor value and actually achieved frequency
+pub fn mpsse_clock_divisor(frequency: u32, high_speed: bool) -> (u16, u32) {
+    let base_clock = if high_speed { MPSSE_H_CLOCK } else { MPSSE_C_CLOCK };
+    // frequency = base / ((1 + divisor) * 2), round the divisor up to never exceed requested value
+    let divisor = if frequency == 0 || frequency >= base_clock / 2 {
+        0
+    } else {
+        let half_base = base_clock / 2;
+        let divisor = half_base.div_ceil(frequency) - 1;
+        std::cmp::min(divisor, 0xffff)
+    };
+    (divisor as u16, base_clock / ((1 + divisor) * 2))
+}
+
+/// MPSSE commands to setup clock for requested frequency
+/// return tuple of commands and actually achieved frequency
+pub fn mpsse_clock_commands(frequency: u32, high_speed: bool) -> (Vec<u8>, u32) {
+    let (divisor, actual) = mpsse_clock_divisor(frequency, high_speed);
+    let mut commands = Vec::with_capacity(4);
+    if high_speed {
+        commands.push(DIS_DIV_5);
+    }
+    commands.extend_from_slice(&[TCK_DIVISOR, (divisor & 0xff) as u8, (divisor >> 8) as u8]);
+    debug!("MPSSE clock divisor = {}, frequency = {} Hz", divisor, actual);
+    (commands, actual)
+}
+
+/// Check MPSSE engine is in sync: an invalid command must be echoed back as 'bad command' reply
+pub fn mpsse_synchronize<T: MpsseTransport>(transport: &mut T) -> Result<()> {
+    debug!("start 'mpsse_synchronize' ...");
+    const INVALID_COMMAND: u8 = 0xaa;
+    transport.mpsse_write(&[INVALID_COMMAND, SEND_IMMEDIATE])?;
+    let reply = transport.mpsse_read(2)?;
+    if reply[0] != MPSSE_BAD_COMMAND || reply[1] != INVALID_COMMAND {
+        let error = FtdiContextError::UsbCommonError {
+            code: -3, message: format!("MPSSE sync failed, reply = {:02x?}", reply),
+            backtrace: GenerateBacktrace::generate()
+        };
+        error!("{}", error);
+        return Err(error);
+    }
+    debug!("'mpsse_synchronize' - OK");
+    Ok(())
+}
+
+/// Length of the MPSSE command at the start of 'commands' with its parameters and data,
+/// None when the command is truncated. Unknown opcodes are one byte long, the chip answers them with bad command reply.
+pub fn mpsse_command_length(commands: &[u8]) -> Option<usize> {
+    let opcode = *commands.first()?;
+    let length = if opcode & 0x80 == 0 {
+        // data shifting commands, TMS ones are always clocked by bits
+        if opcode & (MPSSE_BITMODE | MPSSE_WRITE_TMS) != 0 {
+            if opcode & (MPSSE_DO_WRITE | MPSSE_WRITE_TMS) != 0 { 3 } else { 2 }
+        } else {
+            if commands.len() < 3 {
+                return None;
+            }
+            let count = commands[1] as usize + ((commands[2] as usize) << 8) + 1;
+            if opcode & MPSSE_DO_WRITE != 0 { 3 + count } else { 3 }
+        }
+    } else {
+        match opcode {
+            SET_BITS_LOW | SET_BITS_HIGH | TCK_DIVISOR | CLK_BYTES | CLK_BYTES_OR_HIGH | CLK_BYTES_OR_LOW
+            | DRIVE_OPEN_COLLECTOR | READ_EXTENDED | WRITE_SHORT => 3,
+            CLK_BITS | READ_SHORT => 2,
+            WRITE_EXTENDED => 4,
+            _ => 1,
+        }
+    };
+    if commands.len() < length { None } else { Some(length) }
+}
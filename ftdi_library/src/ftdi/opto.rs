@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+
+use std::io;
+use log::{debug, info};
+use crate::ftdi::chip::ChipCapabilities;
+use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface, ftdi_mpsse_mode, CHANNEL_IS_OPTO};
+use crate::ftdi::ftdi_context::{ftdi_context, Result, common_error};
+use crate::ftdi::mpsse::write_all;
+
+/// How many empty reads in a row are accepted before fast serial read is treated as timed out
+pub const FAST_SERIAL_READ_RETRIES: u32 = 16;
+
+/// Channel a fast serial frame comes from or goes to, carried by the last frame bit
+/// (after start bit and 8 data bits). The interface itself is on channel B.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OptoPort {
+    /// Port bit 0
+    A,
+    /// Port bit 1
+    B,
+}
+impl OptoPort {
+    /// Port served by context opened on 'interface' (0 - A, 1 - B)
+    pub fn from_interface(interface: u8) -> Result<Self> {
+        match interface {
+            0 => Ok(OptoPort::A),
+            1 => Ok(OptoPort::B),
+            _ => Err(common_error(-1, format!("interface {} has no fast serial port", interface))),
+        }
+    }
+}
+
+/// Chip functions used by fast serial session
+pub trait OptoTransport {
+    fn chip_type(&self) -> ftdi_chip_type;
+    /// EEPROM hardware type of the opened channel, CHANNEL_IS_xxx
+    fn channel_type(&self) -> i32;
+    /// Interface the transport is opened on, 0 - A, 1 - B
+    fn interface(&self) -> u8;
+    fn set_bitmode(&mut self, mask: u8, mode: ftdi_mpsse_mode) -> Result<()>;
+    /// Write all bytes
+    fn write_data(&mut self, data: &[u8]) -> Result<()>;
+    /// Read bytes available now, up to buffer size
+    /// return number of bytes read, 0 if nothing came in
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<usize>;
+}
+
+impl OptoTransport for ftdi_context {
+    fn chip_type(&self) -> ftdi_chip_type {
+        self.r#type
+    }
+
+    fn channel_type(&self) -> i32 {
+        if self.interface == 1 { self.eeprom.channel_b_type } else { self.eeprom.channel_a_type }
+    }
+
+    fn interface(&self) -> u8 {
+        self.interface
+    }
+
+    fn set_bitmode(&mut self, mask: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+        self.ftdi_set_bitmode(mask, mode)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        write_all(self, data, "fast serial")
+    }
+
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let mut chunk = Vec::with_capacity(buffer.len());
+        let read = self.ftdi_read_data(&mut chunk, buffer.len())?;
+        buffer[..read].copy_from_slice(&chunk[..read]);
+        Ok(read)
+    }
+}
+
+/// Fast opto-isolated serial interface of FT2232C/D and FT2232H, opened on channel B.
+/// The chip adds start and port bits to written bytes and strips them from received frames,
+/// so data is passed as it is.
+pub struct FastSerial<T: OptoTransport> {
+    transport: T,
+    port: OptoPort,
+}
+impl<T: OptoTransport> FastSerial<T> {
+    /// Check chip type and EEPROM channel type, enable fast serial mode (channel B only)
+    pub fn open(mut transport: T) -> Result<Self> {
+        debug!("start 'fast serial open' ...");
+        let interface = ftdi_interface::from(transport.interface() + 1);
+        ChipCapabilities::new(transport.chip_type(), interface)?.check_bitmode(ftdi_mpsse_mode::BITMODE_OPTO)?;
+        let channel_type = transport.channel_type();
+        if channel_type != CHANNEL_IS_OPTO as i32 {
+            return Err(common_error(-1, format!(
+                "channel is configured as {:#x} in EEPROM, fast serial ({:#x}) is required", channel_type, CHANNEL_IS_OPTO)));
+        }
+        // capabilities allow fast serial on channel B only
+        let port = OptoPort::from_interface(transport.interface())?;
+        transport.set_bitmode(0, ftdi_mpsse_mode::BITMODE_OPTO)?;
+        info!("fast serial opened, port {:?}", port);
+        Ok(FastSerial { transport, port })
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Port bit put into frames sent from this channel
+    pub fn port(&self) -> OptoPort {
+        self.port
+    }
+
+    /// Leave fast serial mode and return the transport
+    pub fn close(mut self) -> Result<T> {
+        self.transport.set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+        Ok(self.transport)
+    }
+
+    /// Send bytes to the far side port of this channel
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.transport.write_data(data)
+    }
+
+    /// Read received bytes, waits for at least one byte
+    /// return number of bytes put to 'buffer'
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        for _ in 0..=FAST_SERIAL_READ_RETRIES {
+            let read = self.transport.read_data(buffer)?;
+            if read > 0 {
+                return Ok(read);
+            }
+        }
+        Err(common_error(-2, "fast serial read timeout".to_string()))
+    }
+}
+impl FastSerial<ftdi_context> {
+    /// Open fast serial session using channel type read from EEPROM
+    pub fn from_context(mut ftdi: ftdi_context) -> Result<Self> {
+        ftdi.ftdi_read_eeprom()?;
+        ftdi.ftdi_eeprom_decode()?;
+        FastSerial::open(ftdi)
+    }
+}
+impl<T: OptoTransport> io::Read for FastSerial<T> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        FastSerial::read(self, buffer).map_err(io::Error::from)
+    }
+}
+impl<T: OptoTransport> io::Write for FastSerial<T> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        FastSerial::write(self, data).map_err(io::Error::from)?;
+        Ok(data.len())
+    }
+
+    /// Data is handed to the chip by write already
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
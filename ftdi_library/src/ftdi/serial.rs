@@ -0,0 +1,203 @@
+#![allow(dead_code)]
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use crate::ftdi::baud::{compute_baud_divisor, BaudPolicy};
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{ftdi_context, Result, common_error};
+
+/// Flow control handshake
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FlowControl {
+    Disabled,
+    RtsCts,
+    DtrDsr,
+    XonXoff,
+}
+impl FlowControl {
+    /// Value for ftdi_setflowctrl()
+    pub fn value(self) -> u16 {
+        match self {
+            FlowControl::Disabled => SIO_DISABLE_FLOW_CTRL,
+            FlowControl::RtsCts => SIO_RTS_CTS_HS,
+            FlowControl::DtrDsr => SIO_DTR_DSR_HS,
+            FlowControl::XonXoff => SIO_XON_XOFF_HS,
+        }
+    }
+}
+
+mod baud_policy_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use crate::ftdi::baud::BaudPolicy;
+
+    pub fn serialize<S: Serializer>(policy: &BaudPolicy, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&policy.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BaudPolicy, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// UART settings of an opened channel, missing values in configuration file are the defaults (115200 8N1)
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SerialConfig {
+    pub baudrate: u32,
+    /// "5%", "best_effort" or "exact" in configuration file
+    #[serde(with = "baud_policy_serde")]
+    pub baud_policy: BaudPolicy,
+    /// 7 or 8
+    pub data_bits: u8,
+    pub parity: ftdi_parity_type,
+    pub stop_bits: ftdi_stopbits_type,
+    pub flow_control: FlowControl,
+    /// Latency timer, ms, 1..255
+    pub latency_timer: u8,
+    /// USB read timeout, ms
+    pub read_timeout: i32,
+    /// USB write timeout, ms
+    pub write_timeout: i32,
+    /// Character making the chip send received data at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_char: Option<u8>,
+}
+impl Default for SerialConfig {
+    fn default() -> Self {
+        SerialConfig {
+            baudrate: 115200,
+            baud_policy: BaudPolicy::default(),
+            data_bits: 8,
+            parity: ftdi_parity_type::NONE,
+            stop_bits: ftdi_stopbits_type::STOP_BIT_1,
+            flow_control: FlowControl::Disabled,
+            latency_timer: 16,
+            read_timeout: 5000,
+            write_timeout: 5000,
+            event_char: None,
+        }
+    }
+}
+impl SerialConfig {
+    pub fn builder() -> SerialConfigBuilder {
+        SerialConfigBuilder { config: SerialConfig::default() }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: SerialConfig = toml::from_str(text).map_err(|error| common_error(-1, format!("serial configuration: {}", error)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|error| common_error(-1, format!("serial configuration: {}", error)))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.baudrate == 0 {
+            return Err(common_error(-2, "baud rate 0 is incorrect".to_string()));
+        }
+        if self.data_bits != 7 && self.data_bits != 8 {
+            return Err(common_error(-3, format!("{} data bits aren't supported, 7 or 8 are", self.data_bits)));
+        }
+        if self.latency_timer == 0 {
+            return Err(common_error(-4, "latency timer is 1..255 ms".to_string()));
+        }
+        if self.read_timeout < 0 || self.write_timeout < 0 {
+            return Err(common_error(-5, "timeouts can't be negative".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Set up opened channel, return the applied settings, see applied()
+    pub fn apply(&self, ftdi: &mut ftdi_context) -> Result<SerialConfig> {
+        debug!("start 'SerialConfig::apply' {:?} ...", self);
+        self.validate()?;
+        ftdi.usb_read_timeout = self.read_timeout;
+        ftdi.usb_write_timeout = self.write_timeout;
+        ftdi.ftdi_set_baud_policy(self.baud_policy);
+        ftdi.ftdi_set_baudrate(self.baudrate as i32)?;
+        ftdi.ftdi_set_line_property(ftdi_bits_type::from(self.data_bits), self.stop_bits, self.parity)?;
+        ftdi.ftdi_setflowctrl(self.flow_control.value())?;
+        ftdi.ftdi_set_latency_timer(self.latency_timer)?;
+        ftdi.ftdi_set_event_char(self.event_char.unwrap_or(0), self.event_char.is_some())?;
+        let applied = SerialConfig::applied(ftdi, self)?;
+        debug!("'SerialConfig::apply' - OK, {:?}", applied);
+        Ok(applied)
+    }
+
+    /// Settings sent to the channel. Only the latency timer is read back from the chip, it can't report
+    /// the others: baud rate is the one the divisor achieves, line properties are cached by the context,
+    /// flow control and event character are taken from 'requested'.
+    fn applied(ftdi: &ftdi_context, requested: &SerialConfig) -> Result<SerialConfig> {
+        let solution = compute_baud_divisor(ftdi.r#type, ftdi_interface::from(ftdi.interface + 1), ftdi.baudrate as u32)?;
+        Ok(SerialConfig {
+            baudrate: solution.achieved,
+            data_bits: ftdi.line_property.bits as u8,
+            parity: ftdi.line_property.parity,
+            stop_bits: ftdi.line_property.stopbits,
+            latency_timer: ftdi.ftdi_get_latency_timer()?,
+            read_timeout: ftdi.usb_read_timeout,
+            write_timeout: ftdi.usb_write_timeout,
+            ..*requested
+        })
+    }
+}
+
+/// SerialConfig built field by field, checked in build()
+#[derive(Clone, Debug)]
+pub struct SerialConfigBuilder {
+    config: SerialConfig,
+}
+impl SerialConfigBuilder {
+    pub fn baudrate(mut self, baudrate: u32) -> Self {
+        self.config.baudrate = baudrate;
+        self
+    }
+
+    pub fn baud_policy(mut self, baud_policy: BaudPolicy) -> Self {
+        self.config.baud_policy = baud_policy;
+        self
+    }
+
+    pub fn data_bits(mut self, data_bits: u8) -> Self {
+        self.config.data_bits = data_bits;
+        self
+    }
+
+    pub fn parity(mut self, parity: ftdi_parity_type) -> Self {
+        self.config.parity = parity;
+        self
+    }
+
+    pub fn stop_bits(mut self, stop_bits: ftdi_stopbits_type) -> Self {
+        self.config.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.config.flow_control = flow_control;
+        self
+    }
+
+    pub fn latency_timer(mut self, latency_timer: u8) -> Self {
+        self.config.latency_timer = latency_timer;
+        self
+    }
+
+    pub fn timeouts(mut self, read_timeout: i32, write_timeout: i32) -> Self {
+        self.config.read_timeout = read_timeout;
+        self.config.write_timeout = write_timeout;
+        self
+    }
+
+    pub fn event_char(mut self, event_char: Option<u8>) -> Self {
+        self.config.event_char = event_char;
+        self
+    }
+
+    pub fn build(self) -> Result<SerialConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
@@ -0,0 +1,500 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+use log::{debug, info};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::i2c::{I2cMaster, I2C_MAX_CHUNK};
+use crate::ftdi::microwire::MicrowireMaster;
+use crate::ftdi::mpsse::MpsseTransport;
+use crate::ftdi::spi::SpiMaster;
+
+/// 24Cxx device address without chip select (A2..A0) and block bits
+pub const I2C_EEPROM_BASE_ADDRESS: u8 = 0x50;
+/// Bytes compared by 24Cxx address width detection
+pub const I2C_EEPROM_DETECT_LENGTH: usize = 16;
+
+/// 25xx SPI EEPROM commands
+pub const SPI_EEPROM_READ: u8 = 0x03;
+pub const SPI_EEPROM_WRITE: u8 = 0x02;
+pub const SPI_EEPROM_WRITE_ENABLE: u8 = 0x06;
+pub const SPI_EEPROM_WRITE_DISABLE: u8 = 0x04;
+pub const SPI_EEPROM_READ_STATUS: u8 = 0x05;
+pub const SPI_EEPROM_WRITE_STATUS: u8 = 0x01;
+/// Address bit 8 of 4 Kbit parts with one address byte goes into the opcode
+pub const SPI_EEPROM_A8_BIT: u8 = 0x08;
+
+/// 25xx status register: write in progress
+pub const SPI_EEPROM_STATUS_WIP: u8 = 0x01;
+/// 25xx status register: write enable latch
+pub const SPI_EEPROM_STATUS_WEL: u8 = 0x02;
+/// 25xx status register: block protect bits
+pub const SPI_EEPROM_STATUS_BP0: u8 = 0x04;
+pub const SPI_EEPROM_STATUS_BP1: u8 = 0x08;
+/// 25xx status register: WP pin enables status register protection
+pub const SPI_EEPROM_STATUS_WPEN: u8 = 0x80;
+
+/// 93Cxx commands, start bit included, shifted before the address
+pub const MICROWIRE_EEPROM_READ: u32 = 0b110;
+pub const MICROWIRE_EEPROM_WRITE: u32 = 0b101;
+pub const MICROWIRE_EEPROM_ERASE: u32 = 0b111;
+/// 93Cxx commands sharing 0b100 opcode, told apart by two highest address bits
+pub const MICROWIRE_EEPROM_EXTENDED: u32 = 0b100;
+pub const MICROWIRE_EEPROM_EWDS: u32 = 0b00;
+pub const MICROWIRE_EEPROM_WRAL: u32 = 0b01;
+pub const MICROWIRE_EEPROM_ERAL: u32 = 0b10;
+pub const MICROWIRE_EEPROM_EWEN: u32 = 0b11;
+
+/// Longest write cycle of supported parts with a good margin
+pub const SERIAL_EEPROM_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Bytes read by one SPI or Microwire transaction
+pub const SERIAL_EEPROM_READ_CHUNK: usize = 0x1000;
+
+/// Bus and addressing of serial EEPROM
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EepromBus {
+    /// 24Cxx: address bytes after device address, higher address bits go into device address
+    I2c { address_bytes: u8 },
+    /// 25xx: address bytes after opcode
+    Spi { address_bytes: u8 },
+    /// 93Cxx: address bits after opcode and word size in bytes (ORG pin: 1 - x8, 2 - x16)
+    Microwire { address_bits: u8, word_size: u8 },
+}
+
+/// Serial EEPROM part description
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EepromProfile {
+    /// Name without vendor letters, e.g. 24LC02B and AT24C02 are "24c02", M95040 and 25AA040 are "25c040"
+    pub name: &'static str,
+    pub bus: EepromBus,
+    /// Size in bytes
+    pub size: u32,
+    /// Bytes written by one write cycle, word size for Microwire parts
+    pub page_size: u32,
+}
+
+const fn i2c_profile(name: &'static str, size: u32, page_size: u32, address_bytes: u8) -> EepromProfile {
+    EepromProfile { name, bus: EepromBus::I2c { address_bytes }, size, page_size }
+}
+
+const fn spi_profile(name: &'static str, size: u32, page_size: u32, address_bytes: u8) -> EepromProfile {
+    EepromProfile { name, bus: EepromBus::Spi { address_bytes }, size, page_size }
+}
+
+const fn microwire_profile(name: &'static str, size: u32, address_bits: u8, word_size: u8) -> EepromProfile {
+    EepromProfile { name, bus: EepromBus::Microwire { address_bits, word_size }, size, page_size: word_size as u32 }
+}
+
+/// Known serial EEPROM parts
+pub const EEPROM_PROFILES: &[EepromProfile] = &[
+    i2c_profile("24c01", 128, 8, 1),
+    i2c_profile("24c02", 256, 8, 1),
+    i2c_profile("24c04", 512, 16, 1),
+    i2c_profile("24c08", 1024, 16, 1),
+    i2c_profile("24c16", 2048, 16, 1),
+    i2c_profile("24c32", 4096, 32, 2),
+    i2c_profile("24c64", 8192, 32, 2),
+    i2c_profile("24c128", 16384, 64, 2),
+    i2c_profile("24c256", 32768, 64, 2),
+    i2c_profile("24c512", 65536, 128, 2),
+    i2c_profile("24cm01", 131072, 256, 2),
+    i2c_profile("24cm02", 262144, 256, 2),
+    spi_profile("25c010", 128, 16, 1),
+    spi_profile("25c020", 256, 16, 1),
+    spi_profile("25c040", 512, 16, 1),
+    spi_profile("25c080", 1024, 16, 2),
+    spi_profile("25c160", 2048, 16, 2),
+    spi_profile("25c320", 4096, 32, 2),
+    spi_profile("25c640", 8192, 32, 2),
+    spi_profile("25c128", 16384, 64, 2),
+    spi_profile("25c256", 32768, 64, 2),
+    spi_profile("25c512", 65536, 128, 2),
+    spi_profile("25cm01", 131072, 256, 3),
+    spi_profile("25cm02", 262144, 256, 3),
+    microwire_profile("93c46", 128, 6, 2),
+    microwire_profile("93c46x8", 128, 7, 1),
+    microwire_profile("93c56", 256, 8, 2),
+    microwire_profile("93c56x8", 256, 9, 1),
+    microwire_profile("93c66", 512, 8, 2),
+    microwire_profile("93c66x8", 512, 9, 1),
+    microwire_profile("93c76", 1024, 10, 2),
+    microwire_profile("93c76x8", 1024, 11, 1),
+    microwire_profile("93c86", 2048, 10, 2),
+    microwire_profile("93c86x8", 2048, 11, 1),
+];
+
+/// Find profile by part name, case insensitive
+pub fn eeprom_profile(name: &str) -> Option<&'static EepromProfile> {
+    EEPROM_PROFILES.iter().find(|profile| profile.name.eq_ignore_ascii_case(name))
+}
+
+fn check_range(profile: &EepromProfile, address: u32, length: usize) -> Result<()> {
+    if address as u64 + length as u64 > profile.size as u64 {
+        return Err(common_error(-1, format!("area {:#x}+{:#x} is out of {} bytes {}", address, length, profile.size, profile.name)));
+    }
+    Ok(())
+}
+
+/// Split area into chunks not crossing 'boundary' aligned addresses
+fn chunks(address: u32, length: usize, boundary: u32) -> Vec<(u32, usize)> {
+    let mut chunks = Vec::new();
+    let mut done = 0;
+    while done < length {
+        let position = address + done as u32;
+        let count = std::cmp::min((boundary - position % boundary) as usize, length - done);
+        chunks.push((position, count));
+        done += count;
+    }
+    chunks
+}
+
+/// Operations common to all serial EEPROM kinds
+pub trait SerialEeprom {
+    fn profile(&self) -> &EepromProfile;
+
+    fn read(&mut self, address: u32, length: usize) -> Result<Vec<u8>>;
+
+    /// Write data waiting for every write cycle to finish, no erase is needed
+    fn write(&mut self, address: u32, data: &[u8]) -> Result<()>;
+
+    /// Compare content with data, error tells the first differing address
+    fn verify(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        let content = self.read(address, data.len())?;
+        if let Some(offset) = content.iter().zip(data).position(|(actual, expected)| actual != expected) {
+            return Err(common_error(-5, format!("{} verify failed at {:#x}: expected {:#04x}, read {:#04x}",
+                                                self.profile().name, address as usize + offset, data[offset], content[offset])));
+        }
+        Ok(())
+    }
+}
+
+/// 24Cxx EEPROM on I2C
+pub struct I2cEeprom<T: MpsseTransport> {
+    i2c: I2cMaster<T>,
+    profile: EepromProfile,
+    /// Value of A2..A0 pins
+    chip_address: u8,
+}
+impl<T: MpsseTransport> I2cEeprom<T> {
+    /// 'chip_address' is the value strapped on A2..A0 pins, the pins used as block bits by the part are ignored
+    pub fn new(i2c: I2cMaster<T>, profile: &EepromProfile, chip_address: u8) -> Result<Self> {
+        match profile.bus {
+            EepromBus::I2c { .. } => Ok(I2cEeprom { i2c, profile: *profile, chip_address: chip_address & 0x07 }),
+            _ => Err(common_error(-1, format!("{} is not an I2C EEPROM", profile.name))),
+        }
+    }
+
+    /// Tell 1 byte (up to 24C16) and 2 byte (24C32 and bigger) addressed parts apart without writing anything.
+    /// Two random reads are done with 2 byte addresses 0 and 1. A 1 byte addressed part takes the second
+    /// address byte as data (never written because the dummy write ends with repeated start, not stop),
+    /// so both reads return the same bytes. A 2 byte addressed part returns data shifted by one.
+    /// return None when content doesn't tell (e.g. blank part)
+    pub fn detect_address_bytes(i2c: &mut I2cMaster<T>, chip_address: u8) -> Result<Option<u8>> {
+        debug!("start 'detect_address_bytes' ...");
+        let device = I2C_EEPROM_BASE_ADDRESS | (chip_address & 0x07);
+        let first = i2c.write_read(device, &[0, 0], I2C_EEPROM_DETECT_LENGTH)?;
+        let second = i2c.write_read(device, &[0, 1], I2C_EEPROM_DETECT_LENGTH)?;
+        let same = first == second;
+        let shifted = first[1..] == second[..I2C_EEPROM_DETECT_LENGTH - 1];
+        let result = match (same, shifted) {
+            (true, false) => Some(1),
+            (false, true) => Some(2),
+            _ => None,
+        };
+        debug!("'detect_address_bytes' - OK, {:?}", result);
+        Ok(result)
+    }
+
+    pub fn i2c(&mut self) -> &mut I2cMaster<T> {
+        &mut self.i2c
+    }
+
+    pub fn into_inner(self) -> I2cMaster<T> {
+        self.i2c
+    }
+
+    fn address_bytes(&self) -> u8 {
+        match self.profile.bus {
+            EepromBus::I2c { address_bytes } => address_bytes,
+            _ => 1,
+        }
+    }
+
+    /// Memory area addressed by address bytes only
+    fn block_size(&self) -> u32 {
+        1 << (8 * self.address_bytes() as u32)
+    }
+
+    /// Device address (with block bits) and address bytes of memory address
+    fn locate(&self, address: u32) -> (u8, Vec<u8>) {
+        let block_size = self.block_size();
+        let blocks = self.profile.size.div_ceil(block_size);
+        let block_mask = (blocks - 1) as u8;
+        let device = I2C_EEPROM_BASE_ADDRESS | (self.chip_address & !block_mask) | (address / block_size) as u8;
+        let bytes = (0..self.address_bytes()).rev().map(|index| (address >> (8 * index as u32)) as u8).collect();
+        (device, bytes)
+    }
+}
+impl<T: MpsseTransport> SerialEeprom for I2cEeprom<T> {
+    fn profile(&self) -> &EepromProfile {
+        &self.profile
+    }
+
+    fn read(&mut self, address: u32, length: usize) -> Result<Vec<u8>> {
+        check_range(&self.profile, address, length)?;
+        let mut data = Vec::with_capacity(length);
+        for (position, count) in chunks(address, length, self.block_size()) {
+            let mut done = 0;
+            while done < count {
+                let chunk = std::cmp::min(count - done, I2C_MAX_CHUNK);
+                let (device, bytes) = self.locate(position + done as u32);
+                data.extend(self.i2c.write_read(device, &bytes, chunk)?);
+                done += chunk;
+            }
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        debug!("start 'i2c eeprom write' {:#x}, {} bytes ...", address, data.len());
+        check_range(&self.profile, address, data.len())?;
+        let mut done = 0;
+        for (position, count) in chunks(address, data.len(), self.profile.page_size) {
+            let (device, mut bytes) = self.locate(position);
+            bytes.extend_from_slice(&data[done..done + count]);
+            self.i2c.write(device, &bytes)?;
+            // the part doesn't acknowledge its address until write cycle ends
+            self.i2c.wait_ack(device, SERIAL_EEPROM_WRITE_TIMEOUT)?;
+            done += count;
+        }
+        info!("{} written, {} bytes at {:#x}", self.profile.name, data.len(), address);
+        Ok(())
+    }
+}
+
+/// 25xx EEPROM on SPI (mode 0 or 3)
+pub struct SpiEeprom<T: MpsseTransport> {
+    spi: SpiMaster<T>,
+    profile: EepromProfile,
+}
+impl<T: MpsseTransport> SpiEeprom<T> {
+    pub fn new(spi: SpiMaster<T>, profile: &EepromProfile) -> Result<Self> {
+        match profile.bus {
+            EepromBus::Spi { .. } => Ok(SpiEeprom { spi, profile: *profile }),
+            _ => Err(common_error(-1, format!("{} is not a SPI EEPROM", profile.name))),
+        }
+    }
+
+    pub fn spi(&mut self) -> &mut SpiMaster<T> {
+        &mut self.spi
+    }
+
+    pub fn into_inner(self) -> SpiMaster<T> {
+        self.spi
+    }
+
+    pub fn read_status(&mut self) -> Result<u8> {
+        Ok(self.spi.transaction(&[SPI_EEPROM_READ_STATUS], 1)?[0])
+    }
+
+    /// Write status register, e.g. block protect bits
+    pub fn write_status(&mut self, status: u8) -> Result<()> {
+        debug!("start 'spi eeprom write_status' {:#04x} ...", status);
+        self.write_enable()?;
+        self.spi.write(&[SPI_EEPROM_WRITE_STATUS, status])?;
+        self.wait_ready(SERIAL_EEPROM_WRITE_TIMEOUT)?;
+        debug!("'spi eeprom write_status' - OK");
+        Ok(())
+    }
+
+    /// Poll status register until write in progress bit is cleared
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let start = std::time::Instant::now();
+        loop {
+            if self.read_status()? & SPI_EEPROM_STATUS_WIP == 0 {
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                return Err(common_error(-4, format!("{} is still busy after {:?}", self.profile.name, timeout)));
+            }
+        }
+    }
+
+    fn write_enable(&mut self) -> Result<()> {
+        self.spi.write(&[SPI_EEPROM_WRITE_ENABLE])?;
+        if self.read_status()? & SPI_EEPROM_STATUS_WEL == 0 {
+            return Err(common_error(-6, format!("{} write enable failed, is WP pin low?", self.profile.name)));
+        }
+        Ok(())
+    }
+
+    fn command(&self, opcode: u8, address: u32) -> Vec<u8> {
+        match self.profile.bus {
+            EepromBus::Spi { address_bytes: 1 } => vec![opcode | (((address >> 8) & 0x01) as u8 * SPI_EEPROM_A8_BIT), address as u8],
+            EepromBus::Spi { address_bytes: 3 } => vec![opcode, (address >> 16) as u8, (address >> 8) as u8, address as u8],
+            _ => vec![opcode, (address >> 8) as u8, address as u8],
+        }
+    }
+}
+impl<T: MpsseTransport> SerialEeprom for SpiEeprom<T> {
+    fn profile(&self) -> &EepromProfile {
+        &self.profile
+    }
+
+    fn read(&mut self, address: u32, length: usize) -> Result<Vec<u8>> {
+        check_range(&self.profile, address, length)?;
+        let mut data = Vec::with_capacity(length);
+        // 1 byte addressed parts don't carry A8 over, so their reads stop at 256 bytes boundary
+        let boundary = match self.profile.bus {
+            EepromBus::Spi { address_bytes: 1 } => 0x100,
+            _ => self.profile.size,
+        };
+        for (position, count) in chunks(address, length, boundary) {
+            let mut done = 0;
+            while done < count {
+                let chunk = std::cmp::min(count - done, SERIAL_EEPROM_READ_CHUNK);
+                let command = self.command(SPI_EEPROM_READ, position + done as u32);
+                data.extend(self.spi.transaction(&command, chunk)?);
+                done += chunk;
+            }
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        debug!("start 'spi eeprom write' {:#x}, {} bytes ...", address, data.len());
+        check_range(&self.profile, address, data.len())?;
+        let mut done = 0;
+        for (position, count) in chunks(address, data.len(), self.profile.page_size) {
+            self.write_enable()?;
+            let command = self.command(SPI_EEPROM_WRITE, position);
+            self.spi.write_with_prefix(&command, &data[done..done + count])?;
+            self.wait_ready(SERIAL_EEPROM_WRITE_TIMEOUT)?;
+            done += count;
+        }
+        info!("{} written, {} bytes at {:#x}", self.profile.name, data.len(), address);
+        Ok(())
+    }
+}
+
+/// 93Cxx EEPROM on Microwire. Words of x16 parts are stored little endian: byte address 2*N is the low byte of word N.
+pub struct MicrowireEeprom<T: MpsseTransport> {
+    microwire: MicrowireMaster<T>,
+    profile: EepromProfile,
+    address_bits: u8,
+    word_size: u8,
+}
+impl<T: MpsseTransport> MicrowireEeprom<T> {
+    pub fn new(microwire: MicrowireMaster<T>, profile: &EepromProfile) -> Result<Self> {
+        match profile.bus {
+            EepromBus::Microwire { address_bits, word_size } =>
+                Ok(MicrowireEeprom { microwire, profile: *profile, address_bits, word_size }),
+            _ => Err(common_error(-1, format!("{} is not a Microwire EEPROM", profile.name))),
+        }
+    }
+
+    pub fn microwire(&mut self) -> &mut MicrowireMaster<T> {
+        &mut self.microwire
+    }
+
+    pub fn into_inner(self) -> MicrowireMaster<T> {
+        self.microwire
+    }
+
+    /// Erase whole part to all ones
+    pub fn erase_all(&mut self) -> Result<()> {
+        debug!("start 'microwire eeprom erase_all' ...");
+        self.extended(MICROWIRE_EEPROM_EWEN)?;
+        let result = self.extended(MICROWIRE_EEPROM_ERAL)
+            .and_then(|_| self.microwire.wait_ready(SERIAL_EEPROM_WRITE_TIMEOUT));
+        self.extended(MICROWIRE_EEPROM_EWDS)?;
+        result?;
+        debug!("'microwire eeprom erase_all' - OK");
+        Ok(())
+    }
+
+    fn words(&self) -> u32 {
+        self.profile.size / self.word_size as u32
+    }
+
+    /// Opcode with address bits
+    fn command(&self, opcode: u32, word: u32) -> (u32, u8) {
+        ((opcode << self.address_bits) | word, 3 + self.address_bits)
+    }
+
+    /// Command of 0b100 group selected by two highest address bits
+    fn extended(&mut self, code: u32) -> Result<()> {
+        let (command, bits) = self.command(MICROWIRE_EEPROM_EXTENDED, code << (self.address_bits - 2));
+        self.microwire.write(command, bits)
+    }
+
+    /// Read whole words with one sequential read, return bytes in memory order
+    fn read_words(&mut self, first: u32, count: u32) -> Result<Vec<u8>> {
+        let (command, bits) = self.command(MICROWIRE_EEPROM_READ, first);
+        let mut data = self.microwire.transaction(command, bits, (count * self.word_size as u32) as usize)?;
+        if self.word_size == 2 {
+            // words are shifted out most significant bit first
+            for word in data.chunks_mut(2) {
+                word.swap(0, 1);
+            }
+        }
+        Ok(data)
+    }
+
+    fn write_words(&mut self, first: u32, data: &[u8]) -> Result<()> {
+        for (index, word) in data.chunks(self.word_size as usize).enumerate() {
+            let value = word.iter().rev().fold(0u32, |value, byte| (value << 8) | *byte as u32);
+            let (command, bits) = self.command(MICROWIRE_EEPROM_WRITE, first + index as u32);
+            let word_bits = 8 * self.word_size;
+            self.microwire.write((command << word_bits) | value, bits + word_bits)?;
+            self.microwire.wait_ready(SERIAL_EEPROM_WRITE_TIMEOUT)?;
+        }
+        Ok(())
+    }
+}
+impl<T: MpsseTransport> SerialEeprom for MicrowireEeprom<T> {
+    fn profile(&self) -> &EepromProfile {
+        &self.profile
+    }
+
+    fn read(&mut self, address: u32, length: usize) -> Result<Vec<u8>> {
+        check_range(&self.profile, address, length)?;
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+        let word_size = self.word_size as u32;
+        let first = address / word_size;
+        let last = (address + length as u32 - 1) / word_size;
+        let words_per_chunk = SERIAL_EEPROM_READ_CHUNK as u32 / word_size;
+        let mut data = Vec::with_capacity(((last - first + 1) * word_size) as usize);
+        let mut word = first;
+        while word <= last {
+            let count = std::cmp::min(last - word + 1, words_per_chunk);
+            data.extend(self.read_words(word, count)?);
+            word += count;
+        }
+        let skip = (address % word_size) as usize;
+        Ok(data[skip..skip + length].to_vec())
+    }
+
+    fn write(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        debug!("start 'microwire eeprom write' {:#x}, {} bytes ...", address, data.len());
+        check_range(&self.profile, address, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let word_size = self.word_size as u32;
+        let start = address / word_size * word_size;
+        let end = std::cmp::min((address + data.len() as u32).div_ceil(word_size) * word_size,
+                                self.words() * word_size);
+        // partially covered words keep their other byte
+        let mut image = self.read(start, (end - start) as usize)?;
+        let offset = (address - start) as usize;
+        image[offset..offset + data.len()].copy_from_slice(data);
+        self.extended(MICROWIRE_EEPROM_EWEN)?;
+        let result = self.write_words(start / word_size, &image);
+        self.extended(MICROWIRE_EEPROM_EWDS)?;
+        result?;
+        info!("{} written, {} bytes at {:#x}", self.profile.name, data.len(), address);
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+#![allow(dead_code)]
+
+use log::{debug, error, info};
+use snafu::GenerateBacktrace;
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{FtdiContextError, Result};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_clock_commands};
+
+/// ADBUS pins used by SPI: SCK (0), MOSI (1), MISO (2), CS (3)
+pub const SPI_PIN_SCK: u8 = 0x01;
+pub const SPI_PIN_MOSI: u8 = 0x02;
+pub const SPI_PIN_MISO: u8 = 0x04;
+pub const SPI_PIN_CS: u8 = 0x08;
+
+/// One MPSSE data command can clock up to 65536 bytes
+pub const SPI_MAX_BYTES_PER_COMMAND: usize = 0x10000;
+
+/// SPI clock polarity and phase
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SpiMode {
+    /// SCK idle low, data sampled on rising edge
+    Mode0,
+    /// SCK idle low, data sampled on falling edge
+    Mode1,
+    /// SCK idle high, data sampled on falling edge
+    Mode2,
+    /// SCK idle high, data sampled on rising edge
+    Mode3,
+}
+impl SpiMode {
+    fn idle_clock_high(self) -> bool {
+        matches!(self, SpiMode::Mode2 | SpiMode::Mode3)
+    }
+    /// Data is sampled on rising edge, so it's changed on falling one
+    fn sample_on_rising(self) -> bool {
+        matches!(self, SpiMode::Mode0 | SpiMode::Mode3)
+    }
+    /// MPSSE opcode to clock bytes MSB first with given direction(s)
+    pub(crate) fn opcode(self, write: bool, read: bool) -> u8 {
+        let mut opcode = 0;
+        if write {
+            opcode |= MPSSE_DO_WRITE;
+            if self.sample_on_rising() {
+                opcode |= MPSSE_WRITE_NEG;
+            }
+        }
+        if read {
+            opcode |= MPSSE_DO_READ;
+            if !self.sample_on_rising() {
+                opcode |= MPSSE_READ_NEG;
+            }
+        }
+        opcode
+    }
+}
+
+/// SPI master on MPSSE with one chip select on ADBUS3 (active low)
+pub struct SpiMaster<T: MpsseTransport> {
+    transport: T,
+    mode: SpiMode,
+}
+impl<T: MpsseTransport> SpiMaster<T> {
+    pub fn new(transport: T, mode: SpiMode) -> Self {
+        SpiMaster { transport, mode }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub fn mode(&self) -> SpiMode {
+        self.mode
+    }
+
+    /// Setup MPSSE pins and SCK frequency, chip select is left inactive.
+    /// MPSSE mode must be already enabled on the chip (ftdi_set_bitmode with BITMODE_MPSSE).
+    /// return actual SCK frequency
+    pub fn init(&mut self, frequency: u32, high_speed: bool) -> Result<u32> {
+        debug!("start 'spi init' ...");
+        let (mut commands, actual) = mpsse_clock_commands(frequency, high_speed);
+        commands.push(LOOPBACK_END);
+        commands.extend_from_slice(&self.pins(false));
+        self.transport.mpsse_write(&commands)?;
+        info!("SPI initialized, {:?}, SCK = {} Hz", self.mode, actual);
+        Ok(actual)
+    }
+
+    /// Write bytes in one chip select cycle
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.transaction(data, 0).map(|_| ())
+    }
+
+    /// Write 'write' bytes then read 'read_size' bytes in one chip select cycle (half duplex)
+    pub fn transaction(&mut self, write: &[u8], read_size: usize) -> Result<Vec<u8>> {
+        let mut commands = Vec::with_capacity(write.len() + 16);
+        commands.extend_from_slice(&self.pins(true));
+        self.append_data(&mut commands, write, true, false);
+        self.append_read(&mut commands, read_size);
+        commands.extend_from_slice(&self.pins(false));
+        if read_size > 0 {
+            commands.push(SEND_IMMEDIATE);
+        }
+        self.transport.mpsse_write(&commands)?;
+        if read_size == 0 {
+            return Ok(Vec::new());
+        }
+        self.transport.mpsse_read(read_size)
+    }
+
+    /// Full duplex exchange, return bytes clocked in while 'data' was clocked out
+    pub fn exchange(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut commands = Vec::with_capacity(data.len() + 16);
+        commands.extend_from_slice(&self.pins(true));
+        self.append_data(&mut commands, data, true, true);
+        commands.extend_from_slice(&self.pins(false));
+        commands.push(SEND_IMMEDIATE);
+        self.transport.mpsse_write(&commands)?;
+        self.transport.mpsse_read(data.len())
+    }
+
+    /// Write 'prefix' then 'data' in one chip select cycle without joining them into a new buffer
+    pub fn write_with_prefix(&mut self, prefix: &[u8], data: &[u8]) -> Result<()> {
+        if prefix.is_empty() && data.is_empty() {
+            let error = FtdiContextError::UsbCommonError {
+                code: -1, message: "SPI write without data".to_string(),
+                backtrace: GenerateBacktrace::generate()
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        let mut commands = Vec::with_capacity(prefix.len() + data.len() + 16);
+        commands.extend_from_slice(&self.pins(true));
+        self.append_data(&mut commands, prefix, true, false);
+        self.append_data(&mut commands, data, true, false);
+        commands.extend_from_slice(&self.pins(false));
+        self.transport.mpsse_write(&commands)
+    }
+
+    fn append_data(&self, commands: &mut Vec<u8>, data: &[u8], write: bool, read: bool) {
+        let opcode = self.mode.opcode(write, read);
+        for chunk in data.chunks(SPI_MAX_BYTES_PER_COMMAND) {
+            let length = chunk.len() - 1;
+            commands.extend_from_slice(&[opcode, (length & 0xff) as u8, (length >> 8) as u8]);
+            commands.extend_from_slice(chunk);
+        }
+    }
+
+    fn append_read(&self, commands: &mut Vec<u8>, size: usize) {
+        let opcode = self.mode.opcode(false, true);
+        let mut remaining = size;
+        while remaining > 0 {
+            let count = std::cmp::min(remaining, SPI_MAX_BYTES_PER_COMMAND);
+            commands.extend_from_slice(&[opcode, ((count - 1) & 0xff) as u8, ((count - 1) >> 8) as u8]);
+            remaining -= count;
+        }
+    }
+
+    /// Low byte pins setup with chip select active or not
+    fn pins(&self, selected: bool) -> [u8; 3] {
+        let mut value = 0;
+        if !selected {
+            value |= SPI_PIN_CS;
+        }
+        if self.mode.idle_clock_high() {
+            value |= SPI_PIN_SCK;
+        }
+        [SET_BITS_LOW, value, SPI_PIN_SCK | SPI_PIN_MOSI | SPI_PIN_CS]
+    }
+}
@@ -0,0 +1,422 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mpsse::MpsseTransport;
+use crate::ftdi::spi::SpiMaster;
+
+/// SPI NOR flash commands
+pub const SPI_FLASH_WRITE_ENABLE: u8 = 0x06;
+pub const SPI_FLASH_WRITE_DISABLE: u8 = 0x04;
+pub const SPI_FLASH_READ_STATUS: u8 = 0x05;
+pub const SPI_FLASH_READ: u8 = 0x03;
+pub const SPI_FLASH_FAST_READ: u8 = 0x0b;
+pub const SPI_FLASH_PAGE_PROGRAM: u8 = 0x02;
+pub const SPI_FLASH_SECTOR_ERASE: u8 = 0x20;
+pub const SPI_FLASH_BLOCK_ERASE_32K: u8 = 0x52;
+pub const SPI_FLASH_BLOCK_ERASE_64K: u8 = 0xd8;
+pub const SPI_FLASH_CHIP_ERASE: u8 = 0xc7;
+pub const SPI_FLASH_READ_JEDEC_ID: u8 = 0x9f;
+pub const SPI_FLASH_READ_SFDP: u8 = 0x5a;
+pub const SPI_FLASH_ENTER_4BYTE: u8 = 0xb7;
+pub const SPI_FLASH_EXIT_4BYTE: u8 = 0xe9;
+
+/// Status register: write in progress
+pub const SPI_FLASH_STATUS_WIP: u8 = 0x01;
+/// Status register: write enable latch
+pub const SPI_FLASH_STATUS_WEL: u8 = 0x02;
+
+/// "SFDP" signature, little endian
+pub const SFDP_SIGNATURE: u32 = 0x5044_4653;
+/// Parameter ID of JEDEC Basic Flash Parameter Table
+pub const SFDP_BFPT_ID: u16 = 0xff00;
+/// Page size of flashes not telling it in SFDP
+pub const SPI_FLASH_DEFAULT_PAGE_SIZE: u32 = 256;
+/// Flashes bigger than this need 4 byte addresses
+pub const SPI_FLASH_3BYTE_LIMIT: u64 = 0x100_0000;
+
+/// Write in progress timeouts
+pub const SPI_FLASH_PROGRAM_TIMEOUT: Duration = Duration::from_millis(100);
+pub const SPI_FLASH_ERASE_TIMEOUT: Duration = Duration::from_secs(10);
+pub const SPI_FLASH_CHIP_ERASE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Bytes read by one fast read command
+const SPI_FLASH_READ_CHUNK: usize = 0x10000;
+
+/// SPI flash manufacturers by JEDEC ID byte
+const SPI_FLASH_MANUFACTURERS: [(u8, &str); 12] = [
+    (0x01, "Spansion (Cypress)"),
+    (0x1f, "Adesto (Atmel)"),
+    (0x20, "Micron (Numonyx)"),
+    (0x37, "AMIC"),
+    (0x5e, "Zbit"),
+    (0x68, "Boya"),
+    (0x85, "Puya"),
+    (0x9d, "ISSI"),
+    (0xbf, "Microchip (SST)"),
+    (0xc2, "Macronix"),
+    (0xc8, "GigaDevice"),
+    (0xef, "Winbond"),
+];
+
+/// Answer of READ JEDEC ID command
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct JedecId {
+    pub manufacturer: u8,
+    pub memory_type: u8,
+    pub capacity: u8,
+}
+impl JedecId {
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        SPI_FLASH_MANUFACTURERS.iter()
+            .find(|(id, _)| *id == self.manufacturer)
+            .map(|(_, name)| *name)
+    }
+
+    /// Flash size guessed from capacity byte, most vendors use 2^capacity bytes
+    pub fn size(&self) -> Option<u64> {
+        match self.capacity {
+            0x10..=0x1f => Some(1 << self.capacity),
+            // Micron continues from 512 Mbit with 0x20
+            0x20..=0x22 => Some(1 << (self.capacity - 6)),
+            _ => None,
+        }
+    }
+
+    /// All zeros or all ones mean nothing answered
+    pub fn is_valid(&self) -> bool {
+        let all = [self.manufacturer, self.memory_type, self.capacity];
+        all != [0, 0, 0] && all != [0xff, 0xff, 0xff]
+    }
+}
+impl Display for JedecId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:02x} {:02x} {:02x} ({}", self.manufacturer, self.memory_type, self.capacity,
+               self.manufacturer_name().unwrap_or("unknown manufacturer"))?;
+        match self.size() {
+            Some(size) => write!(f, ", {} KiB)", size / 1024),
+            None => write!(f, ")"),
+        }
+    }
+}
+
+/// Erase command with the size of area it erases
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EraseType {
+    pub size: u32,
+    pub opcode: u8,
+}
+
+/// What flash looks like, from SFDP or guessed from JEDEC ID
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FlashGeometry {
+    /// bytes
+    pub size: u64,
+    pub page_size: u32,
+    /// sorted by size, the smallest first
+    pub erase_types: Vec<EraseType>,
+    /// true when it comes from SFDP table
+    pub from_sfdp: bool,
+}
+impl FlashGeometry {
+    /// Common 4 KB / 32 KB / 64 KB erase commands and 256 bytes pages
+    pub fn from_jedec_id(id: &JedecId) -> Option<FlashGeometry> {
+        Some(FlashGeometry {
+            size: id.size()?,
+            page_size: SPI_FLASH_DEFAULT_PAGE_SIZE,
+            erase_types: vec![
+                EraseType { size: 0x1000, opcode: SPI_FLASH_SECTOR_ERASE },
+                EraseType { size: 0x8000, opcode: SPI_FLASH_BLOCK_ERASE_32K },
+                EraseType { size: 0x10000, opcode: SPI_FLASH_BLOCK_ERASE_64K },
+            ],
+            from_sfdp: false,
+        })
+    }
+
+    /// Geometry from JEDEC Basic Flash Parameter Table (JESD216) bytes
+    pub fn from_bfpt(table: &[u8]) -> Result<FlashGeometry> {
+        if table.len() < 9 * 4 {
+            return Err(common_error(-1, format!("SFDP basic parameter table is too short, {} bytes", table.len())));
+        }
+        let dword = |index: usize| u32::from_le_bytes([table[index * 4], table[index * 4 + 1], table[index * 4 + 2], table[index * 4 + 3]]);
+        let density = dword(1);
+        let bits = if density & 0x8000_0000 == 0 {
+            density as u64 + 1
+        } else {
+            let exponent = density & 0x7fff_ffff;
+            if exponent >= 64 {
+                return Err(common_error(-1, format!("SFDP flash density 2^{} bits is bogus", exponent)));
+            }
+            1u64 << exponent
+        };
+        let mut erase_types = Vec::new();
+        for value in [dword(7), dword(8)].iter() {
+            for &(size_shift, opcode_shift) in &[(0, 8), (16, 24)] {
+                let exponent = (value >> size_shift) & 0xff;
+                if exponent != 0 && exponent < 32 {
+                    erase_types.push(EraseType { size: 1 << exponent, opcode: (value >> opcode_shift) as u8 });
+                }
+            }
+        }
+        // JESD216 first revision without erase types table: 4 KB erase from the first DWORD
+        if erase_types.is_empty() && dword(0) & 0x03 == 0x01 {
+            erase_types.push(EraseType { size: 0x1000, opcode: (dword(0) >> 8) as u8 });
+        }
+        erase_types.sort_by_key(|erase| erase.size);
+        let page_size = if table.len() >= 11 * 4 {
+            1 << ((dword(10) >> 4) & 0x0f)
+        } else {
+            SPI_FLASH_DEFAULT_PAGE_SIZE
+        };
+        Ok(FlashGeometry { size: bits / 8, page_size, erase_types, from_sfdp: true })
+    }
+
+    /// Find Basic Flash Parameter Table in SFDP header and parameter headers
+    /// return table address and length in bytes
+    pub fn find_bfpt(header: &[u8]) -> Result<(u32, usize)> {
+        if header.len() < 16 || u32::from_le_bytes([header[0], header[1], header[2], header[3]]) != SFDP_SIGNATURE {
+            return Err(common_error(-2, "SFDP signature is not found".to_string()));
+        }
+        let headers = header[6] as usize + 1;
+        for index in 0..headers {
+            let offset = 8 + index * 8;
+            if offset + 8 > header.len() {
+                break;
+            }
+            let parameter = &header[offset..offset + 8];
+            let id = ((parameter[7] as u16) << 8) | parameter[0] as u16;
+            if id == SFDP_BFPT_ID {
+                let pointer = u32::from_le_bytes([parameter[4], parameter[5], parameter[6], 0]);
+                return Ok((pointer, parameter[3] as usize * 4));
+            }
+        }
+        Err(common_error(-2, "SFDP basic flash parameter table is not found".to_string()))
+    }
+
+    pub fn smallest_erase(&self) -> Option<EraseType> {
+        self.erase_types.first().copied()
+    }
+}
+
+/// SPI NOR flash programmer
+pub struct SpiFlash<T: MpsseTransport> {
+    spi: SpiMaster<T>,
+    id: JedecId,
+    geometry: FlashGeometry,
+    four_byte_address: bool,
+}
+impl<T: MpsseTransport> SpiFlash<T> {
+    /// Read JEDEC ID and geometry (SFDP first, then guess by ID).
+    /// Flashes bigger than 16 MB are switched to 4 byte addressing.
+    pub fn probe(mut spi: SpiMaster<T>) -> Result<Self> {
+        debug!("start 'spi flash probe' ...");
+        let id = Self::read_jedec_id(&mut spi)?;
+        if !id.is_valid() {
+            return Err(common_error(-3, format!("no SPI flash found, JEDEC ID = {}", id)));
+        }
+        let geometry = match Self::read_geometry(&mut spi) {
+            Ok(geometry) => geometry,
+            Err(sfdp_error) => {
+                warn!("SPI flash without usable SFDP ({}), geometry is guessed from JEDEC ID", sfdp_error);
+                FlashGeometry::from_jedec_id(&id)
+                    .ok_or_else(|| common_error(-3, format!("unknown SPI flash size, JEDEC ID = {}", id)))?
+            }
+        };
+        let four_byte_address = geometry.size > SPI_FLASH_3BYTE_LIMIT;
+        if four_byte_address {
+            spi.write(&[SPI_FLASH_ENTER_4BYTE])?;
+        }
+        info!("SPI flash {}, {} bytes, page {} bytes, erase sizes {:?}", id, geometry.size, geometry.page_size,
+              geometry.erase_types.iter().map(|erase| erase.size).collect::<Vec<_>>());
+        Ok(SpiFlash { spi, id, geometry, four_byte_address })
+    }
+
+    pub fn read_jedec_id(spi: &mut SpiMaster<T>) -> Result<JedecId> {
+        let reply = spi.transaction(&[SPI_FLASH_READ_JEDEC_ID], 3)?;
+        Ok(JedecId { manufacturer: reply[0], memory_type: reply[1], capacity: reply[2] })
+    }
+
+    /// Read bytes of SFDP area, it always uses 3 byte address and 8 dummy clocks
+    pub fn read_sfdp(spi: &mut SpiMaster<T>, address: u32, size: usize) -> Result<Vec<u8>> {
+        spi.transaction(&[SPI_FLASH_READ_SFDP, (address >> 16) as u8, (address >> 8) as u8, address as u8, 0], size)
+    }
+
+    fn read_geometry(spi: &mut SpiMaster<T>) -> Result<FlashGeometry> {
+        let header = Self::read_sfdp(spi, 0, 8)?;
+        let headers = if header[..4] == SFDP_SIGNATURE.to_le_bytes() { header[6] as usize + 1 } else { 1 };
+        let header = Self::read_sfdp(spi, 0, 8 + headers * 8)?;
+        let (pointer, length) = FlashGeometry::find_bfpt(&header)?;
+        let table = Self::read_sfdp(spi, pointer, length)?;
+        FlashGeometry::from_bfpt(&table)
+    }
+
+    pub fn id(&self) -> JedecId {
+        self.id
+    }
+
+    pub fn geometry(&self) -> &FlashGeometry {
+        &self.geometry
+    }
+
+    pub fn spi(&mut self) -> &mut SpiMaster<T> {
+        &mut self.spi
+    }
+
+    /// Leave 4 byte address mode (boot ROMs usually expect 3 byte one) and return SPI master
+    pub fn into_inner(mut self) -> Result<SpiMaster<T>> {
+        if self.four_byte_address {
+            self.spi.write(&[SPI_FLASH_EXIT_4BYTE])?;
+        }
+        Ok(self.spi)
+    }
+
+    pub fn read_status(&mut self) -> Result<u8> {
+        Ok(self.spi.transaction(&[SPI_FLASH_READ_STATUS], 1)?[0])
+    }
+
+    /// Poll status register until write in progress bit is cleared
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            if self.read_status()? & SPI_FLASH_STATUS_WIP == 0 {
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                return Err(common_error(-4, format!("SPI flash is still busy after {:?}", timeout)));
+            }
+        }
+    }
+
+    /// Read with fast read command
+    pub fn read(&mut self, address: u32, length: usize) -> Result<Vec<u8>> {
+        self.check_range(address, length)?;
+        let mut data = Vec::with_capacity(length);
+        while data.len() < length {
+            let chunk = std::cmp::min(length - data.len(), SPI_FLASH_READ_CHUNK);
+            let mut command = self.command(SPI_FLASH_FAST_READ, address + data.len() as u32);
+            // 8 dummy clocks
+            command.push(0);
+            data.extend(self.spi.transaction(&command, chunk)?);
+        }
+        Ok(data)
+    }
+
+    /// Erase area with the biggest erase commands fitting it, area must be aligned to the smallest erase size
+    pub fn erase(&mut self, address: u32, length: usize) -> Result<()> {
+        debug!("start 'spi flash erase' {:#x}, {} bytes ...", address, length);
+        self.check_range(address, length)?;
+        let smallest = self.geometry.smallest_erase()
+            .ok_or_else(|| common_error(-1, "SPI flash has no erase commands".to_string()))?;
+        if !address.is_multiple_of(smallest.size) || !length.is_multiple_of(smallest.size as usize) {
+            return Err(common_error(-1, format!("erase area {:#x}+{:#x} is not aligned to {} bytes", address, length, smallest.size)));
+        }
+        let end = address as u64 + length as u64;
+        let mut position = address as u64;
+        while position < end {
+            let erase = self.geometry.erase_types.iter().rev()
+                .find(|erase| position.is_multiple_of(erase.size as u64) && position + erase.size as u64 <= end)
+                .copied()
+                .unwrap_or(smallest);
+            self.write_enable()?;
+            let command = self.command(erase.opcode, position as u32);
+            self.spi.write(&command)?;
+            self.wait_ready(SPI_FLASH_ERASE_TIMEOUT)?;
+            position += erase.size as u64;
+        }
+        debug!("'spi flash erase' - OK");
+        Ok(())
+    }
+
+    pub fn erase_chip(&mut self) -> Result<()> {
+        debug!("start 'spi flash erase_chip' ...");
+        self.write_enable()?;
+        self.spi.write(&[SPI_FLASH_CHIP_ERASE])?;
+        self.wait_ready(SPI_FLASH_CHIP_ERASE_TIMEOUT)?;
+        debug!("'spi flash erase_chip' - OK");
+        Ok(())
+    }
+
+    /// Program erased area page by page, pages of all ones are skipped
+    pub fn program<F: FnMut(usize, usize)>(&mut self, address: u32, data: &[u8], mut progress: F) -> Result<()> {
+        debug!("start 'spi flash program' {:#x}, {} bytes ...", address, data.len());
+        self.check_range(address, data.len())?;
+        let page_size = self.geometry.page_size as usize;
+        let mut done = 0;
+        while done < data.len() {
+            let position = address as usize + done;
+            let count = std::cmp::min(page_size - position % page_size, data.len() - done);
+            let page = &data[done..done + count];
+            if page.iter().any(|&byte| byte != 0xff) {
+                self.write_enable()?;
+                let command = self.command(SPI_FLASH_PAGE_PROGRAM, position as u32);
+                self.spi.write_with_prefix(&command, page)?;
+                self.wait_ready(SPI_FLASH_PROGRAM_TIMEOUT)?;
+            }
+            done += count;
+            progress(done, data.len());
+        }
+        debug!("'spi flash program' - OK");
+        Ok(())
+    }
+
+    /// Compare flash content with data, error tells the first differing address
+    pub fn verify(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        let content = self.read(address, data.len())?;
+        if let Some(offset) = content.iter().zip(data).position(|(actual, expected)| actual != expected) {
+            return Err(common_error(-5, format!("SPI flash verify failed at {:#x}: expected {:#04x}, read {:#04x}",
+                                               address as usize + offset, data[offset], content[offset])));
+        }
+        Ok(())
+    }
+
+    /// Erase, program and verify. Parts of partially covered erase blocks are read and written back.
+    pub fn write_image<F: FnMut(usize, usize)>(&mut self, address: u32, data: &[u8], progress: F) -> Result<()> {
+        self.check_range(address, data.len())?;
+        let block = self.geometry.smallest_erase()
+            .ok_or_else(|| common_error(-1, "SPI flash has no erase commands".to_string()))?
+            .size as usize;
+        let start = address as usize / block * block;
+        let end = (address as usize + data.len()).div_ceil(block) * block;
+        let end = std::cmp::min(end as u64, self.geometry.size) as usize;
+        let mut image = Vec::with_capacity(end - start);
+        if start < address as usize {
+            image.extend(self.read(start as u32, address as usize - start)?);
+        }
+        image.extend_from_slice(data);
+        if image.len() < end - start {
+            let tail_start = start + image.len();
+            image.extend(self.read(tail_start as u32, end - tail_start)?);
+        }
+        self.erase(start as u32, end - start)?;
+        self.program(start as u32, &image, progress)?;
+        self.verify(address, data)?;
+        info!("SPI flash written, {} bytes at {:#x}", data.len(), address);
+        Ok(())
+    }
+
+    fn write_enable(&mut self) -> Result<()> {
+        self.spi.write(&[SPI_FLASH_WRITE_ENABLE])?;
+        if self.read_status()? & SPI_FLASH_STATUS_WEL == 0 {
+            return Err(common_error(-6, "SPI flash write enable failed, is it write protected?".to_string()));
+        }
+        Ok(())
+    }
+
+    fn command(&self, opcode: u8, address: u32) -> Vec<u8> {
+        let mut command = vec![opcode];
+        if self.four_byte_address {
+            command.push((address >> 24) as u8);
+        }
+        command.extend_from_slice(&[(address >> 16) as u8, (address >> 8) as u8, address as u8]);
+        command
+    }
+
+    fn check_range(&self, address: u32, length: usize) -> Result<()> {
+        if address as u64 + length as u64 > self.geometry.size {
+            return Err(common_error(-1, format!("area {:#x}+{:#x} is out of {} bytes flash", address, length, self.geometry.size)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,649 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use log::{debug, error, info, warn};
+use snafu::GenerateBacktrace;
+use crate::ftdi::ftdi_context::{FtdiContextError, Result, common_error};
+use crate::ftdi::jtag::{JtagController, TapState, get_bit, set_bit};
+use crate::ftdi::mpsse::MpsseTransport;
+
+/// TAP state by its SVF name (RESET, IDLE, DRSHIFT...)
+pub fn svf_state(name: &str) -> Option<TapState> {
+    let state = match name.to_ascii_uppercase().as_str() {
+        "RESET" => TapState::TestLogicReset,
+        "IDLE" => TapState::RunTestIdle,
+        "DRSELECT" => TapState::SelectDrScan,
+        "DRCAPTURE" => TapState::CaptureDr,
+        "DRSHIFT" => TapState::ShiftDr,
+        "DREXIT1" => TapState::Exit1Dr,
+        "DRPAUSE" => TapState::PauseDr,
+        "DREXIT2" => TapState::Exit2Dr,
+        "DRUPDATE" => TapState::UpdateDr,
+        "IRSELECT" => TapState::SelectIrScan,
+        "IRCAPTURE" => TapState::CaptureIr,
+        "IRSHIFT" => TapState::ShiftIr,
+        "IREXIT1" => TapState::Exit1Ir,
+        "IRPAUSE" => TapState::PauseIr,
+        "IREXIT2" => TapState::Exit2Ir,
+        "IRUPDATE" => TapState::UpdateIr,
+        _ => return None,
+    };
+    Some(state)
+}
+
+/// Parameters of SIR/SDR/HIR/HDR/TIR/TDR statement, bit vectors are LSB first.
+/// Missing values are taken from the previous statement of the same kind when played.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SvfShift {
+    pub length: usize,
+    pub tdi: Option<Vec<u8>>,
+    pub tdo: Option<Vec<u8>>,
+    pub mask: Option<Vec<u8>>,
+    pub smask: Option<Vec<u8>>,
+}
+
+/// Clock counted by RUNTEST: TCK or system clock
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SvfRunClock {
+    Tck,
+    Sck,
+}
+
+/// TRST pin mode
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SvfTrst {
+    On,
+    Off,
+    Z,
+    Absent,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SvfCommand {
+    EndDr(TapState),
+    EndIr(TapState),
+    /// None restores the default (maximum) frequency
+    Frequency(Option<f64>),
+    Hdr(SvfShift),
+    Hir(SvfShift),
+    Tdr(SvfShift),
+    Tir(SvfShift),
+    Sdr(SvfShift),
+    Sir(SvfShift),
+    RunTest {
+        run_state: Option<TapState>,
+        run_count: Option<u32>,
+        clock: SvfRunClock,
+        /// seconds
+        min_time: Option<f64>,
+        /// seconds
+        max_time: Option<f64>,
+        end_state: Option<TapState>,
+    },
+    State(Vec<TapState>),
+    Trst(SvfTrst),
+}
+
+/// SVF statement with the line number it starts at
+#[derive(Clone, PartialEq, Debug)]
+pub struct SvfStatement {
+    pub line: usize,
+    pub command: SvfCommand,
+}
+
+/// Captured TDO that doesn't match expected value, vectors are LSB first
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TdoMismatch {
+    /// where the failed scan is in the source, e.g. "line 12 (SDR)"
+    pub location: String,
+    /// index of the first differing bit
+    pub first_bit: usize,
+    pub length: usize,
+    pub expected: Vec<u8>,
+    pub actual: Vec<u8>,
+    pub mask: Vec<u8>,
+}
+impl TdoMismatch {
+    /// Compare captured TDO with expected value under mask, return mismatch if any bit differs
+    pub(crate) fn check(location: &str, length: usize, expected: &[u8], actual: &[u8], mask: &[u8]) -> Option<TdoMismatch> {
+        let first_bit = (0..length)
+            .find(|&bit| get_bit(mask, bit) && get_bit(expected, bit) != get_bit(actual, bit))?;
+        Some(TdoMismatch {
+            location: location.to_string(), first_bit, length,
+            expected: expected.to_vec(), actual: actual.to_vec(), mask: mask.to_vec(),
+        })
+    }
+}
+impl Display for TdoMismatch {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "TDO mismatch at {}, bit {} of {}: expected ({}) got ({}) mask ({})",
+               self.location, self.first_bit, self.length,
+               bits_to_hex(&self.expected, self.length), bits_to_hex(&self.actual, self.length),
+               bits_to_hex(&self.mask, self.length))
+    }
+}
+
+/// LSB first bit vector as SVF hex string (most significant digit first)
+pub fn bits_to_hex(data: &[u8], length: usize) -> String {
+    (0..length.div_ceil(4)).rev()
+        .map(|digit| {
+            let nibble = (0..4)
+                .filter(|bit| digit * 4 + bit < length && get_bit(data, digit * 4 + bit))
+                .fold(0u32, |value, bit| value | (1 << bit));
+            std::char::from_digit(nibble, 16).unwrap()
+        })
+        .collect()
+}
+
+/// SVF hex string (most significant digit first) as LSB first vector of 'length' bits
+fn hex_to_bits(hex: &str, length: usize, line: usize) -> Result<Vec<u8>> {
+    let mut data = vec![0u8; length.div_ceil(8)];
+    for (digit, character) in hex.chars().rev().enumerate() {
+        let nibble = character.to_digit(16)
+            .ok_or_else(|| svf_error(line, format!("invalid hex digit '{}'", character)))?;
+        for bit in 0..4 {
+            if nibble & (1 << bit) == 0 {
+                continue;
+            }
+            let index = digit * 4 + bit;
+            if index >= length {
+                return Err(svf_error(line, format!("hex value '{}' is longer than {} bits", hex, length)));
+            }
+            set_bit(&mut data, index, true);
+        }
+    }
+    Ok(data)
+}
+
+fn svf_error(line: usize, message: String) -> FtdiContextError {
+    common_error(-1, format!("SVF line {}: {}", line, message))
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    /// content of parentheses with whitespace removed
+    Hex(String),
+}
+
+fn tokenize(text: &str, line: usize) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut characters = text.chars().peekable();
+    while let Some(&character) = characters.peek() {
+        if character.is_whitespace() {
+            characters.next();
+        } else if character == '(' {
+            characters.next();
+            let mut hex = String::new();
+            loop {
+                match characters.next() {
+                    Some(')') => break,
+                    Some(value) if value.is_whitespace() => {}
+                    Some(value) => hex.push(value),
+                    None => return Err(svf_error(line, "missing ')'".to_string())),
+                }
+            }
+            tokens.push(Token::Hex(hex));
+        } else {
+            let mut word = String::new();
+            while let Some(&value) = characters.peek() {
+                if value.is_whitespace() || value == '(' {
+                    break;
+                }
+                word.push(value);
+                characters.next();
+            }
+            tokens.push(Token::Word(word.to_ascii_uppercase()));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Cursor over statement tokens
+struct Tokens {
+    tokens: Vec<Token>,
+    position: usize,
+    line: usize,
+}
+impl Tokens {
+    fn peek_word(&self) -> Option<&str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+    fn word(&mut self, what: &str) -> Result<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) => {
+                self.position += 1;
+                Ok(word.clone())
+            }
+            _ => Err(svf_error(self.line, format!("{} expected", what))),
+        }
+    }
+    fn hex(&mut self) -> Result<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Hex(hex)) => {
+                self.position += 1;
+                Ok(hex.clone())
+            }
+            _ => Err(svf_error(self.line, "hex value in parentheses expected".to_string())),
+        }
+    }
+    fn number(&mut self, what: &str) -> Result<f64> {
+        let word = self.word(what)?;
+        match word.parse::<f64>() {
+            Ok(value) if value >= 0.0 && value.is_finite() => Ok(value),
+            _ => Err(svf_error(self.line, format!("invalid {} '{}'", what, word))),
+        }
+    }
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        let word = self.word(keyword)?;
+        if word != keyword {
+            return Err(svf_error(self.line, format!("{} expected, found '{}'", keyword, word)));
+        }
+        Ok(())
+    }
+    fn state(&mut self) -> Result<TapState> {
+        let word = self.word("TAP state")?;
+        svf_state(&word).ok_or_else(|| svf_error(self.line, format!("unknown TAP state '{}'", word)))
+    }
+    fn stable_state(&mut self) -> Result<TapState> {
+        let state = self.state()?;
+        if !state.is_stable() {
+            return Err(svf_error(self.line, format!("{:?} is not a stable state", state)));
+        }
+        Ok(state)
+    }
+    fn is_done(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+    fn end(&self) -> Result<()> {
+        if !self.is_done() {
+            return Err(svf_error(self.line, format!("unexpected {:?}", self.tokens[self.position])));
+        }
+        Ok(())
+    }
+}
+
+/// Parse SVF text into statements
+pub fn parse_svf(text: &str) -> Result<Vec<SvfStatement>> {
+    debug!("start 'parse_svf' ...");
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 0;
+    for (index, source_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let comment = [source_line.find('!'), source_line.find("//")].iter()
+            .filter_map(|position| *position)
+            .min()
+            .unwrap_or(source_line.len());
+        let mut rest = &source_line[..comment];
+        while !rest.is_empty() {
+            if current.trim().is_empty() && !rest.trim().is_empty() {
+                start_line = line;
+            }
+            match rest.find(';') {
+                Some(end) => {
+                    current.push_str(&rest[..end]);
+                    if !current.trim().is_empty() {
+                        statements.push(parse_statement(&current, start_line)?);
+                    }
+                    current.clear();
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    current.push_str(rest);
+                    current.push(' ');
+                    rest = "";
+                }
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        return Err(svf_error(start_line, "statement is not terminated by ';'".to_string()));
+    }
+    debug!("'parse_svf' - OK, {} statement(s)", statements.len());
+    Ok(statements)
+}
+
+fn parse_statement(text: &str, line: usize) -> Result<SvfStatement> {
+    let mut tokens = Tokens { tokens: tokenize(text, line)?, position: 0, line };
+    let keyword = tokens.word("command")?;
+    let command = match keyword.as_str() {
+        "ENDDR" => SvfCommand::EndDr(tokens.stable_state()?),
+        "ENDIR" => SvfCommand::EndIr(tokens.stable_state()?),
+        "FREQUENCY" => {
+            if tokens.is_done() {
+                SvfCommand::Frequency(None)
+            } else {
+                let frequency = tokens.number("frequency")?;
+                tokens.keyword("HZ")?;
+                SvfCommand::Frequency(Some(frequency))
+            }
+        }
+        "HDR" => SvfCommand::Hdr(parse_shift(&mut tokens)?),
+        "HIR" => SvfCommand::Hir(parse_shift(&mut tokens)?),
+        "TDR" => SvfCommand::Tdr(parse_shift(&mut tokens)?),
+        "TIR" => SvfCommand::Tir(parse_shift(&mut tokens)?),
+        "SDR" => SvfCommand::Sdr(parse_shift(&mut tokens)?),
+        "SIR" => SvfCommand::Sir(parse_shift(&mut tokens)?),
+        "RUNTEST" => parse_run_test(&mut tokens)?,
+        "STATE" => {
+            let mut path = Vec::new();
+            while !tokens.is_done() {
+                path.push(tokens.state()?);
+            }
+            match path.last() {
+                Some(state) if state.is_stable() => SvfCommand::State(path),
+                _ => return Err(svf_error(line, "STATE must end in a stable state".to_string())),
+            }
+        }
+        "TRST" => {
+            let mode = match tokens.word("TRST mode")?.as_str() {
+                "ON" => SvfTrst::On,
+                "OFF" => SvfTrst::Off,
+                "Z" => SvfTrst::Z,
+                "ABSENT" => SvfTrst::Absent,
+                other => return Err(svf_error(line, format!("unknown TRST mode '{}'", other))),
+            };
+            SvfCommand::Trst(mode)
+        }
+        "PIO" | "PIOMAP" => return Err(svf_error(line, format!("{} is not supported", keyword))),
+        other => return Err(svf_error(line, format!("unknown command '{}'", other))),
+    };
+    tokens.end()?;
+    Ok(SvfStatement { line, command })
+}
+
+fn parse_shift(tokens: &mut Tokens) -> Result<SvfShift> {
+    let length = tokens.number("length")?;
+    if length.fract() != 0.0 {
+        return Err(svf_error(tokens.line, format!("invalid length {}", length)));
+    }
+    let mut shift = SvfShift { length: length as usize, ..SvfShift::default() };
+    while !tokens.is_done() {
+        let name = tokens.word("TDI, TDO, MASK or SMASK")?;
+        let value = hex_to_bits(&tokens.hex()?, shift.length, tokens.line)?;
+        let target = match name.as_str() {
+            "TDI" => &mut shift.tdi,
+            "TDO" => &mut shift.tdo,
+            "MASK" => &mut shift.mask,
+            "SMASK" => &mut shift.smask,
+            other => return Err(svf_error(tokens.line, format!("unknown scan parameter '{}'", other))),
+        };
+        *target = Some(value);
+    }
+    Ok(shift)
+}
+
+/// RUNTEST [run_state] run_count TCK|SCK [min_time SEC [MAXIMUM max_time SEC]] [ENDSTATE end_state]
+/// RUNTEST [run_state] min_time SEC [MAXIMUM max_time SEC] [ENDSTATE end_state]
+fn parse_run_test(tokens: &mut Tokens) -> Result<SvfCommand> {
+    let mut run_state = None;
+    if let Some(state) = tokens.peek_word().and_then(svf_state) {
+        tokens.position += 1;
+        if !state.is_stable() {
+            return Err(svf_error(tokens.line, format!("{:?} is not a stable state", state)));
+        }
+        run_state = Some(state);
+    }
+    let mut run_count = None;
+    let mut clock = SvfRunClock::Tck;
+    let mut min_time = None;
+    let value = tokens.number("run count or time")?;
+    match tokens.word("TCK, SCK or SEC")?.as_str() {
+        "TCK" => run_count = Some(value as u32),
+        "SCK" => {
+            run_count = Some(value as u32);
+            clock = SvfRunClock::Sck;
+        }
+        "SEC" => min_time = Some(value),
+        other => return Err(svf_error(tokens.line, format!("TCK, SCK or SEC expected, found '{}'", other))),
+    }
+    if min_time.is_none() && tokens.peek_word().is_some_and(|word| word.parse::<f64>().is_ok()) {
+        min_time = Some(tokens.number("minimum time")?);
+        tokens.keyword("SEC")?;
+    }
+    let mut max_time = None;
+    if tokens.peek_word() == Some("MAXIMUM") {
+        tokens.position += 1;
+        max_time = Some(tokens.number("maximum time")?);
+        tokens.keyword("SEC")?;
+    }
+    let mut end_state = None;
+    if tokens.peek_word() == Some("ENDSTATE") {
+        tokens.position += 1;
+        end_state = Some(tokens.stable_state()?);
+    }
+    Ok(SvfCommand::RunTest { run_state, run_count, clock, min_time, max_time, end_state })
+}
+
+/// Append 'length' bits of 'source' to LSB first vector holding 'total' bits
+fn append_bits(target: &mut Vec<u8>, total: usize, source: &[u8], length: usize) {
+    target.resize((total + length).div_ceil(8), 0);
+    for bit in 0..length {
+        set_bit(target, total + bit, get_bit(source, bit));
+    }
+}
+
+/// Executes SVF statements on JTAG scan chain
+pub struct SvfPlayer<'a, T: MpsseTransport> {
+    jtag: &'a mut JtagController<T>,
+    end_dr: TapState,
+    end_ir: TapState,
+    run_state: TapState,
+    run_end_state: TapState,
+    frequency: Option<f64>,
+    hdr: SvfShift,
+    hir: SvfShift,
+    tdr: SvfShift,
+    tir: SvfShift,
+    sdr: SvfShift,
+    sir: SvfShift,
+    mismatch: Option<TdoMismatch>,
+}
+impl<'a, T: MpsseTransport> SvfPlayer<'a, T> {
+    /// JTAG controller must be initialized
+    pub fn new(jtag: &'a mut JtagController<T>) -> Self {
+        SvfPlayer {
+            jtag,
+            end_dr: TapState::RunTestIdle,
+            end_ir: TapState::RunTestIdle,
+            run_state: TapState::RunTestIdle,
+            run_end_state: TapState::RunTestIdle,
+            frequency: None,
+            hdr: SvfShift::default(),
+            hir: SvfShift::default(),
+            tdr: SvfShift::default(),
+            tir: SvfShift::default(),
+            sdr: SvfShift::default(),
+            sir: SvfShift::default(),
+            mismatch: None,
+        }
+    }
+
+    /// TDO mismatch that stopped the last play
+    pub fn mismatch(&self) -> Option<&TdoMismatch> {
+        self.mismatch.as_ref()
+    }
+
+    /// Parse and play SVF text, see play()
+    pub fn play_str<F: FnMut(usize, usize)>(&mut self, text: &str, progress: F) -> Result<()> {
+        let statements = parse_svf(text)?;
+        self.play(&statements, progress)
+    }
+
+    /// Execute statements, 'progress' is called with (done, total) statement count after each one.
+    /// Stops on the first TDO mismatch, it's returned as error and kept in mismatch().
+    pub fn play<F: FnMut(usize, usize)>(&mut self, statements: &[SvfStatement], mut progress: F) -> Result<()> {
+        debug!("start 'svf play' ...");
+        self.mismatch = None;
+        for (index, statement) in statements.iter().enumerate() {
+            self.execute(statement)?;
+            progress(index + 1, statements.len());
+        }
+        info!("SVF played, {} statement(s)", statements.len());
+        Ok(())
+    }
+
+    fn execute(&mut self, statement: &SvfStatement) -> Result<()> {
+        let line = statement.line;
+        match &statement.command {
+            SvfCommand::EndDr(state) => self.end_dr = *state,
+            SvfCommand::EndIr(state) => self.end_ir = *state,
+            SvfCommand::Frequency(frequency) => {
+                // 0 gives the maximum
+                let actual = self.jtag.set_frequency(frequency.map_or(0, |frequency| frequency as u32))?;
+                self.frequency = Some(actual as f64);
+            }
+            SvfCommand::Hdr(shift) => Self::merge(&mut self.hdr, shift, line)?,
+            SvfCommand::Hir(shift) => Self::merge(&mut self.hir, shift, line)?,
+            SvfCommand::Tdr(shift) => Self::merge(&mut self.tdr, shift, line)?,
+            SvfCommand::Tir(shift) => Self::merge(&mut self.tir, shift, line)?,
+            SvfCommand::Sdr(shift) => {
+                Self::merge(&mut self.sdr, shift, line)?;
+                self.scan(false, line)?;
+            }
+            SvfCommand::Sir(shift) => {
+                Self::merge(&mut self.sir, shift, line)?;
+                self.scan(true, line)?;
+            }
+            SvfCommand::RunTest { run_state, run_count, clock, min_time, max_time, end_state } => {
+                if let Some(state) = run_state {
+                    self.run_state = *state;
+                    self.run_end_state = *state;
+                }
+                if let Some(state) = end_state {
+                    self.run_end_state = *state;
+                }
+                let mut cycles = match clock {
+                    SvfRunClock::Tck => run_count.unwrap_or(0),
+                    SvfRunClock::Sck => {
+                        warn!("SVF line {}: system clock is not available, RUNTEST uses minimum time only", line);
+                        0
+                    }
+                };
+                if let (Some(time), Some(frequency)) = (min_time, self.frequency) {
+                    cycles = std::cmp::max(cycles, (time * frequency).ceil() as u32);
+                }
+                if let Some(time) = max_time {
+                    debug!("SVF line {}: maximum time {} s is not enforced", line, time);
+                }
+                self.jtag.wait_in_state(self.run_state, cycles)?;
+                if let Some(time) = min_time {
+                    // MPSSE commands are buffered, sleeping makes sure the time passed on the chain
+                    std::thread::sleep(Duration::from_secs_f64(*time));
+                }
+                self.jtag.goto_state(self.run_end_state)?;
+            }
+            SvfCommand::State(path) => {
+                for state in path {
+                    if *state == TapState::TestLogicReset {
+                        self.jtag.reset()?;
+                    } else {
+                        self.jtag.goto_state(*state)?;
+                    }
+                }
+            }
+            SvfCommand::Trst(mode) => {
+                if *mode == SvfTrst::On {
+                    warn!("SVF line {}: no TRST pin, TAP is reset by TMS", line);
+                    self.jtag.reset()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Update persistent scan parameters with new statement: TDI, MASK and SMASK are kept
+    /// while length is the same, TDO is compared only when given.
+    fn merge(current: &mut SvfShift, shift: &SvfShift, line: usize) -> Result<()> {
+        let ones = || {
+            let mut data = vec![0xffu8; shift.length.div_ceil(8)];
+            if !shift.length.is_multiple_of(8) {
+                *data.last_mut().unwrap() = (1u8 << (shift.length % 8)) - 1;
+            }
+            data
+        };
+        if shift.length != current.length {
+            if shift.tdi.is_none() && shift.length > 0 {
+                return Err(svf_error(line, format!("TDI is required when length changes to {}", shift.length)));
+            }
+            current.length = shift.length;
+            current.tdi = Some(shift.tdi.clone().unwrap_or_default());
+            current.mask = Some(shift.mask.clone().unwrap_or_else(ones));
+            current.smask = Some(shift.smask.clone().unwrap_or_else(ones));
+        } else {
+            if shift.tdi.is_some() {
+                current.tdi = shift.tdi.clone();
+            }
+            if shift.mask.is_some() {
+                current.mask = shift.mask.clone();
+            }
+            if shift.smask.is_some() {
+                current.smask = shift.smask.clone();
+            }
+            if current.tdi.is_none() && current.length > 0 {
+                return Err(svf_error(line, "TDI is required by the first scan".to_string()));
+            }
+            if current.mask.is_none() {
+                current.mask = Some(ones());
+            }
+        }
+        current.tdo = shift.tdo.clone();
+        Ok(())
+    }
+
+    /// Shift header, scan and trailer vectors in one go and compare TDO
+    fn scan(&mut self, instruction: bool, line: usize) -> Result<()> {
+        let (parts, end_state, name) = if instruction {
+            ([&self.hir, &self.sir, &self.tir], self.end_ir, "SIR")
+        } else {
+            ([&self.hdr, &self.sdr, &self.tdr], self.end_dr, "SDR")
+        };
+        let mut tdi = Vec::new();
+        let mut expected = Vec::new();
+        let mut mask = Vec::new();
+        let mut total = 0;
+        let mut compare = false;
+        for part in parts.iter() {
+            let zeros = vec![0u8; part.length.div_ceil(8)];
+            append_bits(&mut tdi, total, part.tdi.as_deref().unwrap_or(&zeros), part.length);
+            match &part.tdo {
+                Some(tdo) => {
+                    compare = true;
+                    append_bits(&mut expected, total, tdo, part.length);
+                    append_bits(&mut mask, total, part.mask.as_deref().unwrap_or(&zeros), part.length);
+                }
+                None => {
+                    append_bits(&mut expected, total, &zeros, part.length);
+                    append_bits(&mut mask, total, &zeros, part.length);
+                }
+            }
+            total += part.length;
+        }
+        if total == 0 {
+            return self.jtag.goto_state(end_state);
+        }
+        let tdo = if instruction {
+            self.jtag.shift_ir(&tdi, total, end_state)?
+        } else {
+            self.jtag.shift_dr(&tdi, total, end_state)?
+        };
+        if compare {
+            let location = format!("line {} ({})", line, name);
+            if let Some(mismatch) = TdoMismatch::check(&location, total, &expected, &tdo, &mask) {
+                let error = FtdiContextError::UsbCommonError {
+                    code: -4, message: format!("SVF {}", mismatch),
+                    backtrace: GenerateBacktrace::generate()
+                };
+                error!("{}", error);
+                self.mismatch = Some(mismatch);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
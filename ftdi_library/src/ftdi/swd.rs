@@ -0,0 +1,457 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use log::{debug, info, warn};
+use crate::ftdi::constants::{*};
+use crate::ftdi::ftdi_context::{Result, common_error};
+use crate::ftdi::mpsse::{MpsseTransport, mpsse_clock_commands};
+
+/// ADBUS pins used by SWD: SWCLK (0), SWDIO output through resistor (1), SWDIO input (2)
+pub const SWD_PIN_SWCLK: u8 = 0x01;
+pub const SWD_PIN_SWDIO_OUT: u8 = 0x02;
+pub const SWD_PIN_SWDIO_IN: u8 = 0x04;
+
+/// How many WAIT acknowledges in a row are retried before transfer fails
+pub const SWD_WAIT_RETRIES: u32 = 100;
+/// JTAG-to-SWD select sequence, LSB first
+pub const SWD_JTAG_TO_SWD: u16 = 0xe79e;
+
+/// DP registers (address bits A[3:2])
+pub const DP_DPIDR: u8 = 0x0;
+pub const DP_ABORT: u8 = 0x0;
+pub const DP_CTRL_STAT: u8 = 0x4;
+pub const DP_SELECT: u8 = 0x8;
+pub const DP_RDBUFF: u8 = 0xc;
+
+/// DP ABORT register bits
+pub const DP_ABORT_DAPABORT: u32 = 1 << 0;
+pub const DP_ABORT_STKCMPCLR: u32 = 1 << 1;
+pub const DP_ABORT_STKERRCLR: u32 = 1 << 2;
+pub const DP_ABORT_WDERRCLR: u32 = 1 << 3;
+pub const DP_ABORT_ORUNERRCLR: u32 = 1 << 4;
+/// clears all the sticky error flags
+pub const DP_ABORT_CLEAR_ERRORS: u32 = DP_ABORT_STKCMPCLR | DP_ABORT_STKERRCLR | DP_ABORT_WDERRCLR | DP_ABORT_ORUNERRCLR;
+
+/// DP CTRL/STAT register bits
+pub const DP_CTRL_STICKYERR: u32 = 1 << 5;
+pub const DP_CTRL_CDBGPWRUPREQ: u32 = 1 << 28;
+pub const DP_CTRL_CDBGPWRUPACK: u32 = 1 << 29;
+pub const DP_CTRL_CSYSPWRUPREQ: u32 = 1 << 30;
+pub const DP_CTRL_CSYSPWRUPACK: u32 = 1 << 31;
+
+/// MEM-AP registers, the upper nibble is written to SELECT.APBANKSEL
+pub const AP_CSW: u8 = 0x00;
+pub const AP_TAR: u8 = 0x04;
+pub const AP_DRW: u8 = 0x0c;
+pub const AP_IDR: u8 = 0xfc;
+
+/// MEM-AP CSW: 32 bit access
+pub const AP_CSW_SIZE_WORD: u32 = 0x02;
+/// MEM-AP CSW: single auto increment of TAR after each DRW access
+pub const AP_CSW_ADDRINC_SINGLE: u32 = 0x10;
+/// MEM-AP CSW: debug software access enable and privileged data access (HPROT), as used for Cortex-M
+pub const AP_CSW_DEFAULT: u32 = 0x2300_0000;
+/// TAR auto increment is guaranteed within 1 KB block only
+pub const AP_TAR_AUTOINC_BLOCK: u32 = 0x400;
+
+/// Cortex-M Debug Halting Control and Status Register
+pub const CORTEX_M_DHCSR: u32 = 0xe000_edf0;
+pub const CORTEX_M_DHCSR_DBGKEY: u32 = 0xa05f_0000;
+pub const CORTEX_M_DHCSR_C_DEBUGEN: u32 = 1 << 0;
+pub const CORTEX_M_DHCSR_C_HALT: u32 = 1 << 1;
+pub const CORTEX_M_DHCSR_S_HALT: u32 = 1 << 17;
+
+/// How many CTRL/STAT reads are done waiting for power up acknowledge
+const SWD_POWER_UP_POLLS: u32 = 100;
+
+/// Clock data bits out on -ve edge, LSB first, no read
+const SWD_WRITE_BITS: u8 = MPSSE_DO_WRITE | MPSSE_LSB | MPSSE_BITMODE | MPSSE_WRITE_NEG;
+/// Clock data bytes out on -ve edge, LSB first, no read
+const SWD_WRITE_BYTES: u8 = MPSSE_DO_WRITE | MPSSE_LSB | MPSSE_WRITE_NEG;
+/// Clock data bits in on +ve edge, LSB first
+const SWD_READ_BITS: u8 = MPSSE_DO_READ | MPSSE_LSB | MPSSE_BITMODE;
+/// Clock data bytes in on +ve edge, LSB first
+const SWD_READ_BYTES: u8 = MPSSE_DO_READ | MPSSE_LSB;
+
+/// Target acknowledge of SWD request, as three bits received LSB first
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SwdAck {
+    Ok,
+    Wait,
+    Fault,
+    /// anything else, usually 0b111 when nobody drives the line
+    Invalid(u8),
+}
+impl From<u8> for SwdAck {
+    fn from(value: u8) -> Self {
+        match value {
+            0b001 => SwdAck::Ok,
+            0b010 => SwdAck::Wait,
+            0b100 => SwdAck::Fault,
+            other => SwdAck::Invalid(other),
+        }
+    }
+}
+impl Display for SwdAck {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SwdAck::Ok => write!(f, "OK"),
+            SwdAck::Wait => write!(f, "WAIT"),
+            SwdAck::Fault => write!(f, "FAULT"),
+            SwdAck::Invalid(value) => write!(f, "invalid ({:#05b})", value),
+        }
+    }
+}
+
+/// Debug or access port selected by request APnDP bit
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SwdPort {
+    Dp,
+    Ap,
+}
+
+/// SWD request packet: start, APnDP, RnW, A[2:3], parity, stop, park
+pub fn swd_request(port: SwdPort, read: bool, address: u8) -> u8 {
+    let body = ((port == SwdPort::Ap) as u8) | ((read as u8) << 1) | (((address >> 2) & 0x03) << 2);
+    let parity = (body.count_ones() & 1) as u8;
+    0x01 | (body << 1) | (parity << 5) | 0x80
+}
+
+/// Serial Wire Debug master over MPSSE.
+/// SWDIO is driven by ADBUS1 through a resistor and read back on ADBUS2, ADBUS1 is released for target replies.
+pub struct SwdController<T: MpsseTransport> {
+    transport: T,
+    /// last value written to DP SELECT
+    select: Option<u32>,
+}
+impl<T: MpsseTransport> SwdController<T> {
+    pub fn new(transport: T) -> Self {
+        SwdController { transport, select: None }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Setup MPSSE pins and SWCLK frequency.
+    /// MPSSE mode must be already enabled on the chip (ftdi_set_bitmode with BITMODE_MPSSE).
+    /// return actual SWCLK frequency
+    pub fn init(&mut self, frequency: u32, high_speed: bool) -> Result<u32> {
+        debug!("start 'swd init' ...");
+        let (mut commands, actual) = mpsse_clock_commands(frequency, high_speed);
+        commands.push(LOOPBACK_END);
+        commands.extend_from_slice(&Self::drive(true));
+        self.transport.mpsse_write(&commands)?;
+        info!("SWD initialized, SWCLK = {} Hz", actual);
+        Ok(actual)
+    }
+
+    /// Line reset: more than 50 clocks with SWDIO high followed by idle clocks
+    pub fn line_reset(&mut self) -> Result<()> {
+        let mut commands = Vec::with_capacity(16);
+        commands.extend_from_slice(&Self::drive(true));
+        Self::append_line_reset(&mut commands);
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 0, 0, 0x00]);
+        self.transport.mpsse_write(&commands)?;
+        self.select = None;
+        Ok(())
+    }
+
+    /// Switch SWJ-DP from JTAG to SWD and read DPIDR, that is required after line reset
+    /// return DPIDR value
+    pub fn jtag_to_swd(&mut self) -> Result<u32> {
+        debug!("start 'jtag_to_swd' ...");
+        let mut commands = Vec::with_capacity(32);
+        commands.extend_from_slice(&Self::drive(true));
+        Self::append_line_reset(&mut commands);
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 1, 0, (SWD_JTAG_TO_SWD & 0xff) as u8, (SWD_JTAG_TO_SWD >> 8) as u8]);
+        Self::append_line_reset(&mut commands);
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 0, 0, 0x00]);
+        self.transport.mpsse_write(&commands)?;
+        self.select = None;
+        let dpidr = self.read_dp(DP_DPIDR)?;
+        info!("SWD connected, DPIDR = {:#010x}", dpidr);
+        Ok(dpidr)
+    }
+
+    /// Clear sticky errors and request debug and system power up
+    pub fn power_up(&mut self) -> Result<()> {
+        debug!("start 'swd power_up' ...");
+        self.write_dp(DP_ABORT, DP_ABORT_CLEAR_ERRORS)?;
+        self.write_dp(DP_SELECT, 0)?;
+        self.write_dp(DP_CTRL_STAT, DP_CTRL_CDBGPWRUPREQ | DP_CTRL_CSYSPWRUPREQ)?;
+        let acknowledged = DP_CTRL_CDBGPWRUPACK | DP_CTRL_CSYSPWRUPACK;
+        for _ in 0..SWD_POWER_UP_POLLS {
+            if self.read_dp(DP_CTRL_STAT)? & acknowledged == acknowledged {
+                debug!("'swd power_up' - OK");
+                return Ok(());
+            }
+        }
+        Err(common_error(-5, "SWD debug power up is not acknowledged".to_string()))
+    }
+
+    /// Clear sticky error flags after FAULT
+    pub fn clear_errors(&mut self) -> Result<()> {
+        self.write_dp(DP_ABORT, DP_ABORT_CLEAR_ERRORS)
+    }
+
+    pub fn read_dp(&mut self, address: u8) -> Result<u32> {
+        self.transfer(SwdPort::Dp, address, None)
+    }
+
+    pub fn write_dp(&mut self, address: u8, value: u32) -> Result<()> {
+        self.transfer(SwdPort::Dp, address, Some(value))?;
+        if address == DP_SELECT {
+            self.select = Some(value);
+        }
+        Ok(())
+    }
+
+    /// Read AP register, AP reads are posted so result is taken from DP RDBUFF
+    pub fn read_ap(&mut self, apsel: u8, address: u8) -> Result<u32> {
+        self.select_ap(apsel, address)?;
+        self.transfer(SwdPort::Ap, address, None)?;
+        self.read_dp(DP_RDBUFF)
+    }
+
+    pub fn write_ap(&mut self, apsel: u8, address: u8, value: u32) -> Result<()> {
+        self.select_ap(apsel, address)?;
+        self.transfer(SwdPort::Ap, address, Some(value))?;
+        Ok(())
+    }
+
+    /// Read AP register 'count' times in a row (e.g. MEM-AP DRW with auto increment).
+    /// Every posted read returns the result of the previous one, the last comes from RDBUFF.
+    pub fn read_ap_repeated(&mut self, apsel: u8, address: u8, count: usize) -> Result<Vec<u32>> {
+        let mut values = Vec::with_capacity(count);
+        if count == 0 {
+            return Ok(values);
+        }
+        self.select_ap(apsel, address)?;
+        self.transfer(SwdPort::Ap, address, None)?;
+        for _ in 1..count {
+            values.push(self.transfer(SwdPort::Ap, address, None)?);
+        }
+        values.push(self.read_dp(DP_RDBUFF)?);
+        Ok(values)
+    }
+
+    fn select_ap(&mut self, apsel: u8, address: u8) -> Result<()> {
+        let select = ((apsel as u32) << 24) | (address as u32 & 0xf0);
+        if self.select != Some(select) {
+            self.write_dp(DP_SELECT, select)?;
+        }
+        Ok(())
+    }
+
+    /// One SWD transfer with WAIT retries, write when 'value' is given.
+    /// return read value, or zero for writes
+    fn transfer(&mut self, port: SwdPort, address: u8, value: Option<u32>) -> Result<u32> {
+        let read = value.is_none();
+        let request = swd_request(port, read, address);
+        let mut ack = SwdAck::Wait;
+        for attempt in 0..=SWD_WAIT_RETRIES {
+            ack = self.request(request)?;
+            if ack != SwdAck::Wait {
+                break;
+            }
+            debug!("SWD WAIT on {:?} {:#x}, retry {}", port, address, attempt + 1);
+            self.end_without_data()?;
+        }
+        match ack {
+            SwdAck::Ok => {}
+            SwdAck::Fault => {
+                self.end_without_data()?;
+                warn!("SWD FAULT on {:?} {:#x}, clearing sticky errors", port, address);
+                // ABORT write is accepted even in FAULT state
+                if port != SwdPort::Dp || address != DP_ABORT || read {
+                    self.clear_errors()?;
+                }
+                return Err(common_error(-3, format!("SWD {:?} {:#x} {} failed with FAULT", port, address, if read { "read" } else { "write" })));
+            }
+            SwdAck::Wait => {
+                self.end_without_data()?;
+                return Err(common_error(-2, format!("SWD {:?} {:#x} is still WAIT after {} retries", port, address, SWD_WAIT_RETRIES)));
+            }
+            SwdAck::Invalid(_) => {
+                self.end_without_data()?;
+                // target is not connected or out of sync, jtag_to_swd() brings it back
+                return Err(common_error(-4, format!("SWD {:?} {:#x} got {} acknowledge", port, address, ack)));
+            }
+        }
+        match value {
+            None => self.read_data(port, address),
+            Some(value) => {
+                self.write_data(value)?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// Send request packet, release SWDIO and read turnaround and acknowledge
+    fn request(&mut self, request: u8) -> Result<SwdAck> {
+        let mut commands = Vec::with_capacity(12);
+        commands.extend_from_slice(&Self::drive(true));
+        commands.extend_from_slice(&[SWD_WRITE_BITS, 7, request]);
+        commands.extend_from_slice(&Self::drive(false));
+        commands.extend_from_slice(&[SWD_READ_BITS, 3, SEND_IMMEDIATE]);
+        self.transport.mpsse_write(&commands)?;
+        let reply = self.transport.mpsse_read(1)?;
+        // 4 bits land in the upper nibble: turnaround then ACK[0..2]
+        Ok(SwdAck::from(reply[0] >> 5))
+    }
+
+    /// Read data and parity, then turnaround back to host and idle
+    fn read_data(&mut self, port: SwdPort, address: u8) -> Result<u32> {
+        let mut commands = Vec::with_capacity(16);
+        commands.extend_from_slice(&[SWD_READ_BYTES, 3, 0, SWD_READ_BITS, 1]);
+        commands.extend_from_slice(&Self::drive(true));
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 0, 0, 0x00, SEND_IMMEDIATE]);
+        self.transport.mpsse_write(&commands)?;
+        let reply = self.transport.mpsse_read(5)?;
+        let value = u32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]);
+        // parity and turnaround bits land in the two upper bits
+        let parity = (reply[4] >> 6) & 0x01;
+        if value.count_ones() & 1 != parity as u32 {
+            return Err(common_error(-6, format!("SWD {:?} {:#x} read parity error, value = {:#010x}", port, address, value)));
+        }
+        Ok(value)
+    }
+
+    /// Turnaround back to host, write data and parity, then idle
+    fn write_data(&mut self, value: u32) -> Result<()> {
+        let parity = (value.count_ones() & 1) as u8;
+        let bytes = value.to_le_bytes();
+        let mut commands = Vec::with_capacity(24);
+        // one clock with SWDIO still released
+        commands.extend_from_slice(&[SWD_WRITE_BITS, 0, 0]);
+        commands.extend_from_slice(&Self::drive(true));
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 3, 0, bytes[0], bytes[1], bytes[2], bytes[3]]);
+        commands.extend_from_slice(&[SWD_WRITE_BITS, 0, parity]);
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 0, 0, 0x00]);
+        self.transport.mpsse_write(&commands)
+    }
+
+    /// Turnaround after WAIT or FAULT acknowledge, there is no data phase
+    fn end_without_data(&mut self) -> Result<()> {
+        let mut commands = Vec::with_capacity(12);
+        commands.extend_from_slice(&[SWD_WRITE_BITS, 0, 0]);
+        commands.extend_from_slice(&Self::drive(true));
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 0, 0, 0x00]);
+        self.transport.mpsse_write(&commands)
+    }
+
+    /// Low byte pins setup with SWDIO output driven or released
+    fn drive(enabled: bool) -> [u8; 3] {
+        let direction = if enabled { SWD_PIN_SWCLK | SWD_PIN_SWDIO_OUT } else { SWD_PIN_SWCLK };
+        [SET_BITS_LOW, SWD_PIN_SWDIO_OUT, direction]
+    }
+
+    fn append_line_reset(commands: &mut Vec<u8>) {
+        // 56 clocks with SWDIO high
+        commands.extend_from_slice(&[SWD_WRITE_BYTES, 6, 0]);
+        commands.extend_from_slice(&[0xff; 7]);
+    }
+}
+
+/// Minimal MEM-AP access to target memory by 32 bit words
+pub struct MemAp<'a, T: MpsseTransport> {
+    swd: &'a mut SwdController<T>,
+    apsel: u8,
+    /// last value written to CSW
+    csw: Option<u32>,
+}
+impl<'a, T: MpsseTransport> MemAp<'a, T> {
+    /// Debug port must be powered up
+    pub fn new(swd: &'a mut SwdController<T>, apsel: u8) -> Self {
+        MemAp { swd, apsel, csw: None }
+    }
+
+    /// AP identification register
+    pub fn idr(&mut self) -> Result<u32> {
+        self.swd.read_ap(self.apsel, AP_IDR)
+    }
+
+    pub fn read32(&mut self, address: u32) -> Result<u32> {
+        self.setup(address, false)?;
+        self.swd.read_ap(self.apsel, AP_DRW)
+    }
+
+    pub fn write32(&mut self, address: u32, value: u32) -> Result<()> {
+        self.setup(address, false)?;
+        self.swd.write_ap(self.apsel, AP_DRW, value)
+    }
+
+    /// Read 'count' words using TAR auto increment
+    pub fn read_block(&mut self, address: u32, count: usize) -> Result<Vec<u32>> {
+        let mut values = Vec::with_capacity(count);
+        let mut address = address;
+        while values.len() < count {
+            let words = Self::words_in_block(address, count - values.len());
+            self.setup(address, true)?;
+            values.extend(self.swd.read_ap_repeated(self.apsel, AP_DRW, words)?);
+            address = address.wrapping_add(words as u32 * 4);
+        }
+        Ok(values)
+    }
+
+    /// Write words using TAR auto increment
+    pub fn write_block(&mut self, address: u32, values: &[u32]) -> Result<()> {
+        let mut address = address;
+        let mut done = 0;
+        while done < values.len() {
+            let words = Self::words_in_block(address, values.len() - done);
+            self.setup(address, true)?;
+            for value in &values[done..done + words] {
+                self.swd.write_ap(self.apsel, AP_DRW, *value)?;
+            }
+            done += words;
+            address = address.wrapping_add(words as u32 * 4);
+        }
+        Ok(())
+    }
+
+    /// Halt Cortex-M core and wait until it reports halted state
+    pub fn cortex_m_halt(&mut self) -> Result<()> {
+        debug!("start 'cortex_m_halt' ...");
+        self.write32(CORTEX_M_DHCSR, CORTEX_M_DHCSR_DBGKEY | CORTEX_M_DHCSR_C_DEBUGEN | CORTEX_M_DHCSR_C_HALT)?;
+        for _ in 0..SWD_POWER_UP_POLLS {
+            if self.cortex_m_is_halted()? {
+                debug!("'cortex_m_halt' - OK");
+                return Ok(());
+            }
+        }
+        Err(common_error(-5, "Cortex-M core didn't halt".to_string()))
+    }
+
+    /// Let Cortex-M core run, debug stays enabled
+    pub fn cortex_m_resume(&mut self) -> Result<()> {
+        self.write32(CORTEX_M_DHCSR, CORTEX_M_DHCSR_DBGKEY | CORTEX_M_DHCSR_C_DEBUGEN)
+    }
+
+    pub fn cortex_m_is_halted(&mut self) -> Result<bool> {
+        Ok(self.read32(CORTEX_M_DHCSR)? & CORTEX_M_DHCSR_S_HALT != 0)
+    }
+
+    /// Words that can be accessed from address before auto increment crosses 1 KB block
+    fn words_in_block(address: u32, count: usize) -> usize {
+        let left = (AP_TAR_AUTOINC_BLOCK - (address % AP_TAR_AUTOINC_BLOCK)) / 4;
+        std::cmp::min(count, left as usize)
+    }
+
+    fn setup(&mut self, address: u32, increment: bool) -> Result<()> {
+        if !address.is_multiple_of(4) {
+            return Err(common_error(-1, format!("MEM-AP address {:#010x} is not word aligned", address)));
+        }
+        let csw = AP_CSW_DEFAULT | AP_CSW_SIZE_WORD | if increment { AP_CSW_ADDRINC_SINGLE } else { 0 };
+        if self.csw != Some(csw) {
+            self.swd.write_ap(self.apsel, AP_CSW, csw)?;
+            self.csw = Some(csw);
+        }
+        self.swd.write_ap(self.apsel, AP_TAR, address)
+    }
+}
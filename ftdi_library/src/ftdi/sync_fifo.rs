@@ -0,0 +1,413 @@
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::ptr;
+use libc::{c_int, c_uchar, c_uint, c_void, timeval};
+use libusb_sys as ffi;
+use log::{debug, error, info, warn};
+use snafu::GenerateBacktrace;
+use crate::ftdi::chip::ChipCapabilities;
+use crate::ftdi::constants::{ftdi_chip_type, ftdi_interface, ftdi_mpsse_mode, CHANNEL_IS_FIFO};
+use crate::ftdi::ftdi_context::{ftdi_context, FtdiContextError, Result, common_error};
+
+/// Latency timer for sync FIFO, short packets are flushed to host after it's elapsed
+pub const SYNC_FIFO_LATENCY: u8 = 2;
+/// Bytes moved by one bulk transfer, multiple of max packet size
+pub const SYNC_FIFO_TRANSFER_SIZE: usize = 64 * 1024;
+/// Bulk transfers kept in flight in every direction
+pub const SYNC_FIFO_TRANSFERS: usize = 8;
+/// RTS/CTS handshake, required by the chip in sync FIFO mode
+const SYNC_FIFO_FLOW_CONTROL: u16 = 0x1 << 8;
+/// Modem status bytes at the start of every received packet
+const SYNC_FIFO_STATUS_BYTES: usize = 2;
+/// How long one libusb event loop iteration waits for a completed transfer
+const SYNC_FIFO_EVENT_TIMEOUT_USEC: i64 = 100_000;
+
+/// Finished bulk transfer
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FifoCompletion {
+    /// Data received from chip, modem status bytes included
+    Read(Vec<u8>),
+    /// Number of bytes sent to chip
+    Written(usize),
+}
+
+/// Chip functions and queued bulk transfers used by sync FIFO session.
+/// UsbTransferQueue submits them to the chip.
+pub trait FifoTransport {
+    fn chip_type(&self) -> ftdi_chip_type;
+    /// EEPROM hardware type of the opened channel, CHANNEL_IS_xxx
+    fn channel_type(&self) -> i32;
+    /// Interface the transport is opened on, 0 - A, 1 - B
+    fn interface(&self) -> u8;
+    /// Max packet size of bulk endpoints
+    fn packet_size(&self) -> usize;
+    fn set_bitmode(&mut self, mask: u8, mode: ftdi_mpsse_mode) -> Result<()>;
+    fn set_latency_timer(&mut self, latency: u8) -> Result<()>;
+    fn set_flow_control(&mut self, flowctrl: u16) -> Result<()>;
+    /// Drop data buffered by chip in both directions
+    fn purge(&mut self) -> Result<()>;
+    /// Queue bulk read of 'size' bytes, it doesn't time out
+    fn submit_read(&mut self, size: usize) -> Result<()>;
+    /// Queue bulk write of all bytes
+    fn submit_write(&mut self, data: Vec<u8>) -> Result<()>;
+    /// Wait for the next finished transfer, transfers of one direction finish in submit order.
+    /// Read which timed out finishes as a short read.
+    fn wait_completion(&mut self) -> Result<FifoCompletion>;
+    /// Cancel all queued transfers and wait until they're gone
+    fn cancel_all(&mut self) -> Result<()>;
+}
+
+/// Queue of asynchronous libusb bulk transfers on an opened ftdi_context
+pub struct UsbTransferQueue {
+    ftdi: ftdi_context,
+    /// transfers reported by libusb callback, boxed to keep the address given as transfer user data
+    completed: Box<RefCell<VecDeque<*mut ffi::libusb_transfer>>>,
+    in_flight: Vec<*mut ffi::libusb_transfer>,
+}
+
+extern "C" fn fifo_transfer_cb(transfer: *mut ffi::libusb_transfer) {
+    let completed = unsafe { &*((*transfer).user_data as *const RefCell<VecDeque<*mut ffi::libusb_transfer>>) };
+    completed.borrow_mut().push_back(transfer);
+}
+
+impl UsbTransferQueue {
+    /// Take an opened context, its EEPROM is read and decoded for the channel type check
+    pub fn new(mut ftdi: ftdi_context) -> Result<Self> {
+        if ftdi.usb_ctx.is_none() || ftdi.usb_dev.is_none() {
+            return Err(common_error(-1, "USB device isn't opened".to_string()));
+        }
+        ftdi.ftdi_read_eeprom()?;
+        ftdi.ftdi_eeprom_decode()?;
+        Ok(UsbTransferQueue { ftdi, completed: Box::new(RefCell::new(VecDeque::new())), in_flight: Vec::new() })
+    }
+
+    pub fn context(&mut self) -> &mut ftdi_context {
+        &mut self.ftdi
+    }
+
+    /// Cancel queued transfers and return the context
+    pub fn into_inner(mut self) -> Result<ftdi_context> {
+        self.cancel_all()?;
+        Ok(std::mem::take(&mut self.ftdi))
+    }
+
+    fn submit(&mut self, endpoint: u8, buffer: Vec<u8>, timeout: i32) -> Result<()> {
+        let length = buffer.len();
+        let buffer = Box::into_raw(buffer.into_boxed_slice()) as *mut c_uchar;
+        let transfer = unsafe { ffi::libusb_alloc_transfer(0) };
+        if transfer.is_null() {
+            unsafe { drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, length))) };
+            return Err(common_error(-1, "libusb transfer allocation failed".to_string()));
+        }
+        unsafe {
+            (*transfer).dev_handle = self.ftdi.usb_dev.unwrap();
+            (*transfer).endpoint = endpoint;
+            (*transfer).transfer_type = ffi::LIBUSB_TRANSFER_TYPE_BULK;
+            (*transfer).timeout = timeout as c_uint;
+            (*transfer).length = length as c_int;
+            (*transfer).buffer = buffer;
+            (*transfer).callback = fifo_transfer_cb;
+            (*transfer).user_data = &*self.completed as *const _ as *mut c_void;
+        }
+        let submit_result = unsafe { ffi::libusb_submit_transfer(transfer) };
+        if submit_result < 0 {
+            Self::free(transfer);
+            let error = FtdiContextError::UsbCommandError {
+                code: submit_result, message: "usb bulk transfer submit failed".to_string(),
+                backtrace: GenerateBacktrace::generate(),
+                source: Box::new(ftdi_context::get_usb_sys_native_error(submit_result))
+            };
+            error!("{}", error);
+            return Err(error);
+        }
+        self.in_flight.push(transfer);
+        Ok(())
+    }
+
+    /// Release transfer and its buffer, return buffer content
+    fn free(transfer: *mut ffi::libusb_transfer) -> Vec<u8> {
+        unsafe {
+            let buffer = Box::from_raw(ptr::slice_from_raw_parts_mut((*transfer).buffer, (*transfer).length as usize));
+            ffi::libusb_free_transfer(transfer);
+            buffer.into_vec()
+        }
+    }
+
+    /// Run libusb event loop until some transfer is completed
+    fn next_completed(&mut self) -> Result<*mut ffi::libusb_transfer> {
+        loop {
+            if let Some(transfer) = self.completed.borrow_mut().pop_front() {
+                self.in_flight.retain(|other| *other != transfer);
+                return Ok(transfer);
+            }
+            if self.in_flight.is_empty() {
+                return Err(common_error(-1, "no USB transfer is queued".to_string()));
+            }
+            let timeout = timeval { tv_sec: 0, tv_usec: SYNC_FIFO_EVENT_TIMEOUT_USEC as _ };
+            let events_result = unsafe {
+                ffi::libusb_handle_events_timeout_completed(self.ftdi.usb_ctx.unwrap(), &timeout, ptr::null_mut())
+            };
+            if events_result < 0 && events_result != ffi::LIBUSB_ERROR_INTERRUPTED {
+                let error = FtdiContextError::UsbCommandError {
+                    code: events_result, message: "libusb event handling failed".to_string(),
+                    backtrace: GenerateBacktrace::generate(),
+                    source: Box::new(ftdi_context::get_usb_sys_native_error(events_result))
+                };
+                error!("{}", error);
+                return Err(error);
+            }
+        }
+    }
+}
+impl FifoTransport for UsbTransferQueue {
+    fn chip_type(&self) -> ftdi_chip_type {
+        self.ftdi.r#type
+    }
+
+    fn channel_type(&self) -> i32 {
+        if self.ftdi.interface == 1 { self.ftdi.eeprom.channel_b_type } else { self.ftdi.eeprom.channel_a_type }
+    }
+
+    fn interface(&self) -> u8 {
+        self.ftdi.interface
+    }
+
+    fn packet_size(&self) -> usize {
+        self.ftdi.max_packet_size as usize
+    }
+
+    fn set_bitmode(&mut self, mask: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+        self.ftdi.ftdi_set_bitmode(mask, mode)
+    }
+
+    fn set_latency_timer(&mut self, latency: u8) -> Result<()> {
+        self.ftdi.ftdi_set_latency_timer(latency)
+    }
+
+    fn set_flow_control(&mut self, flowctrl: u16) -> Result<()> {
+        self.ftdi.ftdi_setflowctrl(flowctrl)
+    }
+
+    fn purge(&mut self) -> Result<()> {
+        self.ftdi.ftdi_usb_purge_buffers()
+    }
+
+    fn submit_read(&mut self, size: usize) -> Result<()> {
+        let endpoint = self.ftdi.out_ep;
+        // streaming reads wait for data as long as it takes, an idle FIFO isn't an error
+        self.submit(endpoint, vec![0; size], 0)
+    }
+
+    fn submit_write(&mut self, data: Vec<u8>) -> Result<()> {
+        let endpoint = self.ftdi.in_ep;
+        let timeout = self.ftdi.usb_write_timeout;
+        self.submit(endpoint, data, timeout)
+    }
+
+    fn wait_completion(&mut self) -> Result<FifoCompletion> {
+        let transfer = self.next_completed()?;
+        let (status, endpoint, actual_length) = unsafe { ((*transfer).status, (*transfer).endpoint, (*transfer).actual_length as usize) };
+        let mut buffer = Self::free(transfer);
+        let is_read = endpoint & ffi::LIBUSB_ENDPOINT_IN != 0;
+        // read which timed out is a short read, the bytes it got are kept
+        let timed_out_read = is_read && status == ffi::LIBUSB_TRANSFER_TIMED_OUT;
+        if status != ffi::LIBUSB_TRANSFER_COMPLETED && !timed_out_read {
+            return Err(common_error(status, format!("usb bulk transfer on endpoint {:#04x} failed, status = {}", endpoint, status)));
+        }
+        if is_read {
+            buffer.truncate(actual_length);
+            Ok(FifoCompletion::Read(buffer))
+        } else {
+            Ok(FifoCompletion::Written(actual_length))
+        }
+    }
+
+    fn cancel_all(&mut self) -> Result<()> {
+        for transfer in self.in_flight.iter() {
+            // transfer completed in the meantime can't be cancelled, it's reaped below anyway
+            unsafe { ffi::libusb_cancel_transfer(*transfer) };
+        }
+        while !self.in_flight.is_empty() {
+            let transfer = self.next_completed()?;
+            Self::free(transfer);
+        }
+        for transfer in self.completed.borrow_mut().drain(..) {
+            Self::free(transfer);
+        }
+        Ok(())
+    }
+}
+impl Drop for UsbTransferQueue {
+    fn drop(&mut self) {
+        // libusb must not write to 'completed' after it's gone
+        if let Err(error) = self.cancel_all() {
+            warn!("USB transfers weren't cancelled: {}", error);
+        }
+    }
+}
+
+/// Sync FIFO session parameters
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SyncFifoConfig {
+    /// Latency timer, milliseconds
+    pub latency: u8,
+    /// Bytes per bulk transfer, multiple of max packet size
+    pub transfer_size: usize,
+    /// Read transfers kept in flight
+    pub read_transfers: usize,
+    /// Max write transfers in flight
+    pub write_transfers: usize,
+}
+impl Default for SyncFifoConfig {
+    fn default() -> Self {
+        SyncFifoConfig {
+            latency: SYNC_FIFO_LATENCY,
+            transfer_size: SYNC_FIFO_TRANSFER_SIZE,
+            read_transfers: SYNC_FIFO_TRANSFERS,
+            write_transfers: SYNC_FIFO_TRANSFERS,
+        }
+    }
+}
+
+/// Synchronous 245 FIFO session of FT2232H (channel A) or FT232H.
+/// Reads are always queued so the chip never stalls on a full receive buffer,
+/// writes are queued up to the configured number of transfers.
+pub struct SyncFifo<T: FifoTransport> {
+    transport: T,
+    config: SyncFifoConfig,
+    packet_size: usize,
+    /// received payload not yet taken by reader
+    received: VecDeque<u8>,
+    reads_in_flight: usize,
+    writes_in_flight: usize,
+}
+impl<T: FifoTransport> SyncFifo<T> {
+    /// Check chip type and EEPROM channel type, enable sync FIFO mode and queue reads
+    pub fn open(mut transport: T, config: SyncFifoConfig) -> Result<Self> {
+        debug!("start 'sync fifo open' {:?} ...", config);
+        let chip = transport.chip_type();
+        // FIFO runs on channel A only
+        let interface = ftdi_interface::from(transport.interface() + 1);
+        if !ChipCapabilities::new(chip, interface)?.sync_fifo {
+            return Err(common_error(-1, format!("{:?} has no synchronous FIFO mode on {:?}", chip, interface)));
+        }
+        let channel_type = transport.channel_type();
+        if channel_type != CHANNEL_IS_FIFO as i32 {
+            return Err(common_error(-1, format!(
+                "channel is configured as {:#x} in EEPROM, 245 FIFO ({:#x}) is required", channel_type, CHANNEL_IS_FIFO)));
+        }
+        let packet_size = transport.packet_size();
+        if packet_size <= SYNC_FIFO_STATUS_BYTES || config.transfer_size == 0 || !config.transfer_size.is_multiple_of(packet_size) {
+            return Err(common_error(-1, format!(
+                "transfer size {} isn't a multiple of packet size {}", config.transfer_size, packet_size)));
+        }
+        if config.read_transfers == 0 || config.write_transfers == 0 {
+            return Err(common_error(-1, "at least one transfer per direction must be in flight".to_string()));
+        }
+        transport.set_bitmode(0xff, ftdi_mpsse_mode::BITMODE_RESET)?;
+        transport.set_bitmode(0xff, ftdi_mpsse_mode::BITMODE_SYNCFF)?;
+        transport.set_latency_timer(config.latency)?;
+        transport.set_flow_control(SYNC_FIFO_FLOW_CONTROL)?;
+        transport.purge()?;
+        let mut fifo = SyncFifo {
+            transport, config, packet_size, received: VecDeque::new(), reads_in_flight: 0, writes_in_flight: 0
+        };
+        while fifo.reads_in_flight < config.read_transfers {
+            fifo.transport.submit_read(config.transfer_size)?;
+            fifo.reads_in_flight += 1;
+        }
+        info!("sync FIFO opened, {} x {} bytes transfers", config.read_transfers, config.transfer_size);
+        Ok(fifo)
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn config(&self) -> SyncFifoConfig {
+        self.config
+    }
+
+    /// Received bytes waiting to be read
+    pub fn available(&self) -> usize {
+        self.received.len()
+    }
+
+    /// Read received bytes, waits until at least one byte is there
+    /// return number of bytes put to 'buffer'
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        while self.received.is_empty() {
+            self.pump()?;
+        }
+        let size = std::cmp::min(buffer.len(), self.received.len());
+        for (target, byte) in buffer.iter_mut().zip(self.received.drain(..size)) {
+            *target = byte;
+        }
+        Ok(size)
+    }
+
+    /// Queue data for sending, waits only while all write transfers are in flight
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(self.config.transfer_size) {
+            while self.writes_in_flight >= self.config.write_transfers {
+                self.pump()?;
+            }
+            self.transport.submit_write(chunk.to_vec())?;
+            self.writes_in_flight += 1;
+        }
+        Ok(())
+    }
+
+    /// Wait until all queued data is sent
+    pub fn flush(&mut self) -> Result<()> {
+        while self.writes_in_flight > 0 {
+            self.pump()?;
+        }
+        Ok(())
+    }
+
+    /// Send queued data, cancel reads and leave sync FIFO mode
+    pub fn close(mut self) -> Result<T> {
+        self.flush()?;
+        self.transport.cancel_all()?;
+        self.transport.set_bitmode(0, ftdi_mpsse_mode::BITMODE_RESET)?;
+        Ok(self.transport)
+    }
+
+    /// Handle one finished transfer, finished read is queued again
+    fn pump(&mut self) -> Result<()> {
+        match self.transport.wait_completion()? {
+            FifoCompletion::Read(data) => {
+                self.reads_in_flight -= 1;
+                for packet in data.chunks(self.packet_size) {
+                    self.received.extend(packet.iter().skip(SYNC_FIFO_STATUS_BYTES));
+                }
+                self.transport.submit_read(self.config.transfer_size)?;
+                self.reads_in_flight += 1;
+            }
+            FifoCompletion::Written(_) => self.writes_in_flight -= 1,
+        }
+        Ok(())
+    }
+}
+impl<T: FifoTransport> io::Read for SyncFifo<T> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        SyncFifo::read(self, buffer).map_err(io::Error::from)
+    }
+}
+impl<T: FifoTransport> io::Write for SyncFifo<T> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        SyncFifo::write(self, data).map_err(io::Error::from)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        SyncFifo::flush(self).map_err(io::Error::from)
+    }
+}
@@ -0,0 +1,245 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+use log::{debug, info};
+use crate::ftdi::ftdi_context::{FtdiContextError, Result, common_error};
+use crate::ftdi::jtag::{JtagController, TapState};
+use crate::ftdi::mpsse::MpsseTransport;
+use crate::ftdi::svf::TdoMismatch;
+
+/// XSVF instruction codes (Xilinx XAPP503)
+pub const XCOMPLETE: u8 = 0x00;
+pub const XTDOMASK: u8 = 0x01;
+pub const XSIR: u8 = 0x02;
+pub const XSDR: u8 = 0x03;
+pub const XRUNTEST: u8 = 0x04;
+pub const XREPEAT: u8 = 0x07;
+pub const XSDRSIZE: u8 = 0x08;
+pub const XSDRTDO: u8 = 0x09;
+pub const XSETSDRMASKS: u8 = 0x0a;
+pub const XSDRINC: u8 = 0x0b;
+pub const XSDRB: u8 = 0x0c;
+pub const XSDRC: u8 = 0x0d;
+pub const XSDRE: u8 = 0x0e;
+pub const XSDRTDOB: u8 = 0x0f;
+pub const XSDRTDOC: u8 = 0x10;
+pub const XSDRTDOE: u8 = 0x11;
+pub const XSTATE: u8 = 0x12;
+pub const XENDIR: u8 = 0x13;
+pub const XENDDR: u8 = 0x14;
+pub const XSIR2: u8 = 0x15;
+pub const XCOMMENT: u8 = 0x16;
+pub const XWAIT: u8 = 0x17;
+
+/// TAP state by its XSVF code
+pub fn xsvf_state(code: u8) -> Option<TapState> {
+    TapState::ALL.get(code as usize).copied()
+}
+
+fn xsvf_error(offset: usize, code: i32, message: String) -> FtdiContextError {
+    common_error(code, format!("XSVF offset {:#x}: {}", offset, message))
+}
+
+/// Cursor over XSVF bytes, all numbers are big endian
+struct XsvfReader<'d> {
+    data: &'d [u8],
+    offset: usize,
+}
+impl<'d> XsvfReader<'d> {
+    fn bytes(&mut self, count: usize) -> Result<&'d [u8]> {
+        if self.offset + count > self.data.len() {
+            return Err(xsvf_error(self.offset, -1, format!("unexpected end of data, {} byte(s) needed", count)));
+        }
+        let bytes = &self.data[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn state(&mut self) -> Result<TapState> {
+        let offset = self.offset;
+        let code = self.u8()?;
+        xsvf_state(code).ok_or_else(|| xsvf_error(offset, -1, format!("unknown TAP state {}", code)))
+    }
+    /// Bit vector stored MSB first, returned LSB first
+    fn vector(&mut self, bits: usize) -> Result<Vec<u8>> {
+        let mut vector = self.bytes(bits.div_ceil(8))?.to_vec();
+        vector.reverse();
+        Ok(vector)
+    }
+}
+
+/// Executes XSVF (binary SVF) programs on JTAG scan chain
+pub struct XsvfPlayer<'a, T: MpsseTransport> {
+    jtag: &'a mut JtagController<T>,
+    sdr_size: usize,
+    tdo_mask: Vec<u8>,
+    tdo_expected: Vec<u8>,
+    /// microseconds to wait in Run-Test/Idle after scans
+    run_test: u32,
+    repeat: u8,
+    end_ir: TapState,
+    end_dr: TapState,
+    mismatch: Option<TdoMismatch>,
+}
+impl<'a, T: MpsseTransport> XsvfPlayer<'a, T> {
+    /// JTAG controller must be initialized
+    pub fn new(jtag: &'a mut JtagController<T>) -> Self {
+        XsvfPlayer {
+            jtag, sdr_size: 0, tdo_mask: Vec::new(), tdo_expected: Vec::new(), run_test: 0, repeat: 0,
+            end_ir: TapState::RunTestIdle, end_dr: TapState::RunTestIdle, mismatch: None,
+        }
+    }
+
+    /// TDO mismatch that stopped the last play
+    pub fn mismatch(&self) -> Option<&TdoMismatch> {
+        self.mismatch.as_ref()
+    }
+
+    /// Execute XSVF program, 'progress' is called with (done, total) byte count after each instruction.
+    /// Stops on XCOMPLETE or on the first TDO mismatch left after XREPEAT retries.
+    pub fn play<F: FnMut(usize, usize)>(&mut self, data: &[u8], mut progress: F) -> Result<()> {
+        debug!("start 'xsvf play' ...");
+        self.mismatch = None;
+        let mut reader = XsvfReader { data, offset: 0 };
+        let mut instructions = 0;
+        loop {
+            let offset = reader.offset;
+            let instruction = reader.u8()?;
+            instructions += 1;
+            match instruction {
+                XCOMPLETE => break,
+                XTDOMASK => self.tdo_mask = reader.vector(self.sdr_size)?,
+                XSIR | XSIR2 => {
+                    let length = if instruction == XSIR { reader.u8()? as usize } else { reader.u16()? as usize };
+                    let tdi = reader.vector(length)?;
+                    if length > 0 {
+                        self.jtag.shift_ir(&tdi, length, self.end_ir)?;
+                    }
+                    self.wait_run_test(self.end_ir, self.run_test)?;
+                }
+                XSDR | XSDRTDO => {
+                    let tdi = reader.vector(self.sdr_size)?;
+                    if instruction == XSDRTDO {
+                        self.tdo_expected = reader.vector(self.sdr_size)?;
+                    }
+                    self.scan_dr(&tdi, offset, instruction, self.end_dr, true)?;
+                }
+                XSDRB | XSDRC | XSDRE | XSDRTDOB | XSDRTDOC | XSDRTDOE => {
+                    let tdi = reader.vector(self.sdr_size)?;
+                    let compare = instruction >= XSDRTDOB;
+                    if compare {
+                        self.tdo_expected = reader.vector(self.sdr_size)?;
+                    }
+                    // begin and continue parts keep the TAP shifting (through Pause-DR)
+                    let end_state = if instruction == XSDRE || instruction == XSDRTDOE { self.end_dr } else { TapState::ShiftDr };
+                    self.scan_dr(&tdi, offset, instruction, end_state, compare)?;
+                }
+                XRUNTEST => self.run_test = reader.u32()?,
+                XREPEAT => self.repeat = reader.u8()?,
+                XSDRSIZE => self.sdr_size = reader.u32()? as usize,
+                XSTATE => {
+                    let state = reader.state()?;
+                    if state == TapState::TestLogicReset {
+                        self.jtag.reset()?;
+                    } else {
+                        self.jtag.goto_state(state)?;
+                    }
+                }
+                XENDIR => self.end_ir = if reader.u8()? == 0 { TapState::RunTestIdle } else { TapState::PauseIr },
+                XENDDR => self.end_dr = if reader.u8()? == 0 { TapState::RunTestIdle } else { TapState::PauseDr },
+                XCOMMENT => {
+                    let start = reader.offset;
+                    while reader.u8()? != 0 {}
+                    debug!("XSVF comment: {}", String::from_utf8_lossy(&data[start..reader.offset - 1]));
+                }
+                XWAIT => {
+                    let wait_state = reader.state()?;
+                    let end_state = reader.state()?;
+                    let microseconds = reader.u32()?;
+                    self.jtag.goto_state(wait_state)?;
+                    self.jtag.wait_in_state(wait_state, microseconds)?;
+                    std::thread::sleep(Duration::from_micros(microseconds as u64));
+                    self.jtag.goto_state(end_state)?;
+                }
+                XSETSDRMASKS | XSDRINC => {
+                    return Err(xsvf_error(offset, -1, format!("obsolete instruction {:#04x} is not supported", instruction)));
+                }
+                _ => return Err(xsvf_error(offset, -1, format!("unknown instruction {:#04x}", instruction))),
+            }
+            progress(reader.offset, data.len());
+        }
+        progress(reader.offset, data.len());
+        info!("XSVF played, {} instruction(s)", instructions);
+        Ok(())
+    }
+
+    /// Shift data register, on TDO mismatch go to Run-Test/Idle through Pause-DR and Shift-DR,
+    /// wait 25% longer and shift again up to XREPEAT times, like Xilinx reference player does
+    fn scan_dr(&mut self, tdi: &[u8], offset: usize, instruction: u8, end_state: TapState, compare: bool) -> Result<()> {
+        if self.sdr_size == 0 {
+            return Err(xsvf_error(offset, -1, "data register scan with zero XSDRSIZE".to_string()));
+        }
+        // XTDOMASK and XSDRTDO vectors are sized by XSDRSIZE current when they were read
+        let bytes = self.sdr_size.div_ceil(8);
+        if compare && !self.tdo_mask.is_empty() && (self.tdo_mask.len() < bytes || self.tdo_expected.len() < bytes) {
+            return Err(xsvf_error(offset, -1, format!("TDO mask of {} bytes or expected TDO of {} bytes is shorter than XSDRSIZE {}",
+                                                      self.tdo_mask.len(), self.tdo_expected.len(), self.sdr_size)));
+        }
+        let mut run_test = self.run_test;
+        let mut attempt = 0;
+        loop {
+            // stop in Exit1-DR, next state depends on TDO comparison
+            let tdo = self.jtag.shift_dr(tdi, self.sdr_size, TapState::Exit1Dr)?;
+            let location = format!("offset {:#x} (instruction {:#04x})", offset, instruction);
+            let mismatch = if compare && !self.tdo_mask.is_empty() {
+                TdoMismatch::check(&location, self.sdr_size, &self.tdo_expected, &tdo, &self.tdo_mask)
+            } else {
+                None
+            };
+            match mismatch {
+                None => break,
+                Some(mismatch) if attempt >= self.repeat => {
+                    let error = xsvf_error(offset, -4, format!("{} after {} retries", mismatch, attempt));
+                    self.mismatch = Some(mismatch);
+                    return Err(error);
+                }
+                Some(_) => {
+                    attempt += 1;
+                    debug!("XSVF TDO mismatch at offset {:#x}, retry {}", offset, attempt);
+                    for &state in &[TapState::PauseDr, TapState::Exit2Dr, TapState::ShiftDr,
+                                    TapState::Exit1Dr, TapState::UpdateDr, TapState::RunTestIdle] {
+                        self.jtag.goto_state(state)?;
+                    }
+                    run_test += run_test / 4;
+                    self.wait_run_test(TapState::RunTestIdle, run_test)?;
+                }
+            }
+        }
+        self.jtag.goto_state(end_state)?;
+        if end_state != TapState::ShiftDr {
+            self.wait_run_test(end_state, run_test)?;
+        }
+        Ok(())
+    }
+
+    /// Wait XRUNTEST time in end 'state' of the scan (Run-Test/Idle or Pause after XENDIR/XENDDR)
+    fn wait_run_test(&mut self, state: TapState, microseconds: u32) -> Result<()> {
+        if microseconds == 0 {
+            return Ok(());
+        }
+        // one TCK cycle per microsecond at least, and the real time
+        self.jtag.wait_in_state(state, microseconds)?;
+        std::thread::sleep(Duration::from_micros(microseconds as u64));
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::gpio::{*};
+    use crate::ftdi::mpsse::{MpsseTransport, mpsse_command_length};
+    use crate::ftdi::spi::{SpiMaster, SpiMode, SPI_PIN_CS, SPI_PIN_MISO, SPI_PIN_MOSI, SPI_PIN_SCK};
+
+    /// MPSSE pins: keeps last setup of both ports, external levels drive input pins
+    struct SimulatedPins {
+        value: [u8; 2],
+        direction: [u8; 2],
+        inputs: [u8; 2],
+        /// port setups in order: port index, value, direction
+        setups: Vec<(usize, u8, u8)>,
+        replies: VecDeque<u8>,
+    }
+    impl SimulatedPins {
+        fn new() -> Self {
+            SimulatedPins { value: [0; 2], direction: [0; 2], inputs: [0; 2], setups: Vec::new(), replies: VecDeque::new() }
+        }
+
+        fn level(&self, port: usize) -> u8 {
+            (self.value[port] & self.direction[port]) | (self.inputs[port] & !self.direction[port])
+        }
+    }
+    impl MpsseTransport for SimulatedPins {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let length = mpsse_command_length(&commands[index..]).expect("truncated MPSSE command");
+                let command = &commands[index..index + length];
+                match command[0] {
+                    SET_BITS_LOW | SET_BITS_HIGH => {
+                        let port = if command[0] == SET_BITS_LOW { 0 } else { 1 };
+                        self.value[port] = command[1];
+                        self.direction[port] = command[2];
+                        self.setups.push((port, command[1], command[2]));
+                    }
+                    GET_BITS_LOW => self.replies.push_back(self.level(0)),
+                    GET_BITS_HIGH => self.replies.push_back(self.level(1)),
+                    opcode if opcode & 0x80 == 0 && opcode & MPSSE_DO_READ != 0 => {
+                        let count = command[1] as usize + ((command[2] as usize) << 8) + 1;
+                        self.replies.extend(std::iter::repeat(0x5a).take(count));
+                    }
+                    _ => {}
+                }
+                index += length;
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated pins have {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    #[test]
+    fn mpsse_command_lengths() {
+        assert_eq!(mpsse_command_length(&[SET_BITS_LOW, 0, 0]), Some(3));
+        assert_eq!(mpsse_command_length(&[SET_BITS_LOW, 0]), None);
+        assert_eq!(mpsse_command_length(&[GET_BITS_HIGH, SEND_IMMEDIATE]), Some(1));
+        // write 3 bytes, read 3 bytes, write bits, read bits, TMS with read
+        assert_eq!(mpsse_command_length(&[0x11, 2, 0, 0x80, 0x80, 0x80, SET_BITS_LOW]), Some(6));
+        assert_eq!(mpsse_command_length(&[0x20, 2, 0]), Some(3));
+        assert_eq!(mpsse_command_length(&[0x13, 7, 0x80]), Some(3));
+        assert_eq!(mpsse_command_length(&[0x22, 7]), Some(2));
+        assert_eq!(mpsse_command_length(&[0x6b, 0, 0x81]), Some(3));
+        assert_eq!(mpsse_command_length(&[CLK_BYTES, 0, 1]), Some(3));
+        assert_eq!(mpsse_command_length(&[0xaa]), Some(1));
+    }
+
+    #[test]
+    fn registry_rejects_double_assignment() {
+        let mut registry = PinRegistry::new();
+        registry.claim(GpioPort::Low, 0x0f, "spi").unwrap();
+        assert!(registry.claim(GpioPort::Low, 0x18, GPIO_OWNER).is_err());
+        // failed claim takes nothing
+        assert_eq!(registry.owner(GpioPort::Low, 4), None);
+        registry.claim(GpioPort::Low, 0xf0, GPIO_OWNER).unwrap();
+        registry.claim(GpioPort::High, 0x08, "spi").unwrap();
+        // claiming own pins again is fine
+        registry.claim(GpioPort::Low, 0x01, "spi").unwrap();
+        assert_eq!(registry.owner(GpioPort::Low, 3), Some("spi"));
+        assert_eq!(registry.pins(GpioPort::Low, GPIO_OWNER), 0xf0);
+        assert_eq!(registry.engine_pins(GpioPort::High), 0x08);
+        registry.release("spi");
+        assert_eq!(registry.engine_pins(GpioPort::Low), 0);
+        assert_eq!(registry.engine_pins(GpioPort::High), 0);
+        registry.claim(GpioPort::Low, 0x08, GPIO_OWNER).unwrap();
+    }
+
+    #[test]
+    fn gpio_direction_value_and_read_back() {
+        let mut gpio = GpioController::new(SimulatedPins::new());
+        gpio.claim(GpioPort::High, 0x0f, GPIO_OWNER).unwrap();
+        gpio.set_direction(GpioPort::High, 0, true).unwrap();
+        gpio.set_direction(GpioPort::High, 1, true).unwrap();
+        gpio.set_value(GpioPort::High, 1, true).unwrap();
+        gpio.write(GpioPort::High, 0x0c, 0x04).unwrap();
+        assert_eq!(gpio.state(GpioPort::High), (0x06, 0x03));
+        assert!(gpio.value(GpioPort::High, 1));
+        assert!(gpio.is_output(GpioPort::High, 0));
+        assert!(!gpio.is_output(GpioPort::High, 2));
+        let simulator = gpio.transport();
+        assert_eq!((simulator.value[1], simulator.direction[1]), (0x06, 0x03));
+        simulator.inputs[1] = 0x89;
+        // outputs read their driven level, inputs the external one
+        assert_eq!(gpio.read(GpioPort::High).unwrap(), 0x8a);
+        assert!(gpio.read_pin(GpioPort::High, 3).unwrap());
+        assert!(!gpio.read_pin(GpioPort::High, 0).unwrap());
+        // pins not claimed for GPIO are refused
+        assert!(gpio.set_value(GpioPort::High, 4, true).is_err());
+        assert!(gpio.write(GpioPort::Low, 0x01, 0x01).is_err());
+        assert!(gpio.set_direction(GpioPort::High, 8, true).is_err());
+        assert_eq!(gpio.transport().setups.len(), 4);
+    }
+
+    #[test]
+    fn engine_and_gpio_share_low_byte() {
+        let mut gpio = GpioController::new(SimulatedPins::new());
+        gpio.claim(GpioPort::Low, SPI_PIN_SCK | SPI_PIN_MOSI | SPI_PIN_MISO | SPI_PIN_CS, "spi").unwrap();
+        gpio.claim(GpioPort::Low, 0x30, GPIO_OWNER).unwrap();
+        gpio.set_direction(GpioPort::Low, 4, true).unwrap();
+        gpio.set_value(GpioPort::Low, 4, true).unwrap();
+        let mut spi = SpiMaster::new(gpio, SpiMode::Mode0);
+        spi.init(1_000_000, true).unwrap();
+        assert_eq!(spi.transaction(&[0x9f], 2).unwrap(), vec![0x5a, 0x5a]);
+        {
+            let simulator = spi.transport().transport();
+            // chip select toggled, GPIO output kept high all the time
+            assert!(simulator.setups.iter().any(|&(port, value, _)| port == 0 && value & SPI_PIN_CS == 0));
+            assert!(simulator.setups.iter().skip(2).all(|&(_, value, direction)| value & 0x10 != 0 && direction & 0x10 != 0));
+            assert_eq!((simulator.value[0], simulator.direction[0]), (0x18, 0x1b));
+        }
+        // GPIO update keeps SPI pins idle: chip select high, SCK/MOSI/CS outputs
+        spi.transport().set_value(GpioPort::Low, 4, false).unwrap();
+        spi.transport().set_direction(GpioPort::Low, 5, true).unwrap();
+        let simulator = spi.transport().transport();
+        assert_eq!((simulator.value[0], simulator.direction[0]), (0x08, 0x3b));
+        // SPI can't take GPIO pins away
+        assert!(spi.transport().claim(GpioPort::Low, 0x10, "spi").is_err());
+    }
+}
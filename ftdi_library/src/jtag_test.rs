@@ -0,0 +1,274 @@
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::VecDeque;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::jtag::{TapState, JtagController, IdCode, JtagDevice};
+    use crate::ftdi::mpsse::MpsseTransport;
+
+    /// One TAP on simulated scan chain
+    pub(crate) struct SimulatedDevice {
+        pub ir_length: usize,
+        pub idcode: Option<u32>,
+        /// current instruction, all ones is BYPASS
+        pub instruction: u32,
+        /// last value shifted into data register through Update-DR
+        pub updated_dr: Vec<bool>,
+    }
+    impl SimulatedDevice {
+        pub fn new(ir_length: usize, idcode: Option<u32>) -> Self {
+            SimulatedDevice { ir_length, idcode, instruction: 0, updated_dr: Vec::new() }
+        }
+        fn is_bypass(&self) -> bool {
+            self.idcode.is_none() || self.instruction == (1 << self.ir_length) - 1
+        }
+        fn reset(&mut self) {
+            // IDCODE (or BYPASS for devices without it) is selected by Test-Logic-Reset
+            self.instruction = if self.idcode.is_some() { 0 } else { (1 << self.ir_length) - 1 };
+        }
+    }
+
+    /// Scan chain driven by interpreting MPSSE JTAG commands, device 0 is nearest to TDO
+    pub(crate) struct SimulatedTap {
+        pub devices: Vec<SimulatedDevice>,
+        pub state: TapState,
+        shift_register: VecDeque<bool>,
+        replies: VecDeque<u8>,
+        pub clocks: usize,
+    }
+    impl SimulatedTap {
+        pub fn new(devices: Vec<SimulatedDevice>) -> Self {
+            let mut tap = SimulatedTap {
+                devices, state: TapState::RunTestIdle, shift_register: VecDeque::new(),
+                replies: VecDeque::new(), clocks: 0,
+            };
+            tap.devices.iter_mut().for_each(|device| device.reset());
+            tap
+        }
+
+        /// One TCK rising edge, returns TDO sampled on it
+        fn clock(&mut self, tms: bool, tdi: bool) -> bool {
+            self.clocks += 1;
+            let tdo = match self.state {
+                TapState::ShiftDr | TapState::ShiftIr => *self.shift_register.front().unwrap_or(&false),
+                _ => false,
+            };
+            match self.state {
+                TapState::CaptureDr => {
+                    let register = &mut self.shift_register;
+                    register.clear();
+                    for device in &self.devices {
+                        match (device.is_bypass(), device.idcode) {
+                            (false, Some(idcode)) => (0..32).for_each(|bit| register.push_back(idcode & (1 << bit) != 0)),
+                            _ => register.push_back(false),
+                        }
+                    }
+                }
+                TapState::CaptureIr => {
+                    let register = &mut self.shift_register;
+                    register.clear();
+                    for device in &self.devices {
+                        (0..device.ir_length).for_each(|bit| register.push_back(bit == 0));
+                    }
+                }
+                TapState::ShiftDr | TapState::ShiftIr => {
+                    self.shift_register.pop_front();
+                    self.shift_register.push_back(tdi);
+                }
+                TapState::UpdateIr => {
+                    let mut bits = self.shift_register.iter();
+                    for device in self.devices.iter_mut() {
+                        device.instruction = (0..device.ir_length)
+                            .fold(0, |value, bit| value | ((bits.next().copied().unwrap_or(false) as u32) << bit));
+                    }
+                }
+                TapState::UpdateDr => {
+                    let mut bits = self.shift_register.iter();
+                    for device in self.devices.iter_mut() {
+                        let length = if device.is_bypass() { 1 } else { 32 };
+                        device.updated_dr = (0..length).map(|_| bits.next().copied().unwrap_or(false)).collect();
+                    }
+                }
+                _ => {}
+            }
+            self.state = self.state.next(tms);
+            if self.state == TapState::TestLogicReset {
+                self.devices.iter_mut().for_each(|device| device.reset());
+            }
+            tdo
+        }
+    }
+    impl MpsseTransport for SimulatedTap {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    TCK_DIVISOR | SET_BITS_LOW | SET_BITS_HIGH => index += 2,
+                    0x4b | 0x6b => {
+                        let length = commands[index] as usize + 1;
+                        let data = commands[index + 1];
+                        index += 2;
+                        let mut read = 0u8;
+                        for bit in 0..length {
+                            let tdo = self.clock(data & (1 << bit) != 0, data & 0x80 != 0);
+                            read = (read >> 1) | ((tdo as u8) << 7);
+                        }
+                        if opcode == 0x6b {
+                            self.replies.push_back(read);
+                        }
+                    }
+                    0x19 | 0x39 => {
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for byte in &commands[index..index + length] {
+                            let mut read = 0u8;
+                            for bit in 0..8 {
+                                read |= (self.clock(false, byte & (1 << bit) != 0) as u8) << bit;
+                            }
+                            if opcode == 0x39 {
+                                self.replies.push_back(read);
+                            }
+                        }
+                        index += length;
+                    }
+                    0x1b | 0x3b => {
+                        let length = commands[index] as usize + 1;
+                        let data = commands[index + 1];
+                        index += 2;
+                        let mut read = 0u8;
+                        for bit in 0..length {
+                            let tdo = self.clock(false, data & (1 << bit) != 0);
+                            read = (read >> 1) | ((tdo as u8) << 7);
+                        }
+                        if opcode == 0x3b {
+                            self.replies.push_back(read);
+                        }
+                    }
+                    _ => panic!("simulated TAP got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated TAP has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    const XILINX_XC7A35T: u32 = 0x0362_d093;
+    const ARM_JTAG_DP: u32 = 0x4ba0_0477;
+    const LATTICE_ECP5: u32 = 0x4111_1043;
+
+    fn test_chain() -> JtagController<SimulatedTap> {
+        JtagController::new(SimulatedTap::new(vec![
+            SimulatedDevice::new(6, Some(XILINX_XC7A35T)),
+            SimulatedDevice::new(5, None),
+            SimulatedDevice::new(4, Some(ARM_JTAG_DP)),
+        ]))
+    }
+
+    #[test]
+    fn tap_state_paths() {
+        for &from in TapState::ALL.iter() {
+            for &to in TapState::ALL.iter() {
+                let (bits, length) = from.path_to(to);
+                assert!(length <= 8, "path {:?} -> {:?} too long", from, to);
+                let reached = (0..length).fold(from, |state, bit| state.next(bits & (1 << bit) != 0));
+                assert_eq!(reached, to);
+            }
+        }
+        assert_eq!(TapState::RunTestIdle.path_to(TapState::ShiftDr), (0b001, 3));
+        assert_eq!(TapState::RunTestIdle.path_to(TapState::ShiftIr), (0b0011, 4));
+        assert_eq!(TapState::Exit1Dr.path_to(TapState::RunTestIdle), (0b01, 2));
+    }
+
+    #[test]
+    fn reset_from_any_state() {
+        let mut jtag = test_chain();
+        jtag.transport().state = TapState::PauseIr;
+        jtag.reset().unwrap();
+        assert_eq!(jtag.transport().state, TapState::TestLogicReset);
+        assert_eq!(jtag.state(), TapState::TestLogicReset);
+        jtag.goto_state(TapState::PauseDr).unwrap();
+        assert_eq!(jtag.transport().state, TapState::PauseDr);
+    }
+
+    #[test]
+    fn init_sets_up_and_resets() {
+        let mut jtag = test_chain();
+        assert_eq!(jtag.init(6_000_000, true).unwrap(), 6_000_000);
+        assert_eq!(jtag.transport().state, TapState::TestLogicReset);
+        let mut slow = test_chain();
+        assert_eq!(slow.init(1_000_000, false).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn scan_chain_detection() {
+        let mut jtag = test_chain();
+        let devices = jtag.scan_chain().unwrap();
+        assert_eq!(devices, vec![
+            JtagDevice { position: 0, idcode: Some(IdCode(XILINX_XC7A35T)) },
+            JtagDevice { position: 1, idcode: None },
+            JtagDevice { position: 2, idcode: Some(IdCode(ARM_JTAG_DP)) },
+        ]);
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+    }
+
+    #[test]
+    fn scan_chain_empty_fails_on_stuck_tdo() {
+        // no devices: TDO never returns the ones, simulated TDO is stuck low
+        let mut jtag = JtagController::new(SimulatedTap::new(vec![]));
+        let devices = jtag.scan_chain();
+        assert!(devices.is_err() || devices.unwrap().iter().all(|device| device.idcode.is_none()));
+    }
+
+    #[test]
+    fn ir_chain_length_and_bypass() {
+        let mut jtag = test_chain();
+        jtag.reset().unwrap();
+        assert_eq!(jtag.ir_chain_length().unwrap(), 6 + 5 + 4);
+        assert!(jtag.transport().devices.iter().all(|device| device.is_bypass()));
+        // every device in BYPASS delays data by one bit
+        let tdo = jtag.shift_dr(&[0b1011_0101, 0b0000_0001], 12, TapState::RunTestIdle).unwrap();
+        assert_eq!(tdo, vec![0b1010_1000, 0b0000_1101]);
+    }
+
+    #[test]
+    fn shift_long_dr_round_trip() {
+        let mut jtag = JtagController::new(SimulatedTap::new(vec![SimulatedDevice::new(4, Some(LATTICE_ECP5))]));
+        jtag.reset().unwrap();
+        let data: Vec<u8> = (0..40u32).map(|value| (value * 7 + 3) as u8).collect();
+        let tdo = jtag.shift_dr(&data, 32 * 10, TapState::PauseDr).unwrap();
+        assert_eq!(&tdo[..4], &LATTICE_ECP5.to_le_bytes());
+        assert_eq!(&tdo[4..], &data[..36]);
+        assert_eq!(jtag.transport().state, TapState::PauseDr);
+        let updated: Vec<bool> = (0..32).map(|bit| data[36 + bit / 8] & (1 << (bit % 8)) != 0).collect();
+        jtag.goto_state(TapState::RunTestIdle).unwrap();
+        assert_eq!(jtag.transport().devices[0].updated_dr, updated);
+    }
+
+    #[test]
+    fn shift_rejects_bad_length() {
+        let mut jtag = test_chain();
+        assert!(jtag.shift_ir(&[0xff], 9, TapState::RunTestIdle).is_err());
+        assert!(jtag.shift_dr(&[], 0, TapState::RunTestIdle).is_err());
+    }
+
+    #[test]
+    fn idcode_decoding() {
+        let xilinx = IdCode(XILINX_XC7A35T);
+        assert_eq!(xilinx.manufacturer(), Some("Xilinx"));
+        assert_eq!(xilinx.part_number(), 0x362d);
+        assert_eq!(xilinx.version(), 0);
+        assert_eq!(IdCode(ARM_JTAG_DP).manufacturer(), Some("ARM Ltd"));
+        assert_eq!(IdCode(ARM_JTAG_DP).version(), 4);
+        assert_eq!(IdCode(LATTICE_ECP5).manufacturer(), Some("Lattice Semiconductor"));
+        assert_eq!(IdCode(0x020f_30dd).manufacturer(), Some("Altera"));
+        assert_eq!(IdCode(0x0000_0fff).manufacturer(), None);
+        assert_eq!(format!("{}", xilinx), "0x0362d093 (manufacturer: Xilinx, part: 0x362d, version: 0)");
+    }
+}
@@ -1,11 +1,28 @@
 pub mod ftdi;
+pub mod baud_test;
+pub mod bitbang_test;
+pub mod cbus_test;
+pub mod chip_test;
 pub mod constants_test;
+pub mod eeprom_config_test;
+pub mod eeprom_test;
+pub mod gpio_test;
+pub mod jtag_test;
+pub mod mcu_test;
+pub mod opto_test;
+pub mod serial_eeprom_test;
+pub mod serial_test;
+pub mod spi_flash_test;
+pub mod svf_test;
+pub mod swd_test;
+pub mod sync_fifo_test;
 
 #[cfg(test)]
 mod tests {
     use crate::ftdi::ftdi_context::ftdi_context;
     use crate::ftdi::ftdi_device_list::ftdi_device_list;
-    use crate::ftdi::ftdi_context::FtdiContextError;
+    use crate::ftdi::ftdi_context::{ChipId, FtdiContextError, ftdi_line_property};
+    use crate::ftdi::constants::{ftdi_bits_type, ftdi_break_type, ftdi_parity_type, ftdi_stopbits_type};
     use snafu::{GenerateBacktrace};
 
     #[test]
@@ -136,4 +153,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chip_id_descramble() {
+        // known answers of libftdi ftdi_read_chipid
+        let values = [
+            (0x0000, 0x0000, 0xa5f0f7d1u32),
+            (0x1234, 0xabcd, 0xbcb85023),
+            (0xa1b2, 0x0f3c, 0xddc26e12),
+            (0xffff, 0xffff, 0x5a0f082e),
+        ];
+        for (high, low, expected) in values.iter() {
+            assert_eq!(ChipId::from_words(*high, *low), ChipId(*expected));
+        }
+        assert_eq!(ChipId::shift(0x01), 0x02);
+        assert_eq!(ChipId::shift(0x80), 0x20);
+        assert_eq!(ChipId(0x0123abcd).to_string(), "0x0123abcd");
+        assert_eq!(format!("{:x}", ChipId(0xbcb85023)), "bcb85023");
+    }
+
+    #[test]
+    fn line_property_value() {
+        assert_eq!(ftdi_line_property::default().value(), 0x0008);
+        let mut line_property = ftdi_line_property {
+            bits: ftdi_bits_type::BITS_7,
+            stopbits: ftdi_stopbits_type::STOP_BIT_2,
+            parity: ftdi_parity_type::EVEN,
+            break_type: ftdi_break_type::BREAK_OFF,
+        };
+        assert_eq!(line_property.value(), 0x1207);
+        // break keeps the rest
+        line_property.break_type = ftdi_break_type::BREAK_ON;
+        assert_eq!(line_property.value(), 0x5207);
+        line_property.parity = ftdi_parity_type::SPACE;
+        line_property.stopbits = ftdi_stopbits_type::STOP_BIT_15;
+        assert_eq!(line_property.value(), 0x4c07);
+    }
+
 }
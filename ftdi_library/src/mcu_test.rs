@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::eeprom::ftdi_eeprom;
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::mcu::{*};
+    use crate::ftdi::mpsse::{MpsseTransport, mpsse_command_length};
+
+    /// Peripheral with 64K registers on MCU host bus
+    struct SimulatedMcuBus {
+        registers: Vec<u8>,
+        /// bus cycles in order: address, extended, write
+        cycles: Vec<(u16, bool, bool)>,
+        writes: usize,
+        replies: VecDeque<u8>,
+    }
+    impl SimulatedMcuBus {
+        fn new() -> Self {
+            let registers = (0..0x10000u32).map(|address| (address ^ (address >> 8)) as u8).collect();
+            SimulatedMcuBus { registers, cycles: Vec::new(), writes: 0, replies: VecDeque::new() }
+        }
+    }
+    impl MpsseTransport for SimulatedMcuBus {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            self.writes += 1;
+            let mut index = 0;
+            while index < commands.len() {
+                let length = mpsse_command_length(&commands[index..]).expect("truncated MCU command");
+                let command = &commands[index..index + length];
+                match command[0] {
+                    READ_SHORT => {
+                        self.cycles.push((command[1] as u16, false, false));
+                        self.replies.push_back(self.registers[command[1] as usize]);
+                    }
+                    READ_EXTENDED => {
+                        let address = ((command[1] as u16) << 8) | command[2] as u16;
+                        self.cycles.push((address, true, false));
+                        self.replies.push_back(self.registers[address as usize]);
+                    }
+                    WRITE_SHORT => {
+                        self.cycles.push((command[1] as u16, false, true));
+                        self.registers[command[1] as usize] = command[2];
+                    }
+                    WRITE_EXTENDED => {
+                        let address = ((command[1] as u16) << 8) | command[2] as u16;
+                        self.cycles.push((address, true, true));
+                        self.registers[address as usize] = command[3];
+                    }
+                    SEND_IMMEDIATE | WAIT_ON_HIGH | WAIT_ON_LOW => {}
+                    opcode => panic!("unexpected command {:#04x} in MCU mode", opcode),
+                }
+                index += length;
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated bus has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    #[test]
+    fn mcu_single_accesses() {
+        let mut bus = McuBus::new(SimulatedMcuBus::new());
+        assert_eq!(bus.read8(0x12).unwrap(), 0x12);
+        assert_eq!(bus.read16(0x1234).unwrap(), 0x26);
+        bus.write8(0x40, 0xaa).unwrap();
+        bus.write16(0xbeef, 0x55).unwrap();
+        assert_eq!(bus.read8(0x40).unwrap(), 0xaa);
+        assert_eq!(bus.read16(0xbeef).unwrap(), 0x55);
+        assert_eq!(bus.transport().cycles[..4], [(0x12, false, false), (0x1234, true, false), (0x40, false, true), (0xbeef, true, true)]);
+        let replies = bus.execute(&[McuAccess::Write8(1, 9), McuAccess::Read8(1), McuAccess::Read16(0x0101)]).unwrap();
+        assert_eq!(replies, vec![9, 0]);
+    }
+
+    #[test]
+    fn mcu_blocks_are_batched() {
+        let mut bus = McuBus::new(SimulatedMcuBus::new());
+        let data: Vec<u8> = (0..600u32).map(|index| (index * 3) as u8).collect();
+        bus.write_block16(0x2000, &data).unwrap();
+        // 600 accesses: 3 USB writes
+        assert_eq!(bus.transport().writes, 3);
+        assert_eq!(bus.read_block16(0x2000, 600).unwrap(), data);
+        assert_eq!(bus.transport().writes, 6);
+        assert_eq!(bus.read_block8(0xf0, 16).unwrap(), (0xf0..=0xff).collect::<Vec<u8>>());
+        assert!(bus.read_block8(0xf0, 17).is_err());
+        assert!(bus.write_block16(0xffff, &[1, 2]).is_err());
+        bus.wait_io(true).unwrap();
+    }
+
+    #[test]
+    fn ft1284_config_in_eeprom() {
+        let config = Ft1284Config { clock_idle_high: true, lsb_first: false, flow_control: true };
+        assert_eq!(config.flags(), FT1284_CLK_IDLE_STATE | FT1284_FLOW_CONTROL);
+        assert_eq!(Ft1284Config::from_flags(0x06), Ft1284Config { clock_idle_high: false, lsb_first: true, flow_control: true });
+        let mut eeprom = ftdi_eeprom::default();
+        assert_eq!(Ft1284Config::from_eeprom(&eeprom), None);
+        config.apply(&mut eeprom);
+        assert_eq!(eeprom.channel_a_type, CHANNEL_IS_FT1284 as i32);
+        assert_eq!(Ft1284Config::from_eeprom(&eeprom), Some(config));
+    }
+}
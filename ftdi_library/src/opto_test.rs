@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{Read, Write};
+    use crate::ftdi::constants::{ftdi_chip_type, ftdi_mpsse_mode, CHANNEL_IS_OPTO, CHANNEL_IS_UART};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::opto::{*};
+
+    /// Channel of a fast serial chip: sent bytes are collected, far side data comes back in small pieces
+    struct SimulatedOpto {
+        chip: ftdi_chip_type,
+        channel_type: i32,
+        interface: u8,
+        mode: ftdi_mpsse_mode,
+        written: Vec<u8>,
+        incoming: VecDeque<u8>,
+        empty_reads: u32,
+    }
+    impl SimulatedOpto {
+        fn new(chip: ftdi_chip_type, channel_type: i32, interface: u8) -> Self {
+            SimulatedOpto {
+                chip, channel_type, interface, mode: ftdi_mpsse_mode::BITMODE_RESET,
+                written: Vec::new(), incoming: VecDeque::new(), empty_reads: 0,
+            }
+        }
+    }
+    impl OptoTransport for SimulatedOpto {
+        fn chip_type(&self) -> ftdi_chip_type {
+            self.chip
+        }
+
+        fn channel_type(&self) -> i32 {
+            self.channel_type
+        }
+
+        fn interface(&self) -> u8 {
+            self.interface
+        }
+
+        fn set_bitmode(&mut self, _mask: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+            self.mode = mode;
+            Ok(())
+        }
+
+        fn write_data(&mut self, data: &[u8]) -> Result<()> {
+            self.written.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn read_data(&mut self, buffer: &mut [u8]) -> Result<usize> {
+            // latency timer packets without data come in between
+            if self.empty_reads < 2 {
+                self.empty_reads += 1;
+                return Ok(0);
+            }
+            self.empty_reads = 0;
+            let size = buffer.len().min(self.incoming.len()).min(3);
+            for (target, byte) in buffer.iter_mut().zip(self.incoming.drain(..size)) {
+                *target = byte;
+            }
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn fast_serial_open_checks_chip_and_eeprom() {
+        assert!(FastSerial::open(SimulatedOpto::new(ftdi_chip_type::TYPE_232H, CHANNEL_IS_OPTO as i32, 0)).is_err());
+        assert!(FastSerial::open(SimulatedOpto::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_UART as i32, 1)).is_err());
+        // channel A has no fast serial interface
+        assert!(FastSerial::open(SimulatedOpto::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_OPTO as i32, 0)).is_err());
+        assert!(FastSerial::open(SimulatedOpto::new(ftdi_chip_type::TYPE_2232HP, CHANNEL_IS_OPTO as i32, 1)).is_ok());
+        let mut serial = FastSerial::open(SimulatedOpto::new(ftdi_chip_type::TYPE_2232C, CHANNEL_IS_OPTO as i32, 1)).unwrap();
+        assert_eq!(serial.port(), OptoPort::B);
+        assert_eq!(serial.transport().mode, ftdi_mpsse_mode::BITMODE_OPTO);
+        let simulator = serial.close().unwrap();
+        assert_eq!(simulator.mode, ftdi_mpsse_mode::BITMODE_RESET);
+    }
+
+    #[test]
+    fn fast_serial_duplex_stream() {
+        let mut simulator = SimulatedOpto::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_OPTO as i32, 1);
+        simulator.incoming.extend(b"status: ok\n".iter());
+        let mut serial = FastSerial::open(simulator).unwrap();
+        serial.write_all(b"hi").unwrap();
+        serial.flush().unwrap();
+        assert_eq!(serial.transport().written, b"hi".to_vec());
+        let mut reply = [0u8; 11];
+        serial.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"status: ok\n");
+        // nothing more comes from the far side
+        assert!(serial.read(&mut reply).is_err());
+    }
+}
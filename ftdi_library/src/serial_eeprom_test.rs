@@ -0,0 +1,739 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::i2c::{I2cMaster, I2C_PIN_SCL, I2C_PIN_SDA_OUT};
+    use crate::ftdi::microwire::{MicrowireMaster, MICROWIRE_PIN_CS, MICROWIRE_PIN_DO};
+    use crate::ftdi::mpsse::MpsseTransport;
+    use crate::ftdi::serial_eeprom::{*};
+    use crate::ftdi::spi::{SpiMaster, SpiMode, SPI_PIN_CS};
+
+    /// 24Cxx EEPROM on I2C bus driven by interpreting MPSSE commands bit by bit
+    struct SimulatedI2cEeprom {
+        memory: Vec<u8>,
+        address_bytes: u8,
+        page_size: u32,
+        chip_address: u8,
+        // bus lines, SDA true means released by master
+        scl: bool,
+        sda_master: bool,
+        /// SDA pin is set as output
+        sda_driven: bool,
+        sda_out: bool,
+        // slave state
+        active: bool,
+        receiving: bool,
+        first_byte: bool,
+        reading: bool,
+        clock: u8,
+        shift: u8,
+        transmit: u8,
+        master_ack: bool,
+        block: u32,
+        word_address: u32,
+        address_received: u8,
+        pointer: u32,
+        write_start: Option<u32>,
+        page: Vec<u8>,
+        /// address acknowledge attempts left during write cycle
+        busy: u32,
+        busy_nacks: u32,
+        /// committed page writes: address and length
+        writes: Vec<(u32, usize)>,
+        replies: VecDeque<u8>,
+        three_phase: bool,
+    }
+    impl SimulatedI2cEeprom {
+        fn new(profile: &EepromProfile, chip_address: u8) -> Self {
+            let address_bytes = match profile.bus {
+                EepromBus::I2c { address_bytes } => address_bytes,
+                _ => panic!("not I2C profile"),
+            };
+            SimulatedI2cEeprom {
+                memory: vec![0xff; profile.size as usize], address_bytes, page_size: profile.page_size, chip_address,
+                scl: true, sda_master: true, sda_driven: false, sda_out: true, active: false, receiving: true, first_byte: true,
+                reading: false, clock: 0, shift: 0, transmit: 0xff, master_ack: false, block: 0, word_address: 0,
+                address_received: 0, pointer: 0, write_start: None, page: Vec::new(), busy: 0, busy_nacks: 0,
+                writes: Vec::new(), replies: VecDeque::new(), three_phase: false,
+            }
+        }
+
+        fn block_size(&self) -> u32 {
+            1 << (8 * self.address_bytes as u32)
+        }
+
+        fn block_mask(&self) -> u8 {
+            let blocks = (self.memory.len() as u32 + self.block_size() - 1) / self.block_size();
+            (blocks - 1) as u8
+        }
+
+        fn line(&self) -> bool {
+            self.sda_master && self.sda_out
+        }
+
+        fn set_lines(&mut self, scl: bool, sda_master: bool) {
+            if sda_master != self.sda_master {
+                let before = self.line();
+                self.sda_master = sda_master;
+                let after = self.line();
+                if self.scl && before && !after {
+                    self.start();
+                } else if self.scl && !before && after {
+                    self.stop();
+                }
+            }
+            if scl && !self.scl {
+                self.scl = true;
+                let sda = self.line();
+                self.rising(sda);
+            } else if !scl && self.scl {
+                self.scl = false;
+                self.falling();
+            }
+        }
+
+        fn start(&mut self) {
+            self.active = true;
+            self.receiving = true;
+            self.first_byte = true;
+            self.clock = 0;
+            self.shift = 0;
+            self.sda_out = true;
+            self.page.clear();
+            self.write_start = None;
+        }
+
+        fn stop(&mut self) {
+            if let Some(start) = self.write_start.take() {
+                if !self.page.is_empty() {
+                    let page_base = start / self.page_size * self.page_size;
+                    for (offset, byte) in self.page.iter().enumerate() {
+                        let target = page_base + (start + offset as u32) % self.page_size;
+                        self.memory[target as usize] = *byte;
+                    }
+                    self.writes.push((start, self.page.len()));
+                    self.busy = 3;
+                }
+            }
+            self.page.clear();
+            self.active = false;
+            self.sda_out = true;
+        }
+
+        fn rising(&mut self, sda: bool) {
+            if !self.active {
+                return;
+            }
+            if self.clock < 8 {
+                if self.receiving {
+                    self.shift = (self.shift << 1) | sda as u8;
+                }
+            } else if !self.receiving {
+                self.master_ack = !sda;
+            }
+            self.clock += 1;
+        }
+
+        fn falling(&mut self) {
+            if !self.active {
+                return;
+            }
+            if self.clock == 8 {
+                if self.receiving {
+                    let ack = self.receive(self.shift);
+                    self.sda_out = !ack;
+                    if !ack {
+                        self.active = false;
+                        self.sda_out = true;
+                    }
+                } else {
+                    self.sda_out = true;
+                }
+            } else if self.clock == 9 {
+                self.clock = 0;
+                self.shift = 0;
+                self.sda_out = true;
+                if (self.receiving && self.reading) || (!self.receiving && self.master_ack) {
+                    self.receiving = false;
+                    self.transmit = self.memory[self.pointer as usize];
+                    self.pointer = (self.pointer + 1) % self.memory.len() as u32;
+                    self.sda_out = self.transmit & 0x80 != 0;
+                } else if !self.receiving {
+                    self.active = false;
+                }
+            } else if !self.receiving {
+                self.sda_out = (self.transmit >> (7 - self.clock)) & 0x01 != 0;
+            }
+        }
+
+        /// Byte written by master, return acknowledge
+        fn receive(&mut self, byte: u8) -> bool {
+            if self.first_byte {
+                self.first_byte = false;
+                let device = byte >> 1;
+                if device & 0x78 != 0x50 || (device ^ self.chip_address) & 0x07 & !self.block_mask() != 0 {
+                    return false;
+                }
+                if self.busy > 0 {
+                    self.busy -= 1;
+                    self.busy_nacks += 1;
+                    return false;
+                }
+                self.block = (device & self.block_mask()) as u32;
+                self.reading = byte & 0x01 != 0;
+                self.address_received = 0;
+                self.word_address = 0;
+                return true;
+            }
+            if self.address_received < self.address_bytes {
+                self.word_address = (self.word_address << 8) | byte as u32;
+                self.address_received += 1;
+                if self.address_received == self.address_bytes {
+                    self.pointer = (self.block * self.block_size() + self.word_address) % self.memory.len() as u32;
+                    self.write_start = Some(self.pointer);
+                }
+            } else {
+                self.page.push(byte);
+                // address counter rolls over inside the page
+                let page_base = self.pointer / self.page_size * self.page_size;
+                self.pointer = page_base + (self.pointer + 1) % self.page_size;
+            }
+            true
+        }
+    }
+    impl MpsseTransport for SimulatedI2cEeprom {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    EN_3_PHASE => self.three_phase = true,
+                    TCK_DIVISOR => index += 2,
+                    SET_BITS_LOW => {
+                        let (value, direction) = (commands[index], commands[index + 1]);
+                        assert_ne!(direction & I2C_PIN_SCL, 0, "SCL must be driven");
+                        self.sda_driven = direction & I2C_PIN_SDA_OUT != 0;
+                        let sda = !self.sda_driven || value & I2C_PIN_SDA_OUT != 0;
+                        self.set_lines(value & I2C_PIN_SCL != 0, sda);
+                        index += 2;
+                    }
+                    0x13 | 0x22 => {
+                        assert!(!self.scl, "data clocked with SCL high");
+                        let bits = commands[index] + 1;
+                        let data = if opcode & MPSSE_DO_WRITE != 0 { commands[index + 1] } else { 0xff };
+                        index += if opcode & MPSSE_DO_WRITE != 0 { 2 } else { 1 };
+                        let mut result = 0u8;
+                        for bit in 0..bits {
+                            let level = data & (0x80 >> bit) != 0;
+                            // MPSSE drives only pins set as output
+                            let sda = if opcode & MPSSE_DO_WRITE != 0 && self.sda_driven { level } else { self.sda_master };
+                            self.set_lines(false, sda);
+                            self.set_lines(true, sda);
+                            result = (result << 1) | self.line() as u8;
+                            self.set_lines(false, sda);
+                        }
+                        if opcode & MPSSE_DO_READ != 0 {
+                            self.replies.push_back(result);
+                        }
+                    }
+                    _ => panic!("simulated I2C EEPROM got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated I2C EEPROM has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    fn i2c_eeprom(name: &str, chip_address: u8) -> I2cEeprom<SimulatedI2cEeprom> {
+        let profile = eeprom_profile(name).unwrap();
+        let mut i2c = I2cMaster::new(SimulatedI2cEeprom::new(profile, chip_address));
+        i2c.init(400_000, true).unwrap();
+        I2cEeprom::new(i2c, profile, chip_address).unwrap()
+    }
+
+    fn pattern(length: usize, seed: u8) -> Vec<u8> {
+        (0..length).map(|index| (index as u8).wrapping_mul(37).wrapping_add(seed)).collect()
+    }
+
+    #[test]
+    fn eeprom_profiles() {
+        let profile = eeprom_profile("24C02").unwrap();
+        assert_eq!(profile.size, 256);
+        assert_eq!(profile.bus, EepromBus::I2c { address_bytes: 1 });
+        assert_eq!(eeprom_profile("25c040").unwrap().bus, EepromBus::Spi { address_bytes: 1 });
+        assert_eq!(eeprom_profile("93c46").unwrap().bus, EepromBus::Microwire { address_bits: 6, word_size: 2 });
+        assert_eq!(eeprom_profile("93c46x8").unwrap().bus, EepromBus::Microwire { address_bits: 7, word_size: 1 });
+        assert!(eeprom_profile("24c03").is_none());
+        let i2c = I2cMaster::new(SimulatedI2cEeprom::new(profile, 0));
+        assert!(I2cEeprom::new(i2c, eeprom_profile("25c040").unwrap(), 0).is_err());
+    }
+
+    #[test]
+    fn i2c_three_phase_clocking_on_high_speed_only() {
+        let profile = eeprom_profile("24C02").unwrap();
+        let mut i2c = I2cMaster::new(SimulatedI2cEeprom::new(profile, 0x50));
+        assert_eq!(i2c.init(100_000, false).unwrap(), 100_000);
+        assert!(!i2c.transport().three_phase);
+        assert_eq!(i2c.init(400_000, true).unwrap(), 400_000);
+        assert!(i2c.transport().three_phase);
+    }
+
+    #[test]
+    fn i2c_eeprom_pages_and_ack_polling() {
+        let mut eeprom = i2c_eeprom("24c02", 0);
+        let data = pattern(20, 1);
+        eeprom.write(5, &data).unwrap();
+        let simulator = eeprom.i2c().transport();
+        assert_eq!(simulator.writes, vec![(5, 3), (8, 8), (16, 8), (24, 1)]);
+        assert!(simulator.busy_nacks >= 4, "write cycles weren't polled");
+        assert_eq!(&simulator.memory[5..25], &data[..]);
+        assert_eq!(simulator.memory[4], 0xff);
+        assert_eq!(eeprom.read(0, 30).unwrap()[5..25], data[..]);
+        eeprom.verify(5, &data).unwrap();
+        assert!(eeprom.read(250, 10).is_err());
+    }
+
+    #[test]
+    fn i2c_eeprom_block_bits() {
+        let mut eeprom = i2c_eeprom("24c16", 0);
+        let data = pattern(24, 2);
+        eeprom.write(0x3f4, &data).unwrap();
+        let simulator = eeprom.i2c().transport();
+        assert_eq!(&simulator.memory[0x3f4..0x40c], &data[..]);
+        assert_eq!(simulator.writes, vec![(0x3f4, 12), (0x400, 12)]);
+        assert_eq!(eeprom.read(0x3f0, 32).unwrap()[4..28], data[..]);
+    }
+
+    #[test]
+    fn i2c_eeprom_two_byte_address() {
+        let mut eeprom = i2c_eeprom("24c256", 3);
+        let data = pattern(100, 3);
+        eeprom.write(0x1fa0, &data).unwrap();
+        assert_eq!(eeprom.i2c().transport().writes, vec![(0x1fa0, 32), (0x1fc0, 64), (0x2000, 4)]);
+        assert_eq!(eeprom.read(0x1fa0, 100).unwrap(), data);
+        // no part strapped to chip address 2
+        assert!(!eeprom.i2c().probe(I2C_EEPROM_BASE_ADDRESS | 2).unwrap());
+        assert!(eeprom.i2c().probe(I2C_EEPROM_BASE_ADDRESS | 3).unwrap());
+        let mut other = I2cEeprom::new(eeprom.into_inner(), eeprom_profile("24c256").unwrap(), 2).unwrap();
+        assert!(other.read(0, 4).is_err());
+    }
+
+    #[test]
+    fn i2c_eeprom_address_width_detection() {
+        let detect = |name: &str, content: Option<Vec<u8>>| {
+            let profile = eeprom_profile(name).unwrap();
+            let mut simulator = SimulatedI2cEeprom::new(profile, 0);
+            if let Some(content) = content {
+                simulator.memory[..content.len()].copy_from_slice(&content);
+            }
+            let mut i2c = I2cMaster::new(simulator);
+            let result = I2cEeprom::detect_address_bytes(&mut i2c, 0).unwrap();
+            assert!(i2c.transport().writes.is_empty(), "detection must not write");
+            result
+        };
+        assert_eq!(detect("24c02", Some(pattern(64, 5))), Some(1));
+        assert_eq!(detect("24c16", Some(pattern(64, 5))), Some(1));
+        assert_eq!(detect("24c64", Some(pattern(64, 5))), Some(2));
+        assert_eq!(detect("24c64", None), None);
+    }
+
+    /// 25xx EEPROM driven by interpreting MPSSE commands (SPI mode 0)
+    struct SimulatedSpiEeprom {
+        memory: Vec<u8>,
+        address_bytes: usize,
+        page_size: usize,
+        status: u8,
+        selected: bool,
+        command: Vec<u8>,
+        busy: u32,
+        writes: Vec<(u32, usize)>,
+        replies: VecDeque<u8>,
+    }
+    impl SimulatedSpiEeprom {
+        fn new(profile: &EepromProfile) -> Self {
+            let address_bytes = match profile.bus {
+                EepromBus::Spi { address_bytes } => address_bytes as usize,
+                _ => panic!("not SPI profile"),
+            };
+            SimulatedSpiEeprom {
+                memory: vec![0xff; profile.size as usize], address_bytes, page_size: profile.page_size as usize,
+                status: 0, selected: false, command: Vec::new(), busy: 0, writes: Vec::new(), replies: VecDeque::new(),
+            }
+        }
+
+        /// Opcode without A8 and address of current command
+        fn decode(&self) -> (u8, usize) {
+            let mut opcode = self.command[0];
+            let mut address = self.command[1..1 + self.address_bytes].iter().fold(0, |address, byte| (address << 8) | *byte as usize);
+            if self.address_bytes == 1 {
+                address |= ((opcode & SPI_EEPROM_A8_BIT) as usize) << 5;
+                opcode &= !SPI_EEPROM_A8_BIT;
+            }
+            (opcode, address % self.memory.len())
+        }
+
+        fn protected(&self, address: usize) -> bool {
+            let protected = match (self.status >> 2) & 0x03 {
+                0 => 0,
+                1 => self.memory.len() / 4,
+                2 => self.memory.len() / 2,
+                _ => self.memory.len(),
+            };
+            address >= self.memory.len() - protected
+        }
+
+        fn byte(&mut self, mosi: u8) -> u8 {
+            let index = self.command.len();
+            self.command.push(mosi);
+            match self.command[0] {
+                SPI_EEPROM_READ_STATUS if index >= 1 => {
+                    if self.busy > 0 {
+                        self.busy -= 1;
+                        self.status | SPI_EEPROM_STATUS_WIP
+                    } else {
+                        self.status
+                    }
+                }
+                _ if index > self.address_bytes => {
+                    let (opcode, address) = self.decode();
+                    if opcode == SPI_EEPROM_READ {
+                        self.memory[(address + index - 1 - self.address_bytes) % self.memory.len()]
+                    } else {
+                        0xff
+                    }
+                }
+                _ => 0xff,
+            }
+        }
+
+        fn deselect(&mut self) {
+            if self.command.is_empty() {
+                return;
+            }
+            let write_enabled = self.status & SPI_EEPROM_STATUS_WEL != 0;
+            match self.command[0] {
+                SPI_EEPROM_WRITE_ENABLE if self.busy == 0 => self.status |= SPI_EEPROM_STATUS_WEL,
+                SPI_EEPROM_WRITE_DISABLE => self.status &= !SPI_EEPROM_STATUS_WEL,
+                SPI_EEPROM_WRITE_STATUS if write_enabled && self.command.len() == 2 => {
+                    self.status = self.command[1] & (SPI_EEPROM_STATUS_BP0 | SPI_EEPROM_STATUS_BP1 | SPI_EEPROM_STATUS_WPEN);
+                    self.busy = 2;
+                }
+                _ if write_enabled && self.command.len() > 1 + self.address_bytes => {
+                    let (opcode, address) = self.decode();
+                    if opcode == SPI_EEPROM_WRITE {
+                        let data = self.command[1 + self.address_bytes..].to_vec();
+                        let page_base = address / self.page_size * self.page_size;
+                        for (offset, byte) in data.iter().enumerate() {
+                            let target = page_base + (address + offset) % self.page_size;
+                            if !self.protected(target) {
+                                self.memory[target] = *byte;
+                            }
+                        }
+                        self.writes.push((address as u32, data.len()));
+                        self.status &= !SPI_EEPROM_STATUS_WEL;
+                        self.busy = 3;
+                    }
+                }
+                _ => {}
+            }
+            self.command.clear();
+        }
+    }
+    impl MpsseTransport for SimulatedSpiEeprom {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    TCK_DIVISOR => index += 2,
+                    SET_BITS_LOW => {
+                        let selected = commands[index] & SPI_PIN_CS == 0;
+                        if self.selected && !selected {
+                            self.deselect();
+                        }
+                        self.selected = selected;
+                        index += 2;
+                    }
+                    0x11 | 0x20 | 0x31 => {
+                        assert!(self.selected, "SPI data clocked without chip select");
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for position in 0..length {
+                            let mosi = if opcode & MPSSE_DO_WRITE != 0 { commands[index + position] } else { 0xff };
+                            let miso = self.byte(mosi);
+                            if opcode & MPSSE_DO_READ != 0 {
+                                self.replies.push_back(miso);
+                            }
+                        }
+                        if opcode & MPSSE_DO_WRITE != 0 {
+                            index += length;
+                        }
+                    }
+                    _ => panic!("simulated SPI EEPROM got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated SPI EEPROM has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    fn spi_eeprom(name: &str) -> SpiEeprom<SimulatedSpiEeprom> {
+        let profile = eeprom_profile(name).unwrap();
+        let mut spi = SpiMaster::new(SimulatedSpiEeprom::new(profile), SpiMode::Mode0);
+        spi.init(5_000_000, true).unwrap();
+        SpiEeprom::new(spi, profile).unwrap()
+    }
+
+    #[test]
+    fn spi_eeprom_a8_in_opcode() {
+        let mut eeprom = spi_eeprom("25c040");
+        let data = pattern(40, 4);
+        eeprom.write(0xf0, &data).unwrap();
+        let simulator = eeprom.spi().transport();
+        assert_eq!(simulator.writes, vec![(0xf0, 16), (0x100, 16), (0x110, 8)]);
+        assert_eq!(&simulator.memory[0xf0..0x118], &data[..]);
+        assert_eq!(eeprom.read(0xe8, 56).unwrap()[8..48], data[..]);
+        eeprom.verify(0xf0, &data).unwrap();
+    }
+
+    #[test]
+    fn spi_eeprom_status_and_protection() {
+        let mut eeprom = spi_eeprom("25c256");
+        eeprom.write(0x7ffc, &[1, 2, 3, 4]).unwrap();
+        eeprom.write_status(SPI_EEPROM_STATUS_BP0).unwrap();
+        assert_eq!(eeprom.read_status().unwrap(), SPI_EEPROM_STATUS_BP0);
+        // upper quarter is protected now, write cycle happens but content stays
+        eeprom.write(0x7ffc, &[5, 6, 7, 8]).unwrap();
+        let error = eeprom.verify(0x7ffc, &[5, 6, 7, 8]).unwrap_err();
+        assert!(format!("{}", error).contains("0x7ffc"), "{}", error);
+        eeprom.write(0x10, &[9, 10]).unwrap();
+        eeprom.verify(0x10, &[9, 10]).unwrap();
+    }
+
+    /// 93Cxx EEPROM driven by interpreting MPSSE commands bit by bit
+    struct SimulatedMicrowireEeprom {
+        memory: Vec<u8>,
+        address_bits: u8,
+        word_size: u8,
+        selected: bool,
+        started: bool,
+        shift: u64,
+        count: u8,
+        write_enabled: bool,
+        /// word being shifted out by READ
+        reading: Option<u32>,
+        output: Vec<bool>,
+        data_out: bool,
+        /// write or erase started on deselect: first word, words, value
+        pending: Option<(u32, u32, Option<u32>)>,
+        busy: u32,
+        writes: u32,
+        replies: VecDeque<u8>,
+    }
+    impl SimulatedMicrowireEeprom {
+        fn new(profile: &EepromProfile) -> Self {
+            let (address_bits, word_size) = match profile.bus {
+                EepromBus::Microwire { address_bits, word_size } => (address_bits, word_size),
+                _ => panic!("not Microwire profile"),
+            };
+            SimulatedMicrowireEeprom {
+                memory: vec![0xff; profile.size as usize], address_bits, word_size, selected: false, started: false,
+                shift: 0, count: 0, write_enabled: false, reading: None, output: Vec::new(), data_out: true,
+                pending: None, busy: 0, writes: 0, replies: VecDeque::new(),
+            }
+        }
+
+        fn words(&self) -> u32 {
+            self.memory.len() as u32 / self.word_size as u32
+        }
+
+        fn word(&self, index: u32) -> u32 {
+            let base = (index * self.word_size as u32) as usize;
+            self.memory[base..base + self.word_size as usize].iter().rev().fold(0, |value, byte| (value << 8) | *byte as u32)
+        }
+
+        fn load_word(&mut self, index: u32) {
+            let bits = 8 * self.word_size;
+            let value = self.word(index);
+            self.output = (0..bits).map(|bit| value & (1 << (bits - 1 - bit)) != 0).collect();
+            self.reading = Some(index);
+        }
+
+        fn rising(&mut self, data_in: bool) {
+            if !self.selected {
+                return;
+            }
+            if let Some(word) = self.reading {
+                if self.output.is_empty() {
+                    self.load_word((word + 1) % self.words());
+                }
+                self.data_out = self.output.remove(0);
+                return;
+            }
+            if !self.started {
+                self.started = data_in;
+                return;
+            }
+            self.shift = (self.shift << 1) | data_in as u64;
+            self.count += 1;
+            let command_bits = 2 + self.address_bits;
+            let word_bits = 8 * self.word_size;
+            if self.count == command_bits {
+                let opcode = (self.shift >> self.address_bits) as u32 & 0x03;
+                let address = self.shift as u32 & ((1 << self.address_bits) - 1);
+                match opcode {
+                    0b10 => {
+                        self.load_word(address % self.words());
+                        // dummy zero before data
+                        self.data_out = false;
+                    }
+                    0b11 => self.pending = Some((address % self.words(), 1, None)),
+                    _ if opcode == 0 => match address >> (self.address_bits - 2) {
+                        0b11 => self.write_enabled = true,
+                        0b00 => self.write_enabled = false,
+                        0b10 => self.pending = Some((0, self.words(), None)),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            } else if self.count == command_bits + word_bits {
+                let opcode = (self.shift >> (self.count - 2)) as u32 & 0x03;
+                let address = (self.shift >> word_bits) as u32 & ((1 << self.address_bits) - 1);
+                let value = self.shift as u32 & ((1 << word_bits) - 1);
+                match opcode {
+                    0b01 => self.pending = Some((address % self.words(), 1, Some(value))),
+                    0b00 if address >> (self.address_bits - 2) == 0b01 => self.pending = Some((0, self.words(), Some(value))),
+                    _ => {}
+                }
+            }
+        }
+
+        fn select(&mut self, selected: bool) {
+            if selected && !self.selected {
+                self.started = false;
+                self.shift = 0;
+                self.count = 0;
+                self.reading = None;
+                self.output.clear();
+            }
+            if !selected && self.selected {
+                if let (Some((first, count, value)), true) = (self.pending.take(), self.write_enabled) {
+                    for word in first..first + count {
+                        let value = value.unwrap_or(0xffff_ffff);
+                        for byte in 0..self.word_size as u32 {
+                            self.memory[(word * self.word_size as u32 + byte) as usize] = (value >> (8 * byte)) as u8;
+                        }
+                    }
+                    self.writes += 1;
+                    self.busy = 2;
+                }
+                self.pending = None;
+            }
+            self.selected = selected;
+        }
+    }
+    impl MpsseTransport for SimulatedMicrowireEeprom {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    TCK_DIVISOR => index += 2,
+                    SET_BITS_LOW => {
+                        self.select(commands[index] & MICROWIRE_PIN_CS != 0);
+                        index += 2;
+                    }
+                    GET_BITS_LOW => {
+                        let ready = if self.busy > 0 {
+                            self.busy -= 1;
+                            false
+                        } else {
+                            true
+                        };
+                        let ready = self.selected && !self.started && ready;
+                        self.replies.push_back(if ready { MICROWIRE_PIN_DO } else { 0 });
+                    }
+                    0x13 => {
+                        assert!(self.selected, "Microwire data clocked without chip select");
+                        let bits = commands[index] + 1;
+                        let data = commands[index + 1];
+                        index += 2;
+                        for bit in 0..bits {
+                            self.rising(data & (0x80 >> bit) != 0);
+                        }
+                    }
+                    0x24 => {
+                        assert!(self.selected, "Microwire data clocked without chip select");
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for _ in 0..length {
+                            let mut byte = 0u8;
+                            for _ in 0..8 {
+                                self.rising(false);
+                                byte = (byte << 1) | self.data_out as u8;
+                            }
+                            self.replies.push_back(byte);
+                        }
+                    }
+                    _ => panic!("simulated Microwire EEPROM got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated Microwire EEPROM has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    fn microwire_eeprom(name: &str) -> MicrowireEeprom<SimulatedMicrowireEeprom> {
+        let profile = eeprom_profile(name).unwrap();
+        let mut microwire = MicrowireMaster::new(SimulatedMicrowireEeprom::new(profile));
+        microwire.init(1_000_000, true).unwrap();
+        MicrowireEeprom::new(microwire, profile).unwrap()
+    }
+
+    #[test]
+    fn microwire_eeprom_x16_unaligned_write() {
+        let mut eeprom = microwire_eeprom("93c46");
+        let content = pattern(128, 6);
+        eeprom.microwire().transport().memory.copy_from_slice(&content);
+        assert_eq!(eeprom.read(0, 128).unwrap(), content);
+        assert_eq!(eeprom.read(3, 5).unwrap(), content[3..8].to_vec());
+        eeprom.write(3, &[1, 2, 3, 4, 5]).unwrap();
+        let simulator = eeprom.microwire().transport();
+        assert_eq!(simulator.writes, 3);
+        assert!(!simulator.write_enabled, "writes must be disabled again");
+        assert_eq!(&simulator.memory[2..9], &[content[2], 1, 2, 3, 4, 5, content[8]]);
+        eeprom.verify(3, &[1, 2, 3, 4, 5]).unwrap();
+    }
+
+    #[test]
+    fn microwire_eeprom_x8_and_erase_all() {
+        let mut eeprom = microwire_eeprom("93c66x8");
+        let data = pattern(20, 7);
+        eeprom.write(0x1f0, &data[..16]).unwrap();
+        eeprom.write(0x0f8, &data).unwrap();
+        assert_eq!(eeprom.read(0x1f0, 16).unwrap(), data[..16].to_vec());
+        assert_eq!(eeprom.read(0x0f8, 20).unwrap(), data);
+        assert_eq!(eeprom.microwire().transport().writes, 36);
+        eeprom.erase_all().unwrap();
+        assert!(eeprom.read(0, 512).unwrap().iter().all(|&byte| byte == 0xff));
+    }
+}
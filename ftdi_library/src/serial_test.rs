@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use crate::ftdi::baud::BaudPolicy;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::serial::{*};
+
+    #[test]
+    fn serial_config_builder() {
+        assert_eq!(SerialConfig::builder().build().unwrap(), SerialConfig::default());
+        let config = SerialConfig::builder()
+            .baudrate(250_000)
+            .baud_policy(BaudPolicy::Exact)
+            .data_bits(8)
+            .stop_bits(ftdi_stopbits_type::STOP_BIT_2)
+            .flow_control(FlowControl::RtsCts)
+            .latency_timer(2)
+            .timeouts(100, 200)
+            .event_char(Some(b'\n'))
+            .build().unwrap();
+        assert_eq!((config.baudrate, config.baud_policy, config.stop_bits), (250_000, BaudPolicy::Exact, ftdi_stopbits_type::STOP_BIT_2));
+        assert_eq!((config.latency_timer, config.read_timeout, config.write_timeout, config.event_char), (2, 100, 200, Some(0x0a)));
+        assert_eq!(config.parity, ftdi_parity_type::NONE);
+
+        assert!(SerialConfig::builder().data_bits(9).build().is_err());
+        assert!(SerialConfig::builder().baudrate(0).build().is_err());
+        assert!(SerialConfig::builder().latency_timer(0).build().is_err());
+        assert!(SerialConfig::builder().timeouts(-1, 0).build().is_err());
+    }
+
+    #[test]
+    fn serial_config_toml() {
+        let config = SerialConfig::from_toml(r#"
+            baudrate = 31250
+            data_bits = 7
+            parity = "EVEN"
+            flow_control = "XonXoff"
+            baud_policy = "1.5%"
+        "#).unwrap();
+        assert_eq!((config.baudrate, config.data_bits, config.parity), (31250, 7, ftdi_parity_type::EVEN));
+        assert_eq!((config.flow_control, config.baud_policy), (FlowControl::XonXoff, BaudPolicy::Strict(1.5)));
+        assert_eq!(config.stop_bits, SerialConfig::default().stop_bits);
+        assert_eq!(SerialConfig::from_toml(&config.to_toml().unwrap()).unwrap(), config);
+
+        assert!(SerialConfig::from_toml("data_bits = 6").is_err());
+        assert!(SerialConfig::from_toml("speed = 9600").is_err());
+        assert!(SerialConfig::from_toml("baud_policy = \"close\"").is_err());
+        for policy in ["exact", "best_effort", "5%", "0.25%"].iter() {
+            assert_eq!(policy.parse::<BaudPolicy>().unwrap().to_string(), *policy);
+        }
+    }
+
+    #[test]
+    fn flow_control_values() {
+        assert_eq!(FlowControl::Disabled.value(), 0);
+        assert_eq!(FlowControl::RtsCts.value(), 0x0100);
+        assert_eq!(FlowControl::DtrDsr.value(), 0x0200);
+        assert_eq!(FlowControl::XonXoff.value(), 0x0400);
+        assert_eq!((SIO_SET_DTR_HIGH, SIO_SET_DTR_LOW), (0x0101, 0x0100));
+        assert_eq!((SIO_SET_RTS_HIGH, SIO_SET_RTS_LOW), (0x0202, 0x0200));
+    }
+}
@@ -0,0 +1,320 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::time::Duration;
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::mpsse::MpsseTransport;
+    use crate::ftdi::spi::{SpiMaster, SpiMode, SPI_PIN_CS};
+    use crate::ftdi::spi_flash::{*};
+
+    /// SPI NOR flash driven by interpreting MPSSE commands (SPI mode 0)
+    struct SimulatedFlash {
+        id: [u8; 3],
+        size: u32,
+        sfdp: Vec<u8>,
+        memory: HashMap<u32, u8>,
+        selected: bool,
+        command: Vec<u8>,
+        four_byte: bool,
+        write_enabled: bool,
+        /// status reads left until the current operation completes, u32::MAX never completes
+        busy: u32,
+        /// executed commands with their addresses
+        log: Vec<(u8, u32)>,
+        replies: VecDeque<u8>,
+    }
+    impl SimulatedFlash {
+        fn new(id: [u8; 3], size: u32, sfdp: Vec<u8>) -> Self {
+            SimulatedFlash {
+                id, size, sfdp, memory: HashMap::new(), selected: false, command: Vec::new(), four_byte: false,
+                write_enabled: false, busy: 0, log: Vec::new(), replies: VecDeque::new(),
+            }
+        }
+
+        fn address_bytes(&self) -> usize {
+            if self.four_byte { 4 } else { 3 }
+        }
+
+        fn address(&self, bytes: usize) -> u32 {
+            self.command[1..1 + bytes].iter().fold(0, |address, byte| (address << 8) | *byte as u32)
+        }
+
+        fn read(&self, address: u32) -> u8 {
+            *self.memory.get(&(address % self.size)).unwrap_or(&0xff)
+        }
+
+        /// Byte clocked in while 'mosi' is clocked out
+        fn byte(&mut self, mosi: u8) -> u8 {
+            let index = self.command.len();
+            self.command.push(mosi);
+            let opcode = self.command[0];
+            let address_bytes = self.address_bytes();
+            match opcode {
+                SPI_FLASH_READ_JEDEC_ID if index >= 1 => *self.id.get(index - 1).unwrap_or(&0),
+                SPI_FLASH_READ_STATUS if index >= 1 => {
+                    if self.busy > 0 && self.busy != u32::MAX {
+                        self.busy -= 1;
+                    }
+                    (if self.busy > 0 { SPI_FLASH_STATUS_WIP } else { 0 })
+                        | (if self.write_enabled { SPI_FLASH_STATUS_WEL } else { 0 })
+                }
+                SPI_FLASH_READ_SFDP if index >= 5 => {
+                    let offset = self.address(3) as usize + index - 5;
+                    *self.sfdp.get(offset).unwrap_or(&0xff)
+                }
+                SPI_FLASH_FAST_READ if index >= 2 + address_bytes => {
+                    self.read(self.address(address_bytes) + (index - 2 - address_bytes) as u32)
+                }
+                _ => 0xff,
+            }
+        }
+
+        fn deselect(&mut self) {
+            let command: Vec<u8> = self.command.drain(..).collect();
+            if command.is_empty() {
+                return;
+            }
+            self.command = command;
+            let opcode = self.command[0];
+            let address_bytes = self.address_bytes();
+            match opcode {
+                SPI_FLASH_WRITE_ENABLE => self.write_enabled = self.busy == 0,
+                SPI_FLASH_WRITE_DISABLE => self.write_enabled = false,
+                SPI_FLASH_ENTER_4BYTE => self.four_byte = true,
+                SPI_FLASH_EXIT_4BYTE => self.four_byte = false,
+                SPI_FLASH_PAGE_PROGRAM if self.write_enabled => {
+                    let address = self.address(address_bytes);
+                    let data = &self.command[1 + address_bytes..];
+                    assert!(data.len() <= 256, "page program of {} bytes", data.len());
+                    for (offset, byte) in data.to_vec().into_iter().enumerate() {
+                        // address wraps inside the page
+                        let target = (address & !0xff) | ((address + offset as u32) & 0xff);
+                        let old = self.read(target);
+                        self.memory.insert(target, old & byte);
+                    }
+                    self.log.push((opcode, address));
+                    self.finish_write(3);
+                }
+                SPI_FLASH_SECTOR_ERASE | SPI_FLASH_BLOCK_ERASE_32K | SPI_FLASH_BLOCK_ERASE_64K if self.write_enabled => {
+                    let size = match opcode {
+                        SPI_FLASH_SECTOR_ERASE => 0x1000,
+                        SPI_FLASH_BLOCK_ERASE_32K => 0x8000,
+                        _ => 0x10000,
+                    };
+                    let address = self.address(address_bytes) & !(size - 1);
+                    self.memory.retain(|&key, _| key < address || key >= address + size);
+                    self.log.push((opcode, address));
+                    self.finish_write(10);
+                }
+                SPI_FLASH_CHIP_ERASE if self.write_enabled => {
+                    self.memory.clear();
+                    self.log.push((opcode, 0));
+                    self.finish_write(100);
+                }
+                _ => {}
+            }
+            self.command.clear();
+        }
+
+        fn finish_write(&mut self, polls: u32) {
+            self.write_enabled = false;
+            if self.busy != u32::MAX {
+                self.busy = polls;
+            }
+        }
+    }
+    impl MpsseTransport for SimulatedFlash {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    TCK_DIVISOR | SET_BITS_HIGH => index += 2,
+                    SET_BITS_LOW => {
+                        let selected = commands[index] & SPI_PIN_CS == 0;
+                        if self.selected && !selected {
+                            self.deselect();
+                        }
+                        self.selected = selected;
+                        index += 2;
+                    }
+                    0x11 | 0x20 | 0x31 => {
+                        assert!(self.selected, "SPI data clocked without chip select");
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for position in 0..length {
+                            let mosi = if opcode & MPSSE_DO_WRITE != 0 { commands[index + position] } else { 0xff };
+                            let miso = self.byte(mosi);
+                            if opcode & MPSSE_DO_READ != 0 {
+                                self.replies.push_back(miso);
+                            }
+                        }
+                        if opcode & MPSSE_DO_WRITE != 0 {
+                            index += length;
+                        }
+                    }
+                    _ => panic!("simulated flash got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated flash has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    const W25Q128_ID: [u8; 3] = [0xef, 0x40, 0x18];
+
+    /// SFDP of 16 MB flash: 4 KB / 32 KB / 64 KB erase, 256 bytes page
+    fn w25q128_sfdp() -> Vec<u8> {
+        let mut sfdp = vec![0xffu8; 0x80 + 16 * 4];
+        sfdp[..16].copy_from_slice(&[
+            0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff,
+            0x00, 0x06, 0x01, 0x10, 0x80, 0x00, 0x00, 0xff,
+        ]);
+        let table: [u32; 16] = [
+            0xfff9_20e5, 0x07ff_ffff, 0x6b08_eb44, 0x3b42_bb08, 0xffff_fffe, 0xff00_ffff, 0xeb40_ffff, 0x520f_200c,
+            0x0000_d810, 0x0060_0220, 0xf1a2_0081, 0xd6c3_e7b0, 0xb3a6_7a0f, 0x7a75_7a75, 0x5cd5_a2f7, 0xff3d_f319,
+        ];
+        for (index, value) in table.iter().enumerate() {
+            sfdp[0x80 + index * 4..0x84 + index * 4].copy_from_slice(&value.to_le_bytes());
+        }
+        sfdp
+    }
+
+    fn flash(id: [u8; 3], size: u32, sfdp: Vec<u8>) -> SpiFlash<SimulatedFlash> {
+        let mut spi = SpiMaster::new(SimulatedFlash::new(id, size, sfdp), SpiMode::Mode0);
+        spi.init(30_000_000, true).unwrap();
+        SpiFlash::probe(spi).unwrap()
+    }
+
+    #[test]
+    fn spi_mode_opcodes() {
+        assert_eq!(SpiMode::Mode0.opcode(true, false), 0x11);
+        assert_eq!(SpiMode::Mode0.opcode(false, true), 0x20);
+        assert_eq!(SpiMode::Mode0.opcode(true, true), 0x31);
+        assert_eq!(SpiMode::Mode3.opcode(true, true), 0x31);
+        assert_eq!(SpiMode::Mode1.opcode(true, false), 0x10);
+        assert_eq!(SpiMode::Mode1.opcode(false, true), 0x24);
+        assert_eq!(SpiMode::Mode2.opcode(true, true), 0x34);
+    }
+
+    #[test]
+    fn jedec_id_and_sfdp_geometry() {
+        let mut flash = flash(W25Q128_ID, 0x100_0000, w25q128_sfdp());
+        assert_eq!(flash.id(), JedecId { manufacturer: 0xef, memory_type: 0x40, capacity: 0x18 });
+        assert_eq!(flash.id().manufacturer_name(), Some("Winbond"));
+        assert_eq!(format!("{}", flash.id()), "ef 40 18 (Winbond, 16384 KiB)");
+        assert_eq!(flash.geometry(), &FlashGeometry {
+            size: 0x100_0000,
+            page_size: 256,
+            erase_types: vec![
+                EraseType { size: 0x1000, opcode: 0x20 },
+                EraseType { size: 0x8000, opcode: 0x52 },
+                EraseType { size: 0x10000, opcode: 0xd8 },
+            ],
+            from_sfdp: true,
+        });
+        assert!(!flash.spi().transport().four_byte);
+        // blank table has density 2^0x7fffffff bits
+        let mut table = vec![0u8; 9 * 4];
+        table[4..8].copy_from_slice(&[0xff; 4]);
+        assert!(FlashGeometry::from_bfpt(&table).is_err());
+        table[4..8].copy_from_slice(&(0x8000_0000u32 | 27).to_le_bytes());
+        assert_eq!(FlashGeometry::from_bfpt(&table).unwrap().size, 0x100_0000);
+    }
+
+    #[test]
+    fn geometry_guessed_without_sfdp() {
+        let flash = flash([0xc2, 0x20, 0x17], 0x80_0000, Vec::new());
+        assert!(!flash.geometry().from_sfdp);
+        assert_eq!(flash.geometry().size, 0x80_0000);
+        assert_eq!(flash.geometry().smallest_erase(), Some(EraseType { size: 0x1000, opcode: SPI_FLASH_SECTOR_ERASE }));
+        assert_eq!(JedecId { manufacturer: 0x20, memory_type: 0xba, capacity: 0x21 }.size(), Some(128 * 1024 * 1024));
+
+        let mut spi = SpiMaster::new(SimulatedFlash::new([0xff; 3], 0, Vec::new()), SpiMode::Mode0);
+        spi.init(1_000_000, false).unwrap();
+        assert!(SpiFlash::probe(spi).is_err());
+    }
+
+    #[test]
+    fn write_image_keeps_neighbours() {
+        let mut flash = flash(W25Q128_ID, 0x100_0000, w25q128_sfdp());
+        flash.spi().transport().memory.insert(0x0fff, 0x11);
+        flash.spi().transport().memory.insert(0x1000, 0x22);
+        flash.spi().transport().memory.insert(0x2100, 0x33);
+        flash.spi().transport().memory.insert(0x3000, 0x44);
+        let image: Vec<u8> = (0..0x1000u32).map(|value| (value * 13 + 7) as u8).collect();
+        let mut last_progress = (0, 0);
+        flash.write_image(0x1100, &image, |done, total| last_progress = (done, total)).unwrap();
+        assert_eq!(last_progress, (0x2000, 0x2000));
+        assert_eq!(flash.read(0x1100, image.len()).unwrap(), image);
+        assert_eq!(flash.read(0x0fff, 2).unwrap(), vec![0x11, 0x22]);
+        assert_eq!(flash.read(0x3000, 1).unwrap(), vec![0x44]);
+        let transport = flash.spi().transport();
+        let erases: Vec<(u8, u32)> = transport.log.iter().filter(|(opcode, _)| *opcode != SPI_FLASH_PAGE_PROGRAM).copied().collect();
+        assert_eq!(erases, vec![(SPI_FLASH_SECTOR_ERASE, 0x1000), (SPI_FLASH_SECTOR_ERASE, 0x2000)]);
+        assert!(transport.log.iter().all(|(_, address)| address % 256 == 0));
+    }
+
+    #[test]
+    fn erase_picks_biggest_blocks() {
+        let mut flash = flash(W25Q128_ID, 0x100_0000, w25q128_sfdp());
+        flash.erase(0xf000, 0x1_a000).unwrap();
+        assert_eq!(flash.spi().transport().log, vec![
+            (SPI_FLASH_SECTOR_ERASE, 0xf000),
+            (SPI_FLASH_BLOCK_ERASE_64K, 0x10000),
+            (SPI_FLASH_BLOCK_ERASE_32K, 0x20000),
+            (SPI_FLASH_SECTOR_ERASE, 0x28000),
+        ]);
+        assert!(flash.erase(0x800, 0x1000).is_err());
+        assert!(flash.erase(0xfff000, 0x2000).is_err());
+        assert!(flash.read(0xffffff, 2).is_err());
+        flash.erase_chip().unwrap();
+        assert_eq!(flash.spi().transport().log.last(), Some(&(SPI_FLASH_CHIP_ERASE, 0)));
+    }
+
+    #[test]
+    fn verify_reports_address() {
+        let mut flash = flash(W25Q128_ID, 0x100_0000, w25q128_sfdp());
+        flash.spi().transport().memory.insert(0x205, 0x0f);
+        // programming can clear bits only
+        flash.program(0x200, &[0xf0; 16], |_, _| {}).unwrap();
+        assert!(flash.verify(0x200, &[0x00; 16]).is_err());
+        assert!(flash.verify(0x200, &[0xf0; 16]).is_err());
+        let mut expected = [0xf0u8; 16];
+        expected[5] = 0x00;
+        flash.verify(0x200, &expected).unwrap();
+    }
+
+    #[test]
+    fn four_byte_addressing() {
+        let mut sfdp = w25q128_sfdp();
+        // 256 Mbit
+        sfdp[0x84..0x88].copy_from_slice(&0x0fff_ffffu32.to_le_bytes());
+        let mut flash = flash([0xef, 0x40, 0x19], 0x200_0000, sfdp);
+        assert_eq!(flash.geometry().size, 0x200_0000);
+        assert!(flash.spi().transport().four_byte);
+        let image = vec![0x5a; 300];
+        flash.write_image(0x1ff_fe00, &image, |_, _| {}).unwrap();
+        assert_eq!(flash.spi().transport().memory.get(&0x1ff_fe00), Some(&0x5a));
+        assert_eq!(flash.spi().transport().memory.get(&0x0ff_fe00), None);
+        assert!(flash.write_image(0x1ff_ff00, &[0; 0x101], |_, _| {}).is_err());
+        let spi = flash.into_inner().unwrap();
+        assert!(!spi.into_inner().four_byte);
+    }
+
+    #[test]
+    fn busy_timeout() {
+        let mut flash = flash(W25Q128_ID, 0x100_0000, w25q128_sfdp());
+        flash.spi().transport().busy = u32::MAX;
+        assert!(flash.wait_ready(Duration::from_millis(5)).is_err());
+        // write enable is refused while busy
+        assert!(flash.erase(0, 0x1000).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use crate::ftdi::jtag::{JtagController, TapState};
+    use crate::ftdi::svf::{SvfCommand, SvfPlayer, SvfRunClock, SvfShift, SvfStatement, bits_to_hex, parse_svf};
+    use crate::ftdi::xsvf::{*};
+    use crate::jtag_test::tests::{SimulatedDevice, SimulatedTap};
+
+    const XILINX_XC7A35T: u32 = 0x0362_d093;
+    const ARM_JTAG_DP: u32 = 0x4ba0_0477;
+    const LATTICE_ECP5: u32 = 0x4111_1043;
+
+    fn test_chain() -> JtagController<SimulatedTap> {
+        let mut jtag = JtagController::new(SimulatedTap::new(vec![
+            SimulatedDevice::new(6, Some(XILINX_XC7A35T)),
+            SimulatedDevice::new(5, None),
+            SimulatedDevice::new(4, Some(ARM_JTAG_DP)),
+        ]));
+        jtag.init(6_000_000, true).unwrap();
+        jtag
+    }
+
+    fn single_device() -> JtagController<SimulatedTap> {
+        let mut jtag = JtagController::new(SimulatedTap::new(vec![SimulatedDevice::new(4, Some(LATTICE_ECP5))]));
+        jtag.init(1_000_000, false).unwrap();
+        jtag
+    }
+
+    const ARM_IDCODE_SVF: &str = "! read ARM DP IDCODE behind two other devices
+TRST ABSENT;
+ENDIR IDLE;
+ENDDR IDLE;
+STATE RESET;
+STATE IDLE;
+FREQUENCY 1.00E+06 HZ;
+HIR 11 TDI (7ff); // Xilinx and bypass-only device in BYPASS
+HDR 2 TDI (0);
+SIR 4 TDI (e);
+SDR 32 TDI (00000000)
+    TDO (4ba00477) MASK (0fffffff);
+RUNTEST 100 TCK ENDSTATE IDLE;
+";
+
+    #[test]
+    fn parse_statements() {
+        let statements = parse_svf(ARM_IDCODE_SVF).unwrap();
+        assert_eq!(statements.len(), 11);
+        assert_eq!(statements[0].line, 2);
+        assert_eq!(statements[5], SvfStatement { line: 7, command: SvfCommand::Frequency(Some(1e6)) });
+        assert_eq!(statements[9], SvfStatement {
+            line: 11,
+            command: SvfCommand::Sdr(SvfShift {
+                length: 32,
+                tdi: Some(vec![0; 4]),
+                tdo: Some(vec![0x77, 0x04, 0xa0, 0x4b]),
+                mask: Some(vec![0xff, 0xff, 0xff, 0x0f]),
+                smask: None,
+            }),
+        });
+        assert_eq!(statements[10].command, SvfCommand::RunTest {
+            run_state: None, run_count: Some(100), clock: SvfRunClock::Tck,
+            min_time: None, max_time: None, end_state: Some(TapState::RunTestIdle),
+        });
+
+        let statements = parse_svf("runtest drpause 1.0e+03 tck 1.5E-3 sec maximum 1 SEC;\nstate drpause\n  idle;").unwrap();
+        assert_eq!(statements[0].command, SvfCommand::RunTest {
+            run_state: Some(TapState::PauseDr), run_count: Some(1000), clock: SvfRunClock::Tck,
+            min_time: Some(1.5e-3), max_time: Some(1.0), end_state: None,
+        });
+        assert_eq!(statements[1], SvfStatement { line: 2, command: SvfCommand::State(vec![TapState::PauseDr, TapState::RunTestIdle]) });
+    }
+
+    #[test]
+    fn parse_hex_vectors() {
+        let statements = parse_svf("SDR 12 TDI (a\nbc);").unwrap();
+        match &statements[0].command {
+            SvfCommand::Sdr(shift) => assert_eq!(shift.tdi, Some(vec![0xbc, 0x0a])),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(bits_to_hex(&[0xbc, 0x0a], 12), "abc");
+        assert_eq!(bits_to_hex(&[0x05], 3), "5");
+        // value wider than the scan
+        assert!(parse_svf("SDR 4 TDI (1f);").is_err());
+        assert!(parse_svf("SDR 8 TDI (xx);").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_svf("SDR 8 TDI (ff)").is_err());
+        assert!(parse_svf("FOO 1;").is_err());
+        assert!(parse_svf("ENDDR DRSHIFT2;").is_err());
+        assert!(parse_svf("ENDDR DREXIT1;").is_err());
+        assert!(parse_svf("STATE DRSELECT;").is_err());
+        assert!(parse_svf("PIOMAP (IN A);").is_err());
+        assert!(parse_svf("SIR 4 TDI (1;").is_err());
+        assert!(parse_svf("! only comments\n// here\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn play_with_header_and_progress() {
+        let mut jtag = test_chain();
+        let mut calls = Vec::new();
+        SvfPlayer::new(&mut jtag).play_str(ARM_IDCODE_SVF, |done, total| calls.push((done, total))).unwrap();
+        assert_eq!(calls.len(), 11);
+        assert_eq!(calls.last(), Some(&(11, 11)));
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+        let devices = &jtag.transport().devices;
+        assert_eq!(devices[0].instruction, 0x3f);
+        assert_eq!(devices[1].instruction, 0x1f);
+        assert_eq!(devices[2].instruction, 0xe);
+    }
+
+    #[test]
+    fn play_reports_mismatch_location() {
+        let mut jtag = test_chain();
+        let text = ARM_IDCODE_SVF.replace("4ba00477", "4ba00478");
+        let mut player = SvfPlayer::new(&mut jtag);
+        assert!(player.play_str(&text, |_, _| {}).is_err());
+        let mismatch = player.mismatch().unwrap();
+        assert_eq!(mismatch.location, "line 11 (SDR)");
+        // two header bits of BYPASS registers come first
+        assert_eq!(mismatch.first_bit, 2);
+        assert_eq!(mismatch.length, 34);
+        assert_eq!(bits_to_hex(&mismatch.actual, 34), "12e8011dc");
+    }
+
+    #[test]
+    fn scan_parameters_persist() {
+        let mut jtag = single_device();
+        let mut player = SvfPlayer::new(&mut jtag);
+        player.play_str("SIR 4 TDI (1);\nSDR 32 TDI (0) TDO (41111043);\nSDR 32 TDO (41111043);", |_, _| {}).unwrap();
+        // TDO isn't persistent, but TDI is required when the length changes
+        player.play_str("SDR 32;", |_, _| {}).unwrap();
+        assert!(player.play_str("SDR 16 TDO (0);", |_, _| {}).is_err());
+        assert!(player.mismatch().is_none());
+        // masked bits are not compared
+        player.play_str("SDR 32 TDI (0) TDO (f1111043) MASK (0fffffff);", |_, _| {}).unwrap();
+    }
+
+    #[test]
+    fn end_and_run_states() {
+        let mut jtag = single_device();
+        SvfPlayer::new(&mut jtag).play_str("ENDDR DRPAUSE;\nSDR 32 TDI (0);", |_, _| {}).unwrap();
+        assert_eq!(jtag.transport().state, TapState::PauseDr);
+
+        let clocks = jtag.transport().clocks;
+        SvfPlayer::new(&mut jtag).play_str("RUNTEST IDLE 1000 TCK;", |_, _| {}).unwrap();
+        assert!(jtag.transport().clocks - clocks >= 1000);
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+
+        // with known frequency minimum time is converted to TCK cycles
+        let clocks = jtag.transport().clocks;
+        SvfPlayer::new(&mut jtag)
+            .play_str("FREQUENCY 1E6 HZ;\nRUNTEST DRPAUSE 1E-4 SEC ENDSTATE IDLE;", |_, _| {})
+            .unwrap();
+        assert!(jtag.transport().clocks - clocks >= 100);
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+
+        // FREQUENCY without value restores the maximum, 6 MHz on non-H chips
+        let clocks = jtag.transport().clocks;
+        SvfPlayer::new(&mut jtag)
+            .play_str("FREQUENCY 1E6 HZ;\nFREQUENCY;\nRUNTEST IDLE 1E-4 SEC;", |_, _| {})
+            .unwrap();
+        assert!(jtag.transport().clocks - clocks >= 600);
+    }
+
+    fn xsvf_idcode_program(expected: u32, repeat: u8) -> Vec<u8> {
+        let mut program = vec![
+            XREPEAT, repeat,
+            XRUNTEST, 0, 0, 0, 10,
+            XENDIR, 0,
+            XENDDR, 0,
+            XSIR, 4, 0x01,
+            XSDRSIZE, 0, 0, 0, 32,
+            XTDOMASK, 0xff, 0xff, 0xff, 0xff,
+            XSDRTDO, 0, 0, 0, 0,
+        ];
+        program.extend_from_slice(&expected.to_be_bytes());
+        program.extend_from_slice(&[XCOMMENT, b'o', b'k', 0, XSTATE, 1, XCOMPLETE]);
+        program
+    }
+
+    #[test]
+    fn xsvf_idcode_check() {
+        let mut jtag = single_device();
+        let program = xsvf_idcode_program(LATTICE_ECP5, 0);
+        let mut last = (0, 0);
+        XsvfPlayer::new(&mut jtag).play(&program, |done, total| last = (done, total)).unwrap();
+        assert_eq!(last, (program.len(), program.len()));
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+        assert_eq!(jtag.transport().devices[0].instruction, 1);
+    }
+
+    #[test]
+    fn xsvf_mismatch_after_retries() {
+        let mut jtag = single_device();
+        let program = xsvf_idcode_program(LATTICE_ECP5 ^ 0x100, 3);
+        let clocks = jtag.transport().clocks;
+        let mut player = XsvfPlayer::new(&mut jtag);
+        assert!(player.play(&program, |_, _| {}).is_err());
+        let mismatch = player.mismatch().unwrap();
+        assert_eq!(mismatch.location, "offset 0x18 (instruction 0x09)");
+        assert_eq!(mismatch.first_bit, 8);
+        // the first attempt and three retries
+        assert!(jtag.transport().clocks - clocks >= 4 * 32);
+    }
+
+    #[test]
+    fn xsvf_split_scan() {
+        let mut jtag = single_device();
+        // BYPASS delays data by one bit across all the parts of one scan
+        let program = vec![
+            XSIR, 4, 0x0f,
+            XSDRSIZE, 0, 0, 0, 8,
+            XTDOMASK, 0xff,
+            XSDRTDOB, 0xaa, 0x54,
+            XSDRTDOC, 0x55, 0xab,
+            XSDRTDOE, 0x01, 0x02,
+            XCOMPLETE,
+        ];
+        XsvfPlayer::new(&mut jtag).play(&program, |_, _| {}).unwrap();
+        assert_eq!(jtag.transport().state, TapState::RunTestIdle);
+        // XRUNTEST wait keeps XENDIR state
+        let program = [XRUNTEST, 0, 0, 0, 10, XENDIR, 1, XSIR, 4, 0x01, XCOMPLETE];
+        XsvfPlayer::new(&mut jtag).play(&program, |_, _| {}).unwrap();
+        assert_eq!(jtag.transport().state, TapState::PauseIr);
+        assert!(XsvfPlayer::new(&mut jtag).play(&[XSDRINC], |_, _| {}).is_err());
+        assert!(XsvfPlayer::new(&mut jtag).play(&[XSIR, 8], |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn xsvf_mask_shorter_than_scan() {
+        let mut jtag = single_device();
+        // XTDOMASK is read for 8 bits, scan is 32 bits long
+        let program = [
+            XSDRSIZE, 0, 0, 0, 8,
+            XTDOMASK, 0xff,
+            XSDRSIZE, 0, 0, 0, 32,
+            XSDRTDO, 0, 0, 0, 0, 0, 0, 0, 0,
+            XCOMPLETE,
+        ];
+        let mut player = XsvfPlayer::new(&mut jtag);
+        assert!(player.play(&program, |_, _| {}).is_err());
+        assert!(player.mismatch().is_none());
+    }
+}
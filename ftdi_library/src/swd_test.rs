@@ -0,0 +1,361 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use crate::ftdi::constants::{*};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::mpsse::MpsseTransport;
+    use crate::ftdi::swd::{*};
+
+    const DPIDR: u32 = 0x0bc1_1477;
+    const AHB_AP_IDR: u32 = 0x2477_0011;
+
+    /// SW-DP with one MEM-AP driven by interpreting MPSSE commands bit by bit
+    struct SimulatedSwdTarget {
+        /// host drives SWDIO (ADBUS1 is output)
+        driving: bool,
+        swd: bool,
+        /// protocol error happened, target waits for line reset
+        locked: bool,
+        ones: usize,
+        history: u16,
+        request: Vec<bool>,
+        /// bits driven by target on the next clocks, None is turnaround
+        output: VecDeque<Option<bool>>,
+        /// (AP, address) of acknowledged write and data bits received so far
+        write: Option<(bool, u8, Vec<bool>)>,
+        replies: VecDeque<u8>,
+        ctrl_stat: u32,
+        select: u32,
+        sticky: bool,
+        rdbuff: u32,
+        csw: u32,
+        tar: u32,
+        memory: HashMap<u32, u32>,
+        /// next requests answered with WAIT
+        waits: u32,
+        /// next AP requests answered with FAULT
+        faults: u32,
+        bad_parity: bool,
+        aborts: u32,
+    }
+    impl SimulatedSwdTarget {
+        fn new() -> Self {
+            SimulatedSwdTarget {
+                driving: false, swd: false, locked: true, ones: 0, history: 0, request: Vec::new(),
+                output: VecDeque::new(), write: None, replies: VecDeque::new(),
+                ctrl_stat: 0, select: 0, sticky: false, rdbuff: 0, csw: 0, tar: 0, memory: HashMap::new(),
+                waits: 0, faults: 0, bad_parity: false, aborts: 0,
+            }
+        }
+
+        fn clock(&mut self, host_bit: bool) -> bool {
+            let target = self.output.pop_front();
+            let line = match (self.driving, target) {
+                (true, Some(Some(_))) => panic!("SWDIO is driven by both host and target"),
+                (true, _) => host_bit,
+                (false, Some(Some(bit))) => bit,
+                // pull-up
+                (false, _) => true,
+            };
+            if target.is_some() {
+                return line;
+            }
+            if let Some((ap, address, mut bits)) = self.write.take() {
+                bits.push(line);
+                if bits.len() == 33 {
+                    let value = (0..32).fold(0u32, |value, bit| value | ((bits[bit] as u32) << bit));
+                    assert_eq!(bits[32], value.count_ones() & 1 == 1, "host sent wrong parity");
+                    self.write_register(ap, address, value);
+                } else {
+                    self.write = Some((ap, address, bits));
+                }
+                return line;
+            }
+            if !self.driving {
+                return line;
+            }
+            if line {
+                self.ones += 1;
+            } else {
+                if self.ones >= 50 {
+                    self.request.clear();
+                    self.locked = !self.swd;
+                }
+                self.ones = 0;
+            }
+            self.history = (self.history >> 1) | ((line as u16) << 15);
+            if self.history == SWD_JTAG_TO_SWD {
+                self.swd = true;
+            }
+            if self.locked || (self.request.is_empty() && !line) {
+                return line;
+            }
+            self.request.push(line);
+            if self.request.len() == 8 {
+                let request: Vec<bool> = self.request.drain(..).collect();
+                self.handle_request(&request);
+            }
+            line
+        }
+
+        fn handle_request(&mut self, bits: &[bool]) {
+            let (ap, read) = (bits[1], bits[2]);
+            let address = ((bits[3] as u8) << 2) | ((bits[4] as u8) << 3);
+            if !bits[0] || bits[6] || !bits[7] || bits[5] != (ap ^ read ^ bits[3] ^ bits[4]) {
+                self.locked = true;
+                return;
+            }
+            if ap && self.faults > 0 {
+                self.faults -= 1;
+                self.sticky = true;
+            }
+            let ack = if self.waits > 0 {
+                self.waits -= 1;
+                0b010
+            } else if ap && self.sticky {
+                0b100
+            } else {
+                0b001
+            };
+            self.output.push_back(None);
+            (0..3).for_each(|bit| self.output.push_back(Some(ack & (1 << bit) != 0)));
+            if ack != 0b001 {
+                self.output.push_back(None);
+            } else if read {
+                let value = self.read_register(ap, address);
+                (0..32).for_each(|bit| self.output.push_back(Some(value & (1 << bit) != 0)));
+                self.output.push_back(Some((value.count_ones() & 1 == 1) ^ self.bad_parity));
+                self.output.push_back(None);
+            } else {
+                self.output.push_back(None);
+                self.write = Some((ap, address, Vec::new()));
+            }
+        }
+
+        fn read_register(&mut self, ap: bool, address: u8) -> u32 {
+            if !ap {
+                return match address {
+                    DP_DPIDR => DPIDR,
+                    DP_CTRL_STAT => self.ctrl_stat | ((self.ctrl_stat & (DP_CTRL_CDBGPWRUPREQ | DP_CTRL_CSYSPWRUPREQ)) << 1)
+                        | if self.sticky { DP_CTRL_STICKYERR } else { 0 },
+                    DP_RDBUFF => self.rdbuff,
+                    _ => self.select,
+                };
+            }
+            let value = match (self.select as u8 & 0xf0) | address {
+                AP_CSW => self.csw,
+                AP_TAR => self.tar,
+                AP_DRW => {
+                    let value = *self.memory.get(&self.tar).unwrap_or(&0);
+                    self.increment();
+                    value
+                }
+                AP_IDR => AHB_AP_IDR,
+                _ => 0,
+            };
+            // AP reads are posted
+            std::mem::replace(&mut self.rdbuff, value)
+        }
+
+        fn write_register(&mut self, ap: bool, address: u8, value: u32) {
+            if !ap {
+                match address {
+                    DP_ABORT => {
+                        self.aborts += 1;
+                        if value & DP_ABORT_STKERRCLR != 0 {
+                            self.sticky = false;
+                        }
+                    }
+                    DP_CTRL_STAT => self.ctrl_stat = value,
+                    DP_SELECT => self.select = value,
+                    _ => {}
+                }
+                return;
+            }
+            match (self.select as u8 & 0xf0) | address {
+                AP_CSW => self.csw = value,
+                AP_TAR => self.tar = value,
+                AP_DRW => {
+                    let value = if self.tar == CORTEX_M_DHCSR {
+                        assert_eq!(value & 0xffff_0000, CORTEX_M_DHCSR_DBGKEY);
+                        (value & 0xffff) | if value & CORTEX_M_DHCSR_C_HALT != 0 { CORTEX_M_DHCSR_S_HALT } else { 0 }
+                    } else {
+                        value
+                    };
+                    self.memory.insert(self.tar, value);
+                    self.increment();
+                }
+                _ => {}
+            }
+        }
+
+        /// TAR auto increment wraps inside 1 KB block
+        fn increment(&mut self) {
+            if self.csw & 0x30 == AP_CSW_ADDRINC_SINGLE {
+                self.tar = (self.tar & !0x3ff) | (self.tar.wrapping_add(4) & 0x3ff);
+            }
+        }
+    }
+    impl MpsseTransport for SimulatedSwdTarget {
+        fn mpsse_write(&mut self, commands: &[u8]) -> Result<()> {
+            let mut index = 0;
+            while index < commands.len() {
+                let opcode = commands[index];
+                index += 1;
+                match opcode {
+                    DIS_DIV_5 | EN_DIV_5 | LOOPBACK_END | SEND_IMMEDIATE => {}
+                    TCK_DIVISOR | SET_BITS_HIGH => index += 2,
+                    SET_BITS_LOW => {
+                        self.driving = commands[index + 1] & SWD_PIN_SWDIO_OUT != 0;
+                        index += 2;
+                    }
+                    0x1b => {
+                        let (length, data) = (commands[index] as usize + 1, commands[index + 1]);
+                        index += 2;
+                        (0..length).for_each(|bit| { self.clock(data & (1 << bit) != 0); });
+                    }
+                    0x19 => {
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for byte in commands[index..index + length].to_vec() {
+                            (0..8).for_each(|bit| { self.clock(byte & (1 << bit) != 0); });
+                        }
+                        index += length;
+                    }
+                    0x2a => {
+                        let length = commands[index] as usize + 1;
+                        index += 1;
+                        let mut read = 0u8;
+                        for _ in 0..length {
+                            read = (read >> 1) | ((self.clock(false) as u8) << 7);
+                        }
+                        self.replies.push_back(read);
+                    }
+                    0x28 => {
+                        let length = commands[index] as usize + ((commands[index + 1] as usize) << 8) + 1;
+                        index += 2;
+                        for _ in 0..length {
+                            let read = (0..8).fold(0u8, |read, bit| read | ((self.clock(false) as u8) << bit));
+                            self.replies.push_back(read);
+                        }
+                    }
+                    _ => panic!("simulated SWD target got unsupported MPSSE command {:#04x}", opcode),
+                }
+            }
+            Ok(())
+        }
+
+        fn mpsse_read(&mut self, size: usize) -> Result<Vec<u8>> {
+            assert!(self.replies.len() >= size, "simulated SWD target has {} reply bytes, {} requested", self.replies.len(), size);
+            Ok(self.replies.drain(..size).collect())
+        }
+    }
+
+    fn connected() -> SwdController<SimulatedSwdTarget> {
+        let mut swd = SwdController::new(SimulatedSwdTarget::new());
+        assert_eq!(swd.init(1_000_000, true).unwrap(), 1_000_000);
+        assert_eq!(swd.jtag_to_swd().unwrap(), DPIDR);
+        swd.power_up().unwrap();
+        swd
+    }
+
+    #[test]
+    fn request_packets() {
+        assert_eq!(swd_request(SwdPort::Dp, true, DP_DPIDR), 0xa5);
+        assert_eq!(swd_request(SwdPort::Dp, false, DP_ABORT), 0x81);
+        assert_eq!(swd_request(SwdPort::Dp, true, DP_CTRL_STAT), 0x8d);
+        assert_eq!(swd_request(SwdPort::Dp, false, DP_SELECT), 0xb1);
+        assert_eq!(swd_request(SwdPort::Dp, true, DP_RDBUFF), 0xbd);
+        assert_eq!(swd_request(SwdPort::Ap, true, AP_DRW), 0x9f);
+        assert_eq!(swd_request(SwdPort::Ap, false, AP_TAR), 0x8b);
+        assert_eq!(SwdAck::from(0b111), SwdAck::Invalid(0b111));
+    }
+
+    #[test]
+    fn connect_needs_switch_sequence() {
+        let mut swd = SwdController::new(SimulatedSwdTarget::new());
+        swd.init(1_000_000, true).unwrap();
+        // still in JTAG mode, nobody drives the line
+        swd.line_reset().unwrap();
+        assert!(swd.read_dp(DP_DPIDR).is_err());
+        assert_eq!(swd.jtag_to_swd().unwrap(), DPIDR);
+        assert_eq!(swd.read_dp(DP_DPIDR).unwrap(), DPIDR);
+        // line reset keeps SWD mode
+        swd.line_reset().unwrap();
+        assert_eq!(swd.read_dp(DP_DPIDR).unwrap(), DPIDR);
+    }
+
+    #[test]
+    fn power_up_and_ap_access() {
+        let mut swd = connected();
+        let status = swd.read_dp(DP_CTRL_STAT).unwrap();
+        assert_eq!(status & (DP_CTRL_CDBGPWRUPACK | DP_CTRL_CSYSPWRUPACK), DP_CTRL_CDBGPWRUPACK | DP_CTRL_CSYSPWRUPACK);
+        assert_eq!(swd.read_ap(0, AP_IDR).unwrap(), AHB_AP_IDR);
+        assert_eq!(swd.transport().select, 0xf0);
+        swd.write_ap(0, AP_TAR, 0x2000_0000).unwrap();
+        assert_eq!(swd.read_ap(0, AP_TAR).unwrap(), 0x2000_0000);
+        assert_eq!(swd.transport().select, 0x00);
+    }
+
+    #[test]
+    fn wait_is_retried() {
+        let mut swd = connected();
+        swd.transport().waits = 5;
+        assert_eq!(swd.read_dp(DP_DPIDR).unwrap(), DPIDR);
+        swd.transport().waits = 3;
+        swd.write_ap(0, AP_TAR, 0x1234_5678).unwrap();
+        assert_eq!(swd.transport().tar, 0x1234_5678);
+        swd.transport().waits = SWD_WAIT_RETRIES + 5;
+        assert!(swd.read_dp(DP_DPIDR).is_err());
+        swd.transport().waits = 0;
+        assert_eq!(swd.read_dp(DP_DPIDR).unwrap(), DPIDR);
+    }
+
+    #[test]
+    fn fault_clears_sticky_error() {
+        let mut swd = connected();
+        let aborts = swd.transport().aborts;
+        swd.transport().faults = 1;
+        assert!(swd.read_ap(0, AP_IDR).is_err());
+        assert!(!swd.transport().sticky);
+        assert_eq!(swd.transport().aborts, aborts + 1);
+        assert_eq!(swd.read_ap(0, AP_IDR).unwrap(), AHB_AP_IDR);
+    }
+
+    #[test]
+    fn read_parity_error() {
+        let mut swd = connected();
+        swd.transport().bad_parity = true;
+        assert!(swd.read_dp(DP_DPIDR).is_err());
+        swd.transport().bad_parity = false;
+        assert_eq!(swd.read_dp(DP_DPIDR).unwrap(), DPIDR);
+    }
+
+    #[test]
+    fn mem_ap_read_write() {
+        let mut swd = connected();
+        let mut memory = MemAp::new(&mut swd, 0);
+        assert_eq!(memory.idr().unwrap(), AHB_AP_IDR);
+        memory.write32(0x2000_0000, 0xdead_beef).unwrap();
+        assert_eq!(memory.read32(0x2000_0000).unwrap(), 0xdead_beef);
+        assert!(memory.read32(0x2000_0002).is_err());
+
+        // block crosses 1 KB auto increment boundary
+        let values: Vec<u32> = (0..6).map(|value| 0x1111_1111 * value).collect();
+        memory.write_block(0x2000_03f8, &values).unwrap();
+        assert_eq!(memory.read_block(0x2000_03f8, 6).unwrap(), values);
+        assert_eq!(memory.read32(0x2000_0400).unwrap(), 0x2222_2222);
+        assert_eq!(swd.transport().memory.get(&0x2000_0000), Some(&0xdead_beef));
+    }
+
+    #[test]
+    fn cortex_m_halt_and_resume() {
+        let mut swd = connected();
+        let mut memory = MemAp::new(&mut swd, 0);
+        assert!(!memory.cortex_m_is_halted().unwrap());
+        memory.cortex_m_halt().unwrap();
+        assert!(memory.cortex_m_is_halted().unwrap());
+        memory.cortex_m_resume().unwrap();
+        assert!(!memory.cortex_m_is_halted().unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{Read, Write};
+    use crate::ftdi::constants::{ftdi_chip_type, ftdi_mpsse_mode, CHANNEL_IS_FIFO, CHANNEL_IS_UART};
+    use crate::ftdi::ftdi_context::Result;
+    use crate::ftdi::sync_fifo::{*};
+
+    const PACKET_SIZE: usize = 64;
+
+    /// FIFO chip with FPGA behind it: 'fpga_output' is sent to host, writes are collected in 'fpga_input'
+    struct SimulatedFifo {
+        chip: ftdi_chip_type,
+        channel_type: i32,
+        interface: u8,
+        bitmodes: Vec<(u8, ftdi_mpsse_mode)>,
+        latency: u8,
+        flowctrl: u16,
+        purged: bool,
+        fpga_output: VecDeque<u8>,
+        fpga_input: Vec<u8>,
+        reads: VecDeque<usize>,
+        writes: VecDeque<Vec<u8>>,
+        max_writes: usize,
+    }
+    impl SimulatedFifo {
+        fn new(chip: ftdi_chip_type, channel_type: i32) -> Self {
+            SimulatedFifo {
+                chip, channel_type, interface: 0, bitmodes: Vec::new(), latency: 16, flowctrl: 0, purged: false,
+                fpga_output: VecDeque::new(), fpga_input: Vec::new(), reads: VecDeque::new(), writes: VecDeque::new(), max_writes: 0,
+            }
+        }
+    }
+    impl FifoTransport for SimulatedFifo {
+        fn chip_type(&self) -> ftdi_chip_type {
+            self.chip
+        }
+
+        fn channel_type(&self) -> i32 {
+            self.channel_type
+        }
+
+        fn interface(&self) -> u8 {
+            self.interface
+        }
+
+        fn packet_size(&self) -> usize {
+            PACKET_SIZE
+        }
+
+        fn set_bitmode(&mut self, mask: u8, mode: ftdi_mpsse_mode) -> Result<()> {
+            self.bitmodes.push((mask, mode));
+            Ok(())
+        }
+
+        fn set_latency_timer(&mut self, latency: u8) -> Result<()> {
+            self.latency = latency;
+            Ok(())
+        }
+
+        fn set_flow_control(&mut self, flowctrl: u16) -> Result<()> {
+            self.flowctrl = flowctrl;
+            Ok(())
+        }
+
+        fn purge(&mut self) -> Result<()> {
+            self.purged = true;
+            Ok(())
+        }
+
+        fn submit_read(&mut self, size: usize) -> Result<()> {
+            self.reads.push_back(size);
+            Ok(())
+        }
+
+        fn submit_write(&mut self, data: Vec<u8>) -> Result<()> {
+            self.writes.push_back(data);
+            self.max_writes = self.max_writes.max(self.writes.len());
+            Ok(())
+        }
+
+        fn wait_completion(&mut self) -> Result<FifoCompletion> {
+            if let Some(data) = self.writes.pop_front() {
+                self.fpga_input.extend_from_slice(&data);
+                return Ok(FifoCompletion::Written(data.len()));
+            }
+            let size = self.reads.pop_front().expect("no transfer is queued");
+            // every packet starts with modem status, short packet ends the transfer
+            let mut data = Vec::new();
+            while data.len() < size {
+                data.extend_from_slice(&[0x32, 0x60]);
+                let payload = std::cmp::min(PACKET_SIZE - 2, self.fpga_output.len());
+                data.extend(self.fpga_output.drain(..payload));
+                if payload < PACKET_SIZE - 2 {
+                    break;
+                }
+            }
+            Ok(FifoCompletion::Read(data))
+        }
+
+        fn cancel_all(&mut self) -> Result<()> {
+            self.reads.clear();
+            self.writes.clear();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sync_fifo_open_checks_chip_and_eeprom() {
+        assert!(SyncFifo::open(SimulatedFifo::new(ftdi_chip_type::TYPE_R, CHANNEL_IS_FIFO as i32), SyncFifoConfig::default()).is_err());
+        assert!(SyncFifo::open(SimulatedFifo::new(ftdi_chip_type::TYPE_232H, CHANNEL_IS_UART as i32), SyncFifoConfig::default()).is_err());
+        let channel_b = SimulatedFifo { interface: 1, ..SimulatedFifo::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_FIFO as i32) };
+        assert!(SyncFifo::open(channel_b, SyncFifoConfig::default()).is_err());
+        let config = SyncFifoConfig { transfer_size: 1000, ..SyncFifoConfig::default() };
+        assert!(SyncFifo::open(SimulatedFifo::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_FIFO as i32), config).is_err());
+        let mut fifo = SyncFifo::open(SimulatedFifo::new(ftdi_chip_type::TYPE_2232H, CHANNEL_IS_FIFO as i32), SyncFifoConfig::default()).unwrap();
+        let simulator = fifo.transport();
+        assert_eq!(simulator.bitmodes, vec![(0xff, ftdi_mpsse_mode::BITMODE_RESET), (0xff, ftdi_mpsse_mode::BITMODE_SYNCFF)]);
+        assert_eq!(simulator.latency, SYNC_FIFO_LATENCY);
+        assert_eq!(simulator.flowctrl, 0x0100);
+        assert!(simulator.purged);
+        assert_eq!(simulator.reads, vec![SYNC_FIFO_TRANSFER_SIZE; SYNC_FIFO_TRANSFERS]);
+    }
+
+    #[test]
+    fn sync_fifo_duplex_stream() {
+        let config = SyncFifoConfig { latency: 1, transfer_size: 4 * PACKET_SIZE, read_transfers: 3, write_transfers: 4 };
+        let mut simulator = SimulatedFifo::new(ftdi_chip_type::TYPE_232H, CHANNEL_IS_FIFO as i32);
+        let sent: Vec<u8> = (0..1000u32).map(|index| (index * 7) as u8).collect();
+        simulator.fpga_output.extend(sent.iter());
+        let mut fifo = SyncFifo::open(simulator, config).unwrap();
+        // status bytes are stripped from every packet
+        let mut received = vec![0; sent.len()];
+        fifo.read_exact(&mut received).unwrap();
+        assert_eq!(received, sent);
+        assert_eq!(fifo.available(), 0);
+        assert_eq!(fifo.transport().reads.len(), 3);
+
+        let data: Vec<u8> = (0..10_000u32).map(|index| (index / 3) as u8).collect();
+        fifo.write_all(&data).unwrap();
+        assert_eq!(fifo.transport().max_writes, 4);
+        Write::flush(&mut fifo).unwrap();
+        assert!(fifo.transport().writes.is_empty());
+        // reads finished while writing are queued again
+        assert_eq!(fifo.transport().reads.len(), 3);
+        let simulator = fifo.close().unwrap();
+        assert_eq!(simulator.fpga_input, data);
+        assert!(simulator.reads.is_empty());
+        assert_eq!(simulator.bitmodes.last(), Some(&(0, ftdi_mpsse_mode::BITMODE_RESET)));
+    }
+}